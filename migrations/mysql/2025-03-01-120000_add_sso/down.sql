@@ -0,0 +1,3 @@
+DROP TABLE sso_users;
+
+DROP TABLE sso_auth;
@@ -0,0 +1,18 @@
+CREATE TABLE sso_auth (
+    state          VARCHAR(64)  NOT NULL PRIMARY KEY,
+    client_state   TEXT         NOT NULL,
+    code_challenge TEXT         NOT NULL,
+    redirect_uri   TEXT         NOT NULL,
+    nonce          VARCHAR(64)  NOT NULL,
+    verifier       VARCHAR(128),
+    code           VARCHAR(64),
+    idp_code       TEXT,
+    created_at     DATETIME     NOT NULL
+);
+
+CREATE TABLE sso_users (
+    user_uuid  CHAR(36)     NOT NULL PRIMARY KEY,
+    identifier VARCHAR(512) NOT NULL UNIQUE,
+
+    FOREIGN KEY (user_uuid) REFERENCES users (uuid)
+);
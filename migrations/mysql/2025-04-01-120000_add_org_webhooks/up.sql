@@ -0,0 +1,29 @@
+CREATE TABLE org_webhooks (
+    uuid        CHAR(36)     NOT NULL PRIMARY KEY,
+    org_uuid    CHAR(36)     NOT NULL,
+    url         TEXT         NOT NULL,
+    secret      VARCHAR(64)  NOT NULL,
+    event_types TEXT         NOT NULL,
+    enabled     BOOLEAN      NOT NULL,
+    created_at  DATETIME     NOT NULL,
+
+    FOREIGN KEY (org_uuid) REFERENCES organizations (uuid)
+);
+
+CREATE TABLE webhook_deliveries (
+    uuid            CHAR(36) NOT NULL PRIMARY KEY,
+    webhook_uuid    CHAR(36) NOT NULL,
+    event_type      INTEGER  NOT NULL,
+    payload         TEXT     NOT NULL,
+    status          INTEGER  NOT NULL,
+    attempts        INTEGER  NOT NULL,
+    next_attempt_at DATETIME NOT NULL,
+    last_attempt_at DATETIME,
+    response_code   INTEGER,
+    last_error      TEXT,
+    created_at      DATETIME NOT NULL,
+
+    FOREIGN KEY (webhook_uuid) REFERENCES org_webhooks (uuid)
+);
+
+CREATE INDEX webhook_deliveries_status_idx ON webhook_deliveries (status, next_attempt_at);
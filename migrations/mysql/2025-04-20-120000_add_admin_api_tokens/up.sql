@@ -0,0 +1,11 @@
+CREATE TABLE admin_api_tokens (
+    uuid         CHAR(36)     NOT NULL PRIMARY KEY,
+    name         VARCHAR(255) NOT NULL,
+    token_hash   CHAR(64)     NOT NULL UNIQUE,
+    scopes       TEXT         NOT NULL,
+    created_by   VARCHAR(255) NOT NULL,
+    created_at   DATETIME     NOT NULL,
+    expires_at   DATETIME,
+    last_used_at DATETIME,
+    revoked_at   DATETIME
+);
@@ -0,0 +1,5 @@
+ALTER TABLE devices ADD COLUMN last_seen_at DATETIME;
+ALTER TABLE devices ADD COLUMN last_ip TEXT;
+ALTER TABLE devices ADD COLUMN encrypted_user_key TEXT;
+ALTER TABLE devices ADD COLUMN encrypted_public_key TEXT;
+ALTER TABLE devices ADD COLUMN encrypted_private_key TEXT;
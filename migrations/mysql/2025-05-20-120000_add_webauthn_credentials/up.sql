@@ -0,0 +1,15 @@
+CREATE TABLE webauthn_credentials (
+    uuid                  CHAR(36)     NOT NULL PRIMARY KEY,
+    user_uuid             CHAR(36)     NOT NULL REFERENCES users (uuid),
+    name                  VARCHAR(255) NOT NULL,
+    -- Up to 1023 bytes encoded as unpadded base64url. The binary collation keeps the comparisons case sensitive,
+    -- and as ASCII the column stays below the size limit of an index.
+    credential_id         VARCHAR(1400) CHARACTER SET ascii COLLATE ascii_bin NOT NULL UNIQUE,
+    credential            TEXT         NOT NULL,
+    supports_prf          BOOLEAN      NOT NULL DEFAULT FALSE,
+    encrypted_user_key    TEXT,
+    encrypted_public_key  TEXT,
+    encrypted_private_key TEXT,
+    created_at            DATETIME     NOT NULL,
+    updated_at            DATETIME     NOT NULL
+);
@@ -0,0 +1,12 @@
+CREATE TABLE admin_audit_log (
+    uuid        CHAR(36)     NOT NULL PRIMARY KEY,
+    created_at  TIMESTAMP    NOT NULL,
+    actor       VARCHAR(255) NOT NULL,
+    ip_address  VARCHAR(45)  NOT NULL,
+    action      VARCHAR(64)  NOT NULL,
+    target_type VARCHAR(64),
+    target_id   VARCHAR(255),
+    details     TEXT
+);
+
+CREATE INDEX admin_audit_log_created_at_idx ON admin_audit_log (created_at);
@@ -0,0 +1,11 @@
+CREATE TABLE admin_api_tokens (
+    uuid         CHAR(36)     NOT NULL PRIMARY KEY,
+    name         VARCHAR(255) NOT NULL,
+    token_hash   CHAR(64)     NOT NULL UNIQUE,
+    scopes       TEXT         NOT NULL,
+    created_by   VARCHAR(255) NOT NULL,
+    created_at   TIMESTAMP    NOT NULL,
+    expires_at   TIMESTAMP,
+    last_used_at TIMESTAMP,
+    revoked_at   TIMESTAMP
+);
@@ -0,0 +1,3 @@
+ALTER TABLE users ADD COLUMN last_login_at TIMESTAMP;
+ALTER TABLE users ADD COLUMN last_sync_at TIMESTAMP;
+ALTER TABLE users ADD COLUMN inactivity_warned_at TIMESTAMP;
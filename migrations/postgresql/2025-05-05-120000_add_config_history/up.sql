@@ -0,0 +1,9 @@
+CREATE TABLE config_history (
+    uuid          CHAR(36)     NOT NULL PRIMARY KEY,
+    version       INTEGER      NOT NULL UNIQUE,
+    created_at    TIMESTAMP    NOT NULL,
+    actor         VARCHAR(255) NOT NULL,
+    action        VARCHAR(64)  NOT NULL,
+    config        TEXT         NOT NULL,
+    restored_from INTEGER
+);
@@ -0,0 +1,3 @@
+ALTER TABLE devices ADD COLUMN previous_refresh_token TEXT;
+ALTER TABLE devices ADD COLUMN session_started_at TIMESTAMP;
+ALTER TABLE devices ADD COLUMN last_refreshed_at TIMESTAMP;
@@ -0,0 +1,16 @@
+CREATE TABLE sso_auth (
+    state          TEXT     NOT NULL PRIMARY KEY,
+    client_state   TEXT     NOT NULL,
+    code_challenge TEXT     NOT NULL,
+    redirect_uri   TEXT     NOT NULL,
+    nonce          TEXT     NOT NULL,
+    verifier       TEXT,
+    code           TEXT,
+    idp_code       TEXT,
+    created_at     DATETIME NOT NULL
+);
+
+CREATE TABLE sso_users (
+    user_uuid  TEXT NOT NULL PRIMARY KEY REFERENCES users (uuid),
+    identifier TEXT NOT NULL UNIQUE
+);
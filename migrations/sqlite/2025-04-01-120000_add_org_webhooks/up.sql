@@ -0,0 +1,25 @@
+CREATE TABLE org_webhooks (
+    uuid        TEXT     NOT NULL PRIMARY KEY,
+    org_uuid    TEXT     NOT NULL REFERENCES organizations (uuid),
+    url         TEXT     NOT NULL,
+    secret      TEXT     NOT NULL,
+    event_types TEXT     NOT NULL,
+    enabled     BOOLEAN  NOT NULL,
+    created_at  DATETIME NOT NULL
+);
+
+CREATE TABLE webhook_deliveries (
+    uuid            TEXT     NOT NULL PRIMARY KEY,
+    webhook_uuid    TEXT     NOT NULL REFERENCES org_webhooks (uuid),
+    event_type      INTEGER  NOT NULL,
+    payload         TEXT     NOT NULL,
+    status          INTEGER  NOT NULL,
+    attempts        INTEGER  NOT NULL,
+    next_attempt_at DATETIME NOT NULL,
+    last_attempt_at DATETIME,
+    response_code   INTEGER,
+    last_error      TEXT,
+    created_at      DATETIME NOT NULL
+);
+
+CREATE INDEX webhook_deliveries_status_idx ON webhook_deliveries (status, next_attempt_at);
@@ -0,0 +1,11 @@
+CREATE TABLE admin_accounts (
+    uuid           TEXT     NOT NULL PRIMARY KEY,
+    username       TEXT     NOT NULL UNIQUE,
+    password_hash  TEXT     NOT NULL,
+    role           INTEGER  NOT NULL,
+    totp_secret    TEXT,
+    totp_last_used BIGINT   NOT NULL DEFAULT 0,
+    enabled        BOOLEAN  NOT NULL DEFAULT 1,
+    created_at     DATETIME NOT NULL,
+    last_login_at  DATETIME
+);
@@ -0,0 +1,12 @@
+CREATE TABLE admin_audit_log (
+    uuid        TEXT     NOT NULL PRIMARY KEY,
+    created_at  DATETIME NOT NULL,
+    actor       TEXT     NOT NULL,
+    ip_address  TEXT     NOT NULL,
+    action      TEXT     NOT NULL,
+    target_type TEXT,
+    target_id   TEXT,
+    details     TEXT
+);
+
+CREATE INDEX admin_audit_log_created_at_idx ON admin_audit_log (created_at);
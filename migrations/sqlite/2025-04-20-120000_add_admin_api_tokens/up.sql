@@ -0,0 +1,11 @@
+CREATE TABLE admin_api_tokens (
+    uuid         TEXT     NOT NULL PRIMARY KEY,
+    name         TEXT     NOT NULL,
+    token_hash   TEXT     NOT NULL UNIQUE,
+    scopes       TEXT     NOT NULL,
+    created_by   TEXT     NOT NULL,
+    created_at   DATETIME NOT NULL,
+    expires_at   DATETIME,
+    last_used_at DATETIME,
+    revoked_at   DATETIME
+);
@@ -0,0 +1,3 @@
+ALTER TABLE users ADD COLUMN attachment_quota_kb BIGINT;
+ALTER TABLE users ADD COLUMN send_quota_kb BIGINT;
+ALTER TABLE organizations ADD COLUMN attachment_quota_kb BIGINT;
@@ -0,0 +1,9 @@
+CREATE TABLE config_history (
+    uuid          TEXT     NOT NULL PRIMARY KEY,
+    version       INTEGER  NOT NULL UNIQUE,
+    created_at    DATETIME NOT NULL,
+    actor         TEXT     NOT NULL,
+    action        TEXT     NOT NULL,
+    config        TEXT     NOT NULL,
+    restored_from INTEGER
+);
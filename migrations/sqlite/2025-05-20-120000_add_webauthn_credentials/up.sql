@@ -0,0 +1,13 @@
+CREATE TABLE webauthn_credentials (
+    uuid                  TEXT     NOT NULL PRIMARY KEY,
+    user_uuid             TEXT     NOT NULL REFERENCES users (uuid),
+    name                  TEXT     NOT NULL,
+    credential_id         TEXT     NOT NULL UNIQUE,
+    credential            TEXT     NOT NULL,
+    supports_prf          BOOLEAN  NOT NULL DEFAULT 0,
+    encrypted_user_key    TEXT,
+    encrypted_public_key  TEXT,
+    encrypted_private_key TEXT,
+    created_at            DATETIME NOT NULL,
+    updated_at            DATETIME NOT NULL
+);
@@ -1,4 +1,5 @@
 use once_cell::sync::Lazy;
+use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
 use reqwest::Method;
 use serde::de::DeserializeOwned;
 use serde_json::Value;
@@ -7,7 +8,8 @@ use std::env;
 use rocket::serde::json::Json;
 use rocket::{
     form::Form,
-    http::{Cookie, CookieJar, MediaType, SameSite, Status},
+    http::{Cookie, CookieJar, Header, MediaType, SameSite, Status},
+    outcome::try_outcome,
     request::{FromRequest, Outcome, Request},
     response::{content::RawHtml as Html, Redirect},
     Catcher, Route,
@@ -25,18 +27,28 @@ use crate::{
     http_client::make_http_request,
     mail,
     util::{
-        container_base_image, format_naive_datetime_local, get_display_size, get_web_vault_version,
-        is_running_in_container, NumberOrString,
+        container_base_image, csv_field, format_date, format_naive_datetime_local, get_display_size,
+        get_web_vault_version, is_running_in_container, NumberOrString,
     },
     CONFIG, VERSION,
 };
 
+mod api;
+mod config_history;
+mod config_tools;
+mod user_details;
+mod user_import;
+
+fn is_admin_panel_enabled() -> bool {
+    CONFIG.disable_admin_token() || CONFIG.is_admin_token_set() || CONFIG.enable_admin_accounts()
+}
+
 pub fn routes() -> Vec<Route> {
-    if !CONFIG.disable_admin_token() && !CONFIG.is_admin_token_set() {
+    if !is_admin_panel_enabled() {
         return routes![admin_disabled];
     }
 
-    routes![
+    let mut routes = routes![
         get_users_json,
         get_user_json,
         get_user_by_mail_json,
@@ -49,7 +61,10 @@ pub fn routes() -> Vec<Route> {
         deauth_user,
         disable_user,
         enable_user,
+        unlock_user,
         remove_2fa,
+        bulk_user_action,
+        set_user_quota,
         update_membership_type,
         update_revision_users,
         post_config,
@@ -59,15 +74,38 @@ pub fn routes() -> Vec<Route> {
         users_overview,
         organizations_overview,
         delete_organization,
+        create_organization,
+        rename_organization,
+        set_organization_quota,
+        set_organization_owner,
         diagnostics,
         get_diagnostics_config,
         resend_user_invite,
         get_diagnostics_http,
-    ]
+        admin_accounts_overview,
+        create_admin_account,
+        update_admin_account,
+        delete_admin_account,
+        reset_admin_account_totp,
+        own_admin_account,
+        change_own_admin_password,
+        enable_own_admin_totp,
+        audit_log_overview,
+        export_audit_log,
+        api_tokens_overview,
+        create_api_token,
+        revoke_api_token,
+    ];
+    routes.append(&mut api::routes());
+    routes.append(&mut config_history::routes());
+    routes.append(&mut config_tools::routes());
+    routes.append(&mut user_details::routes());
+    routes.append(&mut user_import::routes());
+    routes
 }
 
 pub fn catchers() -> Vec<Catcher> {
-    if !CONFIG.disable_admin_token() && !CONFIG.is_admin_token_set() {
+    if !is_admin_panel_enabled() {
         catchers![]
     } else {
         catchers![admin_login]
@@ -89,16 +127,18 @@ static CAN_BACKUP: Lazy<bool> =
 
 #[get("/")]
 fn admin_disabled() -> &'static str {
-    "The admin panel is disabled, please configure the 'ADMIN_TOKEN' variable to enable it"
+    "The admin panel is disabled, please configure the 'ADMIN_TOKEN' or 'ENABLE_ADMIN_ACCOUNTS' variable to enable it"
 }
 
 const COOKIE_NAME: &str = "VW_ADMIN";
+// The subject of the sessions which were started with the `ADMIN_TOKEN`, the sessions of admin accounts use the account id
+const ADMIN_TOKEN_SUBJECT: &str = "admin_panel";
 const ADMIN_PATH: &str = "/admin";
 const DT_FMT: &str = "%Y-%m-%d %H:%M:%S %Z";
 
 const BASE_TEMPLATE: &str = "admin/base";
 
-const ACTING_ADMIN_USER: &str = "vaultwarden-admin-00000-000000000000";
+pub const ACTING_ADMIN_USER: &str = "vaultwarden-admin-00000-000000000000";
 pub const FAKE_ADMIN_UUID: &str = "00000000-0000-0000-0000-000000000000";
 
 fn admin_path() -> String {
@@ -143,7 +183,8 @@ enum AdminResponse {
 
 #[catch(401)]
 fn admin_login(request: &Request<'_>) -> ApiResult<Html<String>> {
-    if request.format() == Some(&MediaType::JSON) {
+    let is_api_request = request.uri().path().as_str().starts_with(&format!("{}/api/", admin_path()));
+    if is_api_request || request.format() == Some(&MediaType::JSON) {
         err_code!("Authorization failed.", Status::Unauthorized.code);
     }
     let redirect = request.segments::<std::path::PathBuf>(0..).unwrap_or_default().display().to_string();
@@ -157,6 +198,7 @@ fn render_admin_login(msg: Option<&str>, redirect: Option<String>) -> ApiResult<
         "page_content": "admin/login",
         "error": msg,
         "redirect": redirect,
+        "admin_accounts": CONFIG.enable_admin_accounts(),
         "urlpath": CONFIG.domain_path()
     });
 
@@ -167,16 +209,20 @@ fn render_admin_login(msg: Option<&str>, redirect: Option<String>) -> ApiResult<
 
 #[derive(FromForm)]
 struct LoginForm {
+    // The admin token, or the password when a username is given
     token: String,
+    username: Option<String>,
+    totp: Option<String>,
     redirect: Option<String>,
 }
 
 #[post("/", format = "application/x-www-form-urlencoded", data = "<data>")]
-fn post_admin_login(
+async fn post_admin_login(
     data: Form<LoginForm>,
     cookies: &CookieJar<'_>,
     ip: ClientIp,
     secure: Secure,
+    mut conn: DbConn,
 ) -> Result<Redirect, AdminResponse> {
     let data = data.into_inner();
     let redirect = data.redirect;
@@ -188,46 +234,104 @@ fn post_admin_login(
         )));
     }
 
-    // If the token is invalid, redirect to login page
-    if !_validate_token(&data.token) {
-        error!("Invalid admin token. IP: {}", ip.ip);
-        Err(AdminResponse::Unauthorized(render_admin_login(Some("Invalid admin token, please try again."), redirect)))
-    } else {
-        // If the token received is valid, generate JWT and save it as a cookie
-        let claims = generate_admin_claims();
-        let jwt = encode_jwt(&claims);
-
-        let cookie = Cookie::build((COOKIE_NAME, jwt))
-            .path(admin_path())
-            .max_age(time::Duration::minutes(CONFIG.admin_session_lifetime()))
-            .same_site(SameSite::Strict)
-            .http_only(true)
-            .secure(secure.https);
-
-        cookies.add(cookie);
-        if let Some(redirect) = redirect {
-            Ok(Redirect::to(format!("{}{}", admin_path(), redirect)))
-        } else {
-            Err(AdminResponse::Ok(render_admin_page()))
+    let username = data.username.as_deref().map(str::trim).filter(|u| !u.is_empty());
+    let session = match username {
+        Some(username) if CONFIG.enable_admin_accounts() => {
+            let Some(account) = _validate_account(username, &data.token, data.totp.as_deref(), &mut conn).await else {
+                error!("Invalid admin account login for '{username}'. IP: {}", ip.ip);
+                let entry = AdminAuditLog::new(username.to_lowercase(), ip.ip.to_string(), "login_failed");
+                save_audit_log(entry, None, &mut conn).await;
+                return Err(AdminResponse::Unauthorized(render_admin_login(
+                    Some("Invalid username, password or TOTP code, please try again."),
+                    redirect,
+                )));
+            };
+            AdminToken {
+                role: account.role(),
+                account: Some(account),
+                api_token: None,
+                ip,
+            }
+        }
+        // If the token is invalid, redirect to login page
+        _ if !_validate_token(&data.token) => {
+            error!("Invalid admin token. IP: {}", ip.ip);
+            save_audit_log(
+                AdminAuditLog::new(String::from("admin_token"), ip.ip.to_string(), "login_failed"),
+                None,
+                &mut conn,
+            )
+            .await;
+            return Err(AdminResponse::Unauthorized(render_admin_login(
+                Some("Invalid admin token, please try again."),
+                redirect,
+            )));
         }
+        _ => AdminToken {
+            role: AdminRole::Admin,
+            account: None,
+            api_token: None,
+            ip,
+        },
+    };
+
+    // If the token received is valid, generate JWT and save it as a cookie
+    let subject = match &session.account {
+        Some(account) => account.uuid.to_string(),
+        None => ADMIN_TOKEN_SUBJECT.to_string(),
+    };
+    session.audit("login", None, &mut conn).await;
+    let claims = generate_admin_claims(subject);
+    let jwt = encode_jwt(&claims);
+
+    let cookie = Cookie::build((COOKIE_NAME, jwt))
+        .path(admin_path())
+        .max_age(time::Duration::minutes(CONFIG.admin_session_lifetime()))
+        .same_site(SameSite::Strict)
+        .http_only(true)
+        .secure(secure.https);
+
+    cookies.add(cookie);
+    if let Some(redirect) = redirect {
+        Ok(Redirect::to(format!("{}{}", admin_path(), redirect)))
+    } else if session.role < AdminRole::Admin {
+        // Only full admins can see the settings
+        Ok(Redirect::to(format!("{}/users/overview", admin_path())))
+    } else {
+        Err(AdminResponse::Ok(render_admin_page(&session)))
     }
 }
 
+/// Checks the password and, when enabled, the TOTP code of an admin account
+async fn _validate_account(
+    username: &str,
+    password: &str,
+    totp: Option<&str>,
+    conn: &mut DbConn,
+) -> Option<AdminAccount> {
+    let mut account = AdminAccount::find_by_username(username, conn).await?;
+    if !account.enabled || !account.check_password(password) {
+        return None;
+    }
+
+    if account.totp_secret.is_some() && !account.check_totp(totp.map(str::trim).unwrap_or_default()) {
+        return None;
+    }
+
+    account.last_login_at = Some(chrono::Utc::now().naive_utc());
+    account.save(conn).await.ok()?;
+    Some(account)
+}
+
 fn _validate_token(token: &str) -> bool {
     match CONFIG.admin_token().as_ref() {
         None => false,
         Some(t) if t.starts_with("$argon2") => {
-            use argon2::password_hash::PasswordVerifier;
-            match argon2::password_hash::PasswordHash::new(t) {
-                Ok(h) => {
-                    // NOTE: hash params from `ADMIN_TOKEN` are used instead of what is configured in the `Argon2` instance.
-                    argon2::Argon2::default().verify_password(token.trim().as_ref(), &h).is_ok()
-                }
-                Err(e) => {
-                    error!("The configured Argon2 PHC in `ADMIN_TOKEN` is invalid: {e}");
-                    false
-                }
-            }
+            // NOTE: hash params from `ADMIN_TOKEN` are used instead of what is configured in the `Argon2` instance.
+            crate::crypto::verify_argon2(token.trim(), t).unwrap_or_else(|e| {
+                error!("The configured Argon2 PHC in `ADMIN_TOKEN` is invalid: {e}");
+                false
+            })
         }
         Some(t) => crate::crypto::ct_eq(t.trim(), token.trim()),
     }
@@ -239,15 +343,26 @@ struct AdminTemplateData {
     page_data: Option<Value>,
     logged_in: bool,
     urlpath: String,
+    admin_username: Option<String>,
+    admin_role: &'static str,
+    // Used to only show the actions which are allowed for the role
+    can_support: bool,
+    is_full_admin: bool,
+    admin_accounts: bool,
 }
 
 impl AdminTemplateData {
-    fn new(page_content: &str, page_data: Value) -> Self {
+    fn new(page_content: &str, page_data: Value, token: &AdminToken) -> Self {
         Self {
             page_content: String::from(page_content),
             page_data: Some(page_data),
             logged_in: true,
             urlpath: CONFIG.domain_path(),
+            admin_username: token.account.as_ref().map(|a| a.username.clone()),
+            admin_role: token.role.name(),
+            can_support: token.role >= AdminRole::UserSupport,
+            is_full_admin: token.role >= AdminRole::Admin,
+            admin_accounts: CONFIG.enable_admin_accounts(),
         }
     }
 
@@ -256,18 +371,23 @@ impl AdminTemplateData {
     }
 }
 
-fn render_admin_page() -> ApiResult<Html<String>> {
+fn render_admin_page(token: &AdminToken) -> ApiResult<Html<String>> {
     let settings_json = json!({
         "config": CONFIG.prepare_json(),
         "can_backup": *CAN_BACKUP,
+        "config_history_versions": ConfigHistory::MAX_VERSIONS,
     });
-    let text = AdminTemplateData::new("admin/settings", settings_json).render()?;
+    let text = AdminTemplateData::new("admin/settings", settings_json, token).render()?;
     Ok(Html(text))
 }
 
 #[get("/")]
-fn admin_page(_token: AdminToken) -> ApiResult<Html<String>> {
-    render_admin_page()
+fn admin_page(token: AdminReadToken) -> Result<ApiResult<Html<String>>, Redirect> {
+    if token.role < AdminRole::Admin {
+        // Only full admins can see the settings
+        return Err(Redirect::to(format!("{}/users/overview", admin_path())));
+    }
+    Ok(render_admin_page(&token))
 }
 
 #[get("/", rank = 2)]
@@ -290,7 +410,7 @@ async fn get_user_or_404(user_id: &UserId, conn: &mut DbConn) -> ApiResult<User>
 }
 
 #[post("/invite", format = "application/json", data = "<data>")]
-async fn invite_user(data: Json<InviteData>, _token: AdminToken, mut conn: DbConn) -> JsonResult {
+async fn invite_user(data: Json<InviteData>, token: AdminToken, mut conn: DbConn) -> JsonResult {
     let data: InviteData = data.into_inner();
     if User::find_by_mail(&data.email, &mut conn).await.is_some() {
         err_code!("User already exists", Status::Conflict.code)
@@ -311,6 +431,7 @@ async fn invite_user(data: Json<InviteData>, _token: AdminToken, mut conn: DbCon
 
     _generate_invite(&user, &mut conn).await.map_err(|e| e.with_code(Status::InternalServerError.code))?;
     user.save(&mut conn).await.map_err(|e| e.with_code(Status::InternalServerError.code))?;
+    token.audit_target("invite_user", "user", &user.uuid, Some(json!({"email": user.email})), &mut conn).await;
 
     Ok(Json(user.to_json(&mut conn).await))
 }
@@ -333,17 +454,20 @@ fn logout(cookies: &CookieJar<'_>) -> Redirect {
 }
 
 #[get("/users")]
-async fn get_users_json(_token: AdminToken, mut conn: DbConn) -> Json<Value> {
+async fn get_users_json(_token: AdminReadToken, mut conn: DbConn) -> Json<Value> {
     let users = User::get_all(&mut conn).await;
     let mut users_json = Vec::with_capacity(users.len());
     for u in users {
         let mut usr = u.to_json(&mut conn).await;
         usr["userEnabled"] = json!(u.enabled);
+        usr["loginLockedUntil"] = json!(u.login_locked_until().map(|dt| format_naive_datetime_local(&dt, DT_FMT)));
         usr["createdAt"] = json!(format_naive_datetime_local(&u.created_at, DT_FMT));
         usr["lastActive"] = match u.last_active(&mut conn).await {
             Some(dt) => json!(format_naive_datetime_local(&dt, DT_FMT)),
             None => json!(None::<String>),
         };
+        usr["lastLogin"] = json!(u.last_login_at.map(|dt| format_naive_datetime_local(&dt, DT_FMT)));
+        usr["lastSync"] = json!(u.last_sync_at.map(|dt| format_naive_datetime_local(&dt, DT_FMT)));
         users_json.push(usr);
     }
 
@@ -351,32 +475,45 @@ async fn get_users_json(_token: AdminToken, mut conn: DbConn) -> Json<Value> {
 }
 
 #[get("/users/overview")]
-async fn users_overview(_token: AdminToken, mut conn: DbConn) -> ApiResult<Html<String>> {
+async fn users_overview(token: AdminReadToken, mut conn: DbConn) -> ApiResult<Html<String>> {
     let users = User::get_all(&mut conn).await;
     let mut users_json = Vec::with_capacity(users.len());
     for u in users {
         let mut usr = u.to_json(&mut conn).await;
         usr["cipher_count"] = json!(Cipher::count_owned_by_user(&u.uuid, &mut conn).await);
         usr["attachment_count"] = json!(Attachment::count_by_user(&u.uuid, &mut conn).await);
-        usr["attachment_size"] = json!(get_display_size(Attachment::size_by_user(&u.uuid, &mut conn).await));
+        let attachment_size = Attachment::size_by_user(&u.uuid, &mut conn).await;
+        usr["attachment_size"] = json!(get_display_size(attachment_size));
+        usr["attachment_usage"] =
+            storage_usage_json(attachment_size, u.attachment_limit_kb(), u.attachment_quota_kb.is_some());
+        usr["attachment_quota_kb"] = json!(u.attachment_quota_kb);
+        let send_size = Send::size_by_user(&u.uuid, &mut conn).await.unwrap_or_default();
+        usr["send_usage"] = storage_usage_json(send_size, u.send_limit_kb(), u.send_quota_kb.is_some());
+        usr["send_quota_kb"] = json!(u.send_quota_kb);
         usr["user_enabled"] = json!(u.enabled);
+        usr["login_locked_until"] = json!(u.login_locked_until().map(|dt| format_naive_datetime_local(&dt, DT_FMT)));
         usr["created_at"] = json!(format_naive_datetime_local(&u.created_at, DT_FMT));
         usr["last_active"] = match u.last_active(&mut conn).await {
             Some(dt) => json!(format_naive_datetime_local(&dt, DT_FMT)),
             None => json!("Never"),
         };
+        usr["last_login"] = json!(u.last_login_at.map(|dt| format_naive_datetime_local(&dt, DT_FMT)));
+        usr["last_sync"] = json!(u.last_sync_at.map(|dt| format_naive_datetime_local(&dt, DT_FMT)));
+        let last_activity = u.last_activity(&mut conn).await;
+        usr["inactivity_warned"] = json!(u.inactivity_warned_at.is_some_and(|w| w > last_activity));
         users_json.push(usr);
     }
 
-    let text = AdminTemplateData::new("admin/users", json!(users_json)).render()?;
+    let text = AdminTemplateData::new("admin/users", json!(users_json), &token).render()?;
     Ok(Html(text))
 }
 
 #[get("/users/by-mail/<mail>")]
-async fn get_user_by_mail_json(mail: &str, _token: AdminToken, mut conn: DbConn) -> JsonResult {
+async fn get_user_by_mail_json(mail: &str, _token: AdminReadToken, mut conn: DbConn) -> JsonResult {
     if let Some(u) = User::find_by_mail(mail, &mut conn).await {
         let mut usr = u.to_json(&mut conn).await;
         usr["userEnabled"] = json!(u.enabled);
+        usr["loginLockedUntil"] = json!(u.login_locked_until().map(|dt| format_naive_datetime_local(&dt, DT_FMT)));
         usr["createdAt"] = json!(format_naive_datetime_local(&u.created_at, DT_FMT));
         Ok(Json(usr))
     } else {
@@ -385,21 +522,28 @@ async fn get_user_by_mail_json(mail: &str, _token: AdminToken, mut conn: DbConn)
 }
 
 #[get("/users/<user_id>")]
-async fn get_user_json(user_id: UserId, _token: AdminToken, mut conn: DbConn) -> JsonResult {
+async fn get_user_json(user_id: UserId, _token: AdminReadToken, mut conn: DbConn) -> JsonResult {
     let u = get_user_or_404(&user_id, &mut conn).await?;
     let mut usr = u.to_json(&mut conn).await;
     usr["userEnabled"] = json!(u.enabled);
+    usr["loginLockedUntil"] = json!(u.login_locked_until().map(|dt| format_naive_datetime_local(&dt, DT_FMT)));
     usr["createdAt"] = json!(format_naive_datetime_local(&u.created_at, DT_FMT));
     Ok(Json(usr))
 }
 
 #[post("/users/<user_id>/delete", format = "application/json")]
 async fn delete_user(user_id: UserId, token: AdminToken, mut conn: DbConn) -> EmptyResult {
-    let user = get_user_or_404(&user_id, &mut conn).await?;
+    _delete_user(&user_id, &token, &mut conn).await
+}
+
+async fn _delete_user(user_id: &UserId, token: &AdminToken, conn: &mut DbConn) -> EmptyResult {
+    let user = get_user_or_404(user_id, conn).await?;
 
     // Get the membership records before deleting the actual user
-    let memberships = Membership::find_any_state_by_user(&user_id, &mut conn).await;
-    let res = user.delete(&mut conn).await;
+    let memberships = Membership::find_any_state_by_user(user_id, conn).await;
+    let email = user.email.clone();
+    user.delete(conn).await?;
+    token.audit_target("delete_user", "user", user_id, Some(json!({"email": email})), conn).await;
 
     for membership in memberships {
         log_event(
@@ -409,22 +553,26 @@ async fn delete_user(user_id: UserId, token: AdminToken, mut conn: DbConn) -> Em
             &ACTING_ADMIN_USER.into(),
             14, // Use UnknownBrowser type
             &token.ip.ip,
-            &mut conn,
+            conn,
         )
         .await;
     }
 
-    res
+    Ok(())
 }
 
 #[post("/users/<user_id>/deauth", format = "application/json")]
-async fn deauth_user(user_id: UserId, _token: AdminToken, mut conn: DbConn, nt: Notify<'_>) -> EmptyResult {
-    let mut user = get_user_or_404(&user_id, &mut conn).await?;
+async fn deauth_user(user_id: UserId, token: AdminSupportToken, mut conn: DbConn, nt: Notify<'_>) -> EmptyResult {
+    _deauth_user(&user_id, &token, &mut conn, nt).await
+}
+
+async fn _deauth_user(user_id: &UserId, token: &AdminToken, conn: &mut DbConn, nt: Notify<'_>) -> EmptyResult {
+    let mut user = get_user_or_404(user_id, conn).await?;
 
     nt.send_logout(&user, None).await;
 
     if CONFIG.push_enabled() {
-        for device in Device::find_push_devices_by_user(&user.uuid, &mut conn).await {
+        for device in Device::find_push_devices_by_user(&user.uuid, conn).await {
             match unregister_push_device(device.push_uuid).await {
                 Ok(r) => r,
                 Err(e) => error!("Unable to unregister devices from Bitwarden server: {}", e),
@@ -432,32 +580,104 @@ async fn deauth_user(user_id: UserId, _token: AdminToken, mut conn: DbConn, nt:
         }
     }
 
-    Device::delete_all_by_user(&user.uuid, &mut conn).await?;
+    Device::delete_all_by_user(&user.uuid, conn).await?;
     user.reset_security_stamp();
 
-    user.save(&mut conn).await
+    user.save(conn).await?;
+    token.audit_target("deauth_user", "user", &user.uuid, None, conn).await;
+    Ok(())
 }
 
 #[post("/users/<user_id>/disable", format = "application/json")]
-async fn disable_user(user_id: UserId, _token: AdminToken, mut conn: DbConn, nt: Notify<'_>) -> EmptyResult {
-    let mut user = get_user_or_404(&user_id, &mut conn).await?;
-    Device::delete_all_by_user(&user.uuid, &mut conn).await?;
+async fn disable_user(user_id: UserId, token: AdminSupportToken, mut conn: DbConn, nt: Notify<'_>) -> EmptyResult {
+    _disable_user(&user_id, &token, &mut conn, nt).await
+}
+
+async fn _disable_user(user_id: &UserId, token: &AdminToken, conn: &mut DbConn, nt: Notify<'_>) -> EmptyResult {
+    let mut user = get_user_or_404(user_id, conn).await?;
+    Device::delete_all_by_user(&user.uuid, conn).await?;
     user.reset_security_stamp();
     user.enabled = false;
 
-    let save_result = user.save(&mut conn).await;
+    let save_result = user.save(conn).await;
 
     nt.send_logout(&user, None).await;
 
-    save_result
+    save_result?;
+    token.audit_target("disable_user", "user", &user.uuid, None, conn).await;
+    Ok(())
 }
 
 #[post("/users/<user_id>/enable", format = "application/json")]
-async fn enable_user(user_id: UserId, _token: AdminToken, mut conn: DbConn) -> EmptyResult {
-    let mut user = get_user_or_404(&user_id, &mut conn).await?;
+async fn enable_user(user_id: UserId, token: AdminSupportToken, mut conn: DbConn) -> EmptyResult {
+    _enable_user(&user_id, &token, &mut conn).await
+}
+
+async fn _enable_user(user_id: &UserId, token: &AdminToken, conn: &mut DbConn) -> EmptyResult {
+    let mut user = get_user_or_404(user_id, conn).await?;
     user.enabled = true;
 
-    user.save(&mut conn).await
+    user.save(conn).await?;
+    token.audit_target("enable_user", "user", &user.uuid, None, conn).await;
+    Ok(())
+}
+
+#[post("/users/<user_id>/unlock", format = "application/json")]
+async fn unlock_user(user_id: UserId, token: AdminSupportToken, mut conn: DbConn) -> EmptyResult {
+    _unlock_user(&user_id, &token, &mut conn).await
+}
+
+/// Lifts a lockout after too many failed logins, and clears the failed login count
+async fn _unlock_user(user_id: &UserId, token: &AdminToken, conn: &mut DbConn) -> EmptyResult {
+    let mut user = get_user_or_404(user_id, conn).await?;
+    user.reset_failed_logins();
+
+    user.save_failed_logins(conn).await?;
+    token.audit_target("unlock_user", "user", &user.uuid, None, conn).await;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct BulkUserData {
+    action: String, // One of delete, deauth, disable or enable
+    user_ids: Vec<UserId>,
+}
+
+/// Applies an action to several users, returns the error of each user which failed
+#[post("/users/bulk", format = "application/json", data = "<data>")]
+async fn bulk_user_action(
+    data: Json<BulkUserData>,
+    token: AdminSupportToken,
+    mut conn: DbConn,
+    nt: Notify<'_>,
+) -> JsonResult {
+    let data: BulkUserData = data.into_inner();
+    if !["delete", "deauth", "disable", "enable"].contains(&data.action.as_str()) {
+        err!("Invalid action")
+    }
+    if data.action == "delete" && token.role < AdminRole::Admin {
+        err_code!("Your admin role does not allow this action", Status::Forbidden.code)
+    }
+
+    let mut bulk_response = Vec::with_capacity(data.user_ids.len());
+    for user_id in data.user_ids {
+        let result = match data.action.as_str() {
+            "delete" => _delete_user(&user_id, &token, &mut conn).await,
+            "deauth" => _deauth_user(&user_id, &token, &mut conn, nt).await,
+            "disable" => _disable_user(&user_id, &token, &mut conn, nt).await,
+            _ => _enable_user(&user_id, &token, &mut conn).await,
+        };
+        let err_msg = match result {
+            Ok(_) => String::new(),
+            Err(e) => format!("{e:?}"),
+        };
+        bulk_response.push(json!({
+            "id": user_id,
+            "error": err_msg,
+        }));
+    }
+
+    Ok(Json(Value::Array(bulk_response)))
 }
 
 #[post("/users/<user_id>/remove-2fa", format = "application/json")]
@@ -466,11 +686,13 @@ async fn remove_2fa(user_id: UserId, token: AdminToken, mut conn: DbConn) -> Emp
     TwoFactor::delete_all_by_user(&user.uuid, &mut conn).await?;
     two_factor::enforce_2fa_policy(&user, &ACTING_ADMIN_USER.into(), 14, &token.ip.ip, &mut conn).await?;
     user.totp_recover = None;
-    user.save(&mut conn).await
+    user.save(&mut conn).await?;
+    token.audit_target("remove_2fa", "user", &user.uuid, None, &mut conn).await;
+    Ok(())
 }
 
 #[post("/users/<user_id>/invite/resend", format = "application/json")]
-async fn resend_user_invite(user_id: UserId, _token: AdminToken, mut conn: DbConn) -> EmptyResult {
+async fn resend_user_invite(user_id: UserId, token: AdminSupportToken, mut conn: DbConn) -> EmptyResult {
     if let Some(user) = User::find_by_uuid(&user_id, &mut conn).await {
         //TODO: replace this with user.status check when it will be available (PR#3397)
         if !user.password_hash.is_empty() {
@@ -480,10 +702,10 @@ async fn resend_user_invite(user_id: UserId, _token: AdminToken, mut conn: DbCon
         if CONFIG.mail_enabled() {
             let org_id: OrganizationId = FAKE_ADMIN_UUID.to_string().into();
             let member_id: MembershipId = FAKE_ADMIN_UUID.to_string().into();
-            mail::send_invite(&user, org_id, member_id, &CONFIG.invitation_org_name(), None).await
-        } else {
-            Ok(())
+            mail::send_invite(&user, org_id, member_id, &CONFIG.invitation_org_name(), None).await?;
+            token.audit_target("resend_invite", "user", &user.uuid, None, &mut conn).await;
         }
+        Ok(())
     } else {
         err_code!("User doesn't exist", Status::NotFound.code);
     }
@@ -510,11 +732,8 @@ async fn update_membership_type(data: Json<MembershipTypeData>, token: AdminToke
         None => err!("Invalid type"),
     };
 
-    if member_to_edit.atype == MembershipType::Owner && new_type != MembershipType::Owner {
-        // Removing owner permission, check that there is at least one other confirmed owner
-        if Membership::count_confirmed_by_org_and_type(&data.org_uuid, MembershipType::Owner, &mut conn).await <= 1 {
-            err!("Can't change the type of the last owner")
-        }
+    if new_type != MembershipType::Owner {
+        check_not_last_owner(&member_to_edit, &mut conn).await?;
     }
 
     // This check is also done at api::organizations::{accept_invite, _confirm_invite, _activate_member, edit_member}, update_membership_type
@@ -546,39 +765,389 @@ async fn update_membership_type(data: Json<MembershipTypeData>, token: AdminToke
     )
     .await;
 
+    let old_type = member_to_edit.atype;
     member_to_edit.atype = new_type;
-    member_to_edit.save(&mut conn).await
+    member_to_edit.save(&mut conn).await?;
+    token
+        .audit_target("update_membership_type", "membership", &member_to_edit.uuid,
+            Some(json!({"user": data.user_uuid, "organization": data.org_uuid, "old_type": old_type, "new_type": new_type})),
+            &mut conn,
+        )
+        .await;
+    Ok(())
+}
+
+/// Errors when removing the owner permission from this member would leave the organization without a confirmed owner
+async fn check_not_last_owner(member: &Membership, conn: &mut DbConn) -> EmptyResult {
+    if member.atype == MembershipType::Owner
+        && member.status == MembershipStatus::Confirmed as i32
+        && Membership::count_confirmed_by_org_and_type(&member.org_uuid, MembershipType::Owner, conn).await <= 1
+    {
+        err!("Can't change the type of the last owner")
+    }
+    Ok(())
+}
+
+/// Usage bar data of a storage limit in KB, `custom` tells if the limit comes from a quota set by an admin
+fn storage_usage_json(used: i64, limit_kb: Option<i64>, custom: bool) -> Value {
+    let Some(limit_kb) = limit_kb else {
+        return json!({
+            "used": get_display_size(used),
+            "limited": false,
+            "custom": custom,
+        });
+    };
+    let limit = limit_kb.saturating_mul(1024);
+    let percent = if limit == 0 {
+        100
+    } else {
+        (i128::from(used) * 100 / i128::from(limit)).clamp(0, 100) as i64
+    };
+    json!({
+        "used": get_display_size(used),
+        "limited": true,
+        "custom": custom,
+        "limit": get_display_size(limit),
+        "percent": percent,
+        "level": match percent {
+            0..=74 => "success",
+            75..=89 => "warning",
+            _ => "danger",
+        },
+    })
+}
+
+/// Errors when a quota in KB is outside of the range allowed for the global limits
+fn check_quota_kb(quota_kb: Option<i64>) -> EmptyResult {
+    const MAX_QUOTA_KB: i64 = i64::MAX >> 10;
+    if quota_kb.is_some_and(|q| !(0..=MAX_QUOTA_KB).contains(&q)) {
+        err!("The storage quota is out of bounds")
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct UserQuotaData {
+    attachment_quota_kb: Option<i64>,
+    send_quota_kb: Option<i64>,
+}
+
+#[post("/users/<user_id>/quota", format = "application/json", data = "<data>")]
+async fn set_user_quota(
+    user_id: UserId,
+    data: Json<UserQuotaData>,
+    token: AdminToken,
+    mut conn: DbConn,
+) -> EmptyResult {
+    let data = data.into_inner();
+    check_quota_kb(data.attachment_quota_kb)?;
+    check_quota_kb(data.send_quota_kb)?;
+
+    let mut user = get_user_or_404(&user_id, &mut conn).await?;
+    let old = json!({"attachment_quota_kb": user.attachment_quota_kb, "send_quota_kb": user.send_quota_kb});
+    user.attachment_quota_kb = data.attachment_quota_kb;
+    user.send_quota_kb = data.send_quota_kb;
+    user.save(&mut conn).await?;
+
+    token
+        .audit_target(
+            "set_user_quota",
+            "user",
+            &user_id,
+            Some(json!({
+                "email": user.email,
+                "old": old,
+                "new": {"attachment_quota_kb": user.attachment_quota_kb, "send_quota_kb": user.send_quota_kb},
+            })),
+            &mut conn,
+        )
+        .await;
+    Ok(())
 }
 
 #[post("/users/update_revision", format = "application/json")]
-async fn update_revision_users(_token: AdminToken, mut conn: DbConn) -> EmptyResult {
-    User::update_all_revisions(&mut conn).await
+async fn update_revision_users(token: AdminToken, mut conn: DbConn) -> EmptyResult {
+    User::update_all_revisions(&mut conn).await?;
+    token.audit("update_revision_users", None, &mut conn).await;
+    Ok(())
 }
 
-#[get("/organizations/overview")]
-async fn organizations_overview(_token: AdminToken, mut conn: DbConn) -> ApiResult<Html<String>> {
-    let organizations = Organization::get_all(&mut conn).await;
+async fn get_organizations_json(conn: &mut DbConn) -> Vec<Value> {
+    let organizations = Organization::get_all(conn).await;
     let mut organizations_json = Vec::with_capacity(organizations.len());
     for o in organizations {
         let mut org = o.to_json();
-        org["user_count"] = json!(Membership::count_by_org(&o.uuid, &mut conn).await);
-        org["cipher_count"] = json!(Cipher::count_by_org(&o.uuid, &mut conn).await);
-        org["collection_count"] = json!(Collection::count_by_org(&o.uuid, &mut conn).await);
-        org["group_count"] = json!(Group::count_by_org(&o.uuid, &mut conn).await);
-        org["event_count"] = json!(Event::count_by_org(&o.uuid, &mut conn).await);
-        org["attachment_count"] = json!(Attachment::count_by_org(&o.uuid, &mut conn).await);
-        org["attachment_size"] = json!(get_display_size(Attachment::size_by_org(&o.uuid, &mut conn).await));
+        org["user_count"] = json!(Membership::count_by_org(&o.uuid, conn).await);
+        org["cipher_count"] = json!(Cipher::count_by_org(&o.uuid, conn).await);
+        org["collection_count"] = json!(Collection::count_by_org(&o.uuid, conn).await);
+        org["group_count"] = json!(Group::count_by_org(&o.uuid, conn).await);
+        org["event_count"] = json!(Event::count_by_org(&o.uuid, conn).await);
+        org["attachment_count"] = json!(Attachment::count_by_org(&o.uuid, conn).await);
+        let attachment_size = Attachment::size_by_org(&o.uuid, conn).await;
+        org["attachment_size"] = json!(get_display_size(attachment_size));
+        org["attachment_usage"] =
+            storage_usage_json(attachment_size, o.attachment_limit_kb(), o.attachment_quota_kb.is_some());
+        org["attachment_quota_kb"] = json!(o.attachment_quota_kb);
+        let mut owners = Vec::new();
+        for member in Membership::find_by_org_and_type(&o.uuid, MembershipType::Owner, conn).await {
+            if let Some(user) = User::find_by_uuid(&member.user_uuid, conn).await {
+                owners.push(json!({"email": user.email, "status": member.status}));
+            }
+        }
+        org["owners"] = json!(owners);
+        org["initialized"] = json!(o.public_key.is_some());
         organizations_json.push(org);
     }
+    organizations_json
+}
+
+#[get("/organizations/overview")]
+async fn organizations_overview(token: AdminReadToken, mut conn: DbConn) -> ApiResult<Html<String>> {
+    let organizations_json = get_organizations_json(&mut conn).await;
 
-    let text = AdminTemplateData::new("admin/organizations", json!(organizations_json)).render()?;
+    let text = AdminTemplateData::new("admin/organizations", json!(organizations_json), &token).render()?;
     Ok(Html(text))
 }
 
 #[post("/organizations/<org_id>/delete", format = "application/json")]
-async fn delete_organization(org_id: OrganizationId, _token: AdminToken, mut conn: DbConn) -> EmptyResult {
+async fn delete_organization(org_id: OrganizationId, token: AdminToken, mut conn: DbConn) -> EmptyResult {
     let org = Organization::find_by_uuid(&org_id, &mut conn).await.map_res("Organization doesn't exist")?;
-    org.delete(&mut conn).await
+    let name = org.name.clone();
+    org.delete(&mut conn).await?;
+    token.audit_target("delete_organization", "organization", &org_id, Some(json!({"name": name})), &mut conn).await;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateOrganizationData {
+    name: String,
+    billing_email: Option<String>,
+    owner_email: String,
+}
+
+/// Creates an organization without keys, they are created by the owner when accepting the invitation in the web vault.
+/// When mail is disabled the invitation link is returned, so it can be shared with the owner.
+#[post("/organizations", format = "application/json", data = "<data>")]
+async fn create_organization(data: Json<CreateOrganizationData>, token: AdminToken, mut conn: DbConn) -> JsonResult {
+    let data: CreateOrganizationData = data.into_inner();
+    let name = data.name.trim().to_string();
+    if name.is_empty() {
+        err!("The organization name can't be empty")
+    }
+    let owner_email = data.owner_email.trim().to_lowercase();
+    if !crate::util::is_valid_email(&owner_email) {
+        err!("The owner email is not a valid email address")
+    }
+    let billing_email = match data.billing_email.map(|e| e.trim().to_lowercase()).filter(|e| !e.is_empty()) {
+        Some(email) => email,
+        None => owner_email.clone(),
+    };
+
+    let owner = match User::find_by_mail(&owner_email, &mut conn).await {
+        Some(user) => user,
+        None => {
+            let mut user = User::new(owner_email.clone());
+            user.save(&mut conn).await?;
+            if !CONFIG.mail_enabled() {
+                Invitation::new(&owner_email).save(&mut conn).await?;
+            }
+            user
+        }
+    };
+
+    let org = Organization::new(name, billing_email, None, None);
+    let mut member = Membership::new(owner.uuid.clone(), org.uuid.clone());
+    member.access_all = true;
+    member.atype = MembershipType::Owner as i32;
+    member.status = MembershipStatus::Invited as i32;
+
+    org.save(&mut conn).await?;
+    member.save(&mut conn).await?;
+
+    let invite_url = if CONFIG.mail_enabled() {
+        mail::send_org_owner_invite(&owner, org.uuid.clone(), member.uuid.clone(), &org.name).await?;
+        None
+    } else {
+        Some(mail::org_invite_url(&owner, org.uuid.clone(), member.uuid.clone(), &org.name, None, true)?)
+    };
+
+    token
+        .audit_target(
+            "create_organization",
+            "organization",
+            &org.uuid,
+            Some(json!({"name": org.name, "owner": owner.email})),
+            &mut conn,
+        )
+        .await;
+
+    let mut org_json = org.to_json();
+    org_json["invite_url"] = json!(invite_url);
+    Ok(Json(org_json))
+}
+
+#[derive(Debug, Deserialize)]
+struct RenameOrganizationData {
+    name: String,
+}
+
+#[post("/organizations/<org_id>/rename", format = "application/json", data = "<data>")]
+async fn rename_organization(
+    org_id: OrganizationId,
+    data: Json<RenameOrganizationData>,
+    token: AdminToken,
+    mut conn: DbConn,
+) -> EmptyResult {
+    let name = data.into_inner().name.trim().to_string();
+    if name.is_empty() {
+        err!("The organization name can't be empty")
+    }
+    let mut org = Organization::find_by_uuid(&org_id, &mut conn).await.map_res("Organization doesn't exist")?;
+    let old_name = std::mem::replace(&mut org.name, name);
+    org.save(&mut conn).await?;
+
+    log_event(
+        EventType::OrganizationUpdated as i32,
+        org_id.as_ref(),
+        &org_id,
+        &ACTING_ADMIN_USER.into(),
+        14, // Use UnknownBrowser type
+        &token.ip.ip,
+        &mut conn,
+    )
+    .await;
+
+    token
+        .audit_target(
+            "rename_organization",
+            "organization",
+            &org_id,
+            Some(json!({"old_name": old_name, "new_name": org.name})),
+            &mut conn,
+        )
+        .await;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct OrganizationQuotaData {
+    attachment_quota_kb: Option<i64>,
+}
+
+#[post("/organizations/<org_id>/quota", format = "application/json", data = "<data>")]
+async fn set_organization_quota(
+    org_id: OrganizationId,
+    data: Json<OrganizationQuotaData>,
+    token: AdminToken,
+    mut conn: DbConn,
+) -> EmptyResult {
+    let data = data.into_inner();
+    check_quota_kb(data.attachment_quota_kb)?;
+
+    let mut org = Organization::find_by_uuid(&org_id, &mut conn).await.map_res("Organization doesn't exist")?;
+    let old_quota = std::mem::replace(&mut org.attachment_quota_kb, data.attachment_quota_kb);
+    org.save(&mut conn).await?;
+
+    token
+        .audit_target(
+            "set_organization_quota",
+            "organization",
+            &org_id,
+            Some(json!({"name": org.name, "old_attachment_quota_kb": old_quota, "new_attachment_quota_kb": org.attachment_quota_kb})),
+            &mut conn,
+        )
+        .await;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct OrganizationOwnerData {
+    email: String,
+    #[serde(default)]
+    transfer: bool, // Demote the current owners to admins
+}
+
+/// Makes a member an owner, and with `transfer` the current owners become admins.
+/// The promotion and the demotions are saved in a single transaction.
+#[post("/organizations/<org_id>/owner", format = "application/json", data = "<data>")]
+async fn set_organization_owner(
+    org_id: OrganizationId,
+    data: Json<OrganizationOwnerData>,
+    token: AdminToken,
+    mut conn: DbConn,
+) -> EmptyResult {
+    let data: OrganizationOwnerData = data.into_inner();
+    if Organization::find_by_uuid(&org_id, &mut conn).await.is_none() {
+        err_code!("Organization doesn't exist", Status::NotFound.code)
+    }
+    let Some(mut new_owner) = Membership::find_by_email_and_org(data.email.trim(), &org_id, &mut conn).await else {
+        err!("The specified user isn't member of the organization")
+    };
+    if new_owner.status < MembershipStatus::Invited as i32 {
+        err!("The specified user has been revoked from the organization")
+    }
+    if data.transfer && new_owner.status != MembershipStatus::Confirmed as i32 {
+        err!("The ownership can only be transferred to a confirmed member")
+    }
+    if !data.transfer && new_owner.atype == MembershipType::Owner {
+        err!("The specified user is already an owner of the organization")
+    }
+
+    let demoted_owners: Vec<Membership> = if data.transfer {
+        Membership::find_by_org_and_type(&org_id, MembershipType::Owner, &mut conn)
+            .await
+            .into_iter()
+            .filter(|m| m.uuid != new_owner.uuid)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    // Check once, before anything is written, that the organization keeps a confirmed owner
+    let confirmed_owners = Membership::count_confirmed_by_org_and_type(&org_id, MembershipType::Owner, &mut conn).await;
+    let remaining_owners = i64::from(new_owner.status == MembershipStatus::Confirmed as i32)
+        + if data.transfer {
+            0
+        } else {
+            confirmed_owners
+        };
+    if confirmed_owners > 0 && remaining_owners == 0 {
+        err!("Can't change the type of the last owner")
+    }
+
+    let old_type = new_owner.atype;
+    Membership::transfer_ownership(&new_owner, &demoted_owners, &mut conn).await?;
+    new_owner.atype = MembershipType::Owner as i32;
+
+    for member in std::iter::once(&new_owner).chain(&demoted_owners) {
+        log_event(
+            EventType::OrganizationUserUpdated as i32,
+            &member.uuid,
+            &org_id,
+            &ACTING_ADMIN_USER.into(),
+            14, // Use UnknownBrowser type
+            &token.ip.ip,
+            &mut conn,
+        )
+        .await;
+    }
+    let demoted: Vec<&MembershipId> = demoted_owners.iter().map(|m| &m.uuid).collect();
+
+    let action = if data.transfer {
+        "transfer_organization_ownership"
+    } else {
+        "promote_organization_owner"
+    };
+    token
+        .audit_target(
+            action,
+            "organization",
+            &org_id,
+            Some(json!({"new_owner": new_owner.uuid, "old_type": old_type, "demoted_owners": demoted})),
+            &mut conn,
+        )
+        .await;
+    Ok(())
 }
 
 #[derive(Deserialize)]
@@ -675,7 +1244,7 @@ async fn get_ntp_time(has_http_access: bool) -> String {
 }
 
 #[get("/diagnostics")]
-async fn diagnostics(_token: AdminToken, ip_header: IpHeader, mut conn: DbConn) -> ApiResult<Html<String>> {
+async fn diagnostics(token: AdminReadToken, ip_header: IpHeader, mut conn: DbConn) -> ApiResult<Html<String>> {
     use chrono::prelude::*;
     use std::net::ToSocketAddrs;
 
@@ -720,6 +1289,10 @@ async fn diagnostics(_token: AdminToken, ip_header: IpHeader, mut conn: DbConn)
         "enable_websocket": &CONFIG.enable_websocket(),
         "db_type": *DB_TYPE,
         "db_version": get_sql_server_version(&mut conn).await,
+        "storage_backend": crate::storage::storage().name(),
+        "backup_schedule": CONFIG.backup_schedule(),
+        "backup_folder": CONFIG.backup_folder(),
+        "backups": crate::backup::list_backups().await.unwrap_or_default(),
         "admin_url": format!("{}/diagnostics", admin_url()),
         "overrides": &CONFIG.get_overrides().join(", "),
         "host_arch": env::consts::ARCH,
@@ -729,43 +1302,68 @@ async fn diagnostics(_token: AdminToken, ip_header: IpHeader, mut conn: DbConn)
         "ntp_time": get_ntp_time(has_http_access).await, // Run the ntp check as late as possible to minimize the time difference
     });
 
-    let text = AdminTemplateData::new("admin/diagnostics", diagnostics_json).render()?;
+    let text = AdminTemplateData::new("admin/diagnostics", diagnostics_json, &token).render()?;
     Ok(Html(text))
 }
 
 #[get("/diagnostics/config", format = "application/json")]
-fn get_diagnostics_config(_token: AdminToken) -> Json<Value> {
+fn get_diagnostics_config(_token: AdminReadToken) -> Json<Value> {
     let support_json = CONFIG.get_support_json();
     Json(support_json)
 }
 
 #[get("/diagnostics/http?<code>")]
-fn get_diagnostics_http(code: u16, _token: AdminToken) -> EmptyResult {
+fn get_diagnostics_http(code: u16, _token: AdminReadToken) -> EmptyResult {
     err_code!(format!("Testing error {code} response"), code);
 }
 
+/// Lists the config options which changed, as `{"name": {"old": .., "new": ..}}`
+fn config_diff(
+    before: &serde_json::Map<String, Value>,
+    after: &serde_json::Map<String, Value>,
+) -> serde_json::Map<String, Value> {
+    after
+        .iter()
+        .filter(|(name, value)| before.get(*name) != Some(*value))
+        .map(|(name, value)| (name.clone(), json!({"old": before.get(name), "new": value})))
+        .collect()
+}
+
 #[post("/config", format = "application/json", data = "<data>")]
-fn post_config(data: Json<ConfigBuilder>, _token: AdminToken) -> EmptyResult {
+async fn post_config(data: Json<ConfigBuilder>, token: AdminToken, mut conn: DbConn) -> EmptyResult {
     let data: ConfigBuilder = data.into_inner();
+    let before_usr = CONFIG.get_user_config();
+    let before = CONFIG.get_audit_json();
     if let Err(e) = CONFIG.update_config(data, true) {
         err!(format!("Unable to save config: {e:?}"))
     }
+    let diff = config_diff(&before, &CONFIG.get_audit_json());
+    token.audit("update_config", Some(Value::Object(diff)), &mut conn).await;
+    config_history::save_config_version(&before_usr, "update", None, &token, &mut conn).await;
     Ok(())
 }
 
 #[post("/config/delete", format = "application/json")]
-fn delete_config(_token: AdminToken) -> EmptyResult {
+async fn delete_config(token: AdminToken, mut conn: DbConn) -> EmptyResult {
+    let before_usr = CONFIG.get_user_config();
+    let before = CONFIG.get_audit_json();
     if let Err(e) = CONFIG.delete_user_config() {
         err!(format!("Unable to delete config: {e:?}"))
     }
+    let diff = config_diff(&before, &CONFIG.get_audit_json());
+    token.audit("delete_config", Some(Value::Object(diff)), &mut conn).await;
+    config_history::save_config_version(&before_usr, "delete", None, &token, &mut conn).await;
     Ok(())
 }
 
 #[post("/config/backup_db", format = "application/json")]
-async fn backup_db(_token: AdminToken, mut conn: DbConn) -> ApiResult<String> {
+async fn backup_db(token: AdminToken, mut conn: DbConn) -> ApiResult<String> {
     if *CAN_BACKUP {
         match backup_database(&mut conn).await {
-            Ok(f) => Ok(format!("Backup to '{f}' was successful")),
+            Ok(f) => {
+                token.audit("backup_db", Some(json!({"file": f})), &mut conn).await;
+                Ok(format!("Backup to '{f}' was successful"))
+            }
             Err(e) => err!(format!("Backup was unsuccessful {e}")),
         }
     } else {
@@ -773,52 +1371,517 @@ async fn backup_db(_token: AdminToken, mut conn: DbConn) -> ApiResult<String> {
     }
 }
 
+// The page only shows the newest entries, the CSV export contains all of them
+const AUDIT_LOG_PAGE_LIMIT: i64 = 500;
+
+#[get("/audit?<filter..>")]
+async fn audit_log_overview(filter: AdminAuditFilter, token: AdminToken, mut conn: DbConn) -> ApiResult<Html<String>> {
+    let entries_json: Vec<Value> = AdminAuditLog::find(&filter, Some(AUDIT_LOG_PAGE_LIMIT), &mut conn)
+        .await
+        .iter()
+        .map(AdminAuditLog::to_json)
+        .collect();
+
+    let audit_json = json!({
+        "entries": entries_json,
+        "filter": filter.to_json(),
+        "limit": AUDIT_LOG_PAGE_LIMIT,
+    });
+    let text = AdminTemplateData::new("admin/audit", audit_json, &token).render()?;
+    Ok(Html(text))
+}
+
+#[derive(Responder)]
+#[response(content_type = "text/csv")]
+struct CsvExport(String, Header<'static>);
+
+#[get("/audit/export?<filter..>")]
+async fn export_audit_log(filter: AdminAuditFilter, token: AdminToken, mut conn: DbConn) -> CsvExport {
+    let mut csv = String::from("created_at,actor,ip_address,action,target_type,target_id,details\r\n");
+    for entry in AdminAuditLog::find(&filter, None, &mut conn).await {
+        let created_at = format_date(&entry.created_at);
+        let row = [
+            created_at.as_str(),
+            entry.actor.as_str(),
+            entry.ip_address.as_str(),
+            entry.action.as_str(),
+            entry.target_type.as_deref().unwrap_or_default(),
+            entry.target_id.as_deref().unwrap_or_default(),
+            entry.details.as_deref().unwrap_or_default(),
+        ];
+        csv.push_str(&row.map(csv_field).join(","));
+        csv.push_str("\r\n");
+    }
+    token.audit("export_audit_log", Some(filter.to_json()), &mut conn).await;
+
+    let file_name = format!("vaultwarden-admin-audit-{}.csv", chrono::Utc::now().format("%Y%m%d-%H%M%S"));
+    CsvExport(csv, Header::new("Content-Disposition", format!("attachment; filename=\"{file_name}\"")))
+}
+
+#[get("/api-tokens")]
+async fn api_tokens_overview(token: AdminToken, mut conn: DbConn) -> ApiResult<Html<String>> {
+    let tokens_json: Vec<Value> = AdminApiToken::get_all(&mut conn).await.iter().map(AdminApiToken::to_json).collect();
+    let scopes: Vec<&str> = AdminApiScope::ALL.iter().map(|s| s.name()).collect();
+
+    let text = AdminTemplateData::new("admin/api_tokens", json!({"tokens": tokens_json, "scopes": scopes}), &token)
+        .render()?;
+    Ok(Html(text))
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiTokenData {
+    name: String,
+    scopes: Vec<String>,
+    // The token doesn't expire when this isn't set
+    expires_in_days: Option<i64>,
+}
+
+#[post("/api-tokens", format = "application/json", data = "<data>")]
+async fn create_api_token(data: Json<ApiTokenData>, token: AdminToken, mut conn: DbConn) -> JsonResult {
+    let data: ApiTokenData = data.into_inner();
+
+    let name = data.name.trim();
+    if name.is_empty() {
+        err!("A name is required")
+    }
+    let mut scopes = Vec::with_capacity(data.scopes.len());
+    for scope in &data.scopes {
+        let Some(scope) = AdminApiScope::from_name(scope) else {
+            err!(format!("Invalid scope '{scope}'"))
+        };
+        scopes.push(scope);
+    }
+    if scopes.is_empty() {
+        err!("At least one scope is required")
+    }
+    let expires_at = match data.expires_in_days {
+        Some(days) if days > 0 => Some(chrono::Utc::now().naive_utc() + chrono::TimeDelta::days(days)),
+        Some(_) => err!("The expiration needs to be at least one day"),
+        None => None,
+    };
+
+    let (api_token, secret) = AdminApiToken::new(name.to_string(), &scopes, token.actor(), expires_at);
+    api_token.save(&mut conn).await?;
+    token.audit_target("create_api_token", "api_token", &api_token.uuid, Some(api_token.to_json()), &mut conn).await;
+
+    // This is the only time the token itself is returned
+    let mut api_token_json = api_token.to_json();
+    api_token_json["token"] = json!(secret);
+    Ok(Json(api_token_json))
+}
+
+#[post("/api-tokens/<api_token_id>/revoke", format = "application/json")]
+async fn revoke_api_token(api_token_id: AdminApiTokenId, token: AdminToken, mut conn: DbConn) -> EmptyResult {
+    let Some(mut api_token) = AdminApiToken::find_by_uuid(&api_token_id, &mut conn).await else {
+        err_code!("API token doesn't exist", Status::NotFound.code)
+    };
+    if api_token.revoked_at.is_none() {
+        api_token.revoked_at = Some(chrono::Utc::now().naive_utc());
+        api_token.save(&mut conn).await?;
+        token
+            .audit_target(
+                "revoke_api_token",
+                "api_token",
+                &api_token_id,
+                Some(json!({"name": api_token.name})),
+                &mut conn,
+            )
+            .await;
+    }
+    Ok(())
+}
+
+fn check_admin_accounts_enabled() -> EmptyResult {
+    if !CONFIG.enable_admin_accounts() {
+        err_code!("Admin accounts are not enabled", Status::NotFound.code)
+    }
+    Ok(())
+}
+
+async fn get_admin_account_or_404(account_id: &AdminAccountId, conn: &mut DbConn) -> ApiResult<AdminAccount> {
+    if let Some(account) = AdminAccount::find_by_uuid(account_id, conn).await {
+        Ok(account)
+    } else {
+        err_code!("Admin account doesn't exist", Status::NotFound.code);
+    }
+}
+
+/// Prevents locking everyone out of the admin panel, when there is no `ADMIN_TOKEN` to fall back on
+async fn check_not_last_full_admin(account: &AdminAccount, conn: &mut DbConn) -> EmptyResult {
+    if CONFIG.is_admin_token_set() || CONFIG.disable_admin_token() {
+        return Ok(());
+    }
+    if account.enabled
+        && account.role() == AdminRole::Admin
+        && AdminAccount::count_enabled_by_role(AdminRole::Admin, conn).await <= 1
+    {
+        err!("Can't remove the last enabled admin account with the admin role")
+    }
+    Ok(())
+}
+
+#[get("/accounts")]
+async fn admin_accounts_overview(token: AdminToken, mut conn: DbConn) -> ApiResult<Html<String>> {
+    check_admin_accounts_enabled()?;
+    let accounts_json: Vec<Value> = AdminAccount::get_all(&mut conn).await.iter().map(AdminAccount::to_json).collect();
+
+    let text = AdminTemplateData::new("admin/accounts", json!(accounts_json), &token).render()?;
+    Ok(Html(text))
+}
+
+#[derive(Debug, Deserialize)]
+struct AdminAccountData {
+    // Only used when creating an account, the username can't be changed
+    username: Option<String>,
+    // Keeps the current password when empty
+    password: Option<String>,
+    role: String,
+    enabled: Option<bool>,
+}
+
+#[post("/accounts", format = "application/json", data = "<data>")]
+async fn create_admin_account(data: Json<AdminAccountData>, token: AdminToken, mut conn: DbConn) -> JsonResult {
+    check_admin_accounts_enabled()?;
+    let data: AdminAccountData = data.into_inner();
+
+    let Some(username) = data.username.as_deref().map(str::trim).filter(|u| !u.is_empty()) else {
+        err!("A username is required")
+    };
+    if AdminAccount::find_by_username(username, &mut conn).await.is_some() {
+        err_code!("An admin account with this username already exists", Status::Conflict.code)
+    }
+    let Some(role) = AdminRole::from_str(&data.role) else {
+        err!("Invalid role")
+    };
+
+    let mut account = AdminAccount::new(username.to_string(), role);
+    account.set_password(data.password.as_deref().unwrap_or_default())?;
+    account.enabled = data.enabled.unwrap_or(true);
+    account.save(&mut conn).await?;
+    token
+        .audit_target("create_admin_account", "admin_account", &account.uuid, Some(account.to_json()), &mut conn)
+        .await;
+
+    Ok(Json(account.to_json()))
+}
+
+#[post("/accounts/<account_id>/update", format = "application/json", data = "<data>")]
+async fn update_admin_account(
+    account_id: AdminAccountId,
+    data: Json<AdminAccountData>,
+    token: AdminToken,
+    mut conn: DbConn,
+) -> JsonResult {
+    check_admin_accounts_enabled()?;
+    let data: AdminAccountData = data.into_inner();
+    let mut account = get_admin_account_or_404(&account_id, &mut conn).await?;
+
+    let Some(role) = AdminRole::from_str(&data.role) else {
+        err!("Invalid role")
+    };
+    let enabled = data.enabled.unwrap_or(account.enabled);
+    if role < AdminRole::Admin || !enabled {
+        check_not_last_full_admin(&account, &mut conn).await?;
+    }
+
+    let mut details = json!({"username": account.username});
+    if account.role != role as i32 {
+        details["role"] = json!({"old": account.role().name(), "new": role.name()});
+    }
+    if account.enabled != enabled {
+        details["enabled"] = json!({"old": account.enabled, "new": enabled});
+    }
+
+    account.role = role as i32;
+    account.enabled = enabled;
+    if let Some(password) = data.password.as_deref().filter(|p| !p.is_empty()) {
+        account.set_password(password)?;
+        details["password_changed"] = json!(true);
+    }
+    account.save(&mut conn).await?;
+    token.audit_target("update_admin_account", "admin_account", &account.uuid, Some(details), &mut conn).await;
+
+    Ok(Json(account.to_json()))
+}
+
+#[post("/accounts/<account_id>/delete", format = "application/json")]
+async fn delete_admin_account(account_id: AdminAccountId, token: AdminToken, mut conn: DbConn) -> EmptyResult {
+    check_admin_accounts_enabled()?;
+    let account = get_admin_account_or_404(&account_id, &mut conn).await?;
+    check_not_last_full_admin(&account, &mut conn).await?;
+    let username = account.username.clone();
+    account.delete(&mut conn).await?;
+    token
+        .audit_target(
+            "delete_admin_account",
+            "admin_account",
+            &account_id,
+            Some(json!({"username": username})),
+            &mut conn,
+        )
+        .await;
+    Ok(())
+}
+
+#[post("/accounts/<account_id>/reset-totp", format = "application/json")]
+async fn reset_admin_account_totp(account_id: AdminAccountId, token: AdminToken, mut conn: DbConn) -> EmptyResult {
+    check_admin_accounts_enabled()?;
+    let mut account = get_admin_account_or_404(&account_id, &mut conn).await?;
+    account.totp_secret = None;
+    account.totp_last_used = 0;
+    account.save(&mut conn).await?;
+    token.audit_target("reset_admin_account_totp", "admin_account", &account_id, None, &mut conn).await;
+    Ok(())
+}
+
+/// The page where an admin account changes its own password and sets up TOTP
+#[get("/account")]
+async fn own_admin_account(token: AdminReadToken) -> ApiResult<Html<String>> {
+    let Some(account) = &token.account else {
+        err_code!("You are not logged in with an admin account", Status::NotFound.code)
+    };
+
+    // A new secret is proposed every time the page is shown, it is only stored once a code of it is confirmed
+    let totp_secret = crate::crypto::encode_random_bytes::<20>(data_encoding::BASE32);
+    let username = percent_encode(account.username.as_bytes(), NON_ALPHANUMERIC);
+    let mut account_json = account.to_json();
+    account_json["totp_secret"] = json!(totp_secret);
+    account_json["totp_uri"] =
+        json!(format!("otpauth://totp/Vaultwarden%20Admin:{username}?secret={totp_secret}&issuer=Vaultwarden%20Admin"));
+
+    let text = AdminTemplateData::new("admin/account", account_json, &token).render()?;
+    Ok(Html(text))
+}
+
+#[derive(Debug, Deserialize)]
+struct AdminPasswordData {
+    current_password: String,
+    new_password: String,
+}
+
+#[post("/account/password", format = "application/json", data = "<data>")]
+async fn change_own_admin_password(
+    data: Json<AdminPasswordData>,
+    token: AdminReadToken,
+    mut conn: DbConn,
+) -> EmptyResult {
+    let data: AdminPasswordData = data.into_inner();
+    let Some(account) = &token.account else {
+        err_code!("You are not logged in with an admin account", Status::NotFound.code)
+    };
+
+    let mut account = get_admin_account_or_404(&account.uuid, &mut conn).await?;
+    if !account.check_password(&data.current_password) {
+        err!("The current password is not correct")
+    }
+    account.set_password(&data.new_password)?;
+    account.save(&mut conn).await?;
+    token.audit_target("change_own_password", "admin_account", &account.uuid, None, &mut conn).await;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct AdminTotpData {
+    secret: String,
+    code: String,
+}
+
+#[post("/account/totp", format = "application/json", data = "<data>")]
+async fn enable_own_admin_totp(data: Json<AdminTotpData>, token: AdminReadToken, mut conn: DbConn) -> EmptyResult {
+    let data: AdminTotpData = data.into_inner();
+    let Some(account) = &token.account else {
+        err_code!("You are not logged in with an admin account", Status::NotFound.code)
+    };
+
+    let mut account = get_admin_account_or_404(&account.uuid, &mut conn).await?;
+    if data_encoding::BASE32.decode(data.secret.as_bytes()).is_err() {
+        err!("Invalid TOTP secret")
+    }
+
+    // Only store the new secret when the code shows it was set up correctly
+    account.totp_secret = Some(data.secret);
+    account.totp_last_used = 0;
+    if !account.check_totp(data.code.trim()) {
+        err!("Invalid TOTP code")
+    }
+    account.save(&mut conn).await?;
+    token.audit_target("enable_own_totp", "admin_account", &account.uuid, None, &mut conn).await;
+    Ok(())
+}
+
+/// A failure to save the audit log is only logged, so it doesn't fail the action which already happened
+async fn save_audit_log(mut entry: AdminAuditLog, details: Option<Value>, conn: &mut DbConn) {
+    entry.details = details.map(|d| d.to_string());
+    if let Err(e) = entry.save(conn).await {
+        error!("Unable to save the admin audit log entry for '{}': {e:#?}", entry.action);
+    }
+}
+
+/// An admin panel session with full access, this is the guard of all the routes which don't need a lower role
 pub struct AdminToken {
     ip: ClientIp,
+    role: AdminRole,
+    // None when logged in with the `ADMIN_TOKEN`, or when `DISABLE_ADMIN_TOKEN` is set
+    account: Option<AdminAccount>,
+    // Set when the request is made through the JSON admin API
+    api_token: Option<AdminApiToken>,
 }
 
-#[rocket::async_trait]
-impl<'r> FromRequest<'r> for AdminToken {
-    type Error = &'static str;
-
-    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+impl AdminToken {
+    async fn authenticate(request: &Request<'_>) -> Outcome<Self, &'static str> {
         let ip = match ClientIp::from_request(request).await {
             Outcome::Success(ip) => ip,
             _ => err_handler!("Error getting Client IP"),
         };
 
         if CONFIG.disable_admin_token() {
-            Outcome::Success(Self {
+            return Outcome::Success(Self {
                 ip,
-            })
-        } else {
-            let cookies = request.cookies();
-
-            let access_token = match cookies.get(COOKIE_NAME) {
-                Some(cookie) => cookie.value(),
-                None => {
-                    let requested_page =
-                        request.segments::<std::path::PathBuf>(0..).unwrap_or_default().display().to_string();
-                    // When the requested page is empty, it is `/admin`, in that case, Forward, so it will render the login page
-                    // Else, return a 401 failure, which will be caught
-                    if requested_page.is_empty() {
-                        return Outcome::Forward(Status::Unauthorized);
-                    } else {
-                        return Outcome::Error((Status::Unauthorized, "Unauthorized"));
-                    }
-                }
-            };
+                role: AdminRole::Admin,
+                account: None,
+                api_token: None,
+            });
+        }
 
-            if decode_admin(access_token).is_err() {
-                // Remove admin cookie
-                cookies.remove(Cookie::build(COOKIE_NAME).path(admin_path()));
-                error!("Invalid or expired admin JWT. IP: {}.", &ip.ip);
-                return Outcome::Error((Status::Unauthorized, "Session expired"));
+        let cookies = request.cookies();
+
+        let access_token = match cookies.get(COOKIE_NAME) {
+            Some(cookie) => cookie.value(),
+            None => {
+                let requested_page =
+                    request.segments::<std::path::PathBuf>(0..).unwrap_or_default().display().to_string();
+                // When the requested page is empty, it is `/admin`, in that case, Forward, so it will render the login page
+                // Else, return a 401 failure, which will be caught
+                if requested_page.is_empty() {
+                    return Outcome::Forward(Status::Unauthorized);
+                } else {
+                    return Outcome::Error((Status::Unauthorized, "Unauthorized"));
+                }
             }
+        };
 
-            Outcome::Success(Self {
+        let Ok(claims) = decode_admin(access_token) else {
+            // Remove admin cookie
+            cookies.remove(Cookie::build(COOKIE_NAME).path(admin_path()));
+            error!("Invalid or expired admin JWT. IP: {}.", &ip.ip);
+            return Outcome::Error((Status::Unauthorized, "Session expired"));
+        };
+
+        // The role of an account is checked on every request, so changes apply to the existing sessions right away
+        if claims.sub == ADMIN_TOKEN_SUBJECT && CONFIG.is_admin_token_set() {
+            return Outcome::Success(Self {
                 ip,
-            })
+                role: AdminRole::Admin,
+                account: None,
+                api_token: None,
+            });
+        } else if claims.sub != ADMIN_TOKEN_SUBJECT && CONFIG.enable_admin_accounts() {
+            let mut conn = match DbConn::from_request(request).await {
+                Outcome::Success(conn) => conn,
+                _ => err_handler!("Error getting DB"),
+            };
+            let account_id: AdminAccountId = claims.sub.into();
+            if let Some(account) = AdminAccount::find_by_uuid(&account_id, &mut conn).await {
+                if account.enabled {
+                    return Outcome::Success(Self {
+                        ip,
+                        role: account.role(),
+                        account: Some(account),
+                        api_token: None,
+                    });
+                }
+            }
         }
+
+        cookies.remove(Cookie::build(COOKIE_NAME).path(admin_path()));
+        error!("Admin session is no longer valid. IP: {}.", &ip.ip);
+        Outcome::Error((Status::Unauthorized, "Session expired"))
+    }
+
+    /// The name recorded in the audit log
+    fn actor(&self) -> String {
+        if let Some(api_token) = &self.api_token {
+            return format!("api:{}", api_token.name);
+        }
+        match &self.account {
+            Some(account) => account.username.clone(),
+            None if CONFIG.disable_admin_token() => String::from("disable_admin_token"),
+            None => String::from("admin_token"),
+        }
+    }
+
+    async fn audit(&self, action: &str, details: Option<Value>, conn: &mut DbConn) {
+        save_audit_log(AdminAuditLog::new(self.actor(), self.ip.ip.to_string(), action), details, conn).await;
+    }
+
+    async fn audit_target(
+        &self,
+        action: &str,
+        target_type: &str,
+        target_id: &str,
+        details: Option<Value>,
+        conn: &mut DbConn,
+    ) {
+        let mut entry = AdminAuditLog::new(self.actor(), self.ip.ip.to_string(), action);
+        entry.target_type = Some(target_type.to_string());
+        entry.target_id = Some(target_id.to_string());
+        save_audit_log(entry, details, conn).await;
+    }
+
+    fn require_role(self, role: AdminRole) -> Outcome<Self, &'static str> {
+        if self.role >= role {
+            Outcome::Success(self)
+        } else {
+            Outcome::Error((Status::Forbidden, "Your admin role does not allow this action"))
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminToken {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        try_outcome!(Self::authenticate(request).await).require_role(AdminRole::Admin)
+    }
+}
+
+/// An admin panel session which is allowed to enable, disable and deauthorize users and resend invitations
+pub struct AdminSupportToken(AdminToken);
+
+impl std::ops::Deref for AdminSupportToken {
+    type Target = AdminToken;
+
+    fn deref(&self) -> &AdminToken {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminSupportToken {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        try_outcome!(AdminToken::authenticate(request).await).require_role(AdminRole::UserSupport).map(Self)
+    }
+}
+
+/// Any admin panel session, including read-only ones
+pub struct AdminReadToken(AdminToken);
+
+impl std::ops::Deref for AdminReadToken {
+    type Target = AdminToken;
+
+    fn deref(&self) -> &AdminToken {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminReadToken {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        AdminToken::authenticate(request).await.map(Self)
     }
 }
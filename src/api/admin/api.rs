@@ -0,0 +1,272 @@
+//
+// JSON admin API, for automating the admin panel actions with the API tokens created in the admin panel.
+// The requests are authenticated with `Authorization: Bearer <token>` and each route needs a scope of the token.
+// The routes call the admin panel routes, so both record the same admin audit log entries.
+//
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    serde::json::Json,
+    Request, Route,
+};
+use serde_json::Value;
+
+use super::{
+    config_diff, config_history::save_config_version, create_organization, deauth_user, delete_organization,
+    delete_user, disable_user, enable_user, get_organizations_json, get_user_by_mail_json, get_user_json,
+    get_users_json, invite_user, remove_2fa, rename_organization, resend_user_invite, set_organization_owner,
+    unlock_user, update_membership_type, AdminReadToken, AdminSupportToken, AdminToken, CreateOrganizationData,
+    InviteData, MembershipTypeData, OrganizationOwnerData, RenameOrganizationData,
+};
+use crate::{
+    api::{ApiResult, EmptyResult, JsonResult, Notify},
+    auth::ClientIp,
+    config::ConfigBuilder,
+    db::{models::*, DbConn},
+    CONFIG,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        api_get_users,
+        api_get_user,
+        api_get_user_by_mail,
+        api_delete_user,
+        api_deauth_user,
+        api_disable_user,
+        api_enable_user,
+        api_unlock_user,
+        api_remove_2fa,
+        api_invite_user,
+        api_resend_invite,
+        api_get_organizations,
+        api_create_organization,
+        api_delete_organization,
+        api_rename_organization,
+        api_set_organization_owner,
+        api_update_membership_type,
+        api_get_config,
+        api_patch_config,
+        api_get_backups,
+        api_create_backup,
+    ]
+}
+
+/// A request authenticated with an admin API token
+pub struct AdminApiSession {
+    ip: ClientIp,
+    token: AdminApiToken,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminApiSession {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let ip = match ClientIp::from_request(request).await {
+            Outcome::Success(ip) => ip,
+            _ => err_handler!("Error getting Client IP"),
+        };
+
+        let mut conn = match DbConn::from_request(request).await {
+            Outcome::Success(conn) => conn,
+            _ => err_handler!("Error getting DB"),
+        };
+
+        let secret = request.headers().get_one("Authorization").and_then(|a| a.strip_prefix("Bearer "));
+        let token = match secret {
+            Some(secret) => AdminApiToken::find_by_secret(secret.trim(), &mut conn).await.filter(|t| t.is_active()),
+            None => None,
+        };
+        let Some(mut token) = token else {
+            // Only the failed attempts count towards the limit of the admin panel logins, which prevents guessing tokens
+            if crate::ratelimit::check_limit_admin(&ip.ip).is_err() {
+                return Outcome::Error((Status::TooManyRequests, "Too many admin requests"));
+            }
+            if secret.is_none() {
+                err_handler!("No admin API token provided")
+            }
+            err_handler!("Invalid admin API token", format!("IP: {}", ip.ip))
+        };
+
+        if let Err(e) = token.update_last_used(&mut conn).await {
+            error!("Unable to update the last use of admin API token '{}': {e:#?}", token.name);
+        }
+        Outcome::Success(Self {
+            ip,
+            token,
+        })
+    }
+}
+
+impl AdminApiSession {
+    /// Returns the session to pass to the admin panel routes, if the token has the needed scope
+    fn scoped(self, scope: AdminApiScope) -> ApiResult<AdminToken> {
+        if !self.token.has_scope(scope) {
+            err_code!(format!("The admin API token is missing the '{}' scope", scope.name()), Status::Forbidden.code)
+        }
+        Ok(AdminToken {
+            ip: self.ip,
+            role: AdminRole::Admin,
+            account: None,
+            api_token: Some(self.token),
+        })
+    }
+}
+
+#[get("/api/v1/users")]
+async fn api_get_users(session: AdminApiSession, conn: DbConn) -> JsonResult {
+    let token = session.scoped(AdminApiScope::UsersRead)?;
+    Ok(get_users_json(AdminReadToken(token), conn).await)
+}
+
+#[get("/api/v1/users/<user_id>")]
+async fn api_get_user(user_id: UserId, session: AdminApiSession, conn: DbConn) -> JsonResult {
+    let token = session.scoped(AdminApiScope::UsersRead)?;
+    get_user_json(user_id, AdminReadToken(token), conn).await
+}
+
+#[get("/api/v1/users/by-mail/<mail>")]
+async fn api_get_user_by_mail(mail: &str, session: AdminApiSession, conn: DbConn) -> JsonResult {
+    let token = session.scoped(AdminApiScope::UsersRead)?;
+    get_user_by_mail_json(mail, AdminReadToken(token), conn).await
+}
+
+#[delete("/api/v1/users/<user_id>")]
+async fn api_delete_user(user_id: UserId, session: AdminApiSession, conn: DbConn) -> EmptyResult {
+    let token = session.scoped(AdminApiScope::UsersWrite)?;
+    delete_user(user_id, token, conn).await
+}
+
+#[post("/api/v1/users/<user_id>/deauth")]
+async fn api_deauth_user(user_id: UserId, session: AdminApiSession, conn: DbConn, nt: Notify<'_>) -> EmptyResult {
+    let token = session.scoped(AdminApiScope::UsersWrite)?;
+    deauth_user(user_id, AdminSupportToken(token), conn, nt).await
+}
+
+#[post("/api/v1/users/<user_id>/disable")]
+async fn api_disable_user(user_id: UserId, session: AdminApiSession, conn: DbConn, nt: Notify<'_>) -> EmptyResult {
+    let token = session.scoped(AdminApiScope::UsersWrite)?;
+    disable_user(user_id, AdminSupportToken(token), conn, nt).await
+}
+
+#[post("/api/v1/users/<user_id>/enable")]
+async fn api_enable_user(user_id: UserId, session: AdminApiSession, conn: DbConn) -> EmptyResult {
+    let token = session.scoped(AdminApiScope::UsersWrite)?;
+    enable_user(user_id, AdminSupportToken(token), conn).await
+}
+
+#[post("/api/v1/users/<user_id>/unlock")]
+async fn api_unlock_user(user_id: UserId, session: AdminApiSession, conn: DbConn) -> EmptyResult {
+    let token = session.scoped(AdminApiScope::UsersWrite)?;
+    unlock_user(user_id, AdminSupportToken(token), conn).await
+}
+
+#[post("/api/v1/users/<user_id>/remove-2fa")]
+async fn api_remove_2fa(user_id: UserId, session: AdminApiSession, conn: DbConn) -> EmptyResult {
+    let token = session.scoped(AdminApiScope::UsersWrite)?;
+    remove_2fa(user_id, token, conn).await
+}
+
+#[post("/api/v1/invitations", format = "application/json", data = "<data>")]
+async fn api_invite_user(data: Json<InviteData>, session: AdminApiSession, conn: DbConn) -> JsonResult {
+    let token = session.scoped(AdminApiScope::InvitationsWrite)?;
+    invite_user(data, token, conn).await
+}
+
+#[post("/api/v1/users/<user_id>/invite/resend")]
+async fn api_resend_invite(user_id: UserId, session: AdminApiSession, conn: DbConn) -> EmptyResult {
+    let token = session.scoped(AdminApiScope::InvitationsWrite)?;
+    resend_user_invite(user_id, AdminSupportToken(token), conn).await
+}
+
+#[get("/api/v1/organizations")]
+async fn api_get_organizations(session: AdminApiSession, mut conn: DbConn) -> JsonResult {
+    session.scoped(AdminApiScope::OrganizationsRead)?;
+    Ok(Json(Value::Array(get_organizations_json(&mut conn).await)))
+}
+
+#[delete("/api/v1/organizations/<org_id>")]
+async fn api_delete_organization(org_id: OrganizationId, session: AdminApiSession, conn: DbConn) -> EmptyResult {
+    let token = session.scoped(AdminApiScope::OrganizationsWrite)?;
+    delete_organization(org_id, token, conn).await
+}
+
+#[post("/api/v1/organizations", format = "application/json", data = "<data>")]
+async fn api_create_organization(
+    data: Json<CreateOrganizationData>,
+    session: AdminApiSession,
+    conn: DbConn,
+) -> JsonResult {
+    let token = session.scoped(AdminApiScope::OrganizationsWrite)?;
+    create_organization(data, token, conn).await
+}
+
+#[post("/api/v1/organizations/<org_id>/rename", format = "application/json", data = "<data>")]
+async fn api_rename_organization(
+    org_id: OrganizationId,
+    data: Json<RenameOrganizationData>,
+    session: AdminApiSession,
+    conn: DbConn,
+) -> EmptyResult {
+    let token = session.scoped(AdminApiScope::OrganizationsWrite)?;
+    rename_organization(org_id, data, token, conn).await
+}
+
+#[post("/api/v1/organizations/<org_id>/owner", format = "application/json", data = "<data>")]
+async fn api_set_organization_owner(
+    org_id: OrganizationId,
+    data: Json<OrganizationOwnerData>,
+    session: AdminApiSession,
+    conn: DbConn,
+) -> EmptyResult {
+    let token = session.scoped(AdminApiScope::OrganizationsWrite)?;
+    set_organization_owner(org_id, data, token, conn).await
+}
+
+#[post("/api/v1/organizations/membership-type", format = "application/json", data = "<data>")]
+async fn api_update_membership_type(
+    data: Json<MembershipTypeData>,
+    session: AdminApiSession,
+    conn: DbConn,
+) -> EmptyResult {
+    let token = session.scoped(AdminApiScope::OrganizationsWrite)?;
+    update_membership_type(data, token, conn).await
+}
+
+/// Returns the current value of every option, the passwords are masked
+#[get("/api/v1/config")]
+fn api_get_config(session: AdminApiSession) -> JsonResult {
+    session.scoped(AdminApiScope::ConfigRead)?;
+    Ok(Json(Value::Object(CONFIG.get_audit_json())))
+}
+
+/// Only changes the options in the request, unlike the admin panel which saves all of them
+#[patch("/api/v1/config", format = "application/json", data = "<data>")]
+async fn api_patch_config(data: Json<ConfigBuilder>, session: AdminApiSession, mut conn: DbConn) -> JsonResult {
+    let token = session.scoped(AdminApiScope::ConfigWrite)?;
+    let before_usr = CONFIG.get_user_config();
+    let before = CONFIG.get_audit_json();
+    if let Err(e) = CONFIG.patch_config(data.into_inner()) {
+        err!(format!("Unable to save config: {e:?}"))
+    }
+
+    let diff = Value::Object(config_diff(&before, &CONFIG.get_audit_json()));
+    token.audit("update_config", Some(diff.clone()), &mut conn).await;
+    save_config_version(&before_usr, "update", None, &token, &mut conn).await;
+    Ok(Json(diff))
+}
+
+#[get("/api/v1/backups")]
+async fn api_get_backups(session: AdminApiSession) -> JsonResult {
+    session.scoped(AdminApiScope::BackupsRead)?;
+    Ok(Json(json!(crate::backup::list_backups().await?)))
+}
+
+#[post("/api/v1/backups")]
+async fn api_create_backup(session: AdminApiSession, mut conn: DbConn) -> JsonResult {
+    let token = session.scoped(AdminApiScope::BackupsWrite)?;
+    let file_name = crate::backup::create_folder_backup(&mut conn).await?;
+    token.audit("create_backup", Some(json!({"file": file_name})), &mut conn).await;
+    Ok(Json(json!({"file": file_name})))
+}
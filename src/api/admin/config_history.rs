@@ -0,0 +1,113 @@
+//
+// Versions of the config file, saved on every change made through the admin panel or the admin API.
+// The history shows what changed in every version, and any version can be restored.
+// The passwords and secret keys are not saved, restoring a version keeps the current ones.
+//
+use rocket::{http::Status, serde::json::Json, Route};
+use serde_json::Value;
+
+use super::{config_diff, AdminToken, DT_FMT};
+use crate::{
+    api::{EmptyResult, JsonResult},
+    config::ConfigBuilder,
+    db::{models::*, DbConn},
+    error::Error,
+    util::format_naive_datetime_local,
+    CONFIG,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![get_config_history, rollback_config]
+}
+
+/// Saves the current config file as a new version, `before` being the config file before the change.
+/// When there is no history yet, `before` is saved first so the very first change can also be rolled back.
+pub(super) async fn save_config_version(
+    before: &ConfigBuilder,
+    action: &str,
+    restored_from: Option<i32>,
+    token: &AdminToken,
+    conn: &mut DbConn,
+) {
+    if let Err(e) = _save_config_version(before, action, restored_from, token, conn).await {
+        error!("Unable to save the config history: {e:#?}");
+    }
+}
+
+async fn _save_config_version(
+    before: &ConfigBuilder,
+    action: &str,
+    restored_from: Option<i32>,
+    token: &AdminToken,
+    conn: &mut DbConn,
+) -> EmptyResult {
+    if ConfigHistory::latest_version(conn).await.is_none() {
+        let mut initial = ConfigHistory::new(String::from("unknown"), "initial", config_snapshot(before)?);
+        initial.save_new_version(conn).await?;
+    }
+
+    let mut entry = ConfigHistory::new(token.actor(), action, config_snapshot(&CONFIG.get_user_config())?);
+    entry.restored_from = restored_from;
+    entry.save_new_version(conn).await?;
+
+    ConfigHistory::prune(conn).await
+}
+
+/// Serializes the config file without the passwords and secret keys
+fn config_snapshot(config: &ConfigBuilder) -> Result<String, Error> {
+    let mut config = config.clone();
+    config.clear_passwords();
+    Ok(serde_json::to_string_pretty(&config)?)
+}
+
+fn parse_config(entry: &ConfigHistory) -> Result<ConfigBuilder, Error> {
+    serde_json::from_str(&entry.config).map_err(Into::into)
+}
+
+/// Lists the versions, newest first, with the options changed compared to the previous version
+#[get("/config/history")]
+async fn get_config_history(_token: AdminToken, mut conn: DbConn) -> JsonResult {
+    let history = ConfigHistory::get_all(&mut conn).await;
+    let configs = history.iter().map(parse_config).collect::<Result<Vec<_>, _>>()?;
+
+    let empty = ConfigBuilder::default();
+    let versions: Vec<Value> = history
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            // The oldest kept version is compared to an empty config file
+            let previous = configs.get(i + 1).unwrap_or(&empty);
+            json!({
+                "version": entry.version,
+                "created_at": format_naive_datetime_local(&entry.created_at, DT_FMT),
+                "actor": entry.actor,
+                "action": entry.action,
+                "restored_from": entry.restored_from,
+                "current": i == 0,
+                "changes": previous.masked_diff(&configs[i]),
+            })
+        })
+        .collect();
+
+    Ok(Json(Value::Array(versions)))
+}
+
+#[post("/config/history/<version>/rollback", format = "application/json")]
+async fn rollback_config(version: i32, token: AdminToken, mut conn: DbConn) -> EmptyResult {
+    let Some(entry) = ConfigHistory::find_by_version(version, &mut conn).await else {
+        err_code!("Config version doesn't exist", Status::NotFound.code);
+    };
+    let mut config = parse_config(&entry)?;
+    config.copy_passwords(&CONFIG.get_user_config());
+
+    let before_usr = CONFIG.get_user_config();
+    let before = CONFIG.get_audit_json();
+    if let Err(e) = CONFIG.update_config(config, true) {
+        err!(format!("Unable to restore config: {e:?}"))
+    }
+
+    let diff = config_diff(&before, &CONFIG.get_audit_json());
+    token.audit("rollback_config", Some(json!({"version": version, "changes": diff})), &mut conn).await;
+    save_config_version(&before_usr, "rollback", Some(version), &token, &mut conn).await;
+    Ok(())
+}
@@ -0,0 +1,270 @@
+//
+// Dry run of a proposed config and export/import of the effective config.
+// A dry run validates the config the same way as saving it would, and tests the SMTP, Yubico, Duo and push relay
+// settings with the proposed values, without applying anything. Yubico and Duo are tested without a real user,
+// using a well-formed dummy OTP and the Duo health check.
+//
+use std::collections::HashMap;
+
+use rocket::{
+    http::{ContentType, Header},
+    serde::json::Json,
+    Route,
+};
+use serde_json::Value;
+
+use super::{config_diff, config_history, AdminToken};
+use crate::{
+    api::{
+        check_push_credentials,
+        core::two_factor::{duo_oidc::check_duo_credentials, yubikey::check_yubico_credentials},
+        JsonResult,
+    },
+    config::{Config, ConfigBuilder},
+    db::DbConn,
+    error::Error,
+    mail, CONFIG,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![validate_config, export_config, import_config]
+}
+
+/// Validates the config sent by the settings form, without saving it
+#[post("/config/validate", format = "application/json", data = "<data>")]
+async fn validate_config(data: Json<ConfigBuilder>, _token: AdminToken) -> Json<Value> {
+    let report = dry_run_report(CONFIG.dry_run_update(data.into_inner())).await;
+    Json(report)
+}
+
+#[derive(Responder)]
+struct ConfigExport(String, ContentType, Header<'static>);
+
+#[get("/config/export?<format>&<include_secrets>")]
+async fn export_config(
+    format: Option<String>,
+    include_secrets: Option<bool>,
+    token: AdminToken,
+    mut conn: DbConn,
+) -> Result<ConfigExport, Error> {
+    let include_secrets = include_secrets.unwrap_or(false);
+    let config = CONFIG.export_config(include_secrets);
+
+    let format = format.unwrap_or_else(|| String::from("json"));
+    let (content, content_type, extension) = match format.as_str() {
+        "json" => (serde_json::to_string_pretty(&config)?, ContentType::JSON, "json"),
+        "env" => (config.to_env_file() + "\n", ContentType::Plain, "env"),
+        _ => err!("Unknown export format, use `json` or `env`"),
+    };
+    token.audit("export_config", Some(json!({"format": format, "include_secrets": include_secrets})), &mut conn).await;
+
+    let file_name = format!("vaultwarden-config-{}.{extension}", chrono::Utc::now().format("%Y%m%d-%H%M%S"));
+    Ok(ConfigExport(
+        content,
+        content_type,
+        Header::new("Content-Disposition", format!("attachment; filename=\"{file_name}\"")),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportData {
+    format: String,
+    content: String,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Imports a JSON or `.env` export. Only the options present in the file are changed, like `PATCH /api/config`.
+/// The options which are not editable in the admin panel can only be set by the environment and are ignored.
+#[post("/config/import", format = "application/json", data = "<data>")]
+async fn import_config(data: Json<ImportData>, token: AdminToken, mut conn: DbConn) -> JsonResult {
+    let data: ImportData = data.into_inner();
+    let (config, ignored) = match data.format.as_str() {
+        "json" => match serde_json::from_str::<ConfigBuilder>(&data.content) {
+            Ok(config) => (config, Vec::new()),
+            Err(e) => err!(format!("Invalid JSON config file: {e}")),
+        },
+        "env" => {
+            let mut vars = HashMap::new();
+            for item in dotenvy::from_read_iter(data.content.as_bytes()) {
+                match item {
+                    Ok((key, value)) => vars.insert(key, value),
+                    Err(e) => err!(format!("Invalid .env file: {e}")),
+                };
+            }
+            ConfigBuilder::from_env_vars(&vars)?
+        }
+        _ => err!("Unknown import format, use `json` or `env`"),
+    };
+
+    if data.dry_run {
+        let mut report = dry_run_report(CONFIG.dry_run_patch(config)).await;
+        report["ignored"] = json!(ignored);
+        return Ok(Json(report));
+    }
+
+    let before_usr = CONFIG.get_user_config();
+    let before = CONFIG.get_audit_json();
+    if let Err(e) = CONFIG.patch_config(config) {
+        err!(format!("Unable to import config: {e:?}"))
+    }
+
+    let diff = config_diff(&before, &CONFIG.get_audit_json());
+    token.audit("import_config", Some(json!({"format": data.format, "changes": diff})), &mut conn).await;
+    config_history::save_config_version(&before_usr, "import", None, &token, &mut conn).await;
+
+    Ok(Json(json!({
+        "changes": before_usr.masked_diff(&CONFIG.get_user_config()),
+        "ignored": ignored,
+    })))
+}
+
+/// The result of a dry run: whether the config is valid, the options changed compared to the config file,
+/// and the connectivity checks which could be run with the proposed config
+async fn dry_run_report(proposed: Result<Config, Error>) -> Value {
+    match proposed {
+        Ok(config) => json!({
+            "valid": true,
+            "error": null,
+            "changes": CONFIG.get_user_config().masked_diff(&config.get_user_config()),
+            "checks": connectivity_checks(&config).await,
+        }),
+        Err(e) => json!({
+            "valid": false,
+            "error": e.to_string(),
+            "changes": [],
+            "checks": [],
+        }),
+    }
+}
+
+fn check_result(name: &str, result: Result<(), Error>) -> Value {
+    match result {
+        Ok(()) => json!({"name": name, "status": "ok", "message": null}),
+        Err(e) => json!({"name": name, "status": "failed", "message": e.to_string()}),
+    }
+}
+
+fn check_skipped(name: &str, reason: &str) -> Value {
+    json!({"name": name, "status": "skipped", "message": reason})
+}
+
+async fn connectivity_checks(config: &Config) -> Vec<Value> {
+    let mut checks = Vec::new();
+
+    checks.push(if !config.mail_enabled() {
+        check_skipped("SMTP", "Mail is not enabled")
+    } else if config.use_sendmail() {
+        check_skipped("SMTP", "Sendmail is used, only SMTP servers can be checked")
+    } else {
+        check_result("SMTP", mail::test_smtp_connection(config).await)
+    });
+
+    checks.push(match (config._enable_yubico(), config.yubico_client_id(), config.yubico_secret_key()) {
+        (true, Some(client_id), Some(secret_key)) => {
+            check_result("Yubico", check_yubico_credentials(client_id, secret_key, config.yubico_server()).await)
+        }
+        _ => check_skipped("Yubico", "Yubico is not enabled or not configured"),
+    });
+
+    checks.push(match (config._enable_duo(), config.duo_ikey(), config.duo_skey(), config.duo_host()) {
+        (true, Some(ikey), Some(skey), Some(host)) => {
+            check_result("Duo", check_duo_credentials(ikey, skey, host).await)
+        }
+        _ => check_skipped("Duo", "Global Duo is not enabled or not configured"),
+    });
+
+    checks.push(if config.push_enabled() {
+        let result = check_push_credentials(
+            &config.push_identity_uri(),
+            &config.push_installation_id(),
+            &config.push_installation_key(),
+        )
+        .await;
+        check_result("Push relay", result)
+    } else {
+        check_skipped("Push relay", "Push notifications are not enabled")
+    });
+
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// A minimal SMTP server which greets every connection with `greeting` and accepts the commands of a test connection
+    async fn serve_smtp(greeting: &'static str) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    writer.write_all(format!("{greeting}\r\n").as_bytes()).await?;
+                    while let Some(line) = lines.next_line().await? {
+                        let command = line.split(' ').next().unwrap_or_default().to_uppercase();
+                        let reply = match command.as_str() {
+                            "EHLO" | "HELO" => "250 localhost",
+                            "NOOP" | "RSET" => "250 OK",
+                            "QUIT" => {
+                                writer.write_all(b"221 Bye\r\n").await?;
+                                break;
+                            }
+                            _ => "502 Command not implemented",
+                        };
+                        writer.write_all(format!("{reply}\r\n").as_bytes()).await?;
+                    }
+                    Ok::<_, std::io::Error>(())
+                });
+            }
+        });
+        port
+    }
+
+    fn smtp_config(port: u16) -> Config {
+        let vars = HashMap::from([
+            (String::from("SMTP_HOST"), String::from("127.0.0.1")),
+            (String::from("SMTP_PORT"), port.to_string()),
+            (String::from("SMTP_SECURITY"), String::from("off")),
+            (String::from("SMTP_FROM"), String::from("vaultwarden@example.com")),
+            (String::from("SMTP_TIMEOUT"), String::from("5")),
+            (String::from("USE_SENDMAIL"), String::from("false")),
+        ]);
+        let (builder, _) = ConfigBuilder::from_env_vars(&vars).unwrap();
+        Config::from_builder(builder)
+    }
+
+    fn smtp_check(checks: &[Value]) -> &Value {
+        checks.iter().find(|c| c["name"] == "SMTP").unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn test_smtp_check_with_local_server() {
+        let port = serve_smtp("220 localhost ESMTP test").await;
+        let checks = connectivity_checks(&smtp_config(port)).await;
+        let smtp = smtp_check(&checks);
+        assert_eq!(smtp["status"], "ok", "{smtp}");
+    }
+
+    #[rocket::async_test]
+    async fn test_smtp_check_rejected_by_server() {
+        let port = serve_smtp("554 No SMTP service here").await;
+        let checks = connectivity_checks(&smtp_config(port)).await;
+        assert_eq!(smtp_check(&checks)["status"], "failed");
+    }
+
+    #[rocket::async_test]
+    async fn test_smtp_check_without_server() {
+        // Bind and drop a listener to get a port which refuses connections
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let checks = connectivity_checks(&smtp_config(port)).await;
+        assert_eq!(smtp_check(&checks)["status"], "failed");
+    }
+}
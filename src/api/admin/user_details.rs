@@ -0,0 +1,216 @@
+//
+// Support view of a single user for the admin panel, without having to log in as that user.
+// Only account metadata is shown (devices, 2FA providers, memberships, emergency access, storage and logins),
+// never any vault contents.
+//
+use num_traits::FromPrimitive;
+use rocket::{http::Status, response::content::RawHtml as Html, Route};
+use serde_json::Value;
+
+use super::{get_user_or_404, AdminReadToken, AdminSupportToken, AdminTemplateData, DT_FMT};
+use crate::{
+    api::{core::accounts::remove_device, ApiResult, EmptyResult},
+    db::{models::*, DbConn},
+    util::{format_naive_datetime_local, get_display_size},
+    CONFIG,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![user_details, revoke_user_device]
+}
+
+const LOGIN_EVENTS_LIMIT: i64 = 25;
+
+#[get("/users/<user_id>/details")]
+async fn user_details(user_id: UserId, token: AdminReadToken, mut conn: DbConn) -> ApiResult<Html<String>> {
+    let user = get_user_or_404(&user_id, &mut conn).await?;
+    let fmt = |dt: &chrono::NaiveDateTime| format_naive_datetime_local(dt, DT_FMT);
+
+    let mut devices = Device::find_by_user(&user.uuid, &mut conn).await;
+    devices.sort_by(|a, b| b.last_seen_at.unwrap_or(b.updated_at).cmp(&a.last_seen_at.unwrap_or(a.updated_at)));
+    let devices_json: Vec<Value> = devices
+        .iter()
+        .map(|d| {
+            json!({
+                "id": d.uuid,
+                "name": d.name,
+                "type": DeviceType::from_i32(d.atype).to_string(),
+                "created_at": fmt(&d.created_at),
+                "last_seen": fmt(d.last_seen_at.as_ref().unwrap_or(&d.updated_at)),
+                "last_ip": d.last_ip,
+                "trusted": d.is_trusted(),
+                "push_registered": d.is_registered() && d.push_token.is_some(),
+            })
+        })
+        .collect();
+
+    let two_factors_json: Vec<Value> = TwoFactor::find_by_user(&user.uuid, &mut conn)
+        .await
+        .iter()
+        .filter(|tf| tf.enabled)
+        .filter_map(|tf| two_factor_type_name(tf.atype))
+        .map(|name| json!(name))
+        .collect();
+
+    let mut memberships_json = Vec::new();
+    for member in Membership::find_any_state_by_user(&user.uuid, &mut conn).await {
+        let Some(org) = Organization::find_by_uuid(&member.org_uuid, &mut conn).await else {
+            continue;
+        };
+        memberships_json.push(json!({
+            "org_id": org.uuid,
+            "org_name": org.name,
+            "type": MembershipType::from_i32(member.atype).map_or("unknown", membership_type_name),
+            "status": membership_status_name(member.status),
+            "access_all": member.access_all,
+        }));
+    }
+
+    let mut granted_json = Vec::new();
+    for ea in EmergencyAccess::find_all_by_grantor_uuid(&user.uuid, &mut conn).await {
+        let grantee_email = match ea.grantee_uuid {
+            Some(ref grantee_id) => User::find_by_uuid(grantee_id, &mut conn).await.map(|u| u.email),
+            None => ea.email.clone(),
+        };
+        granted_json.push(emergency_access_json(&ea, grantee_email, &fmt));
+    }
+
+    let mut received_json = Vec::new();
+    for ea in EmergencyAccess::find_all_by_grantee_uuid(&user.uuid, &mut conn).await {
+        let grantor_email = User::find_by_uuid(&ea.grantor_uuid, &mut conn).await.map(|u| u.email);
+        received_json.push(emergency_access_json(&ea, grantor_email, &fmt));
+    }
+
+    let login_events_json: Vec<Value> = Event::find_login_events_by_user(&user.uuid, LOGIN_EVENTS_LIMIT, &mut conn)
+        .await
+        .iter()
+        .map(|e| {
+            json!({
+                "date": fmt(&e.event_date),
+                "success": e.event_type == EventType::UserLoggedIn as i32,
+                "event": login_event_name(e.event_type),
+                "device_type": e.device_type.map(|t| DeviceType::from_i32(t).to_string()),
+                "ip_address": e.ip_address,
+            })
+        })
+        .collect();
+
+    let page_data = json!({
+        "id": user.uuid,
+        "name": user.name,
+        "email": user.email,
+        "enabled": user.enabled,
+        "invited": user.password_hash.is_empty(),
+        "email_verified": user.verified_at.is_some(),
+        "created_at": fmt(&user.created_at),
+        "last_login": user.last_login_at.as_ref().map(fmt),
+        "last_sync": user.last_sync_at.as_ref().map(fmt),
+        "devices": devices_json,
+        "two_factors": two_factors_json,
+        "memberships": memberships_json,
+        "emergency_access_granted": granted_json,
+        "emergency_access_received": received_json,
+        "cipher_count": Cipher::count_owned_by_user(&user.uuid, &mut conn).await,
+        "send_count": Send::find_by_user(&user.uuid, &mut conn).await.len(),
+        "send_size": get_display_size(Send::size_by_user(&user.uuid, &mut conn).await.unwrap_or_default()),
+        "attachment_count": Attachment::count_by_user(&user.uuid, &mut conn).await,
+        "attachment_size": get_display_size(Attachment::size_by_user(&user.uuid, &mut conn).await),
+        "login_events": login_events_json,
+        "events_enabled": CONFIG.org_events_enabled(),
+    });
+
+    let text = AdminTemplateData::new("admin/user_details", page_data, &token).render()?;
+    Ok(Html(text))
+}
+
+/// Removes a single device of a user, which logs out that device without touching the other sessions
+#[post("/users/<user_id>/devices/<device_id>/revoke", format = "application/json")]
+async fn revoke_user_device(
+    user_id: UserId,
+    device_id: DeviceId,
+    token: AdminSupportToken,
+    mut conn: DbConn,
+) -> EmptyResult {
+    let user = get_user_or_404(&user_id, &mut conn).await?;
+    let Some(device) = Device::find_by_uuid_and_user(&device_id, &user.uuid, &mut conn).await else {
+        err_code!("Device doesn't exist", Status::NotFound.code);
+    };
+
+    let details = json!({
+        "email": user.email,
+        "device_id": device.uuid,
+        "device_name": device.name,
+        "device_type": DeviceType::from_i32(device.atype).to_string(),
+    });
+    remove_device(device, &mut conn).await?;
+
+    token.audit_target("revoke_user_device", "user", &user.uuid, Some(details), &mut conn).await;
+    Ok(())
+}
+
+fn emergency_access_json(
+    ea: &EmergencyAccess,
+    other_email: Option<String>,
+    fmt: &impl Fn(&chrono::NaiveDateTime) -> String,
+) -> Value {
+    json!({
+        "email": other_email,
+        "type": ea.get_type_as_str(),
+        "status": emergency_access_status_name(ea.status),
+        "wait_time_days": ea.wait_time_days,
+        "recovery_initiated_at": ea.recovery_initiated_at.as_ref().map(fmt),
+    })
+}
+
+fn two_factor_type_name(atype: i32) -> Option<&'static str> {
+    let name = match TwoFactorType::from_i32(atype)? {
+        TwoFactorType::Authenticator => "Authenticator app",
+        TwoFactorType::Email => "Email",
+        TwoFactorType::Duo => "Duo",
+        TwoFactorType::YubiKey => "YubiKey OTP",
+        TwoFactorType::U2f => "FIDO U2F",
+        TwoFactorType::Webauthn => "WebAuthn",
+        // Remembered devices and organization Duo are not providers of the user itself
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn membership_type_name(membership_type: MembershipType) -> &'static str {
+    match membership_type {
+        MembershipType::Owner => "Owner",
+        MembershipType::Admin => "Admin",
+        MembershipType::User => "User",
+        MembershipType::Manager => "Manager",
+    }
+}
+
+fn membership_status_name(status: i32) -> &'static str {
+    match status {
+        s if s == MembershipStatus::Revoked as i32 => "Revoked",
+        s if s == MembershipStatus::Invited as i32 => "Invited",
+        s if s == MembershipStatus::Accepted as i32 => "Accepted",
+        s if s == MembershipStatus::Confirmed as i32 => "Confirmed",
+        _ => "Unknown",
+    }
+}
+
+fn emergency_access_status_name(status: i32) -> &'static str {
+    match status {
+        s if s == EmergencyAccessStatus::Invited as i32 => "Invited",
+        s if s == EmergencyAccessStatus::Accepted as i32 => "Accepted",
+        s if s == EmergencyAccessStatus::Confirmed as i32 => "Confirmed",
+        s if s == EmergencyAccessStatus::RecoveryInitiated as i32 => "Recovery initiated",
+        s if s == EmergencyAccessStatus::RecoveryApproved as i32 => "Recovery approved",
+        _ => "Unknown",
+    }
+}
+
+fn login_event_name(event_type: i32) -> &'static str {
+    match event_type {
+        t if t == EventType::UserLoggedIn as i32 => "Logged in",
+        t if t == EventType::UserFailedLogIn as i32 => "Failed login",
+        t if t == EventType::UserFailedLogIn2fa as i32 => "Failed two-step login",
+        _ => "Unknown",
+    }
+}
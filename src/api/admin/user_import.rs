@@ -0,0 +1,420 @@
+//
+// Bulk invitation of users from a CSV file, optionally adding them to an organization and its groups.
+// The admin panel first sends the file as a dry run, which returns the same per-row report without changing anything.
+//
+use std::collections::{HashMap, HashSet};
+
+use rocket::{serde::json::Json, Route};
+
+use super::{AdminToken, ACTING_ADMIN_USER, FAKE_ADMIN_UUID};
+use crate::{
+    api::{core::log_event, EmptyResult, JsonResult},
+    db::{models::*, DbConn},
+    mail,
+    util::is_valid_email,
+    CONFIG,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![import_users]
+}
+
+const MAX_IMPORT_ROWS: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+struct ImportData {
+    csv: String,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// The position of each column, only `email` is required
+struct Columns {
+    email: usize,
+    organization: Option<usize>,
+    membership_type: Option<usize>,
+    groups: Option<usize>,
+}
+
+impl Columns {
+    fn from_header(header: &[String]) -> Result<Self, String> {
+        let find = |name: &str| header.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+        let Some(email) = find("email") else {
+            return Err(String::from("The CSV file needs a header row with at least an `email` column"));
+        };
+        Ok(Self {
+            email,
+            organization: find("organization"),
+            membership_type: find("type"),
+            groups: find("groups"),
+        })
+    }
+
+    fn get<'a>(fields: &'a [String], column: Option<usize>) -> &'a str {
+        column.and_then(|c| fields.get(c)).map_or("", |f| f.trim())
+    }
+}
+
+/// A row of the CSV file, after checking that the organization and groups exist
+struct ImportRow {
+    email: String,
+    org: Option<(OrganizationId, String)>,
+    membership_type: MembershipType,
+    groups: Vec<(GroupId, String)>,
+}
+
+/// Lookups shared by all the rows of an import
+struct ImportContext {
+    organizations: Vec<Organization>,
+    groups: HashMap<OrganizationId, Vec<Group>>,
+    // Users created by an earlier row, which do not exist yet during a dry run
+    new_users: HashSet<String>,
+}
+
+#[post("/users/import", format = "application/json", data = "<data>")]
+async fn import_users(data: Json<ImportData>, token: AdminToken, mut conn: DbConn) -> JsonResult {
+    let data: ImportData = data.into_inner();
+    let mut rows = parse_csv(&data.csv).into_iter();
+    let Some(header) = rows.next() else {
+        err!("The CSV file is empty")
+    };
+    let columns = match Columns::from_header(&header) {
+        Ok(columns) => columns,
+        Err(e) => err!(e.as_str()),
+    };
+    let rows: Vec<Vec<String>> = rows.collect();
+    if rows.len() > MAX_IMPORT_ROWS {
+        err!(format!("The CSV file can't have more than {MAX_IMPORT_ROWS} rows"))
+    }
+
+    let mut context = ImportContext {
+        organizations: Organization::get_all(&mut conn).await,
+        groups: HashMap::new(),
+        new_users: HashSet::new(),
+    };
+
+    let mut report = Vec::with_capacity(rows.len());
+    let mut failed = 0;
+    for (i, fields) in rows.iter().enumerate() {
+        let email = Columns::get(fields, Some(columns.email)).to_lowercase();
+        let result = match parse_row(fields, &columns, &mut context, &mut conn).await {
+            Ok(row) => import_row(&row, data.dry_run, &token, &mut context, &mut conn).await,
+            Err(e) => Err(e),
+        };
+        let (actions, error) = match result {
+            Ok(actions) => (actions, None),
+            Err(e) => {
+                failed += 1;
+                (Vec::new(), Some(e))
+            }
+        };
+        report.push(json!({
+            "row": i + 2, // The line in the file, after the header
+            "email": email,
+            "actions": actions,
+            "error": error,
+        }));
+    }
+
+    if !data.dry_run {
+        token.audit("import_users", Some(json!({"rows": rows.len(), "failed": failed})), &mut conn).await;
+    }
+
+    Ok(Json(json!({
+        "dry_run": data.dry_run,
+        "rows": report,
+        "failed": failed,
+    })))
+}
+
+async fn parse_row(
+    fields: &[String],
+    columns: &Columns,
+    context: &mut ImportContext,
+    conn: &mut DbConn,
+) -> Result<ImportRow, String> {
+    let email = Columns::get(fields, Some(columns.email)).to_lowercase();
+    if !is_valid_email(&email) {
+        return Err(format!("`{email}` is not a valid email address"));
+    }
+
+    let org_value = Columns::get(fields, columns.organization);
+    let type_value = Columns::get(fields, columns.membership_type);
+    let group_names: Vec<&str> =
+        Columns::get(fields, columns.groups).split(';').map(str::trim).filter(|g| !g.is_empty()).collect();
+
+    let Some(membership_type) = parse_membership_type(type_value) else {
+        return Err(format!("Unknown type `{type_value}`, use owner, admin, manager or user"));
+    };
+
+    if org_value.is_empty() {
+        if !type_value.is_empty() || !group_names.is_empty() {
+            return Err(String::from("A type or groups need an organization"));
+        }
+        return Ok(ImportRow {
+            email,
+            org: None,
+            membership_type,
+            groups: Vec::new(),
+        });
+    }
+
+    // The organization can be given by its id or its name
+    let matches: Vec<&Organization> =
+        context.organizations.iter().filter(|o| *o.uuid == *org_value || o.name == org_value).collect();
+    let org = match matches.as_slice() {
+        [org] => (org.uuid.clone(), org.name.clone()),
+        [] => return Err(format!("Organization `{org_value}` not found")),
+        _ => return Err(format!("Several organizations are named `{org_value}`, use the organization id")),
+    };
+
+    let mut groups = Vec::with_capacity(group_names.len());
+    if !group_names.is_empty() {
+        if !CONFIG.org_groups_enabled() {
+            return Err(String::from("Groups are disabled, see ORG_GROUPS_ENABLED"));
+        }
+        if !context.groups.contains_key(&org.0) {
+            let org_groups = Group::find_by_organization(&org.0, conn).await;
+            context.groups.insert(org.0.clone(), org_groups);
+        }
+        let org_groups = &context.groups[&org.0];
+        for name in group_names {
+            match org_groups.iter().find(|g| g.name == name) {
+                Some(group) => groups.push((group.uuid.clone(), group.name.clone())),
+                None => return Err(format!("Group `{name}` not found in organization `{}`", org.1)),
+            }
+        }
+    }
+
+    Ok(ImportRow {
+        email,
+        org: Some(org),
+        membership_type,
+        groups,
+    })
+}
+
+/// Returns the changes made, or which would be made during a dry run
+async fn import_row(
+    row: &ImportRow,
+    dry_run: bool,
+    token: &AdminToken,
+    context: &mut ImportContext,
+    conn: &mut DbConn,
+) -> Result<Vec<String>, String> {
+    let mut actions = Vec::new();
+    let existing_user = User::find_by_mail(&row.email, conn).await;
+
+    let existing_member = match (&existing_user, &row.org) {
+        (Some(user), Some((org_id, _))) => Membership::find_by_user_and_org(&user.uuid, org_id, conn).await,
+        _ => None,
+    };
+
+    if existing_user.is_none() {
+        if context.new_users.contains(&row.email) {
+            actions.push(String::from("User invited by an earlier row"));
+        } else {
+            actions.push(String::from("Invite new user"));
+            context.new_users.insert(row.email.clone());
+        }
+    } else if row.org.is_none() {
+        actions.push(String::from("User already exists, nothing to do"));
+    }
+
+    if let Some((_, org_name)) = &row.org {
+        match &existing_member {
+            Some(member) if member.atype != row.membership_type => actions.push(format!(
+                "Already a member of `{org_name}`, the type is not changed, use the user overview instead"
+            )),
+            Some(_) => actions.push(format!("Already a member of `{org_name}`")),
+            None => actions.push(format!("Add to `{org_name}` as {}", membership_type_name(row.membership_type))),
+        }
+        for (_, group_name) in &row.groups {
+            actions.push(format!("Add to group `{group_name}`"));
+        }
+    }
+
+    if dry_run {
+        return Ok(actions);
+    }
+
+    apply_row(row, existing_user, existing_member, token, conn).await.map_err(|e| format!("{e:?}"))?;
+    Ok(actions)
+}
+
+async fn apply_row(
+    row: &ImportRow,
+    existing_user: Option<User>,
+    existing_member: Option<Membership>,
+    token: &AdminToken,
+    conn: &mut DbConn,
+) -> EmptyResult {
+    let user_created = existing_user.is_none();
+    let user = match existing_user {
+        Some(user) => user,
+        None => {
+            let mut user = User::new(row.email.clone());
+            if !CONFIG.mail_enabled() {
+                Invitation::new(&user.email).save(conn).await?;
+            } else if row.org.is_none() {
+                // With an organization, its invitation also allows to register
+                let org_id: OrganizationId = FAKE_ADMIN_UUID.to_string().into();
+                let member_id: MembershipId = FAKE_ADMIN_UUID.to_string().into();
+                mail::send_invite(&user, org_id, member_id, &CONFIG.invitation_org_name(), None).await?;
+            }
+            user.save(conn).await?;
+            user
+        }
+    };
+
+    let Some((org_id, org_name)) = &row.org else {
+        token.audit_target("import_user", "user", &user.uuid, Some(json!({"email": user.email})), conn).await;
+        return Ok(());
+    };
+
+    let member = match existing_member {
+        Some(member) => member,
+        None => {
+            let mut member = Membership::new(user.uuid.clone(), org_id.clone());
+            member.access_all = row.membership_type >= MembershipType::Admin;
+            member.atype = row.membership_type as i32;
+            // Existing users are accepted automatically when mail is disabled, like the organization invitations
+            member.status = if !CONFIG.mail_enabled() && !user.password_hash.is_empty() {
+                MembershipStatus::Accepted as i32
+            } else {
+                MembershipStatus::Invited as i32
+            };
+            member.save(conn).await?;
+
+            if CONFIG.mail_enabled() {
+                if let Err(e) = mail::send_invite(&user, org_id.clone(), member.uuid.clone(), org_name, None).await {
+                    // Upon error delete the user or org member record, like the organization invitations
+                    if user_created {
+                        user.delete(conn).await?;
+                    } else {
+                        member.delete(conn).await?;
+                    }
+                    err!(format!("Error sending invite: {e:?} "));
+                }
+            }
+
+            log_event(
+                EventType::OrganizationUserInvited as i32,
+                &member.uuid,
+                org_id,
+                &ACTING_ADMIN_USER.into(),
+                14, // Use UnknownBrowser type
+                &token.ip.ip,
+                conn,
+            )
+            .await;
+            member
+        }
+    };
+
+    for (group_id, _) in &row.groups {
+        GroupUser::new(group_id.clone(), member.uuid.clone()).save(conn).await?;
+    }
+
+    token
+        .audit_target(
+            "import_user",
+            "user",
+            &user.uuid,
+            Some(json!({
+                "email": user.email,
+                "organization": org_id,
+                "type": row.membership_type as i32,
+                "groups": row.groups.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+            })),
+            conn,
+        )
+        .await;
+    Ok(())
+}
+
+fn parse_membership_type(value: &str) -> Option<MembershipType> {
+    match value.to_lowercase().as_str() {
+        "" | "2" | "user" => Some(MembershipType::User),
+        "0" | "owner" => Some(MembershipType::Owner),
+        "1" | "admin" => Some(MembershipType::Admin),
+        "3" | "manager" => Some(MembershipType::Manager),
+        _ => None,
+    }
+}
+
+fn membership_type_name(membership_type: MembershipType) -> &'static str {
+    match membership_type {
+        MembershipType::Owner => "owner",
+        MembershipType::Admin => "admin",
+        MembershipType::User => "user",
+        MembershipType::Manager => "manager",
+    }
+}
+
+/// Splits a CSV file in rows and fields, quoted fields can contain commas, quotes (as `""`) and newlines.
+/// Empty lines are skipped.
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+        } else {
+            match c {
+                '"' => in_quotes = true,
+                ',' => row.push(std::mem::take(&mut field)),
+                '\r' => {}
+                '\n' => {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                _ => field.push(c),
+            }
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows.retain(|r| r.iter().any(|f| !f.trim().is_empty()));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let csv = "\u{feff}email,organization,groups\r\n\
+                   a@example.com,Acme,\"Dev;Ops\"\r\n\
+                   \r\n\
+                   \"b@example.com\",\"Acme, \"\"Inc\"\"\",\"multi\nline\"";
+        let rows = parse_csv(csv);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], ["email", "organization", "groups"]);
+        assert_eq!(rows[1], ["a@example.com", "Acme", "Dev;Ops"]);
+        assert_eq!(rows[2], ["b@example.com", "Acme, \"Inc\"", "multi\nline"]);
+    }
+
+    #[test]
+    fn test_parse_membership_type() {
+        assert!(parse_membership_type("") == Some(MembershipType::User));
+        assert!(parse_membership_type("Owner") == Some(MembershipType::Owner));
+        assert!(parse_membership_type("3") == Some(MembershipType::Manager));
+        assert!(parse_membership_type("custom").is_none());
+    }
+}
@@ -7,9 +7,10 @@ use serde_json::Value;
 
 use crate::{
     api::{
-        core::{log_user_event, two_factor::email},
-        register_push_device, unregister_push_device, AnonymousNotify, EmptyResult, JsonResult, Notify,
-        PasswordOrOtpData, UpdateType,
+        admin::ACTING_ADMIN_USER,
+        core::{log_event, log_user_event, two_factor::email},
+        push_device_logout, register_push_device, unregister_push_device, AnonymousNotify, EmptyResult, JsonResult,
+        Notify, PasswordOrOtpData, UpdateType, WS_USERS,
     },
     auth::{decode_delete, decode_invite, decode_verify_email, ClientHeaders, Headers},
     crypto,
@@ -34,6 +35,7 @@ pub fn routes() -> Vec<rocket::Route> {
         get_public_keys,
         post_keys,
         post_password,
+        post_set_password,
         post_kdf,
         post_rotatekey,
         post_sstamp,
@@ -54,11 +56,17 @@ pub fn routes() -> Vec<rocket::Route> {
         get_known_device,
         get_all_devices,
         get_device,
+        delete_device,
+        post_device_deactivate,
+        put_device_keys,
+        post_device_keys,
+        post_untrust_devices,
         post_device_token,
         put_device_token,
         put_clear_device_token,
         post_clear_device_token,
         post_auth_request,
+        post_admin_auth_request,
         get_auth_request,
         put_auth_request,
         get_auth_request_response,
@@ -422,6 +430,56 @@ async fn post_keys(data: Json<KeysData>, headers: Headers, mut conn: DbConn) ->
     })))
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetPasswordData {
+    kdf: Option<i32>,
+    kdf_iterations: Option<i32>,
+    kdf_memory: Option<i32>,
+    kdf_parallelism: Option<i32>,
+    key: String,
+    keys: Option<KeysData>,
+    master_password_hash: String,
+    master_password_hint: Option<String>,
+}
+
+// Used by users created during their first SSO login, which do not have a master password yet
+#[post("/accounts/set-password", data = "<data>")]
+async fn post_set_password(data: Json<SetPasswordData>, headers: Headers, mut conn: DbConn) -> EmptyResult {
+    let data: SetPasswordData = data.into_inner();
+    let mut user = headers.user;
+
+    if !user.password_hash.is_empty() {
+        err!("The master password has already been set")
+    }
+
+    let password_hint = clean_password_hint(&data.master_password_hint);
+    enforce_password_hint_setting(&password_hint)?;
+
+    if let Some(client_kdf_type) = data.kdf {
+        user.client_kdf_type = client_kdf_type;
+    }
+    if let Some(client_kdf_iter) = data.kdf_iterations {
+        user.client_kdf_iter = client_kdf_iter;
+    }
+    user.client_kdf_memory = data.kdf_memory;
+    user.client_kdf_parallelism = data.kdf_parallelism;
+
+    // Don't reset the security stamp, the client continues with its current session
+    user.set_password(&data.master_password_hash, Some(data.key), false, None);
+    user.password_hint = password_hint;
+
+    if let Some(keys) = data.keys {
+        user.private_key = Some(keys.encrypted_private_key);
+        user.public_key = Some(keys.public_key);
+    }
+
+    log_user_event(EventType::UserChangedPassword as i32, &user.uuid, headers.device.atype, &headers.ip.ip, &mut conn)
+        .await;
+
+    user.save(&mut conn).await
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ChangePassData {
@@ -552,6 +610,14 @@ struct UpdateResetPasswordData {
     reset_password_key: String,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateWebauthnKeyData {
+    id: WebAuthnCredentialId,
+    encrypted_public_key: String,
+    encrypted_user_key: String,
+}
+
 use super::ciphers::CipherData;
 use super::sends::{update_send_from_data, SendData};
 
@@ -563,6 +629,8 @@ struct KeyData {
     sends: Vec<SendData>,
     emergency_access_keys: Vec<UpdateEmergencyAccessData>,
     reset_password_keys: Vec<UpdateResetPasswordData>,
+    #[serde(default, alias = "webAuthnKeys")]
+    webauthn_keys: Vec<UpdateWebauthnKeyData>,
     key: String,
     master_password_hash: String,
     private_key: String,
@@ -730,6 +798,28 @@ async fn post_rotatekey(data: Json<KeyData>, headers: Headers, mut conn: DbConn,
     user.private_key = Some(data.private_key);
     user.reset_security_stamp();
 
+    // The keys of the trusted devices still wrap the old user key, so those devices need to be trusted again
+    Device::clear_trust_keys_by_user(&user.uuid, &mut conn).await?;
+
+    // The passkeys which can unlock the vault are re-encrypted by the client, the PRF keys of any other passkey are removed
+    for mut passkey in WebAuthnCredential::find_by_user(&user.uuid, &mut conn).await {
+        if !passkey.has_prf_keys() {
+            continue;
+        }
+        match data.webauthn_keys.iter().find(|k| k.id == passkey.uuid) {
+            Some(keys) => {
+                passkey.encrypted_public_key = Some(keys.encrypted_public_key.clone());
+                passkey.encrypted_user_key = Some(keys.encrypted_user_key.clone());
+            }
+            None => {
+                passkey.encrypted_public_key = None;
+                passkey.encrypted_user_key = None;
+                passkey.encrypted_private_key = None;
+            }
+        }
+        passkey.save(&mut conn).await?;
+    }
+
     let save_result = user.save(&mut conn).await;
 
     // Prevent logging out the client where the user requested this endpoint from.
@@ -1172,6 +1262,92 @@ async fn get_device(device_id: DeviceId, headers: Headers, mut conn: DbConn) ->
     Ok(Json(device.to_json()))
 }
 
+/// Removes a device, which ends its session: the refresh token, the remembered 2FA and the push registration are removed with it.
+/// Only that device receives a logout, by push and on its WebSocket connections, the other devices of the user stay logged in.
+pub async fn remove_device(device: Device, conn: &mut DbConn) -> EmptyResult {
+    if CONFIG.push_enabled() && device.is_registered() {
+        push_device_logout(&device).await;
+        if let Err(e) = unregister_push_device(device.push_uuid.clone()).await {
+            error!("Unable to unregister device from Bitwarden server: {e}");
+        }
+    }
+
+    let user_id = device.user_uuid.clone();
+    let device_id = device.uuid.clone();
+    device.delete(conn).await?;
+
+    WS_USERS.send_device_logout(&user_id, &device_id).await;
+    Ok(())
+}
+
+#[delete("/devices/<device_id>")]
+async fn delete_device(device_id: DeviceId, headers: Headers, mut conn: DbConn) -> EmptyResult {
+    if device_id == headers.device.uuid {
+        err!("The device of the current session can't be removed, log out instead")
+    }
+    let Some(device) = Device::find_by_uuid_and_user(&device_id, &headers.user.uuid, &mut conn).await else {
+        err!("No device found");
+    };
+    remove_device(device, &mut conn).await
+}
+
+#[post("/devices/<device_id>/deactivate")]
+async fn post_device_deactivate(device_id: DeviceId, headers: Headers, conn: DbConn) -> EmptyResult {
+    delete_device(device_id, headers, conn).await
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeviceKeysData {
+    encrypted_user_key: String,
+    encrypted_public_key: String,
+    encrypted_private_key: String,
+}
+
+/// Trusts a device by storing its keys, with which it can decrypt the user key without the master password
+#[put("/devices/<device_id>/keys", data = "<data>")]
+async fn put_device_keys(
+    device_id: DeviceId,
+    data: Json<DeviceKeysData>,
+    headers: Headers,
+    mut conn: DbConn,
+) -> JsonResult {
+    let data = data.into_inner();
+    let Some(mut device) = Device::find_by_uuid_and_user(&device_id, &headers.user.uuid, &mut conn).await else {
+        err!("No device found");
+    };
+    device.set_trust_keys(data.encrypted_user_key, data.encrypted_public_key, data.encrypted_private_key);
+    device.save(&mut conn).await?;
+    Ok(Json(device.to_json()))
+}
+
+#[post("/devices/<device_id>/keys", data = "<data>")]
+async fn post_device_keys(
+    device_id: DeviceId,
+    data: Json<DeviceKeysData>,
+    headers: Headers,
+    conn: DbConn,
+) -> JsonResult {
+    put_device_keys(device_id, data, headers, conn).await
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UntrustDevicesData {
+    devices: Vec<DeviceId>,
+}
+
+#[post("/devices/untrust", data = "<data>")]
+async fn post_untrust_devices(data: Json<UntrustDevicesData>, headers: Headers, mut conn: DbConn) -> EmptyResult {
+    for device_id in data.into_inner().devices {
+        if let Some(mut device) = Device::find_by_uuid_and_user(&device_id, &headers.user.uuid, &mut conn).await {
+            device.clear_trust_keys();
+            device.save(&mut conn).await?;
+        }
+    }
+    Ok(())
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct PushToken {
@@ -1312,6 +1488,70 @@ async fn post_auth_request(
     })))
 }
 
+/// Requests an organization admin to approve the login of a device which is not trusted yet, used with SSO.
+/// The admin wraps the user key with the account recovery key, so the user needs to be enrolled in account recovery.
+#[post("/auth-requests/admin-request", data = "<data>")]
+async fn post_admin_auth_request(data: Json<AuthRequestRequest>, headers: Headers, mut conn: DbConn) -> JsonResult {
+    let data = data.into_inner();
+    let user = headers.user;
+
+    if !user.email.eq_ignore_ascii_case(&data.email) || data.device_identifier != headers.device.uuid {
+        err!("AuthRequest doesn't exist", "User or device verification failed")
+    }
+
+    let mut org_id = None;
+    for member in Membership::find_confirmed_by_user(&user.uuid, &mut conn).await {
+        if member.reset_password_key.is_none() {
+            continue;
+        }
+        if let Some(policy) =
+            OrgPolicy::find_by_org_and_type(&member.org_uuid, OrgPolicyType::ResetPassword, &mut conn).await
+        {
+            if policy.enabled {
+                org_id = Some(member.org_uuid);
+                break;
+            }
+        }
+    }
+    let Some(org_id) = org_id else {
+        err!("You need to be enrolled in account recovery of an organization to request an admin approval")
+    };
+
+    let mut auth_request = AuthRequest::new(
+        user.uuid.clone(),
+        data.device_identifier,
+        headers.device.atype,
+        headers.ip.ip.to_string(),
+        data.access_code,
+        data.public_key,
+    );
+    auth_request.organization_uuid = Some(org_id);
+    auth_request.save(&mut conn).await?;
+
+    log_user_event(
+        EventType::UserRequestedDeviceApproval as i32,
+        &user.uuid,
+        headers.device.atype,
+        &headers.ip.ip,
+        &mut conn,
+    )
+    .await;
+
+    Ok(Json(json!({
+        "id": auth_request.uuid,
+        "publicKey": auth_request.public_key,
+        "requestDeviceType": DeviceType::from_i32(auth_request.device_type).to_string(),
+        "requestIpAddress": auth_request.request_ip,
+        "key": null,
+        "masterPasswordHash": null,
+        "creationDate": format_date(&auth_request.creation_date),
+        "responseDate": null,
+        "requestApproved": false,
+        "origin": CONFIG.domain_origin(),
+        "object": "auth-request"
+    })))
+}
+
 #[get("/auth-requests/<auth_request_id>")]
 async fn get_auth_request(auth_request_id: AuthRequestId, headers: Headers, mut conn: DbConn) -> JsonResult {
     let Some(auth_request) = AuthRequest::find_by_uuid_and_user(&auth_request_id, &headers.user.uuid, &mut conn).await
@@ -1457,7 +1697,8 @@ async fn get_auth_requests(headers: Headers, mut conn: DbConn) -> JsonResult {
     Ok(Json(json!({
         "data": auth_requests
             .iter()
-            .filter(|request| request.approved.is_none())
+            // The admin requests are answered by the organization admins, see `org_auth_requests`
+            .filter(|request| request.approved.is_none() && !request.is_admin_request())
             .map(|request| {
             let response_date_utc = request.response_date.map(|response_date| format_date(&response_date));
 
@@ -1488,3 +1729,114 @@ pub async fn purge_auth_requests(pool: DbPool) {
         error!("Failed to get DB connection while purging trashed ciphers")
     }
 }
+
+/// Warns the users who were inactive for `INACTIVE_USER_WARN_DAYS`, and disables them or revokes their organization
+/// memberships after `INACTIVE_USER_ACTION_DAYS`. Invited, disabled and allowlisted users are skipped.
+pub async fn inactive_user_job(pool: DbPool) {
+    debug!("Start inactive_user_job");
+    let warn_days = CONFIG.inactive_user_warn_days();
+    let action_days = CONFIG.inactive_user_action_days();
+    if warn_days.is_none() && action_days.is_none() {
+        return;
+    }
+
+    let Ok(mut conn) = pool.get().await else {
+        error!("Failed to get DB connection while checking inactive users");
+        return;
+    };
+
+    let now = Utc::now().naive_utc();
+    for mut user in User::get_all(&mut conn).await {
+        if !user.enabled || user.password_hash.is_empty() || CONFIG.is_inactive_user_allowlisted(&user.email) {
+            continue;
+        }
+
+        let last_activity = user.last_activity(&mut conn).await;
+        let inactive_days = (now - last_activity).num_days();
+        // A warning sent before the last activity belongs to an earlier inactive period
+        let warned_at = user.inactivity_warned_at.filter(|w| *w > last_activity);
+
+        if let Some(action_days) = action_days {
+            // After a warning the user always gets the days between both thresholds to become active again
+            let warning_expired = match (warn_days, warned_at) {
+                (None, _) => true,
+                (Some(warn_days), Some(warned_at)) => (now - warned_at).num_days() >= action_days - warn_days,
+                (Some(_), None) => false,
+            };
+            if inactive_days >= action_days && warning_expired {
+                if let Err(e) = apply_inactive_user_action(&mut user, inactive_days, &mut conn).await {
+                    error!("Error applying the inactive user action to {}: {e:#?}", user.email);
+                }
+                continue;
+            }
+        }
+
+        if let Some(warn_days) = warn_days {
+            // Only a warning which was actually emailed starts the grace period before the action
+            if inactive_days >= warn_days && warned_at.is_none() && CONFIG.mail_enabled() {
+                let days_left = action_days.map(|a| (a - inactive_days).max(a - warn_days));
+                if let Err(e) = mail::send_inactive_account_warning(&user.email, &last_activity, days_left).await {
+                    error!("Error sending the inactive account warning to {}: {e:#?}", user.email);
+                    continue;
+                }
+                if let Err(e) = user.set_inactivity_warned(&mut conn).await {
+                    error!("Error saving the inactive account warning of {}: {e:#?}", user.email);
+                }
+            }
+        }
+    }
+}
+
+async fn apply_inactive_user_action(user: &mut User, inactive_days: i64, conn: &mut DbConn) -> EmptyResult {
+    let action = CONFIG.inactive_user_action();
+    let mut details = json!({
+        "email": user.email,
+        "inactive_days": inactive_days,
+    });
+
+    if action == "revoke" {
+        let mut revoked = Vec::new();
+        for mut member in Membership::find_any_state_by_user(&user.uuid, conn).await {
+            // Never leave an organization without a confirmed owner
+            if member.atype == MembershipType::Owner
+                && member.status == MembershipStatus::Confirmed as i32
+                && Membership::count_confirmed_by_org_and_type(&member.org_uuid, MembershipType::Owner, conn).await <= 1
+            {
+                warn!("Not revoking {}, the last owner of organization {}", user.email, member.org_uuid);
+                continue;
+            }
+            if member.revoke() {
+                member.save(conn).await?;
+                log_event(
+                    EventType::OrganizationUserRevoked as i32,
+                    &member.uuid,
+                    &member.org_uuid,
+                    &ACTING_ADMIN_USER.into(),
+                    14, // Use UnknownBrowser type
+                    &std::net::IpAddr::from([0, 0, 0, 0]),
+                    conn,
+                )
+                .await;
+                revoked.push(member.org_uuid);
+            }
+        }
+        if revoked.is_empty() {
+            return Ok(());
+        }
+        details["organizations"] = json!(revoked);
+    } else {
+        Device::delete_all_by_user(&user.uuid, conn).await?;
+        user.reset_security_stamp();
+        user.enabled = false;
+        user.save(conn).await?;
+        WS_USERS.send_logout(user, None).await;
+    }
+
+    info!("Applied the inactive user action '{action}' to {}", user.email);
+    let mut entry =
+        AdminAuditLog::new(String::from("inactive_user_job"), String::new(), &format!("{action}_inactive_user"));
+    entry.target_type = Some(String::from("user"));
+    entry.target_id = Some(user.uuid.to_string());
+    entry.details = Some(details.to_string());
+    entry.save(conn).await
+}
@@ -17,6 +17,7 @@ use crate::{
     auth::Headers,
     crypto,
     db::{models::*, DbConn, DbPool},
+    storage::{storage, PathType},
     CONFIG,
 };
 
@@ -153,6 +154,10 @@ async fn sync(
     let policies_json: Vec<Value> =
         OrgPolicy::find_confirmed_by_user(&headers.user.uuid, &mut conn).await.iter().map(OrgPolicy::to_json).collect();
 
+    if let Err(e) = User::update_last_sync(&headers.user.uuid, &mut conn).await {
+        error!("Error updating the last sync of {}: {e:#?}", headers.user.email);
+    }
+
     let domains_json = if data.exclude_domains {
         Value::Null
     } else {
@@ -1167,7 +1172,8 @@ async fn save_attachment(
     };
 
     let size_limit = if let Some(ref user_id) = cipher.user_uuid {
-        match CONFIG.user_attachment_limit() {
+        // A personal cipher is only write accessible to its owner, so this is the quota of the current user
+        match headers.user.attachment_limit_kb() {
             Some(0) => err!("Attachments are disabled"),
             Some(limit_kb) => {
                 let already_used = Attachment::size_by_user(user_id, &mut conn).await;
@@ -1189,7 +1195,10 @@ async fn save_attachment(
             None => None,
         }
     } else if let Some(ref org_id) = cipher.organization_uuid {
-        match CONFIG.org_attachment_limit() {
+        let Some(org) = Organization::find_by_uuid(org_id, &mut conn).await else {
+            err!("Organization doesn't exist")
+        };
+        match org.attachment_limit_kb() {
             Some(0) => err!("Attachments are disabled"),
             Some(limit_kb) => {
                 let already_used = Attachment::size_by_org(org_id, &mut conn).await;
@@ -1269,13 +1278,7 @@ async fn save_attachment(
         attachment.save(&mut conn).await.expect("Error saving attachment");
     }
 
-    let folder_path = tokio::fs::canonicalize(&CONFIG.attachments_folder()).await?.join(cipher_id.as_ref());
-    let file_path = folder_path.join(file_id.as_ref());
-    tokio::fs::create_dir_all(&folder_path).await?;
-
-    if let Err(_err) = data.data.persist_to(&file_path).await {
-        data.data.move_copy_to(file_path).await?
-    }
+    storage().save_temp_file(PathType::Attachments, &format!("{cipher_id}/{file_id}"), &mut data.data).await?;
 
     nt.send_cipher_update(
         UpdateType::SyncCipherUpdate,
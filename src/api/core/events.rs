@@ -5,12 +5,13 @@ use rocket::{form::FromForm, serde::json::Json, Route};
 use serde_json::Value;
 
 use crate::{
-    api::{EmptyResult, JsonResult},
+    api::{core::webhooks::queue_event_deliveries, EmptyResult, JsonResult},
     auth::{AdminHeaders, Headers},
     db::{
         models::{Cipher, CipherId, Event, Membership, MembershipId, OrganizationId, UserId},
         DbConn, DbPool,
     },
+    event_sink,
     util::parse_date,
     CONFIG,
 };
@@ -268,7 +269,12 @@ async fn _log_user_event(
         events.push(event);
     }
 
-    Event::save_user_event(events, conn).await.unwrap_or(());
+    if Event::save_user_event(&events, conn).await.is_ok() {
+        for event in events {
+            queue_event_deliveries(&event, conn).await;
+            event_sink::forward_event(event);
+        }
+    }
 }
 
 pub async fn log_event(
@@ -330,7 +336,10 @@ async fn _log_event(
     event.act_user_uuid = Some(act_user_id.clone());
     event.device_type = Some(device_type);
     event.ip_address = Some(ip.to_string());
-    event.save(conn).await.unwrap_or(());
+    if event.save(conn).await.is_ok() {
+        queue_event_deliveries(&event, conn).await;
+        event_sink::forward_event(event);
+    }
 }
 
 pub async fn event_cleanup_job(pool: DbPool) {
@@ -3,22 +3,26 @@ mod ciphers;
 mod emergency_access;
 mod events;
 mod folders;
+mod org_auth_requests;
 mod organizations;
+pub mod passkeys;
 mod public;
 mod sends;
 pub mod two_factor;
+mod webhooks;
 
-pub use accounts::purge_auth_requests;
+pub use accounts::{inactive_user_job, purge_auth_requests};
 pub use ciphers::{purge_trashed_ciphers, CipherData, CipherSyncData, CipherSyncType};
 pub use emergency_access::{emergency_notification_reminder_job, emergency_request_timeout_job};
 pub use events::{event_cleanup_job, log_event, log_user_event};
 use reqwest::Method;
 pub use sends::purge_sends;
+pub use webhooks::webhook_delivery_job;
 
 pub fn routes() -> Vec<Route> {
     let mut eq_domains_routes = routes![get_eq_domains, post_eq_domains, put_eq_domains];
     let mut hibp_routes = routes![hibp_breach];
-    let mut meta_routes = routes![alive, now, version, config, get_api_webauthn];
+    let mut meta_routes = routes![alive, now, version, config];
 
     let mut routes = Vec::new();
     routes.append(&mut accounts::routes());
@@ -26,10 +30,13 @@ pub fn routes() -> Vec<Route> {
     routes.append(&mut emergency_access::routes());
     routes.append(&mut events::routes());
     routes.append(&mut folders::routes());
+    routes.append(&mut org_auth_requests::routes());
     routes.append(&mut organizations::routes());
+    routes.append(&mut passkeys::routes());
     routes.append(&mut two_factor::routes());
     routes.append(&mut sends::routes());
     routes.append(&mut public::routes());
+    routes.append(&mut webhooks::routes());
     routes.append(&mut eq_domains_routes);
     routes.append(&mut hibp_routes);
     routes.append(&mut meta_routes);
@@ -184,18 +191,6 @@ fn version() -> Json<&'static str> {
     Json(crate::VERSION.unwrap_or_default())
 }
 
-#[get("/webauthn")]
-fn get_api_webauthn(_headers: Headers) -> Json<Value> {
-    // Prevent a 404 error, which also causes key-rotation issues
-    // It looks like this is used when login with passkeys is enabled, which Vaultwarden does not (yet) support
-    // An empty list/data also works fine
-    Json(json!({
-        "object": "list",
-        "data": [],
-        "continuationToken": null
-    }))
-}
-
 #[get("/config")]
 fn config() -> Json<Value> {
     let domain = crate::CONFIG.domain();
@@ -0,0 +1,219 @@
+//
+// Device approvals by the organization admins, for members logging in with SSO on a device which is not trusted yet.
+// The member needs to be enrolled in account recovery: the admin client decrypts the user key with the account recovery
+// key and wraps it with the public key of the requesting device, so the server never sees the user key.
+//
+use chrono::Utc;
+use num_traits::FromPrimitive;
+use rocket::{serde::json::Json, Route};
+
+use crate::{
+    api::{core::log_event, AnonymousNotify, EmptyResult, JsonResult, Notify},
+    auth::AdminHeaders,
+    db::{models::*, DbConn},
+    util::format_date,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![get_org_auth_requests, post_org_auth_request, bulk_org_auth_requests, bulk_deny_org_auth_requests]
+}
+
+#[get("/organizations/<org_id>/auth-requests")]
+async fn get_org_auth_requests(org_id: OrganizationId, headers: AdminHeaders, mut conn: DbConn) -> JsonResult {
+    if org_id != headers.org_id {
+        err!("Organization not found", "Organization id's do not match");
+    }
+
+    let mut requests_json = Vec::new();
+    for auth_request in AuthRequest::find_pending_by_org(&org_id, &mut conn).await {
+        // Expired requests are only kept until the next purge
+        if auth_request.is_expired_admin_request() {
+            continue;
+        }
+        let Some(member) = Membership::find_by_user_and_org(&auth_request.user_uuid, &org_id, &mut conn).await else {
+            continue;
+        };
+        // Without account recovery there is no key to approve the request with
+        if member.reset_password_key.is_none() {
+            continue;
+        }
+        let Some(user) = User::find_by_uuid(&auth_request.user_uuid, &mut conn).await else {
+            continue;
+        };
+        requests_json.push(json!({
+            "id": auth_request.uuid,
+            "userId": user.uuid,
+            "organizationUserId": member.uuid,
+            "email": user.email,
+            "publicKey": auth_request.public_key,
+            "requestDeviceIdentifier": auth_request.request_device_identifier,
+            "requestDeviceType": DeviceType::from_i32(auth_request.device_type).to_string(),
+            "requestIpAddress": auth_request.request_ip,
+            "creationDate": format_date(&auth_request.creation_date),
+            "object": "pending-org-auth-request"
+        }));
+    }
+
+    Ok(Json(json!({
+        "data": requests_json,
+        "object": "list",
+        "continuationToken": null
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AdminAuthResponseData {
+    encrypted_user_key: Option<String>,
+    request_approved: bool,
+}
+
+#[post("/organizations/<org_id>/auth-requests/<auth_request_id>", data = "<data>", rank = 2)]
+async fn post_org_auth_request(
+    org_id: OrganizationId,
+    auth_request_id: AuthRequestId,
+    data: Json<AdminAuthResponseData>,
+    headers: AdminHeaders,
+    mut conn: DbConn,
+    ant: AnonymousNotify<'_>,
+    nt: Notify<'_>,
+) -> EmptyResult {
+    if org_id != headers.org_id {
+        err!("Organization not found", "Organization id's do not match");
+    }
+    let data = data.into_inner();
+
+    _answer_org_auth_request(
+        &auth_request_id,
+        data.request_approved,
+        data.encrypted_user_key,
+        &headers,
+        &mut conn,
+        &ant,
+        &nt,
+    )
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BulkAdminAuthResponseData {
+    id: AuthRequestId,
+    key: Option<String>,
+    approved: bool,
+}
+
+#[post("/organizations/<org_id>/auth-requests", data = "<data>")]
+async fn bulk_org_auth_requests(
+    org_id: OrganizationId,
+    data: Json<Vec<BulkAdminAuthResponseData>>,
+    headers: AdminHeaders,
+    mut conn: DbConn,
+    ant: AnonymousNotify<'_>,
+    nt: Notify<'_>,
+) -> EmptyResult {
+    if org_id != headers.org_id {
+        err!("Organization not found", "Organization id's do not match");
+    }
+
+    for request in data.into_inner() {
+        // Like the official server, a request which can't be answered doesn't stop the others
+        if let Err(e) =
+            _answer_org_auth_request(&request.id, request.approved, request.key, &headers, &mut conn, &ant, &nt).await
+        {
+            warn!("Unable to answer auth request {}: {e:?}", request.id);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BulkDenyData {
+    ids: Vec<AuthRequestId>,
+}
+
+#[post("/organizations/<org_id>/auth-requests/deny", data = "<data>", rank = 1)]
+async fn bulk_deny_org_auth_requests(
+    org_id: OrganizationId,
+    data: Json<BulkDenyData>,
+    headers: AdminHeaders,
+    mut conn: DbConn,
+    ant: AnonymousNotify<'_>,
+    nt: Notify<'_>,
+) -> EmptyResult {
+    if org_id != headers.org_id {
+        err!("Organization not found", "Organization id's do not match");
+    }
+
+    for auth_request_id in data.into_inner().ids {
+        if let Err(e) = _answer_org_auth_request(&auth_request_id, false, None, &headers, &mut conn, &ant, &nt).await {
+            warn!("Unable to deny auth request {auth_request_id}: {e:?}");
+        }
+    }
+    Ok(())
+}
+
+async fn _answer_org_auth_request(
+    auth_request_id: &AuthRequestId,
+    approved: bool,
+    encrypted_user_key: Option<String>,
+    headers: &AdminHeaders,
+    conn: &mut DbConn,
+    ant: &AnonymousNotify<'_>,
+    nt: &Notify<'_>,
+) -> EmptyResult {
+    let Some(mut auth_request) = AuthRequest::find_by_uuid_and_org(auth_request_id, &headers.org_id, conn).await else {
+        err!("AuthRequest doesn't exist", "Record not found or organization does not match")
+    };
+    if auth_request.approved.is_some() {
+        err!("This request was already answered")
+    }
+    if auth_request.is_expired_admin_request() {
+        err!("This request has expired")
+    }
+
+    let Some(member) = Membership::find_by_user_and_org(&auth_request.user_uuid, &headers.org_id, conn).await else {
+        err!("The user is not a member of this organization")
+    };
+    // The same permissions as an account recovery, an admin can't approve the devices of an owner
+    if headers.membership_type < member.atype {
+        err!("No permission to approve this user's devices")
+    }
+
+    let now = Utc::now().naive_utc();
+    auth_request.approved = Some(approved);
+    auth_request.response_date = Some(now);
+
+    let event_type = if approved {
+        if member.reset_password_key.is_none() {
+            err!("The user is not enrolled in account recovery")
+        }
+        let Some(key) = encrypted_user_key else {
+            err!("The encrypted user key is required to approve a request")
+        };
+        auth_request.enc_key = Some(key);
+        auth_request.response_device_id = Some(headers.device.uuid.clone());
+        EventType::OrganizationUserApprovedAuthRequest
+    } else {
+        EventType::OrganizationUserRejectedAuthRequest
+    };
+    auth_request.save(conn).await?;
+
+    // The requesting device polls for the response, the notifications only make it notice the answer sooner
+    ant.send_auth_response(&auth_request.user_uuid, &auth_request.uuid).await;
+    nt.send_auth_response(&auth_request.user_uuid, &auth_request.uuid, &headers.device.uuid, conn).await;
+
+    log_event(
+        event_type as i32,
+        &member.uuid,
+        &headers.org_id,
+        &headers.user.uuid,
+        headers.device.atype,
+        &headers.ip.ip,
+        conn,
+    )
+    .await;
+
+    Ok(())
+}
@@ -53,6 +53,7 @@ pub fn routes() -> Vec<Route> {
         confirm_invite,
         bulk_confirm_invite,
         accept_invite,
+        accept_init_invite,
         get_org_user_mini_details,
         get_user,
         edit_member,
@@ -102,6 +103,8 @@ pub fn routes() -> Vec<Route> {
         put_reset_password_enrollment,
         get_reset_password_details,
         put_reset_password,
+        get_auto_enroll_status,
+        post_domain_sso_details,
         get_org_export,
         api_key,
         rotate_api_key,
@@ -1327,6 +1330,80 @@ async fn accept_invite(
     Ok(())
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AcceptInitData {
+    token: String,
+    key: String,
+    keys: OrgKeyData,
+    collection_name: Option<String>,
+}
+
+// Used by the owner of an organization created in the admin panel, which has no keys until the owner accepts the invitation
+#[post("/organizations/<org_id>/users/<member_id>/accept-init", data = "<data>")]
+async fn accept_init_invite(
+    org_id: OrganizationId,
+    member_id: MembershipId,
+    data: Json<AcceptInitData>,
+    headers: Headers,
+    mut conn: DbConn,
+) -> EmptyResult {
+    let data: AcceptInitData = data.into_inner();
+    let claims = decode_invite(&data.token)?;
+
+    if !claims.email.eq(&headers.user.email) {
+        err!("Invitation was issued to a different account", "Claim does not match user_id")
+    }
+
+    if !claims.member_id.eq(&member_id) || !claims.org_id.eq(&org_id) {
+        err!("Error accepting the invitation", "Claim does not match the member_id or org_id")
+    }
+
+    let Some(mut org) = Organization::find_by_uuid(&org_id, &mut conn).await else {
+        err!("Organization not found")
+    };
+    if org.private_key.is_some() || org.public_key.is_some() {
+        err!("Organization is already initialized")
+    }
+
+    let Some(mut member) = Membership::find_by_uuid_and_org(&member_id, &org_id, &mut conn).await else {
+        err!("Error accepting the invitation")
+    };
+    if member.user_uuid != headers.user.uuid
+        || member.status != MembershipStatus::Invited as i32
+        || member.atype != MembershipType::Owner
+    {
+        err!("Error accepting the invitation", "Only the invited owner can initialize the organization")
+    }
+
+    Invitation::take(&claims.email, &mut conn).await;
+
+    org.private_key = Some(data.keys.encrypted_private_key);
+    org.public_key = Some(data.keys.public_key);
+    member.akey = data.key;
+    member.status = MembershipStatus::Confirmed as i32;
+
+    org.save(&mut conn).await?;
+    member.save(&mut conn).await?;
+
+    if let Some(collection_name) = data.collection_name {
+        Collection::new(org.uuid.clone(), collection_name, None).save(&mut conn).await?;
+    }
+
+    log_event(
+        EventType::OrganizationUserConfirmed as i32,
+        &member.uuid,
+        &org_id,
+        &headers.user.uuid,
+        headers.device.atype,
+        &headers.ip.ip,
+        &mut conn,
+    )
+    .await;
+
+    Ok(())
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ConfirmData {
@@ -2027,6 +2104,10 @@ async fn put_policy(
         err!("Invalid or unsupported policy type")
     };
 
+    if pol_type_enum == OrgPolicyType::RequireSso && data.enabled && !CONFIG.sso_enabled() {
+        err!("SSO is not enabled on this server. It is mandatory for this policy to be enabled.")
+    }
+
     // Bitwarden only allows the Reset Password policy when Single Org policy is enabled
     // Vaultwarden encouraged to use multiple orgs instead of groups because groups were not available in the past
     // Now that groups are available we can enforce this option when wanted.
@@ -3278,6 +3359,78 @@ async fn put_reset_password_enrollment(
     Ok(())
 }
 
+// Called by the clients after setting the master password during the first SSO login.
+// There is only one identity provider, so the SSO identifier does not need to match an organization.
+#[get("/organizations/<identifier>/auto-enroll-status")]
+async fn get_auto_enroll_status(identifier: &str, headers: Headers, mut conn: DbConn) -> JsonResult {
+    let org = find_sso_identifier_org(identifier, &headers.user, &mut conn).await;
+
+    let reset_password_enabled = match org {
+        Some(ref org) => OrgPolicy::org_is_reset_password_auto_enroll(&org.uuid, &mut conn).await,
+        None => false,
+    };
+
+    Ok(Json(json!({
+        "id": org.map(|o| o.uuid),
+        "resetPasswordEnabled": reset_password_enabled,
+    })))
+}
+
+/// Resolves the SSO identifier sent by the clients to one of the organizations of the user.
+/// `post_domain_sso_details` hands out the domain of the email address, but an organization id is accepted as well.
+async fn find_sso_identifier_org(identifier: &str, user: &User, conn: &mut DbConn) -> Option<Organization> {
+    let memberships = Membership::find_any_state_by_user(&user.uuid, conn).await;
+    if let Some(member) = memberships.iter().find(|m| *m.org_uuid == *identifier) {
+        return Organization::find_by_uuid(&member.org_uuid, conn).await;
+    }
+
+    let domain = user.email.rsplit_once('@').map(|(_, domain)| domain);
+    if !domain.is_some_and(|domain| domain.eq_ignore_ascii_case(identifier)) {
+        return None;
+    }
+
+    // All the organizations share the identity provider, prefer the one which enrolls its members automatically
+    let mut fallback = None;
+    for member in memberships {
+        if OrgPolicy::org_is_reset_password_auto_enroll(&member.org_uuid, conn).await {
+            return Organization::find_by_uuid(&member.org_uuid, conn).await;
+        }
+        fallback.get_or_insert(member.org_uuid);
+    }
+    match fallback {
+        Some(org_id) => Organization::find_by_uuid(&org_id, conn).await,
+        None => None,
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OrgDomainDetailsData {
+    email: String,
+}
+
+// Used by the clients to prefill the SSO identifier.
+// Every identifier is accepted, so the domain of the email address is returned.
+#[post("/organizations/domain/sso/details", data = "<data>")]
+fn post_domain_sso_details(data: Json<OrgDomainDetailsData>) -> JsonResult {
+    if !CONFIG.sso_enabled() {
+        err_code!("SSO sign-in is not available", 404)
+    }
+
+    let data: OrgDomainDetailsData = data.into_inner();
+    let Some((_, domain)) = data.email.rsplit_once('@') else {
+        err!("Invalid email address")
+    };
+
+    Ok(Json(json!({
+        "organizationIdentifier": domain.to_lowercase(),
+        "ssoAvailable": true,
+        "domainName": domain.to_lowercase(),
+        "verifiedDate": null,
+        "object": "organizationDomainSsoDetails",
+    })))
+}
+
 // This is a new function active since the v2022.9.x clients.
 // It combines the previous two calls done before.
 // We call those two functions here and combine them ourselves.
@@ -0,0 +1,364 @@
+//
+// Passkeys to log in without the master password, the login itself is the `webauthn` grant of `api::identity`.
+// When the authenticator supports the PRF extension, the clients also store the user key wrapped with a key derived
+// from the PRF output, so the passkey can unlock the vault as well.
+//
+use chrono::Utc;
+use dashmap::DashMap;
+use data_encoding::BASE64URL_NOPAD;
+use once_cell::sync::Lazy;
+use rocket::{serde::json::Json, Route};
+use serde_json::Value;
+use webauthn_rs::{proto::*, AuthenticationState, RegistrationState};
+
+use crate::{
+    api::{
+        core::two_factor::webauthn::{PublicKeyCredentialCopy, RegisterPublicKeyCredentialCopy, WebauthnConfig},
+        EmptyResult, JsonResult, PasswordOrOtpData,
+    },
+    auth::{
+        decode_webauthn_login, decode_webauthn_register, encode_jwt, generate_webauthn_login_claims,
+        generate_webauthn_register_claims, Headers, WebauthnStateClaims,
+    },
+    db::{models::*, DbConn},
+    error::Error,
+    CONFIG,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        get_api_webauthn,
+        post_attestation_options,
+        post_assertion_options,
+        post_webauthn,
+        put_webauthn,
+        post_webauthn_delete,
+    ]
+}
+
+// The same limit as the official server
+const MAX_PASSKEYS: usize = 5;
+// The maximum length of a credential id in the WebAuthn specification, the database columns are sized for it
+const MAX_CREDENTIAL_ID_LENGTH: usize = 1023;
+
+// The login challenges which were already answered, with the expiration time of their token.
+// This prevents replaying a captured assertion while its token is still valid.
+static USED_LOGIN_CHALLENGES: Lazy<DashMap<String, i64>> = Lazy::new(DashMap::new);
+
+fn check_domain_set() -> EmptyResult {
+    if !CONFIG.domain_set() {
+        err!("`DOMAIN` environment variable is not set. Passkeys disabled")
+    }
+    Ok(())
+}
+
+fn parse_credential(passkey: &WebAuthnCredential) -> Result<Credential, Error> {
+    serde_json::from_str(&passkey.credential).map_err(Into::into)
+}
+
+// This name is also used in the stamp exception of a key rotation, the clients call it while rotating the keys
+#[get("/webauthn")]
+async fn get_api_webauthn(headers: Headers, mut conn: DbConn) -> Json<Value> {
+    let passkeys_json: Vec<Value> = WebAuthnCredential::find_by_user(&headers.user.uuid, &mut conn)
+        .await
+        .iter()
+        .map(WebAuthnCredential::to_json)
+        .collect();
+
+    Json(json!({
+        "object": "list",
+        "data": passkeys_json,
+        "continuationToken": null
+    }))
+}
+
+#[post("/webauthn/attestation-options", data = "<data>")]
+async fn post_attestation_options(data: Json<PasswordOrOtpData>, headers: Headers, mut conn: DbConn) -> JsonResult {
+    check_domain_set()?;
+    let data: PasswordOrOtpData = data.into_inner();
+    let user = headers.user;
+
+    data.validate(&user, false, &mut conn).await?;
+
+    let passkeys = WebAuthnCredential::find_by_user(&user.uuid, &mut conn).await;
+    if passkeys.len() >= MAX_PASSKEYS {
+        err!(format!("A maximum of {MAX_PASSKEYS} passkeys can be registered"))
+    }
+    // We return the credentialIds to the clients to avoid double registering
+    let exclude = passkeys.iter().map(parse_credential).map(|c| c.map(|c| c.cred_id)).collect::<Result<_, _>>()?;
+
+    let (challenge, state) = WebauthnConfig::load().generate_challenge_register_options(
+        user.uuid.as_bytes().to_vec(),
+        user.email,
+        user.name,
+        Some(exclude),
+        Some(UserVerificationPolicy::Required),
+        None,
+    )?;
+
+    let mut options = serde_json::to_value(challenge.public_key)?;
+    // The user is not known yet when logging in, so the passkey needs to be discoverable
+    options["authenticatorSelection"]["residentKey"] = "required".into();
+    options["authenticatorSelection"]["requireResidentKey"] = true.into();
+    options["authenticatorSelection"]["userVerification"] = "required".into();
+
+    let token = encode_jwt(&generate_webauthn_register_claims(&user.uuid, serde_json::to_string(&state)?));
+    Ok(Json(json!({
+        "options": options,
+        "token": token,
+        "object": "webauthnCredentialCreateOptions"
+    })))
+}
+
+/// Used to add the PRF-wrapped keys to a passkey which was registered without them
+#[post("/webauthn/assertion-options", data = "<data>")]
+async fn post_assertion_options(data: Json<PasswordOrOtpData>, headers: Headers, mut conn: DbConn) -> JsonResult {
+    check_domain_set()?;
+    let data: PasswordOrOtpData = data.into_inner();
+    let user = headers.user;
+
+    data.validate(&user, false, &mut conn).await?;
+
+    let passkeys = WebAuthnCredential::find_by_user(&user.uuid, &mut conn).await;
+    let credentials = passkeys.iter().map(parse_credential).collect::<Result<Vec<_>, _>>()?;
+    if credentials.is_empty() {
+        err!("No passkeys registered")
+    }
+
+    let (response, state) = WebauthnConfig::load().generate_challenge_authenticate_options(credentials, None)?;
+    let token = encode_jwt(&generate_webauthn_login_claims(user.uuid.to_string(), serde_json::to_string(&state)?));
+    Ok(Json(json!({
+        "options": response.public_key,
+        "token": token,
+        "object": "webAuthnLoginAssertionOptions"
+    })))
+}
+
+/// The options for a login with a passkey, without any credentials since the user is not known yet
+pub fn generate_passkey_login_options() -> JsonResult {
+    check_domain_set()?;
+
+    let (response, state) = WebauthnConfig::load().generate_challenge_authenticate_options(Vec::new(), None)?;
+    let token = encode_jwt(&generate_webauthn_login_claims(String::new(), serde_json::to_string(&state)?));
+    Ok(Json(json!({
+        "options": response.public_key,
+        "token": token,
+        "object": "webAuthnLoginAssertionOptions"
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreatePasskeyData {
+    device_response: RegisterPublicKeyCredentialCopy,
+    name: String,
+    token: String,
+    #[serde(default)]
+    supports_prf: bool,
+    encrypted_user_key: Option<String>,
+    encrypted_public_key: Option<String>,
+    encrypted_private_key: Option<String>,
+}
+
+#[post("/webauthn", data = "<data>")]
+async fn post_webauthn(data: Json<CreatePasskeyData>, headers: Headers, mut conn: DbConn) -> EmptyResult {
+    check_domain_set()?;
+    let data: CreatePasskeyData = data.into_inner();
+    let user = headers.user;
+
+    let claims = decode_webauthn_register(&data.token)?;
+    if claims.sub != *user.uuid {
+        err!("Invalid token")
+    }
+    let state: RegistrationState = serde_json::from_str(&claims.state)?;
+
+    if WebAuthnCredential::find_by_user(&user.uuid, &mut conn).await.len() >= MAX_PASSKEYS {
+        err!(format!("A maximum of {MAX_PASSKEYS} passkeys can be registered"))
+    }
+
+    let (credential, _data) =
+        WebauthnConfig::load().register_credential(&data.device_response.into(), &state, |_| Ok(false))?;
+
+    if credential.cred_id.len() > MAX_CREDENTIAL_ID_LENGTH {
+        err!("The credential id of this passkey is too long")
+    }
+    let credential_id = BASE64URL_NOPAD.encode(&credential.cred_id);
+    if WebAuthnCredential::find_by_credential_id(&credential_id, &mut conn).await.is_some() {
+        err!("This passkey is already registered")
+    }
+
+    let mut passkey = WebAuthnCredential::new(
+        user.uuid,
+        data.name,
+        credential_id,
+        serde_json::to_string(&credential)?,
+        data.supports_prf,
+    );
+    if data.supports_prf {
+        passkey.encrypted_user_key = data.encrypted_user_key;
+        passkey.encrypted_public_key = data.encrypted_public_key;
+        passkey.encrypted_private_key = data.encrypted_private_key;
+    }
+    passkey.save(&mut conn).await
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdatePasskeyData {
+    device_response: PublicKeyCredentialCopy,
+    token: String,
+    encrypted_user_key: String,
+    encrypted_public_key: String,
+    encrypted_private_key: String,
+}
+
+/// Adds the PRF-wrapped keys to the passkey which answered the challenge of `post_assertion_options`
+#[put("/webauthn", data = "<data>")]
+async fn put_webauthn(data: Json<UpdatePasskeyData>, headers: Headers, mut conn: DbConn) -> EmptyResult {
+    check_domain_set()?;
+    let data: UpdatePasskeyData = data.into_inner();
+    let user = headers.user;
+
+    let claims = decode_webauthn_login(&data.token)?;
+    if claims.sub != *user.uuid {
+        err!("Invalid token")
+    }
+
+    let Some(passkey) = find_passkey(&data.device_response, &mut conn).await else {
+        err!("Passkey not found")
+    };
+    if passkey.user_uuid != user.uuid {
+        err!("Passkey not found")
+    }
+    let mut passkey = validate_passkey_login(passkey, &claims, data.device_response, &mut conn).await?;
+
+    if !passkey.supports_prf {
+        err!("This passkey can't be used to unlock the vault")
+    }
+    passkey.encrypted_user_key = Some(data.encrypted_user_key);
+    passkey.encrypted_public_key = Some(data.encrypted_public_key);
+    passkey.encrypted_private_key = Some(data.encrypted_private_key);
+    passkey.save(&mut conn).await
+}
+
+#[post("/webauthn/<passkey_id>/delete", data = "<data>")]
+async fn post_webauthn_delete(
+    passkey_id: WebAuthnCredentialId,
+    data: Json<PasswordOrOtpData>,
+    headers: Headers,
+    mut conn: DbConn,
+) -> EmptyResult {
+    let data: PasswordOrOtpData = data.into_inner();
+    let user = headers.user;
+
+    data.validate(&user, true, &mut conn).await?;
+
+    let Some(passkey) = WebAuthnCredential::find_by_uuid_and_user(&passkey_id, &user.uuid, &mut conn).await else {
+        err!("Passkey not found")
+    };
+    passkey.delete(&mut conn).await
+}
+
+/// Finds the registered passkey which sent the response, using the credential id and the user handle
+pub async fn find_passkey(response: &PublicKeyCredentialCopy, conn: &mut DbConn) -> Option<WebAuthnCredential> {
+    let passkey = WebAuthnCredential::find_by_credential_id(&BASE64URL_NOPAD.encode(&response.raw_id.0), conn).await?;
+
+    // The user handle is the uuid of the user who registered the passkey
+    match response.response.user_handle {
+        Some(ref user_handle) if user_handle.0 != passkey.user_uuid.as_bytes() => None,
+        _ => Some(passkey),
+    }
+}
+
+/// Verifies the response of the passkey to the challenge of a login token and updates its signature counter
+pub async fn validate_passkey_login(
+    mut passkey: WebAuthnCredential,
+    claims: &WebauthnStateClaims,
+    response: PublicKeyCredentialCopy,
+    conn: &mut DbConn,
+) -> Result<WebAuthnCredential, Error> {
+    use_login_challenge(&claims.state, claims.exp)?;
+
+    let mut credential = parse_credential(&passkey)?;
+    let state = state_with_credential(&claims.state, &credential)?;
+    let (_, auth_data) = WebauthnConfig::load().authenticate_credential(&response.into(), &state)?;
+
+    credential.counter = auth_data.counter;
+    passkey.credential = serde_json::to_string(&credential)?;
+    passkey.save(conn).await?;
+    Ok(passkey)
+}
+
+/// Marks the challenge of a login token as answered, a challenge can only be answered once while its token is valid
+fn use_login_challenge(state: &str, exp: i64) -> EmptyResult {
+    let now = Utc::now().timestamp();
+    USED_LOGIN_CHALLENGES.retain(|_, exp| *exp > now);
+    if USED_LOGIN_CHALLENGES.insert(state.to_string(), exp).is_some() {
+        err!("This login challenge was already used")
+    }
+    Ok(())
+}
+
+/// `webauthn_rs` only accepts a response from one of the credentials the challenge was generated for.
+/// A login challenge is generated without any credentials, so the passkey found by its id is added to the state.
+fn state_with_credential(state: &str, credential: &Credential) -> Result<AuthenticationState, Error> {
+    let mut state: Value = serde_json::from_str(state)?;
+    state["credentials"] = json!([credential]);
+    serde_json::from_value(state).map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_credential() -> Credential {
+        Credential {
+            counter: 0,
+            verified: true,
+            cred: COSEKey {
+                type_: COSEAlgorithm::ES256,
+                key: COSEKeyType::EC_EC2(COSEEC2Key {
+                    curve: ECDSACurve::SECP256R1,
+                    x: [1u8; 32],
+                    y: [2u8; 32],
+                }),
+            },
+            cred_id: vec![3u8; 16],
+            registration_policy: UserVerificationPolicy::Required,
+        }
+    }
+
+    #[test]
+    fn test_login_challenge_replay() {
+        let now = Utc::now().timestamp();
+        let state = crate::util::get_uuid();
+        use_login_challenge(&state, now + 60).unwrap();
+        assert!(use_login_challenge(&state, now + 60).is_err());
+
+        // The challenges of expired tokens are forgotten
+        let expired = crate::util::get_uuid();
+        use_login_challenge(&expired, now - 1).unwrap();
+        use_login_challenge(&crate::util::get_uuid(), now + 60).unwrap();
+        assert!(!USED_LOGIN_CHALLENGES.contains_key(&expired));
+        assert!(USED_LOGIN_CHALLENGES.contains_key(&state));
+    }
+
+    #[test]
+    fn test_state_with_credential() {
+        let (_, state) = WebauthnConfig::load().generate_challenge_authenticate_options(Vec::new(), None).unwrap();
+        let state = serde_json::to_string(&state).unwrap();
+        let credential = test_credential();
+
+        let with_credential = serde_json::to_value(state_with_credential(&state, &credential).unwrap()).unwrap();
+        let original: Value = serde_json::from_str(&state).unwrap();
+        assert_eq!(with_credential["credentials"], json!([credential]));
+        // Everything else, like the challenge, is kept
+        for (key, value) in original.as_object().unwrap() {
+            if key != "credentials" {
+                assert_eq!(&with_credential[key], value, "{key}");
+            }
+        }
+
+        assert!(state_with_credential("{}", &credential).is_err());
+    }
+}
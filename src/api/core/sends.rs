@@ -1,9 +1,6 @@
-use std::path::Path;
-
 use chrono::{DateTime, TimeDelta, Utc};
 use num_traits::ToPrimitive;
 use rocket::form::Form;
-use rocket::fs::NamedFile;
 use rocket::fs::TempFile;
 use rocket::serde::json::Json;
 use serde_json::Value;
@@ -12,6 +9,7 @@ use crate::{
     api::{ApiResult, EmptyResult, JsonResult, Notify, UpdateType},
     auth::{ClientIp, Headers, Host},
     db::{models::*, DbConn, DbPool},
+    storage::{storage, PathType, StorageFile},
     util::NumberOrString,
     CONFIG,
 };
@@ -223,7 +221,7 @@ async fn post_send_file(data: Form<UploadData<'_>>, headers: Headers, mut conn:
 
     enforce_disable_hide_email_policy(&model, &headers, &mut conn).await?;
 
-    let size_limit = match CONFIG.user_send_limit() {
+    let size_limit = match headers.user.send_limit_kb() {
         Some(0) => err!("File uploads are disabled"),
         Some(limit_kb) => {
             let Some(already_used) = Send::size_by_user(&headers.user.uuid, &mut conn).await else {
@@ -250,13 +248,7 @@ async fn post_send_file(data: Form<UploadData<'_>>, headers: Headers, mut conn:
     }
 
     let file_id = crate::crypto::generate_send_file_id();
-    let folder_path = tokio::fs::canonicalize(&CONFIG.sends_folder()).await?.join(&send.uuid);
-    let file_path = folder_path.join(&file_id);
-    tokio::fs::create_dir_all(&folder_path).await?;
-
-    if let Err(_err) = data.persist_to(&file_path).await {
-        data.move_copy_to(file_path).await?
-    }
+    storage().save_temp_file(PathType::Sends, &format!("{}/{file_id}", send.uuid), &mut data).await?;
 
     let mut data_value: Value = serde_json::from_str(&send.data)?;
     if let Some(o) = data_value.as_object_mut() {
@@ -301,7 +293,7 @@ async fn post_send_file_v2(data: Json<SendData>, headers: Headers, mut conn: DbC
         err!("Send size can't be negative")
     }
 
-    let size_limit = match CONFIG.user_send_limit() {
+    let size_limit = match headers.user.send_limit_kb() {
         Some(0) => err!("File uploads are disabled"),
         Some(limit_kb) => {
             let Some(already_used) = Send::size_by_user(&headers.user.uuid, &mut conn).await else {
@@ -406,19 +398,14 @@ async fn post_send_file_v2_data(
         err!("Send file size does not match.", format!("Expected a file size of {} got {size}", send_data.size));
     }
 
-    let folder_path = tokio::fs::canonicalize(&CONFIG.sends_folder()).await?.join(send_id);
-    let file_path = folder_path.join(file_id);
+    let file_path = format!("{send_id}/{file_id}");
 
     // Check if the file already exists, if that is the case do not overwrite it
-    if tokio::fs::metadata(&file_path).await.is_ok() {
+    if storage().exists(PathType::Sends, &file_path).await {
         err!("Send file has already been uploaded.", format!("File {file_path:?} already exists"))
     }
 
-    tokio::fs::create_dir_all(&folder_path).await?;
-
-    if let Err(_err) = data.data.persist_to(&file_path).await {
-        data.data.move_copy_to(file_path).await?
-    }
+    storage().save_temp_file(PathType::Sends, &file_path, &mut data.data).await?;
 
     nt.send_send_update(
         UpdateType::SyncSendCreate,
@@ -551,20 +538,27 @@ async fn post_access_file(
     )
     .await;
 
-    let token_claims = crate::auth::generate_send_claims(&send_id, &file_id);
-    let token = crate::auth::encode_jwt(&token_claims);
+    let url = match storage().presigned_url(PathType::Sends, &format!("{send_id}/{file_id}")) {
+        Some(url) => url,
+        None => {
+            let token_claims = crate::auth::generate_send_claims(&send_id, &file_id);
+            let token = crate::auth::encode_jwt(&token_claims);
+            format!("{}/api/sends/{}/{}?t={}", &host.host, send_id, file_id, token)
+        }
+    };
+
     Ok(Json(json!({
         "object": "send-fileDownload",
         "id": file_id,
-        "url": format!("{}/api/sends/{}/{}?t={}", &host.host, send_id, file_id, token)
+        "url": url
     })))
 }
 
 #[get("/sends/<send_id>/<file_id>?<t>")]
-async fn download_send(send_id: SendId, file_id: SendFileId, t: &str) -> Option<NamedFile> {
+async fn download_send(send_id: SendId, file_id: SendFileId, t: &str) -> Option<StorageFile> {
     if let Ok(claims) = crate::auth::decode_send(t) {
         if claims.sub == format!("{send_id}/{file_id}") {
-            return NamedFile::open(Path::new(&CONFIG.sends_folder()).join(send_id).join(file_id)).await.ok();
+            return storage().download(PathType::Sends, &claims.sub).await;
         }
     }
     None
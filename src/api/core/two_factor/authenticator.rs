@@ -115,8 +115,6 @@ pub async fn validate_totp_code(
     ip: &ClientIp,
     conn: &mut DbConn,
 ) -> EmptyResult {
-    use totp_lite::{totp_custom, Sha1};
-
     let Ok(decoded_secret) = BASE32.decode(secret.as_bytes()) else {
         err!("Invalid TOTP secret")
     };
@@ -127,14 +125,43 @@ pub async fn validate_totp_code(
         _ => TwoFactor::new(user_id.clone(), TwoFactorType::Authenticator, secret.to_string()),
     };
 
+    let current_time = chrono::Utc::now();
+    match check_totp_code(&decoded_secret, totp_code, twofactor.last_used) {
+        TotpCheck::Valid(time_step) => {
+            // Save the last used time step so only totp time steps higher then this one are allowed.
+            // This will also save a newly created twofactor if the code is correct.
+            twofactor.last_used = time_step;
+            twofactor.save(conn).await?;
+            Ok(())
+        }
+        // Else no valid code received, deny access
+        TotpCheck::AlreadyUsed | TotpCheck::Invalid => err!(
+            format!("Invalid TOTP code! Server time: {} IP: {}", current_time.format("%F %T UTC"), ip.ip),
+            ErrorEvent {
+                event: EventType::UserFailedLogIn2fa
+            }
+        ),
+    }
+}
+
+pub enum TotpCheck {
+    /// The code is valid for this time step, which needs to be stored as the last used one
+    Valid(i64),
+    AlreadyUsed,
+    Invalid,
+}
+
+/// Checks a TOTP code, only time steps after `last_used` are accepted so a code can't be used twice
+pub fn check_totp_code(decoded_secret: &[u8], totp_code: &str, last_used: i64) -> TotpCheck {
+    use totp_lite::{totp_custom, Sha1};
+
     // The amount of steps back and forward in time
     // Also check if we need to disable time drifted TOTP codes.
     // If that is the case, we set the steps to 0 so only the current TOTP is valid.
     let steps = i64::from(!CONFIG.authenticator_disable_time_drift());
 
     // Get the current system time in UNIX Epoch (UTC)
-    let current_time = chrono::Utc::now();
-    let current_timestamp = current_time.timestamp();
+    let current_timestamp = chrono::Utc::now().timestamp();
 
     for step in -steps..=steps {
         let time_step = current_timestamp / 30i64 + step;
@@ -142,38 +169,22 @@ pub async fn validate_totp_code(
         // We need to calculate the time offsite and cast it as an u64.
         // Since we only have times into the future and the totp generator needs an u64 instead of the default i64.
         let time = (current_timestamp + step * 30i64) as u64;
-        let generated = totp_custom::<Sha1>(30, 6, &decoded_secret, time);
+        let generated = totp_custom::<Sha1>(30, 6, decoded_secret, time);
 
         // Check the given code equals the generated and if the time_step is larger then the one last used.
-        if generated == totp_code && time_step > twofactor.last_used {
+        if generated == totp_code && time_step > last_used {
             // If the step does not equals 0 the time is drifted either server or client side.
             if step != 0 {
                 warn!("TOTP Time drift detected. The step offset is {}", step);
             }
-
-            // Save the last used time step so only totp time steps higher then this one are allowed.
-            // This will also save a newly created twofactor if the code is correct.
-            twofactor.last_used = time_step;
-            twofactor.save(conn).await?;
-            return Ok(());
-        } else if generated == totp_code && time_step <= twofactor.last_used {
+            return TotpCheck::Valid(time_step);
+        } else if generated == totp_code && time_step <= last_used {
             warn!("This TOTP or a TOTP code within {} steps back or forward has already been used!", steps);
-            err!(
-                format!("Invalid TOTP code! Server time: {} IP: {}", current_time.format("%F %T UTC"), ip.ip),
-                ErrorEvent {
-                    event: EventType::UserFailedLogIn2fa
-                }
-            );
+            return TotpCheck::AlreadyUsed;
         }
     }
 
-    // Else no valid code received, deny access
-    err!(
-        format!("Invalid TOTP code! Server time: {} IP: {}", current_time.format("%F %T UTC"), ip.ip),
-        ErrorEvent {
-            event: EventType::UserFailedLogIn2fa
-        }
-    );
+    TotpCheck::Invalid
 }
 
 #[derive(Debug, Deserialize)]
@@ -374,6 +374,12 @@ fn make_callback_url(client_name: &str) -> Result<String, Error> {
     Ok(callback.to_string())
 }
 
+// Runs the Duo health check with the given keys, to test them before they are saved.
+// The health check does not need a redirect URI.
+pub async fn check_duo_credentials(ikey: String, skey: String, host: String) -> Result<(), Error> {
+    DuoClient::new(ikey, skey, host, String::new()).health_check().await
+}
+
 // Pre-redirect first stage of the Duo OIDC authentication flow.
 // Returns the "AuthUrl" that should be returned to clients for MFA.
 pub async fn get_duo_auth_url(
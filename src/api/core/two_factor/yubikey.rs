@@ -1,7 +1,7 @@
 use rocket::serde::json::Json;
 use rocket::Route;
 use serde_json::Value;
-use yubico::{config::Config, verify_async};
+use yubico::{config::Config, verify_async, yubicoerror::YubicoError};
 
 use crate::{
     api::{
@@ -82,6 +82,23 @@ async fn verify_yubikey_otp(otp: String) -> EmptyResult {
     .map_res("Failed to verify OTP")
 }
 
+/// Checks the Yubico credentials against the validation server with a well-formed but invalid OTP.
+/// The server only reports the OTP as invalid when the client id and the signature with the secret key are accepted.
+pub async fn check_yubico_credentials(client_id: String, secret_key: String, server: Option<String>) -> EmptyResult {
+    const TEST_OTP: &str = "cccccccccccccccccccccccccccccccccccccccccccc";
+
+    let config = Config::default().set_client_id(client_id).set_key(secret_key);
+    let result = match server {
+        Some(server) => verify_async(TEST_OTP, config.set_api_hosts(vec![server])).await,
+        None => verify_async(TEST_OTP, config).await,
+    };
+
+    match result {
+        Ok(_) | Err(YubicoError::BadOTP | YubicoError::ReplayedOTP) => Ok(()),
+        Err(e) => err!(format!("Yubico server rejected the credentials: {e:?}")),
+    }
+}
+
 #[post("/two-factor/get-yubikey", data = "<data>")]
 async fn generate_yubikey(data: Json<PasswordOrOtpData>, headers: Headers, mut conn: DbConn) -> JsonResult {
     // Make sure the credentials are set
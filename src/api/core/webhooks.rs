@@ -0,0 +1,396 @@
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use reqwest::{Method, RequestBuilder};
+use rocket::{serde::json::Json, Route};
+use serde_json::Value;
+
+use crate::{
+    api::{EmptyResult, JsonResult},
+    auth::OwnerHeaders,
+    crypto,
+    db::{
+        models::{Event, EventType, OrganizationId, Webhook, WebhookDelivery, WebhookDeliveryStatus, WebhookId},
+        DbConn, DbPool,
+    },
+    http_client::{make_http_request, should_block_address},
+    CONFIG,
+};
+
+// The delay before the first retry, which is doubled for every following attempt
+const RETRY_BASE_DELAY_SECS: i64 = 30;
+// Caps the backoff at about 8.5 hours, in case `WEBHOOK_MAX_ATTEMPTS` is set to a large value
+const RETRY_MAX_EXPONENT: i32 = 10;
+// Number of deliveries handled per run of the delivery job
+const DELIVERY_BATCH_SIZE: i64 = 100;
+// Number of deliveries returned by the delivery history
+const DELIVERY_HISTORY_SIZE: i64 = 100;
+// Delivered and failed deliveries are kept this long as history
+const DELIVERY_HISTORY_DAYS: i64 = 30;
+
+pub const SIGNATURE_HEADER: &str = "X-Vaultwarden-Signature";
+pub const EVENT_HEADER: &str = "X-Vaultwarden-Event";
+pub const DELIVERY_HEADER: &str = "X-Vaultwarden-Delivery";
+
+pub fn routes() -> Vec<Route> {
+    routes![get_webhooks, post_webhook, put_webhook, delete_webhook, get_webhook_deliveries, post_webhook_test]
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WebhookData {
+    url: String,
+    // All the events are delivered when empty
+    event_types: Option<Vec<i32>>,
+    enabled: Option<bool>,
+}
+
+impl WebhookData {
+    fn validate(&self) -> EmptyResult {
+        let Ok(url) = url::Url::parse(&self.url) else {
+            err!("The webhook URL is not valid")
+        };
+        if url.scheme() != "https" {
+            err!("The webhook URL needs to use https")
+        }
+        match url.host_str() {
+            Some(host) if !should_block_address(host) => Ok(()),
+            _ => err!("The host of the webhook URL is not allowed"),
+        }
+    }
+}
+
+#[get("/organizations/<org_id>/webhooks")]
+async fn get_webhooks(org_id: OrganizationId, headers: OwnerHeaders, mut conn: DbConn) -> JsonResult {
+    if org_id != headers.org_id {
+        err!("Organization not found", "Organization id's do not match");
+    }
+
+    let webhooks_json: Vec<Value> =
+        Webhook::find_by_org(&org_id, &mut conn).await.iter().map(Webhook::to_json).collect();
+
+    Ok(Json(json!({
+        "data": webhooks_json,
+        "object": "list",
+        "continuationToken": null,
+    })))
+}
+
+#[post("/organizations/<org_id>/webhooks", data = "<data>")]
+async fn post_webhook(
+    org_id: OrganizationId,
+    headers: OwnerHeaders,
+    data: Json<WebhookData>,
+    mut conn: DbConn,
+) -> JsonResult {
+    if org_id != headers.org_id {
+        err!("Organization not found", "Organization id's do not match");
+    }
+
+    let data: WebhookData = data.into_inner();
+    data.validate()?;
+
+    let webhook = Webhook::new(org_id, data.url, &data.event_types.unwrap_or_default(), data.enabled.unwrap_or(true));
+    webhook.save(&mut conn).await?;
+
+    Ok(Json(webhook.to_json()))
+}
+
+#[put("/organizations/<org_id>/webhooks/<webhook_id>", data = "<data>")]
+async fn put_webhook(
+    org_id: OrganizationId,
+    webhook_id: WebhookId,
+    headers: OwnerHeaders,
+    data: Json<WebhookData>,
+    mut conn: DbConn,
+) -> JsonResult {
+    if org_id != headers.org_id {
+        err!("Organization not found", "Organization id's do not match");
+    }
+
+    let Some(mut webhook) = Webhook::find_by_uuid_and_org(&webhook_id, &org_id, &mut conn).await else {
+        err!("Webhook not found")
+    };
+
+    let data: WebhookData = data.into_inner();
+    data.validate()?;
+
+    webhook.url = data.url;
+    webhook.set_event_types(&data.event_types.unwrap_or_default());
+    webhook.enabled = data.enabled.unwrap_or(webhook.enabled);
+    webhook.save(&mut conn).await?;
+
+    Ok(Json(webhook.to_json()))
+}
+
+#[delete("/organizations/<org_id>/webhooks/<webhook_id>")]
+async fn delete_webhook(
+    org_id: OrganizationId,
+    webhook_id: WebhookId,
+    headers: OwnerHeaders,
+    mut conn: DbConn,
+) -> EmptyResult {
+    if org_id != headers.org_id {
+        err!("Organization not found", "Organization id's do not match");
+    }
+
+    let Some(webhook) = Webhook::find_by_uuid_and_org(&webhook_id, &org_id, &mut conn).await else {
+        err!("Webhook not found")
+    };
+
+    webhook.delete(&mut conn).await
+}
+
+#[get("/organizations/<org_id>/webhooks/<webhook_id>/deliveries")]
+async fn get_webhook_deliveries(
+    org_id: OrganizationId,
+    webhook_id: WebhookId,
+    headers: OwnerHeaders,
+    mut conn: DbConn,
+) -> JsonResult {
+    if org_id != headers.org_id {
+        err!("Organization not found", "Organization id's do not match");
+    }
+
+    if Webhook::find_by_uuid_and_org(&webhook_id, &org_id, &mut conn).await.is_none() {
+        err!("Webhook not found")
+    }
+
+    let deliveries_json: Vec<Value> = WebhookDelivery::find_by_webhook(&webhook_id, DELIVERY_HISTORY_SIZE, &mut conn)
+        .await
+        .iter()
+        .map(WebhookDelivery::to_json)
+        .collect();
+
+    Ok(Json(json!({
+        "data": deliveries_json,
+        "object": "list",
+        "continuationToken": null,
+    })))
+}
+
+/// Sends a test event right away, regardless of the event types the webhook is subscribed to.
+/// When it can't be delivered it is retried like any other event.
+#[post("/organizations/<org_id>/webhooks/<webhook_id>/test")]
+async fn post_webhook_test(
+    org_id: OrganizationId,
+    webhook_id: WebhookId,
+    headers: OwnerHeaders,
+    mut conn: DbConn,
+) -> JsonResult {
+    if org_id != headers.org_id {
+        err!("Organization not found", "Organization id's do not match");
+    }
+
+    let Some(webhook) = Webhook::find_by_uuid_and_org(&webhook_id, &org_id, &mut conn).await else {
+        err!("Webhook not found")
+    };
+
+    let mut event = Event::new(EventType::OrganizationUpdated as i32, None);
+    event.org_uuid = Some(org_id);
+    event.act_user_uuid = Some(headers.user.uuid);
+    event.device_type = Some(headers.device.atype);
+    event.ip_address = Some(headers.ip.ip.to_string());
+
+    let mut delivery = WebhookDelivery::new(webhook.uuid.clone(), event.event_type, event_payload(&event, true));
+    attempt_delivery(&mut delivery, &webhook, &Utc::now().naive_utc()).await;
+    delivery.save(&mut conn).await?;
+
+    Ok(Json(delivery.to_json()))
+}
+
+fn event_payload(event: &Event, test: bool) -> String {
+    json!({
+        "test": test,
+        "event": event.to_json(),
+    })
+    .to_string()
+}
+
+/// Queues the delivery of an organization event to all the webhooks of the organization which are subscribed to it.
+/// The deliveries are sent by `webhook_delivery_job`, so logging an event never waits on a remote server.
+pub async fn queue_event_deliveries(event: &Event, conn: &mut DbConn) {
+    let Some(org_uuid) = &event.org_uuid else {
+        return;
+    };
+
+    for webhook in Webhook::find_by_org(org_uuid, conn).await {
+        if !webhook.accepts(event.event_type) {
+            continue;
+        }
+
+        let delivery = WebhookDelivery::new(webhook.uuid, event.event_type, event_payload(event, false));
+        if let Err(e) = delivery.save(conn).await {
+            error!("Error queueing webhook delivery: {e:#?}");
+        }
+    }
+}
+
+/// Sends a single delivery with its signature, returns the status code of the response on success
+async fn send_delivery(
+    request: RequestBuilder,
+    secret: &str,
+    delivery: &WebhookDelivery,
+) -> Result<u16, (Option<u16>, String)> {
+    let signature = crypto::hmac_sign(secret, &delivery.payload);
+
+    let response = request
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, format!("sha1={signature}"))
+        .header(EVENT_HEADER, delivery.event_type.to_string())
+        .header(DELIVERY_HEADER, delivery.uuid.to_string())
+        .body(delivery.payload.clone())
+        .send()
+        .await
+        .map_err(|e| (None, e.to_string()))?;
+
+    let status = response.status();
+    if status.is_success() {
+        Ok(status.as_u16())
+    } else {
+        Err((Some(status.as_u16()), format!("Unexpected response status {status}")))
+    }
+}
+
+/// Attempts a delivery and updates its status, the caller needs to save it
+async fn attempt_delivery(delivery: &mut WebhookDelivery, webhook: &Webhook, now: &NaiveDateTime) {
+    let result = match make_http_request(Method::POST, &webhook.url) {
+        Ok(request) => send_delivery(request, &webhook.secret, delivery).await,
+        Err(e) => Err((None, e.to_string())),
+    };
+
+    delivery.attempts += 1;
+    delivery.last_attempt_at = Some(*now);
+    match result {
+        Ok(status) => {
+            delivery.status = WebhookDeliveryStatus::Delivered as i32;
+            delivery.response_code = Some(i32::from(status));
+            delivery.last_error = None;
+        }
+        Err((status, e)) => {
+            debug!("Delivery {} to webhook {} failed: {e}", delivery.uuid, webhook.uuid);
+            delivery.response_code = status.map(i32::from);
+            delivery.last_error = Some(e);
+            if delivery.attempts >= CONFIG.webhook_max_attempts() as i32 {
+                delivery.status = WebhookDeliveryStatus::Failed as i32;
+            } else {
+                delivery.next_attempt_at = *now + retry_delay(delivery.attempts);
+            }
+        }
+    }
+}
+
+fn retry_delay(attempts: i32) -> TimeDelta {
+    let exponent = (attempts - 1).clamp(0, RETRY_MAX_EXPONENT) as u32;
+    TimeDelta::seconds(RETRY_BASE_DELAY_SECS * 2i64.pow(exponent))
+}
+
+pub async fn webhook_delivery_job(pool: DbPool) {
+    debug!("Start webhook delivery job");
+    let Ok(mut conn) = pool.get().await else {
+        error!("Failed to get DB connection while trying to deliver the webhook events");
+        return;
+    };
+
+    let now = Utc::now().naive_utc();
+    for mut delivery in WebhookDelivery::find_due(&now, DELIVERY_BATCH_SIZE, &mut conn).await {
+        match Webhook::find_by_uuid(&delivery.webhook_uuid, &mut conn).await {
+            Some(webhook) if webhook.enabled => attempt_delivery(&mut delivery, &webhook, &now).await,
+            _ => {
+                delivery.status = WebhookDeliveryStatus::Failed as i32;
+                delivery.last_error = Some(String::from("The webhook is disabled"));
+            }
+        }
+
+        if let Err(e) = delivery.save(&mut conn).await {
+            error!("Error saving webhook delivery: {e:#?}");
+        }
+    }
+
+    if let Some(dt) = TimeDelta::try_days(DELIVERY_HISTORY_DAYS).map(|d| now - d) {
+        WebhookDelivery::purge_finished_before(&dt, &mut conn).await.ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    // Accepts a single request and answers it with `status`, returns the raw request
+    async fn serve_once(listener: TcpListener, status: &str) -> String {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .unwrap_or_default();
+                if body.len() >= length {
+                    break;
+                }
+            }
+            if n == 0 {
+                break;
+            }
+        }
+        let response = format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+        stream.write_all(response.as_bytes()).await.unwrap();
+        String::from_utf8(request).unwrap()
+    }
+
+    fn test_delivery() -> WebhookDelivery {
+        let payload = json!({"test": true, "event": {"type": 1600}}).to_string();
+        WebhookDelivery::new(WebhookId::from(String::from("webhook")), 1600, payload)
+    }
+
+    #[test]
+    fn test_send_delivery_signs_payload() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/hook", listener.local_addr().unwrap());
+            let server = tokio::spawn(serve_once(listener, "204 No Content"));
+
+            let delivery = test_delivery();
+            let result = send_delivery(reqwest::Client::new().post(url), "secret", &delivery).await;
+            assert_eq!(result, Ok(204));
+
+            let request = server.await.unwrap().to_lowercase();
+            let signature = crypto::hmac_sign("secret", &delivery.payload);
+            assert!(request.starts_with("post /hook "));
+            assert!(request.contains(&format!("{}: sha1={signature}", SIGNATURE_HEADER.to_lowercase())));
+            assert!(request.contains(&format!("{}: 1600", EVENT_HEADER.to_lowercase())));
+            assert!(request.ends_with(&delivery.payload.to_lowercase()));
+        });
+    }
+
+    #[test]
+    fn test_send_delivery_rejected() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/hook", listener.local_addr().unwrap());
+            let server = tokio::spawn(serve_once(listener, "500 Internal Server Error"));
+
+            let result = send_delivery(reqwest::Client::new().post(url), "secret", &test_delivery()).await;
+            assert!(matches!(result, Err((Some(500), _))));
+            server.await.unwrap();
+        });
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), TimeDelta::seconds(30));
+        assert_eq!(retry_delay(2), TimeDelta::seconds(60));
+        assert_eq!(retry_delay(4), TimeDelta::seconds(240));
+        assert_eq!(retry_delay(100), TimeDelta::seconds(30 * 1024));
+    }
+}
@@ -163,10 +163,12 @@ async fn get_icon(domain: &str) -> Option<(Vec<u8>, String)> {
 
     // Check for expiration of negatively cached copy
     if icon_is_negcached(&path).await {
+        crate::metrics::count_icon_cache("negative_hit");
         return None;
     }
 
     if let Some(icon) = get_cached_icon(&path).await {
+        crate::metrics::count_icon_cache("hit");
         let icon_type = match get_icon_type(&icon) {
             Some(x) => x,
             _ => "x-icon",
@@ -174,6 +176,7 @@ async fn get_icon(domain: &str) -> Option<(Vec<u8>, String)> {
         return Some((icon, icon_type.to_string()));
     }
 
+    crate::metrics::count_icon_cache("miss");
     if CONFIG.disable_icon_download() {
         return None;
     }
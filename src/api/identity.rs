@@ -1,30 +1,43 @@
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use num_traits::FromPrimitive;
 use rocket::serde::json::Json;
 use rocket::{
     form::{Form, FromForm},
+    response::Redirect,
     Route,
 };
 use serde_json::Value;
+use url::Url;
 
 use crate::{
     api::{
         core::{
-            accounts::{PreloginData, RegisterData, _prelogin, _register},
-            log_user_event,
+            accounts::{remove_device, PreloginData, RegisterData, _prelogin, _register},
+            log_user_event, passkeys,
             two_factor::{authenticator, duo, duo_oidc, email, enforce_2fa_policy, webauthn, yubikey},
         },
         push::register_push_device,
         ApiResult, EmptyResult, JsonResult,
     },
-    auth::{generate_organization_api_key_login_claims, ClientHeaders, ClientIp},
-    db::{models::*, DbConn},
+    auth::{decode_webauthn_login, generate_organization_api_key_login_claims, ClientHeaders, ClientIp},
+    crypto,
+    db::{models::*, DbConn, DbPool},
     error::MapResult,
-    mail, util, CONFIG,
+    mail, sso, util, CONFIG,
 };
 
 pub fn routes() -> Vec<Route> {
-    routes![login, prelogin, identity_register, register_verification_email, register_finish]
+    routes![
+        login,
+        prelogin,
+        identity_register,
+        register_verification_email,
+        register_finish,
+        prevalidate,
+        webauthn_assertion_options,
+        authorize,
+        oidc_signin
+    ]
 }
 
 #[post("/connect/token", data = "<data>")]
@@ -36,7 +49,7 @@ async fn login(data: Form<ConnectData>, client_header: ClientHeaders, mut conn:
     let login_result = match data.grant_type.as_ref() {
         "refresh_token" => {
             _check_is_some(&data.refresh_token, "refresh_token cannot be blank")?;
-            _refresh_login(data, &mut conn).await
+            _refresh_login(data, &mut user_id, &mut conn, &client_header.ip).await
         }
         "password" => {
             _check_is_some(&data.client_id, "client_id cannot be blank")?;
@@ -61,6 +74,30 @@ async fn login(data: Form<ConnectData>, client_header: ClientHeaders, mut conn:
 
             _api_key_login(data, &mut user_id, &mut conn, &client_header.ip).await
         }
+        "webauthn" => {
+            _check_is_some(&data.client_id, "client_id cannot be blank")?;
+            _check_is_some(&data.scope, "scope cannot be blank")?;
+            _check_is_some(&data.token, "token cannot be blank")?;
+            _check_is_some(&data.device_response, "device_response cannot be blank")?;
+
+            _check_is_some(&data.device_identifier, "device_identifier cannot be blank")?;
+            _check_is_some(&data.device_name, "device_name cannot be blank")?;
+            _check_is_some(&data.device_type, "device_type cannot be blank")?;
+
+            _webauthn_login(data, &mut user_id, &mut conn, &client_header.ip).await
+        }
+        "authorization_code" if CONFIG.sso_enabled() => {
+            _check_is_some(&data.client_id, "client_id cannot be blank")?;
+            _check_is_some(&data.code, "code cannot be blank")?;
+            _check_is_some(&data.code_verifier, "code_verifier cannot be blank")?;
+            _check_is_some(&data.redirect_uri, "redirect_uri cannot be blank")?;
+
+            _check_is_some(&data.device_identifier, "device_identifier cannot be blank")?;
+            _check_is_some(&data.device_name, "device_name cannot be blank")?;
+            _check_is_some(&data.device_type, "device_type cannot be blank")?;
+
+            _sso_login(data, &mut user_id, &mut conn, &client_header.ip).await
+        }
         t => err!("Invalid type", t),
     };
 
@@ -94,12 +131,58 @@ async fn login(data: Form<ConnectData>, client_header: ClientHeaders, mut conn:
     login_result
 }
 
-async fn _refresh_login(data: ConnectData, conn: &mut DbConn) -> JsonResult {
+async fn _refresh_login(
+    data: ConnectData,
+    user_id: &mut Option<UserId>,
+    conn: &mut DbConn,
+    ip: &ClientIp,
+) -> JsonResult {
     // Extract token
     let token = data.refresh_token.unwrap();
+    if token.is_empty() {
+        err!("Invalid refresh token")
+    }
+
+    // Get device by refresh token, or by the refresh token replaced by the last rotation
+    let (mut device, rotate) = match Device::find_by_refresh_token(&token, conn).await {
+        Some(device) => (device, true),
+        None => match Device::find_by_previous_refresh_token(&token, conn).await {
+            // A client retrying the refresh shortly after the rotation gets the current refresh token again
+            Some(device) if device.is_in_rotation_grace_period() => (device, false),
+            Some(device) => {
+                // The refresh token was already rotated, so it was either stolen or the session was copied.
+                // Both the legitimate client and the other party lose the session of this device.
+                let email = User::find_by_uuid(&device.user_uuid, conn).await.map(|u| u.email).unwrap_or_default();
+                warn!(
+                    target: "security",
+                    "Reuse of a rotated refresh token of device '{}' ({}, {}) of user {email} ({}) from IP {}, revoking the session",
+                    device.name,
+                    DeviceType::from_i32(device.atype),
+                    device.uuid,
+                    device.user_uuid,
+                    ip.ip
+                );
+                *user_id = Some(device.user_uuid.clone());
+                remove_device(device, conn).await?;
+                err!(
+                    "Invalid refresh token",
+                    ErrorEvent {
+                        event: EventType::UserFailedLogIn
+                    }
+                )
+            }
+            None => err!("Invalid refresh token"),
+        },
+    };
 
-    // Get device by refresh token
-    let mut device = Device::find_by_refresh_token(&token, conn).await.map_res("Invalid refresh token")?;
+    if device.is_session_expired() {
+        device.end_session();
+        device.save(conn).await?;
+        err!("Session expired, please log in again")
+    }
+    if rotate && CONFIG.refresh_token_rotation() {
+        device.rotate_refresh_token();
+    }
 
     let scope = "api offline_access";
     let scope_vec = vec!["api".into(), "offline_access".into()];
@@ -112,6 +195,7 @@ async fn _refresh_login(data: ConnectData, conn: &mut DbConn) -> JsonResult {
     // See: https://github.com/dani-garcia/vaultwarden/issues/4156
     // ---
     // let members = Membership::find_confirmed_by_user(&user.uuid, conn).await;
+    device.touch(&ip.ip);
     let (access_token, expires_in) = device.refresh_tokens(&user, scope_vec);
     device.save(conn).await?;
 
@@ -150,7 +234,6 @@ async fn _password_login(
     if scope != "api offline_access" {
         err!("Scope not supported")
     }
-    let scope_vec = vec!["api".into(), "offline_access".into()];
 
     // Ratelimit the login
     crate::ratelimit::check_limit_login(&ip.ip)?;
@@ -160,6 +243,7 @@ async fn _password_login(
     let Some(mut user) = User::find_by_mail(username, conn).await else {
         err!("Username or password is incorrect. Try again", format!("IP: {}. Username: {}.", ip.ip, username))
     };
+    crate::ratelimit::check_limit_login_account(&user.uuid)?;
 
     // Set the user_id here to be passed back used for event logging.
     *user_id = Some(user.uuid.clone());
@@ -175,6 +259,27 @@ async fn _password_login(
         )
     }
 
+    // Check if the account is locked, or has to wait after the previous failed logins from this IP address.
+    // Both get the same error as a wrong password, so they don't tell which email addresses have an account.
+    if let Some(locked_until) = user.login_locked_until() {
+        err!(
+            "Username or password is incorrect. Try again",
+            format!("IP: {}. Username: {}. Account locked until: {}.", ip.ip, username, locked_until),
+            ErrorEvent {
+                event: EventType::UserFailedLogIn
+            }
+        )
+    }
+    if let Some(delay) = crate::ratelimit::login_retry_delay(&user.uuid, &ip.ip) {
+        err!(
+            "Username or password is incorrect. Try again",
+            format!("IP: {}. Username: {}. Retry delay: {} seconds.", ip.ip, username, delay),
+            ErrorEvent {
+                event: EventType::UserFailedLogIn
+            }
+        )
+    }
+
     let password = data.password.as_ref().unwrap();
 
     // If we get an auth request, we don't check the user's password, but the access code of the auth request
@@ -207,6 +312,22 @@ async fn _password_login(
             )
         }
     } else if !user.check_valid_password(password) {
+        crate::ratelimit::register_login_failure(&user.uuid, &ip.ip);
+        let locked = user.register_failed_login();
+        if let Err(e) = user.save_failed_logins(conn).await {
+            error!("Error updating user: {:#?}", e);
+        }
+
+        if let (true, Some(locked_until)) = (locked, user.locked_until) {
+            warn!("Locked the account of {} after too many failed logins. IP: {}", username, ip.ip);
+            if CONFIG.mail_enabled() {
+                let now = Utc::now().naive_utc();
+                if let Err(e) = mail::send_login_lockout(&user.email, &ip.ip.to_string(), &now, &locked_until).await {
+                    error!("Error sending login lockout email: {:#?}", e);
+                }
+            }
+        }
+
         err!(
             "Username or password is incorrect. Try again",
             format!("IP: {}. Username: {}.", ip.ip, username),
@@ -216,6 +337,37 @@ async fn _password_login(
         )
     }
 
+    crate::ratelimit::reset_login_failures(&user.uuid, &ip.ip);
+    if user.failed_login_count > 0 || user.locked_until.is_some() {
+        user.reset_failed_logins();
+        if let Err(e) = user.save_failed_logins(conn).await {
+            error!("Error updating user: {:#?}", e);
+        }
+    }
+
+    // Only the master password login is replaced by SSO, logging in with an auth request is still possible
+    if data.auth_request.is_none() && CONFIG.sso_enabled() {
+        if CONFIG.sso_only() {
+            err!(
+                "SSO sign-in is required",
+                format!("IP: {}. Username: {}.", ip.ip, username),
+                ErrorEvent {
+                    event: EventType::UserFailedLogIn
+                }
+            )
+        }
+
+        if OrgPolicy::is_applicable_to_user(&user.uuid, OrgPolicyType::RequireSso, None, conn).await {
+            err!(
+                "Your organization requires you to sign in using SSO",
+                format!("IP: {}. Username: {}.", ip.ip, username),
+                ErrorEvent {
+                    event: EventType::UserFailedLogIn
+                }
+            )
+        }
+    }
+
     // Change the KDF Iterations (only when not logging in with an auth request)
     if data.auth_request.is_none() && user.password_iterations != CONFIG.password_iterations() {
         user.password_iterations = CONFIG.password_iterations();
@@ -227,45 +379,158 @@ async fn _password_login(
     }
 
     let now = Utc::now().naive_utc();
+    check_email_verified(&mut user, &now, ip, conn).await?;
 
-    if user.verified_at.is_none() && CONFIG.mail_enabled() && CONFIG.signups_verify() {
-        if user.last_verifying_at.is_none()
-            || now.signed_duration_since(user.last_verifying_at.unwrap()).num_seconds()
-                > CONFIG.signups_verify_resend_time() as i64
-        {
-            let resend_limit = CONFIG.signups_verify_resend_limit() as i32;
-            if resend_limit == 0 || user.login_verify_count < resend_limit {
-                // We want to send another email verification if we require signups to verify
-                // their email address, and we haven't sent them a reminder in a while...
-                user.last_verifying_at = Some(now);
-                user.login_verify_count += 1;
-
-                if let Err(e) = user.save(conn).await {
-                    error!("Error updating user: {:#?}", e);
-                }
+    let (mut device, new_device) = get_device(&data, conn, &user).await;
 
-                if let Err(e) = mail::send_verify_email(&user.email, &user.uuid).await {
-                    error!("Error auto-sending email verification email: {:#?}", e);
-                }
+    let twofactor_token = twofactor_auth(&user, &data, &mut device, ip, conn).await?;
+
+    let result = authenticated_response(&user, &mut device, new_device, twofactor_token, &now, ip, conn).await?;
+
+    info!("User {} logged in successfully. IP: {}", username, ip.ip);
+    Ok(result)
+}
+
+/// Sends a new verification email when it's time to remind the user, and fails the login while
+/// the email address is not verified when `SIGNUPS_VERIFY` is enabled
+async fn check_email_verified(user: &mut User, now: &NaiveDateTime, ip: &ClientIp, conn: &mut DbConn) -> EmptyResult {
+    if user.verified_at.is_some() || !CONFIG.mail_enabled() || !CONFIG.signups_verify() {
+        return Ok(());
+    }
+
+    if user.last_verifying_at.is_none()
+        || now.signed_duration_since(user.last_verifying_at.unwrap()).num_seconds()
+            > CONFIG.signups_verify_resend_time() as i64
+    {
+        let resend_limit = CONFIG.signups_verify_resend_limit() as i32;
+        if resend_limit == 0 || user.login_verify_count < resend_limit {
+            // We want to send another email verification if we require signups to verify
+            // their email address, and we haven't sent them a reminder in a while...
+            user.last_verifying_at = Some(*now);
+            user.login_verify_count += 1;
+
+            if let Err(e) = user.save(conn).await {
+                error!("Error updating user: {:#?}", e);
+            }
+
+            if let Err(e) = mail::send_verify_email(&user.email, &user.uuid).await {
+                error!("Error auto-sending email verification email: {:#?}", e);
             }
         }
+    }
+
+    // We still want the login to fail until they actually verified the email address
+    err!(
+        "Please verify your email before trying again.",
+        format!("IP: {}. Username: {}.", ip.ip, user.email),
+        ErrorEvent {
+            event: EventType::UserFailedLogIn
+        }
+    )
+}
+
+async fn _webauthn_login(
+    data: ConnectData,
+    user_id: &mut Option<UserId>,
+    conn: &mut DbConn,
+    ip: &ClientIp,
+) -> JsonResult {
+    // Validate scope
+    let scope = data.scope.as_ref().unwrap();
+    if scope != "api offline_access" {
+        err!("Scope not supported")
+    }
+
+    // Ratelimit the login
+    crate::ratelimit::check_limit_login(&ip.ip)?;
+
+    let claims = decode_webauthn_login(data.token.as_ref().unwrap())?;
+    let Ok(response) =
+        serde_json::from_str::<webauthn::PublicKeyCredentialCopy>(data.device_response.as_ref().unwrap())
+    else {
+        err!("Invalid passkey response", format!("IP: {}.", ip.ip))
+    };
+
+    let Some(passkey) = passkeys::find_passkey(&response, conn).await else {
+        err!("This passkey is not registered", format!("IP: {}.", ip.ip))
+    };
+    let Some(user) = User::find_by_uuid(&passkey.user_uuid, conn).await else {
+        err!("This passkey is not registered", format!("IP: {}.", ip.ip))
+    };
 
-        // We still want the login to fail until they actually verified the email address
+    // Set the user_id here to be passed back used for event logging.
+    *user_id = Some(user.uuid.clone());
+
+    let passkey = match passkeys::validate_passkey_login(passkey, &claims, response, conn).await {
+        Ok(passkey) => passkey,
+        Err(e) => err!(
+            "Passkey verification failed. Try again",
+            format!("IP: {}. Username: {}. {e}", ip.ip, user.email),
+            ErrorEvent {
+                event: EventType::UserFailedLogIn
+            }
+        ),
+    };
+
+    // Check if the user is disabled
+    if !user.enabled {
         err!(
-            "Please verify your email before trying again.",
-            format!("IP: {}. Username: {}.", ip.ip, username),
+            "This user has been disabled",
+            format!("IP: {}. Username: {}.", ip.ip, user.email),
             ErrorEvent {
                 event: EventType::UserFailedLogIn
             }
         )
     }
 
+    // A passkey replaces the master password, so it is not allowed when SSO is required
+    if CONFIG.sso_enabled()
+        && (CONFIG.sso_only()
+            || OrgPolicy::is_applicable_to_user(&user.uuid, OrgPolicyType::RequireSso, None, conn).await)
+    {
+        err!(
+            "SSO sign-in is required",
+            format!("IP: {}. Username: {}.", ip.ip, user.email),
+            ErrorEvent {
+                event: EventType::UserFailedLogIn
+            }
+        )
+    }
+
+    let now = Utc::now().naive_utc();
     let (mut device, new_device) = get_device(&data, conn, &user).await;
 
-    let twofactor_token = twofactor_auth(&user, &data, &mut device, ip, conn).await?;
+    // The passkey is already a second factor, it required user verification on the authenticator
+    let mut result = authenticated_response(&user, &mut device, new_device, None, &now, ip, conn).await?;
+
+    // With the PRF extension the client can unlock the vault with the passkey, using the user key wrapped by it
+    if passkey.has_prf_keys() {
+        result.0["UserDecryptionOptions"]["WebAuthnPrfOption"] = json!({
+            "EncryptedPrivateKey": passkey.encrypted_private_key,
+            "EncryptedUserKey": passkey.encrypted_user_key,
+        });
+    }
+
+    info!("User {} logged in successfully with a passkey. IP: {}", user.email, ip.ip);
+    Ok(result)
+}
+
+/// Registers the device and returns the tokens of a user who successfully logged in,
+/// used by the master password, passkey and SSO logins.
+async fn authenticated_response(
+    user: &User,
+    device: &mut Device,
+    new_device: bool,
+    twofactor_token: Option<String>,
+    now: &NaiveDateTime,
+    ip: &ClientIp,
+    conn: &mut DbConn,
+) -> JsonResult {
+    let scope = "api offline_access";
+    let scope_vec = vec!["api".into(), "offline_access".into()];
 
     if CONFIG.mail_enabled() && new_device {
-        if let Err(e) = mail::send_new_device_logged_in(&user.email, &ip.ip.to_string(), &now, &device).await {
+        if let Err(e) = mail::send_new_device_logged_in(&user.email, &ip.ip.to_string(), now, device).await {
             error!("Error sending new device email: {:#?}", e);
 
             if CONFIG.require_device_email() {
@@ -281,7 +546,7 @@ async fn _password_login(
 
     // register push device
     if !new_device {
-        register_push_device(&mut device, conn).await?;
+        register_push_device(device, conn).await?;
     }
 
     // Common
@@ -291,8 +556,14 @@ async fn _password_login(
     // See: https://github.com/dani-garcia/vaultwarden/issues/4156
     // ---
     // let members = Membership::find_confirmed_by_user(&user.uuid, conn).await;
-    let (access_token, expires_in) = device.refresh_tokens(&user, scope_vec);
+    // Every login starts a new session with a new refresh token
+    device.start_session();
+    device.touch(&ip.ip);
+    let (access_token, expires_in) = device.refresh_tokens(user, scope_vec);
     device.save(conn).await?;
+    if let Err(e) = User::update_last_login(&user.uuid, conn).await {
+        error!("Error updating the last login of {}: {e:#?}", user.email);
+    }
 
     // Fetch all valid Master Password Policies and merge them into one with all true's and larges numbers as one policy
     let master_password_policies: Vec<MasterPasswordPolicy> =
@@ -352,10 +623,128 @@ async fn _password_login(
         result["TwoFactorToken"] = Value::String(token);
     }
 
-    info!("User {} logged in successfully. IP: {}", username, ip.ip);
     Ok(Json(result))
 }
 
+async fn _sso_login(data: ConnectData, user_id: &mut Option<UserId>, conn: &mut DbConn, ip: &ClientIp) -> JsonResult {
+    // Ratelimit the login
+    crate::ratelimit::check_limit_login(&ip.ip)?;
+
+    // Validate scope
+    match data.scope.as_deref() {
+        None | Some("api offline_access") => {}
+        Some(_) => err!("Scope not supported"),
+    }
+
+    let code = data.code.as_ref().unwrap();
+    let Some(auth) = SsoAuth::find_by_code(code, conn).await else {
+        err!("Invalid SSO code", format!("IP: {}.", ip.ip))
+    };
+
+    // A code can only be used once
+    auth.delete(conn).await?;
+
+    if auth.is_expired() {
+        err!("The SSO login has expired. Try again", format!("IP: {}.", ip.ip))
+    }
+
+    // Verify this is the same client which started the login
+    let code_verifier = data.code_verifier.as_ref().unwrap();
+    if !crypto::ct_eq(sso::pkce_challenge(code_verifier), &auth.code_challenge)
+        || data.redirect_uri.as_ref() != Some(&auth.redirect_uri)
+    {
+        err!("Invalid SSO code verifier", format!("IP: {}.", ip.ip))
+    }
+
+    let user_info = sso::exchange_code(&auth).await?;
+    let mut user = sso_user(&user_info, conn).await?;
+
+    // Set the user_id here to be passed back used for event logging.
+    *user_id = Some(user.uuid.clone());
+
+    // Check if the user is disabled
+    if !user.enabled {
+        err!(
+            "This user has been disabled",
+            format!("IP: {}. Username: {}.", ip.ip, user.email),
+            ErrorEvent {
+                event: EventType::UserFailedLogIn
+            }
+        )
+    }
+
+    let now = Utc::now().naive_utc();
+    check_email_verified(&mut user, &now, ip, conn).await?;
+
+    let (mut device, new_device) = get_device(&data, conn, &user).await;
+
+    let twofactor_token = twofactor_auth(&user, &data, &mut device, ip, conn).await?;
+
+    let result = authenticated_response(&user, &mut device, new_device, twofactor_token, &now, ip, conn).await?;
+
+    info!("User {} logged in successfully via SSO. IP: {}", user.email, ip.ip);
+    Ok(result)
+}
+
+/// Returns the user linked to the identity reported by the identity provider.
+/// When no user is linked yet, an existing account with the same verified email address is linked,
+/// or a new account is created which will need to set its master password at the first login.
+async fn sso_user(user_info: &sso::UserInformation, conn: &mut DbConn) -> ApiResult<User> {
+    if let Some(sso_user) = SsoUser::find_by_identifier(&user_info.identifier, conn).await {
+        return User::find_by_uuid(&sso_user.user_uuid, conn)
+            .await
+            .map_res("User linked to the SSO identity not found");
+    }
+
+    let Some(ref email) = user_info.email else {
+        err!("The identity provider did not return an email address")
+    };
+
+    let user = match User::find_by_mail(email, conn).await {
+        Some(user) => {
+            if !CONFIG.sso_signups_match_email() || !user_info.email_verified {
+                err!(
+                    "An account with this email address already exists",
+                    format!("Not linking {email} to {}, the email address is not verified", user_info.identifier)
+                )
+            }
+            if SsoUser::find_by_user(&user.uuid, conn).await.is_some() {
+                err!("This account is already linked to another SSO identity", format!("Email: {email}."))
+            }
+
+            // The user was invited, but did not register yet
+            if user.password_hash.is_empty() && Invitation::take(email, conn).await {
+                for membership in Membership::find_invited_by_user(&user.uuid, conn).await.iter_mut() {
+                    membership.status = MembershipStatus::Accepted as i32;
+                    membership.save(conn).await?;
+                }
+            }
+            user
+        }
+        None => {
+            // Order is important here; the invitation check must come first
+            // because the vaultwarden admin can invite anyone, regardless
+            // of other signup restrictions.
+            if !(Invitation::take(email, conn).await || CONFIG.is_signup_allowed(email)) {
+                err!("Registration not allowed", format!("Email: {email}."))
+            }
+
+            let mut user = User::new(email.clone());
+            if let Some(ref name) = user_info.name {
+                user.name.clone_from(name);
+            }
+            if user_info.email_verified {
+                user.verified_at = Some(Utc::now().naive_utc());
+            }
+            user.save(conn).await?;
+            user
+        }
+    };
+
+    SsoUser::new(user.uuid.clone(), user_info.identifier.clone()).save(conn).await?;
+    Ok(user)
+}
+
 async fn _api_key_login(
     data: ConnectData,
     user_id: &mut Option<UserId>,
@@ -441,8 +830,12 @@ async fn _user_api_key_login(
     // See: https://github.com/dani-garcia/vaultwarden/issues/4156
     // ---
     // let members = Membership::find_confirmed_by_user(&user.uuid, conn).await;
+    device.touch(&ip.ip);
     let (access_token, expires_in) = device.refresh_tokens(&user, scope_vec);
     device.save(conn).await?;
+    if let Err(e) = User::update_last_login(&user.uuid, conn).await {
+        error!("Error updating the last login of {}: {e:#?}", user.email);
+    }
 
     info!("User {} logged in successfully via API key. IP: {}", user.email, ip.ip);
 
@@ -778,13 +1171,130 @@ async fn register_finish(data: Json<RegisterData>, conn: DbConn) -> JsonResult {
     _register(data, true, conn).await
 }
 
+// There is only one identity provider, so every SSO identifier entered by the user is accepted.
+// The token is returned by Bitwarden to bind the following authorize request, but is not needed here.
+#[get("/sso/prevalidate")]
+fn prevalidate() -> JsonResult {
+    if !CONFIG.sso_enabled() {
+        err_code!("SSO sign-in is not available", 400)
+    }
+
+    Ok(Json(json!({
+        "token": crypto::get_random_string_alphanum(32),
+    })))
+}
+
+// The challenge for the `webauthn` grant, the returned token needs to be sent back with the response of the passkey
+#[get("/accounts/webauthn/assertion-options")]
+fn webauthn_assertion_options() -> JsonResult {
+    passkeys::generate_passkey_login_options()
+}
+
+#[derive(FromForm)]
+struct AuthorizeData {
+    #[field(name = uncased("redirect_uri"))]
+    redirect_uri: String,
+    #[field(name = uncased("response_type"))]
+    response_type: String,
+    #[field(name = uncased("state"))]
+    state: String,
+    #[field(name = uncased("code_challenge"))]
+    code_challenge: String,
+    #[field(name = uncased("code_challenge_method"))]
+    code_challenge_method: String,
+}
+
+/// Only allow redirecting to the Bitwarden clients, else the code could be sent to any other site
+fn is_valid_client_redirect(redirect_uri: &str) -> bool {
+    let Ok(url) = Url::parse(redirect_uri) else {
+        return false;
+    };
+
+    match url.scheme() {
+        // Desktop and mobile clients
+        "bitwarden" => true,
+        // The CLI listens on a local port
+        "http" if matches!(url.host_str(), Some("localhost" | "127.0.0.1")) => true,
+        // The web vault and browser extensions
+        _ => redirect_uri.starts_with(&format!("{}/", CONFIG.domain())),
+    }
+}
+
+#[get("/connect/authorize?<data..>")]
+async fn authorize(data: AuthorizeData, mut conn: DbConn) -> ApiResult<Redirect> {
+    if !CONFIG.sso_enabled() {
+        err!("SSO sign-in is not available")
+    }
+    if data.response_type != "code" || data.code_challenge_method != "S256" {
+        err!("Unsupported authorization request")
+    }
+    if !is_valid_client_redirect(&data.redirect_uri) {
+        err!("Invalid redirect_uri", format!("redirect_uri: {}", data.redirect_uri))
+    }
+
+    let (auth, url) = sso::authorize(data.state, data.code_challenge, data.redirect_uri).await?;
+    auth.save(&mut conn).await?;
+
+    Ok(Redirect::temporary(url))
+}
+
+#[get("/connect/oidc-signin?<code>&<state>&<error>&<error_description>")]
+async fn oidc_signin(
+    code: Option<String>,
+    state: String,
+    error: Option<String>,
+    error_description: Option<String>,
+    mut conn: DbConn,
+) -> ApiResult<Redirect> {
+    let Some(mut auth) = SsoAuth::find_by_state(&state, &mut conn).await else {
+        err!("Unknown or expired SSO login. Try again")
+    };
+
+    if auth.is_expired() || auth.code.is_some() {
+        auth.delete(&mut conn).await?;
+        err!("Unknown or expired SSO login. Try again")
+    }
+
+    let Some(code) = code else {
+        auth.delete(&mut conn).await?;
+        err!(
+            "The identity provider returned an error",
+            format!("{}: {}", error.unwrap_or_default(), error_description.unwrap_or_default())
+        )
+    };
+
+    // Hand out a code of our own to the client, it is exchanged for the identity provider code at the token endpoint
+    let client_code = crypto::get_random_string_alphanum(64);
+    auth.idp_code = Some(code);
+    auth.code = Some(client_code.clone());
+    auth.save(&mut conn).await?;
+
+    let mut redirect = match Url::parse(&auth.redirect_uri) {
+        Ok(url) => url,
+        Err(e) => err!(format!("Invalid redirect_uri: {e}")),
+    };
+    redirect.query_pairs_mut().append_pair("code", &client_code).append_pair("state", &auth.client_state);
+
+    Ok(Redirect::temporary(redirect.to_string()))
+}
+
+// Task to clean up SSO logins which were never completed
+pub async fn purge_sso_auth(pool: DbPool) {
+    debug!("Purging unfinished SSO logins");
+    if let Ok(mut conn) = pool.get().await {
+        SsoAuth::purge_expired(&mut conn).await.ok();
+    } else {
+        error!("Failed to get DB connection while purging unfinished SSO logins")
+    }
+}
+
 // https://github.com/bitwarden/jslib/blob/master/common/src/models/request/tokenRequest.ts
 // https://github.com/bitwarden/mobile/blob/master/src/Core/Models/Request/TokenRequest.cs
 #[derive(Debug, Clone, Default, FromForm)]
 struct ConnectData {
     #[field(name = uncased("grant_type"))]
     #[field(name = uncased("granttype"))]
-    grant_type: String, // refresh_token, password, client_credentials (API key)
+    grant_type: String, // refresh_token, password, client_credentials (API key), authorization_code (SSO), webauthn (passkey)
 
     // Needed for grant_type="refresh_token"
     #[field(name = uncased("refresh_token"))]
@@ -831,6 +1341,23 @@ struct ConnectData {
     two_factor_remember: Option<i32>,
     #[field(name = uncased("authrequest"))]
     auth_request: Option<AuthRequestId>,
+
+    // Needed for grant_type = "authorization_code"
+    #[field(name = uncased("code"))]
+    code: Option<String>,
+    #[field(name = uncased("code_verifier"))]
+    #[field(name = uncased("codeverifier"))]
+    code_verifier: Option<String>,
+    #[field(name = uncased("redirect_uri"))]
+    #[field(name = uncased("redirecturi"))]
+    redirect_uri: Option<String>,
+
+    // Needed for grant_type = "webauthn"
+    #[field(name = uncased("token"))]
+    token: Option<String>,
+    #[field(name = uncased("device_response"))]
+    #[field(name = uncased("deviceresponse"))]
+    device_response: Option<String>,
 }
 
 fn _check_is_some<T>(value: &Option<T>, msg: &str) -> EmptyResult {
@@ -0,0 +1,55 @@
+use rocket::{
+    http::{ContentType, Status},
+    request::{FromRequest, Outcome},
+    Request, Route, State,
+};
+
+use crate::{
+    api::notifications::{WS_ANONYMOUS_SUBSCRIPTIONS, WS_USERS},
+    db::DbPool,
+    metrics::{self, Gauges},
+    CONFIG,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![get_metrics]
+}
+
+/// Authenticates a metrics scraper using `METRICS_TOKEN` as bearer token
+pub struct MetricsToken;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MetricsToken {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if !CONFIG.metrics_enabled() {
+            return Outcome::Error((Status::NotFound, "Metrics are disabled"));
+        }
+
+        let Some(token) = request.headers().get_one("Authorization").and_then(|a| a.strip_prefix("Bearer ")) else {
+            err_handler!("No metrics token provided")
+        };
+
+        match CONFIG.metrics_token() {
+            Some(t) if crate::crypto::ct_eq(t.trim(), token.trim()) => Outcome::Success(MetricsToken),
+            _ => err_handler!("Invalid metrics token"),
+        }
+    }
+}
+
+#[get("/")]
+fn get_metrics(_token: MetricsToken, pool: &State<DbPool>) -> (ContentType, String) {
+    let (db_connections, db_connections_in_use) = pool.state();
+    let gauges = Gauges {
+        db_connections,
+        db_connections_in_use,
+        db_max_connections: CONFIG.database_max_conns(),
+        websocket_connections: WS_USERS.connection_count(),
+        websocket_anonymous_subscriptions: WS_ANONYMOUS_SUBSCRIPTIONS.subscription_count(),
+    };
+
+    // The content type of the Prometheus text exposition format
+    let content_type = ContentType::new("text", "plain").with_params([("version", "0.0.4"), ("charset", "utf-8")]);
+    (content_type, metrics::render(&gauges))
+}
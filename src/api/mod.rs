@@ -2,8 +2,10 @@ mod admin;
 pub mod core;
 mod icons;
 mod identity;
+mod metrics;
 mod notifications;
 mod push;
+mod scim;
 mod web;
 
 use rocket::serde::json::Json;
@@ -13,21 +15,25 @@ pub use crate::api::{
     admin::catchers as admin_catchers,
     admin::routes as admin_routes,
     core::catchers as core_catchers,
+    core::inactive_user_job,
     core::purge_auth_requests,
     core::purge_sends,
     core::purge_trashed_ciphers,
     core::routes as core_routes,
     core::two_factor::send_incomplete_2fa_notifications,
+    core::webhook_delivery_job,
     core::{emergency_notification_reminder_job, emergency_request_timeout_job},
     core::{event_cleanup_job, events_routes as core_events_routes},
     icons::routes as icons_routes,
-    identity::routes as identity_routes,
+    identity::{purge_sso_auth, routes as identity_routes},
+    metrics::routes as metrics_routes,
     notifications::routes as notifications_routes,
     notifications::{AnonymousNotify, Notify, UpdateType, WS_ANONYMOUS_SUBSCRIPTIONS, WS_USERS},
     push::{
-        push_cipher_update, push_folder_update, push_logout, push_send_update, push_user_update, register_push_device,
-        unregister_push_device,
+        check_push_credentials, push_cipher_update, push_device_logout, push_folder_update, push_logout,
+        push_send_update, push_user_update, register_push_device, unregister_push_device,
     },
+    scim::routes as scim_routes,
     web::catchers as web_catchers,
     web::routes as web_routes,
     web::static_files,
@@ -73,7 +73,7 @@ impl Drop for WSEntryMapGuard {
     fn drop(&mut self) {
         info!("Closing WS connection from {}", self.addr);
         if let Some(mut entry) = self.users.map.get_mut(self.user_uuid.as_ref()) {
-            entry.retain(|(uuid, _)| uuid != &self.entry_uuid);
+            entry.retain(|(uuid, _, _)| uuid != &self.entry_uuid);
         }
     }
 }
@@ -130,7 +130,7 @@ fn websockets_hub<'r>(
         // Add a channel to send messages to this client to the map
         let entry_uuid = uuid::Uuid::new_v4();
         let (tx, rx) = tokio::sync::mpsc::channel::<Message>(100);
-        users.map.entry(claims.sub.to_string()).or_default().push((entry_uuid, tx));
+        users.map.entry(claims.sub.to_string()).or_default().push((entry_uuid, claims.device, tx));
 
         // Once the guard goes out of scope, the connection will have been closed and the entry will be deleted from the map
         (rx, WSEntryMapGuard::new(users, claims.sub, entry_uuid, addr))
@@ -320,17 +320,33 @@ static INITIAL_MESSAGE: InitialMessage<'static> = InitialMessage {
     version: 1,
 };
 
-// We attach the UUID to the sender so we can differentiate them when we need to remove them from the Vec
-type UserSenders = (uuid::Uuid, Sender<Message>);
+// We attach the UUID to the sender so we can differentiate them when we need to remove them from the Vec,
+// and the device of the connection to be able to send an update to a single device
+type UserSenders = (uuid::Uuid, DeviceId, Sender<Message>);
 #[derive(Clone)]
 pub struct WebSocketUsers {
     map: Arc<dashmap::DashMap<String, Vec<UserSenders>>>,
 }
 
 impl WebSocketUsers {
+    /// Number of open WebSocket connections, a user can have one per device
+    pub fn connection_count(&self) -> usize {
+        self.map.iter().map(|user| user.len()).sum()
+    }
+
     async fn send_update(&self, user_id: &UserId, data: &[u8]) {
         if let Some(user) = self.map.get(user_id.as_ref()).map(|v| v.clone()) {
-            for (_, sender) in user.iter() {
+            for (_, _, sender) in user.iter() {
+                if let Err(e) = sender.send(Message::binary(data)).await {
+                    error!("Error sending WS update {e}");
+                }
+            }
+        }
+    }
+
+    async fn send_device_update(&self, user_id: &UserId, device_id: &DeviceId, data: &[u8]) {
+        if let Some(user) = self.map.get(user_id.as_ref()).map(|v| v.clone()) {
+            for (_, _, sender) in user.iter().filter(|(_, d, _)| d == device_id) {
                 if let Err(e) = sender.send(Message::binary(data)).await {
                     error!("Error sending WS update {e}");
                 }
@@ -379,6 +395,23 @@ impl WebSocketUsers {
         }
     }
 
+    /// Logs out a single device. Only the WebSocket connections of that device are notified,
+    /// the push relay can only send a logout to all the devices of a user.
+    pub async fn send_device_logout(&self, user_id: &UserId, device_id: &DeviceId) {
+        if !CONFIG.enable_websocket() {
+            return;
+        }
+        let data = create_update(
+            vec![
+                ("UserId".into(), user_id.to_string().into()),
+                ("Date".into(), serialize_date(Utc::now().naive_utc())),
+            ],
+            UpdateType::LogOut,
+            None,
+        );
+        self.send_device_update(user_id, device_id, &data).await;
+    }
+
     pub async fn send_folder_update(
         &self,
         ut: UpdateType,
@@ -549,6 +582,10 @@ pub struct AnonymousWebSocketSubscriptions {
 }
 
 impl AnonymousWebSocketSubscriptions {
+    pub fn subscription_count(&self) -> usize {
+        self.map.len()
+    }
+
     async fn send_update(&self, token: &str, data: &[u8]) {
         if let Some(sender) = self.map.get(token).map(|v| v.clone()) {
             if let Err(e) = sender.send(Message::binary(data)).await {
@@ -43,40 +43,53 @@ async fn get_auth_push_token() -> ApiResult<String> {
     }
     drop(push_token); // Drop the read lock now
 
-    let installation_id = CONFIG.push_installation_id();
+    let json_pushtoken = request_push_token(
+        &CONFIG.push_identity_uri(),
+        &CONFIG.push_installation_id(),
+        &CONFIG.push_installation_key(),
+    )
+    .await?;
+
+    let mut push_token = PUSH_TOKEN.write().await;
+    push_token.valid_until = Instant::now()
+        .checked_add(Duration::new((json_pushtoken.expires_in / 2) as u64, 0)) // Token valid for half the specified time
+        .unwrap();
+
+    push_token.access_token = json_pushtoken.access_token;
+
+    debug!("Token still valid for {}", push_token.valid_until.saturating_duration_since(Instant::now()).as_secs());
+    Ok(push_token.access_token.clone())
+}
+
+async fn request_push_token(
+    identity_uri: &str,
+    installation_id: &str,
+    installation_key: &str,
+) -> ApiResult<AuthPushToken> {
     let client_id = format!("installation.{installation_id}");
-    let client_secret = CONFIG.push_installation_key();
 
     let params = [
         ("grant_type", "client_credentials"),
         ("scope", "api.push"),
         ("client_id", &client_id),
-        ("client_secret", &client_secret),
+        ("client_secret", installation_key),
     ];
 
-    let res = match make_http_request(Method::POST, &format!("{}/connect/token", CONFIG.push_identity_uri()))?
-        .form(&params)
-        .send()
-        .await
-    {
-        Ok(r) => r,
-        Err(e) => err!(format!("Error getting push token from bitwarden server: {e}")),
-    };
+    let res =
+        match make_http_request(Method::POST, &format!("{identity_uri}/connect/token"))?.form(&params).send().await {
+            Ok(r) => r,
+            Err(e) => err!(format!("Error getting push token from bitwarden server: {e}")),
+        };
 
-    let json_pushtoken = match res.json::<AuthPushToken>().await {
-        Ok(r) => r,
+    match res.json::<AuthPushToken>().await {
+        Ok(r) => Ok(r),
         Err(e) => err!(format!("Unexpected push token received from bitwarden server: {e}")),
-    };
-
-    let mut push_token = PUSH_TOKEN.write().await;
-    push_token.valid_until = Instant::now()
-        .checked_add(Duration::new((json_pushtoken.expires_in / 2) as u64, 0)) // Token valid for half the specified time
-        .unwrap();
-
-    push_token.access_token = json_pushtoken.access_token;
+    }
+}
 
-    debug!("Token still valid for {}", push_token.valid_until.saturating_duration_since(Instant::now()).as_secs());
-    Ok(push_token.access_token.clone())
+/// Requests a push token with the given installation, to test it before it is saved
+pub async fn check_push_credentials(identity_uri: &str, installation_id: &str, installation_key: &str) -> EmptyResult {
+    request_push_token(identity_uri, installation_id, installation_key).await.map(|_| ())
 }
 
 pub async fn register_push_device(device: &mut Device, conn: &mut crate::db::DbConn) -> EmptyResult {
@@ -194,6 +207,26 @@ pub fn push_logout(user: &User, acting_device_id: Option<DeviceId>) {
     })));
 }
 
+/// Logs out a single device, the relay only delivers it while the device is still registered
+pub async fn push_device_logout(device: &Device) {
+    if !device.is_registered() {
+        return;
+    }
+
+    send_to_push_relay(json!({
+        "userId": device.user_uuid,
+        "organizationId": (),
+        "deviceId": device.push_uuid,
+        "identifier": (),
+        "type": UpdateType::LogOut as i32,
+        "payload": {
+            "UserId": device.user_uuid,
+            "Date": format_date(&chrono::Utc::now().naive_utc())
+        }
+    }))
+    .await;
+}
+
 pub fn push_user_update(ut: UpdateType, user: &User) {
     tokio::task::spawn(send_to_push_relay(json!({
         "userId": user.uuid,
@@ -0,0 +1,933 @@
+//
+// SCIM 2.0 provisioning of organization members and groups
+// See: https://datatracker.ietf.org/doc/html/rfc7643 and https://datatracker.ietf.org/doc/html/rfc7644
+//
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    response::status::{Created, NoContent},
+    serde::json::Json,
+    Request, Route,
+};
+use serde_json::Value;
+
+use crate::{
+    api::{
+        core::{log_event, two_factor},
+        ApiResult, EmptyResult, JsonResult, Notify, UpdateType,
+    },
+    auth::ClientIp,
+    db::{models::*, DbConn},
+    mail, CONFIG,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        get_users,
+        get_user,
+        post_user,
+        put_user,
+        patch_user,
+        delete_user,
+        get_groups,
+        get_group,
+        post_group,
+        put_group,
+        patch_group,
+        delete_group
+    ]
+}
+
+const SCHEMA_USER: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+const SCHEMA_GROUP: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+const SCHEMA_LIST: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+
+// Path used to remove a single member from a group, like `members[value eq "<id>"]`
+const MEMBER_PATH_PREFIX: &str = "members[value eq ";
+
+// The lowercased attributes which can be used in the filter of a list request
+const USER_FILTER_ATTRIBUTES: &[&str] = &["username", "emails", "emails.value", "externalid", "id"];
+const GROUP_FILTER_ATTRIBUTES: &[&str] = &["displayname", "externalid", "id"];
+
+// Used as the acting user of the events logged for changes made through SCIM
+const ACTING_SCIM_USER: &str = "vaultwarden-scim-000000-000000000000";
+
+/// Authenticates a SCIM client using the API key of the organization in the path as bearer token
+pub struct ScimToken {
+    org_id: OrganizationId,
+    ip: ClientIp,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ScimToken {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let ip = match ClientIp::from_request(request).await {
+            Outcome::Success(ip) => ip,
+            _ => err_handler!("Error getting Client IP"),
+        };
+
+        let Some(api_key) = request.headers().get_one("Authorization").and_then(|a| a.strip_prefix("Bearer ")) else {
+            err_handler!("No access token provided")
+        };
+
+        let Some(Ok(org_id)) = request.param::<OrganizationId>(0) else {
+            err_handler!("Invalid organization id")
+        };
+
+        let conn = match DbConn::from_request(request).await {
+            Outcome::Success(conn) => conn,
+            _ => err_handler!("Error getting DB"),
+        };
+
+        match OrganizationApiKey::find_by_org_uuid(&org_id, &conn).await {
+            Some(org_api_key) if org_api_key.check_valid_api_key(api_key.trim()) => Outcome::Success(ScimToken {
+                org_id,
+                ip,
+            }),
+            _ => err_handler!("Invalid API key"),
+        }
+    }
+}
+
+impl ScimToken {
+    async fn log_event(&self, event_type: EventType, source_uuid: &str, conn: &mut DbConn) {
+        log_event(
+            event_type as i32,
+            source_uuid,
+            &self.org_id,
+            &ACTING_SCIM_USER.into(),
+            14, // Use UnknownBrowser type
+            &self.ip.ip,
+            conn,
+        )
+        .await;
+    }
+}
+
+#[derive(FromForm)]
+struct ListQuery {
+    filter: Option<String>,
+    #[field(name = "startIndex")]
+    start_index: Option<usize>,
+    count: Option<usize>,
+}
+
+impl ListQuery {
+    /// Only the `eq` operator on a single attribute is supported, which is what identity providers use to look up resources.
+    /// Returns the lowercased attribute name, which needs to be one of `attributes`, and the value to compare with.
+    fn filter(&self, attributes: &[&str]) -> ApiResult<Option<(String, String)>> {
+        let Some(ref filter) = self.filter else {
+            return Ok(None);
+        };
+
+        let mut parts = filter.trim().splitn(3, ' ');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(attribute), Some(operator), Some(value)) if operator.eq_ignore_ascii_case("eq") => {
+                let attribute = attribute.to_lowercase();
+                if !attributes.contains(&attribute.as_str()) {
+                    err_code!(format!("Unsupported filter attribute: {attribute}"), Status::BadRequest.code)
+                }
+                let value = value.trim().trim_matches('"').replace("\\\"", "\"");
+                Ok(Some((attribute, value)))
+            }
+            _ => err_code!(format!("Unsupported filter: {filter}"), Status::BadRequest.code),
+        }
+    }
+
+    fn to_list_response(&self, resources: Vec<Value>) -> Json<Value> {
+        let total = resources.len();
+        // The startIndex is 1-based
+        let start_index = self.start_index.unwrap_or(1).max(1);
+        let page: Vec<Value> =
+            resources.into_iter().skip(start_index - 1).take(self.count.unwrap_or(usize::MAX)).collect();
+
+        Json(json!({
+            "schemas": [SCHEMA_LIST],
+            "totalResults": total,
+            "startIndex": start_index,
+            "itemsPerPage": page.len(),
+            "Resources": page,
+        }))
+    }
+}
+
+#[derive(Deserialize)]
+struct ScimPatchData {
+    #[serde(rename = "Operations", alias = "operations")]
+    operations: Vec<ScimPatchOperation>,
+}
+
+#[derive(Deserialize)]
+struct ScimPatchOperation {
+    op: String,
+    path: Option<String>,
+    value: Option<Value>,
+}
+
+/// A change to a group requested by a patch operation
+#[derive(Debug, PartialEq)]
+enum GroupChange {
+    AddMembers(Vec<MembershipId>),
+    RemoveMembers(Vec<MembershipId>),
+    ReplaceMembers(Vec<MembershipId>),
+    DisplayName(String),
+    ExternalId(Option<String>),
+}
+
+impl ScimPatchOperation {
+    /// The lowercased attributes of a user set by an `add` or `replace` operation, given by the path or as an object without a path
+    fn user_attributes(self) -> ApiResult<Vec<(String, Value)>> {
+        if !self.op.eq_ignore_ascii_case("replace") && !self.op.eq_ignore_ascii_case("add") {
+            err_code!(format!("Unsupported patch operation: {}", self.op), Status::BadRequest.code)
+        }
+        let Some(value) = self.value else {
+            err_code!(format!("The {} operation requires a value", self.op), Status::BadRequest.code)
+        };
+
+        match (self.path, value) {
+            (Some(path), value) => Ok(vec![(path.to_lowercase(), value)]),
+            (None, Value::Object(map)) => Ok(map.into_iter().map(|(k, v)| (k.to_lowercase(), v)).collect()),
+            (None, _) => err_code!("A patch operation without path requires an object value", Status::BadRequest.code),
+        }
+    }
+
+    fn group_changes(self) -> ApiResult<Vec<GroupChange>> {
+        let op = self.op.to_lowercase();
+        let path = self.path.unwrap_or_default();
+        let lower_path = path.to_lowercase();
+
+        let path_member = lower_path
+            .starts_with(MEMBER_PATH_PREFIX)
+            .then(|| path.get(MEMBER_PATH_PREFIX.len()..))
+            .flatten()
+            .map(|v| v.trim_end_matches(']').trim().trim_matches('"').to_string());
+
+        let members: Vec<MembershipId> = self
+            .value
+            .as_ref()
+            .and_then(|v| serde_json::from_value::<Vec<ScimMember>>(v.clone()).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|m| m.value)
+            .collect();
+
+        let changes = match (op.as_str(), lower_path.as_str()) {
+            ("add", "members") => vec![GroupChange::AddMembers(members)],
+            ("remove", "members") => vec![GroupChange::RemoveMembers(members)],
+            ("remove", _) if path_member.is_some() => {
+                vec![GroupChange::RemoveMembers(vec![path_member.unwrap_or_default().into()])]
+            }
+            ("replace", "members") => vec![GroupChange::ReplaceMembers(members)],
+            ("replace", "displayname") => self
+                .value
+                .as_ref()
+                .and_then(Value::as_str)
+                .map(|n| GroupChange::DisplayName(n.to_string()))
+                .into_iter()
+                .collect(),
+            ("replace", "externalid") => {
+                vec![GroupChange::ExternalId(self.value.as_ref().and_then(Value::as_str).map(String::from))]
+            }
+            ("replace", "") => match self.value {
+                Some(Value::Object(map)) => map
+                    .into_iter()
+                    .filter_map(|(attribute, value)| match attribute.to_lowercase().as_str() {
+                        "displayname" => value.as_str().map(|n| GroupChange::DisplayName(n.to_string())),
+                        "externalid" => Some(GroupChange::ExternalId(value.as_str().map(String::from))),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            },
+            _ => err_code!(format!("Unsupported patch operation: {op} {path}"), Status::BadRequest.code),
+        };
+        Ok(changes)
+    }
+}
+
+// Some identity providers send booleans as strings, like "False"
+fn value_as_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(b) => Some(*b),
+        Value::String(s) if s.eq_ignore_ascii_case("true") => Some(true),
+        Value::String(s) if s.eq_ignore_ascii_case("false") => Some(false),
+        _ => None,
+    }
+}
+
+fn resource_location(org_id: &OrganizationId, resource: &str, id: &str) -> String {
+    format!("{}/scim/v2/{org_id}/{resource}/{id}", CONFIG.domain())
+}
+
+//
+// Users
+//
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScimUserData {
+    user_name: Option<String>,
+    external_id: Option<String>,
+    emails: Option<Vec<ScimEmail>>,
+    active: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct ScimEmail {
+    value: String,
+    primary: Option<bool>,
+}
+
+impl ScimUserData {
+    /// The primary email address is preferred over the userName, which is not always an email address
+    fn email(&self) -> Option<String> {
+        let emails = self.emails.as_deref().unwrap_or_default();
+        emails
+            .iter()
+            .find(|e| e.primary.unwrap_or(false))
+            .or_else(|| emails.first())
+            .map(|e| e.value.clone())
+            .or_else(|| self.user_name.clone().filter(|u| u.contains('@')))
+            .map(|e| e.trim().to_lowercase())
+    }
+}
+
+fn member_to_json(member: &Membership, user: &User) -> Value {
+    use crate::util::format_date;
+
+    json!({
+        "schemas": [SCHEMA_USER],
+        "id": member.uuid,
+        "externalId": member.external_id,
+        "userName": user.email,
+        "displayName": user.name,
+        "name": {
+            "formatted": user.name,
+        },
+        "emails": [{
+            "primary": true,
+            "type": "work",
+            "value": user.email,
+        }],
+        "active": member.status > MembershipStatus::Revoked as i32,
+        "meta": {
+            "resourceType": "User",
+            "created": format_date(&user.created_at),
+            "lastModified": format_date(&user.updated_at),
+            "location": resource_location(&member.org_uuid, "Users", &member.uuid),
+        },
+    })
+}
+
+async fn get_member(member_id: &MembershipId, token: &ScimToken, conn: &mut DbConn) -> ApiResult<(Membership, User)> {
+    let Some(member) = Membership::find_by_uuid_and_org(member_id, &token.org_id, conn).await else {
+        err_code!("User not found", Status::NotFound.code)
+    };
+    let Some(user) = User::find_by_uuid(&member.user_uuid, conn).await else {
+        err_code!("User not found", Status::NotFound.code)
+    };
+    Ok((member, user))
+}
+
+#[get("/<_org_id>/Users?<query..>")]
+async fn get_users(_org_id: OrganizationId, query: ListQuery, token: ScimToken, mut conn: DbConn) -> JsonResult {
+    let filter = query.filter(USER_FILTER_ATTRIBUTES)?;
+
+    let mut resources = Vec::new();
+    for member in Membership::find_by_org(&token.org_id, &mut conn).await {
+        let Some(user) = User::find_by_uuid(&member.user_uuid, &mut conn).await else {
+            continue;
+        };
+
+        let matches = match filter {
+            None => true,
+            Some((ref attribute, ref value)) => match attribute.as_str() {
+                "username" | "emails" | "emails.value" => user.email.eq_ignore_ascii_case(value),
+                "externalid" => member.external_id.as_deref() == Some(value.as_str()),
+                "id" => *member.uuid == *value,
+                _ => false,
+            },
+        };
+
+        if matches {
+            resources.push(member_to_json(&member, &user));
+        }
+    }
+
+    Ok(query.to_list_response(resources))
+}
+
+#[get("/<_org_id>/Users/<member_id>")]
+async fn get_user(_org_id: OrganizationId, member_id: MembershipId, token: ScimToken, mut conn: DbConn) -> JsonResult {
+    let (member, user) = get_member(&member_id, &token, &mut conn).await?;
+    Ok(Json(member_to_json(&member, &user)))
+}
+
+#[post("/<_org_id>/Users", data = "<data>")]
+async fn post_user(
+    _org_id: OrganizationId,
+    data: Json<ScimUserData>,
+    token: ScimToken,
+    mut conn: DbConn,
+) -> ApiResult<Created<Json<Value>>> {
+    let data = data.into_inner();
+    let Some(email) = data.email() else {
+        err_code!("An email address is required", Status::BadRequest.code)
+    };
+
+    if Membership::find_by_email_and_org(&email, &token.org_id, &mut conn).await.is_some() {
+        err_code!("User is already a member of the organization", Status::Conflict.code)
+    }
+
+    let mut user_created = false;
+    let user = match User::find_by_mail(&email, &mut conn).await {
+        Some(user) => user,
+        None => {
+            // The same policy applies as to invitations from the web vault
+            if !CONFIG.invitations_allowed() {
+                err_code!(format!("User does not exist: {email}"), Status::Forbidden.code)
+            }
+            if !CONFIG.is_email_domain_allowed(&email) {
+                err_code!("Email domain not eligible for invitations", Status::Forbidden.code)
+            }
+
+            let mut new_user = User::new(email.clone());
+            new_user.save(&mut conn).await?;
+
+            if !CONFIG.mail_enabled() {
+                Invitation::new(&new_user.email).save(&mut conn).await?;
+            }
+            user_created = true;
+            new_user
+        }
+    };
+
+    let mut new_member = Membership::new(user.uuid.clone(), token.org_id.clone());
+    new_member.set_external_id(data.external_id.clone());
+    new_member.access_all = false;
+    new_member.atype = MembershipType::User as i32;
+    new_member.status = if CONFIG.mail_enabled() || user.password_hash.is_empty() {
+        MembershipStatus::Invited as i32
+    } else {
+        MembershipStatus::Accepted as i32 // Automatically mark user as accepted if no email invites
+    };
+    if data.active == Some(false) {
+        new_member.revoke();
+    }
+    new_member.save(&mut conn).await?;
+
+    if CONFIG.mail_enabled() {
+        let (org_name, org_email) = match Organization::find_by_uuid(&token.org_id, &mut conn).await {
+            Some(org) => (org.name, org.billing_email),
+            None => err!("Error looking up organization"),
+        };
+
+        if let Err(e) =
+            mail::send_invite(&user, token.org_id.clone(), new_member.uuid.clone(), &org_name, Some(org_email)).await
+        {
+            // Upon error delete the user, invite and org member records when needed
+            if user_created {
+                user.delete(&mut conn).await?;
+            } else {
+                new_member.delete(&mut conn).await?;
+            }
+
+            err!(format!("Error sending invite: {e:?} "));
+        }
+    }
+
+    token.log_event(EventType::OrganizationUserInvited, &new_member.uuid, &mut conn).await;
+
+    let location = resource_location(&token.org_id, "Users", &new_member.uuid);
+    Ok(Created::new(location).body(Json(member_to_json(&new_member, &user))))
+}
+
+#[put("/<_org_id>/Users/<member_id>", data = "<data>")]
+async fn put_user(
+    _org_id: OrganizationId,
+    member_id: MembershipId,
+    data: Json<ScimUserData>,
+    token: ScimToken,
+    mut conn: DbConn,
+) -> JsonResult {
+    let data = data.into_inner();
+    let (mut member, user) = get_member(&member_id, &token, &mut conn).await?;
+
+    if member.set_external_id(data.external_id) {
+        member.save(&mut conn).await?;
+        token.log_event(EventType::OrganizationUserUpdated, &member.uuid, &mut conn).await;
+    }
+    if let Some(active) = data.active {
+        set_member_active(&mut member, active, &token, &mut conn).await?;
+    }
+
+    Ok(Json(member_to_json(&member, &user)))
+}
+
+#[patch("/<_org_id>/Users/<member_id>", data = "<data>")]
+async fn patch_user(
+    _org_id: OrganizationId,
+    member_id: MembershipId,
+    data: Json<ScimPatchData>,
+    token: ScimToken,
+    mut conn: DbConn,
+) -> JsonResult {
+    let (mut member, user) = get_member(&member_id, &token, &mut conn).await?;
+
+    // Validate all the operations before applying any of them
+    let mut attributes = Vec::new();
+    for operation in data.into_inner().operations {
+        attributes.extend(operation.user_attributes()?);
+    }
+
+    for (attribute, value) in attributes {
+        match attribute.as_str() {
+            "active" => {
+                let Some(active) = value_as_bool(&value) else {
+                    err_code!("Invalid value for active", Status::BadRequest.code)
+                };
+                set_member_active(&mut member, active, &token, &mut conn).await?;
+            }
+            "externalid" => {
+                if member.set_external_id(value.as_str().map(String::from)) {
+                    member.save(&mut conn).await?;
+                    token.log_event(EventType::OrganizationUserUpdated, &member.uuid, &mut conn).await;
+                }
+            }
+            // The email address and name are managed by the user itself
+            _ => {}
+        }
+    }
+
+    Ok(Json(member_to_json(&member, &user)))
+}
+
+/// Revokes or restores a member, with the same checks as when done by an admin of the organization
+async fn set_member_active(member: &mut Membership, active: bool, token: &ScimToken, conn: &mut DbConn) -> EmptyResult {
+    if active {
+        if member.status > MembershipStatus::Revoked as i32 {
+            return Ok(());
+        }
+
+        if member.atype < MembershipType::Admin {
+            match OrgPolicy::is_user_allowed(&member.user_uuid, &token.org_id, false, conn).await {
+                Ok(_) => {}
+                Err(OrgPolicyErr::TwoFactorMissing) => {
+                    if CONFIG.email_2fa_auto_fallback() {
+                        two_factor::email::find_and_activate_email_2fa(&member.user_uuid, conn).await?;
+                    } else {
+                        err!("You cannot restore this user because they have not setup 2FA");
+                    }
+                }
+                Err(OrgPolicyErr::SingleOrgEnforced) => {
+                    err!("You cannot restore this user because they are a member of an organization which forbids it");
+                }
+            }
+        }
+
+        member.restore();
+        member.save(conn).await?;
+        token.log_event(EventType::OrganizationUserRestored, &member.uuid, conn).await;
+    } else {
+        if member.status <= MembershipStatus::Revoked as i32 {
+            return Ok(());
+        }
+
+        if member.atype == MembershipType::Owner
+            && Membership::count_confirmed_by_org_and_type(&token.org_id, MembershipType::Owner, conn).await <= 1
+        {
+            err!("Organization must have at least one confirmed owner")
+        }
+
+        member.revoke();
+        member.save(conn).await?;
+        token.log_event(EventType::OrganizationUserRevoked, &member.uuid, conn).await;
+    }
+    Ok(())
+}
+
+#[delete("/<_org_id>/Users/<member_id>")]
+async fn delete_user(
+    _org_id: OrganizationId,
+    member_id: MembershipId,
+    token: ScimToken,
+    mut conn: DbConn,
+    nt: Notify<'_>,
+) -> ApiResult<NoContent> {
+    let (member, user) = get_member(&member_id, &token, &mut conn).await?;
+
+    if member.atype == MembershipType::Owner && member.status == MembershipStatus::Confirmed as i32 {
+        // Removing owner, check that there is at least one other confirmed owner
+        if Membership::count_confirmed_by_org_and_type(&token.org_id, MembershipType::Owner, &mut conn).await <= 1 {
+            err!("Can't delete the last owner")
+        }
+    }
+
+    token.log_event(EventType::OrganizationUserRemoved, &member.uuid, &mut conn).await;
+    nt.send_user_update(UpdateType::SyncOrgKeys, &user).await;
+
+    member.delete(&mut conn).await?;
+    Ok(NoContent)
+}
+
+//
+// Groups
+//
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScimGroupData {
+    display_name: String,
+    external_id: Option<String>,
+    members: Option<Vec<ScimMember>>,
+}
+
+#[derive(Deserialize)]
+struct ScimMember {
+    value: MembershipId,
+}
+
+async fn group_to_json(group: &Group, conn: &mut DbConn) -> Value {
+    use crate::util::format_date;
+
+    let members: Vec<Value> = GroupUser::find_by_group(&group.uuid, conn)
+        .await
+        .into_iter()
+        .map(|gu| {
+            json!({
+                "value": gu.users_organizations_uuid,
+                "$ref": resource_location(&group.organizations_uuid, "Users", &gu.users_organizations_uuid),
+            })
+        })
+        .collect();
+
+    json!({
+        "schemas": [SCHEMA_GROUP],
+        "id": group.uuid,
+        "externalId": group.external_id,
+        "displayName": group.name,
+        "members": members,
+        "meta": {
+            "resourceType": "Group",
+            "created": format_date(&group.creation_date),
+            "lastModified": format_date(&group.revision_date),
+            "location": resource_location(&group.organizations_uuid, "Groups", &group.uuid),
+        },
+    })
+}
+
+async fn get_org_group(group_id: &GroupId, token: &ScimToken, conn: &mut DbConn) -> ApiResult<Group> {
+    if !CONFIG.org_groups_enabled() {
+        err!("Group support is disabled");
+    }
+
+    match Group::find_by_uuid_and_org(group_id, &token.org_id, conn).await {
+        Some(group) => Ok(group),
+        None => err_code!("Group not found", Status::NotFound.code),
+    }
+}
+
+/// Adds a member to a group, members which are not part of the organization are ignored
+async fn add_group_member(
+    group: &Group,
+    member_id: &MembershipId,
+    token: &ScimToken,
+    conn: &mut DbConn,
+) -> EmptyResult {
+    if Membership::find_by_uuid_and_org(member_id, &token.org_id, conn).await.is_none() {
+        warn!("SCIM group member {member_id} is not part of the organization");
+        return Ok(());
+    }
+
+    let mut group_user = GroupUser::new(group.uuid.clone(), member_id.clone());
+    group_user.save(conn).await?;
+    token.log_event(EventType::OrganizationUserUpdatedGroups, member_id, conn).await;
+    Ok(())
+}
+
+async fn remove_group_member(
+    group: &Group,
+    member_id: &MembershipId,
+    token: &ScimToken,
+    conn: &mut DbConn,
+) -> EmptyResult {
+    GroupUser::delete_by_group_and_member(&group.uuid, member_id, conn).await?;
+    token.log_event(EventType::OrganizationUserUpdatedGroups, member_id, conn).await;
+    Ok(())
+}
+
+/// Only the members which are added or removed are changed, and get an event
+async fn replace_group_members(
+    group: &Group,
+    members: Vec<MembershipId>,
+    token: &ScimToken,
+    conn: &mut DbConn,
+) -> EmptyResult {
+    let mut current: Vec<MembershipId> =
+        GroupUser::find_by_group(&group.uuid, conn).await.into_iter().map(|gu| gu.users_organizations_uuid).collect();
+
+    for member_id in current.iter().filter(|id| !members.contains(id)) {
+        remove_group_member(group, member_id, token, conn).await?;
+    }
+
+    for member_id in members {
+        if !current.contains(&member_id) {
+            add_group_member(group, &member_id, token, conn).await?;
+            current.push(member_id);
+        }
+    }
+    Ok(())
+}
+
+#[get("/<_org_id>/Groups?<query..>")]
+async fn get_groups(_org_id: OrganizationId, query: ListQuery, token: ScimToken, mut conn: DbConn) -> JsonResult {
+    if !CONFIG.org_groups_enabled() {
+        err!("Group support is disabled");
+    }
+
+    let filter = query.filter(GROUP_FILTER_ATTRIBUTES)?;
+
+    let mut resources = Vec::new();
+    for group in Group::find_by_organization(&token.org_id, &mut conn).await {
+        let matches = match filter {
+            None => true,
+            Some((ref attribute, ref value)) => match attribute.as_str() {
+                "displayname" => group.name == *value,
+                "externalid" => group.external_id.as_deref() == Some(value.as_str()),
+                "id" => *group.uuid == *value,
+                _ => false,
+            },
+        };
+
+        if matches {
+            resources.push(group_to_json(&group, &mut conn).await);
+        }
+    }
+
+    Ok(query.to_list_response(resources))
+}
+
+#[get("/<_org_id>/Groups/<group_id>")]
+async fn get_group(_org_id: OrganizationId, group_id: GroupId, token: ScimToken, mut conn: DbConn) -> JsonResult {
+    let group = get_org_group(&group_id, &token, &mut conn).await?;
+    Ok(Json(group_to_json(&group, &mut conn).await))
+}
+
+#[post("/<_org_id>/Groups", data = "<data>")]
+async fn post_group(
+    _org_id: OrganizationId,
+    data: Json<ScimGroupData>,
+    token: ScimToken,
+    mut conn: DbConn,
+) -> ApiResult<Created<Json<Value>>> {
+    if !CONFIG.org_groups_enabled() {
+        err!("Group support is disabled");
+    }
+
+    let data = data.into_inner();
+    if let Some(ref external_id) = data.external_id {
+        if Group::find_by_external_id_and_org(external_id, &token.org_id, &mut conn).await.is_some() {
+            err_code!("A group with this externalId already exists", Status::Conflict.code)
+        }
+    }
+
+    let mut group = Group::new(token.org_id.clone(), data.display_name, false, data.external_id);
+    group.save(&mut conn).await?;
+    token.log_event(EventType::GroupCreated, &group.uuid, &mut conn).await;
+
+    for member in data.members.unwrap_or_default() {
+        add_group_member(&group, &member.value, &token, &mut conn).await?;
+    }
+
+    let location = resource_location(&token.org_id, "Groups", &group.uuid);
+    Ok(Created::new(location).body(Json(group_to_json(&group, &mut conn).await)))
+}
+
+#[put("/<_org_id>/Groups/<group_id>", data = "<data>")]
+async fn put_group(
+    _org_id: OrganizationId,
+    group_id: GroupId,
+    data: Json<ScimGroupData>,
+    token: ScimToken,
+    mut conn: DbConn,
+) -> JsonResult {
+    let data = data.into_inner();
+    let mut group = get_org_group(&group_id, &token, &mut conn).await?;
+
+    group.name = data.display_name;
+    group.set_external_id(data.external_id);
+    group.save(&mut conn).await?;
+    token.log_event(EventType::GroupUpdated, &group.uuid, &mut conn).await;
+
+    if let Some(members) = data.members {
+        replace_group_members(&group, members.into_iter().map(|m| m.value).collect(), &token, &mut conn).await?;
+    }
+
+    Ok(Json(group_to_json(&group, &mut conn).await))
+}
+
+#[patch("/<_org_id>/Groups/<group_id>", data = "<data>")]
+async fn patch_group(
+    _org_id: OrganizationId,
+    group_id: GroupId,
+    data: Json<ScimPatchData>,
+    token: ScimToken,
+    mut conn: DbConn,
+) -> JsonResult {
+    let mut group = get_org_group(&group_id, &token, &mut conn).await?;
+    let mut group_updated = false;
+
+    // Validate all the operations before applying any of them
+    let mut changes = Vec::new();
+    for operation in data.into_inner().operations {
+        changes.extend(operation.group_changes()?);
+    }
+
+    for change in changes {
+        match change {
+            GroupChange::AddMembers(members) => {
+                for member_id in members {
+                    add_group_member(&group, &member_id, &token, &mut conn).await?;
+                }
+            }
+            GroupChange::RemoveMembers(members) => {
+                for member_id in members {
+                    remove_group_member(&group, &member_id, &token, &mut conn).await?;
+                }
+            }
+            GroupChange::ReplaceMembers(members) => replace_group_members(&group, members, &token, &mut conn).await?,
+            GroupChange::DisplayName(name) => {
+                group.name = name;
+                group_updated = true;
+            }
+            GroupChange::ExternalId(external_id) => {
+                group.set_external_id(external_id);
+                group_updated = true;
+            }
+        }
+    }
+
+    if group_updated {
+        group.save(&mut conn).await?;
+        token.log_event(EventType::GroupUpdated, &group.uuid, &mut conn).await;
+    }
+
+    Ok(Json(group_to_json(&group, &mut conn).await))
+}
+
+#[delete("/<_org_id>/Groups/<group_id>")]
+async fn delete_group(
+    _org_id: OrganizationId,
+    group_id: GroupId,
+    token: ScimToken,
+    mut conn: DbConn,
+) -> ApiResult<NoContent> {
+    let group = get_org_group(&group_id, &token, &mut conn).await?;
+
+    token.log_event(EventType::GroupDeleted, &group.uuid, &mut conn).await;
+    group.delete(&mut conn).await?;
+    Ok(NoContent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(filter: &str) -> ListQuery {
+        ListQuery {
+            filter: Some(filter.to_string()),
+            start_index: None,
+            count: None,
+        }
+    }
+
+    fn operation(value: Value) -> ScimPatchOperation {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_filter() {
+        assert_eq!(
+            query(r#"userName eq "User@Example.com""#).filter(USER_FILTER_ATTRIBUTES).unwrap(),
+            Some((String::from("username"), String::from("User@Example.com")))
+        );
+        assert_eq!(
+            query(r#"displayName EQ "Quoted \"name\"""#).filter(GROUP_FILTER_ATTRIBUTES).unwrap(),
+            Some((String::from("displayname"), String::from(r#"Quoted "name""#)))
+        );
+        let no_filter = ListQuery {
+            filter: None,
+            start_index: None,
+            count: None,
+        };
+        assert_eq!(no_filter.filter(USER_FILTER_ATTRIBUTES).unwrap(), None);
+    }
+
+    #[test]
+    fn test_filter_rejects_unsupported() {
+        // Rejected before any resource is compared, so also for an empty organization
+        assert!(query(r#"displayName eq "Group""#).filter(USER_FILTER_ATTRIBUTES).is_err());
+        assert!(query(r#"userName eq "user@example.com""#).filter(GROUP_FILTER_ATTRIBUTES).is_err());
+        assert!(query(r#"userName co "example""#).filter(USER_FILTER_ATTRIBUTES).is_err());
+        assert!(query("userName").filter(USER_FILTER_ATTRIBUTES).is_err());
+    }
+
+    #[test]
+    fn test_list_response_paging() {
+        let response = query("").to_list_response((0..5).map(|i| json!(i)).collect());
+        assert_eq!(response["totalResults"], 5);
+        assert_eq!(response["itemsPerPage"], 5);
+
+        let paged = ListQuery {
+            filter: None,
+            start_index: Some(2),
+            count: Some(2),
+        };
+        let response = paged.to_list_response((0..5).map(|i| json!(i)).collect());
+        assert_eq!(response["totalResults"], 5);
+        assert_eq!(response["Resources"], json!([1, 2]));
+    }
+
+    #[test]
+    fn test_user_patch() {
+        let attributes =
+            operation(json!({"op": "Replace", "path": "active", "value": "False"})).user_attributes().unwrap();
+        assert_eq!(attributes, vec![(String::from("active"), json!("False"))]);
+
+        let attributes = operation(json!({"op": "add", "value": {"externalId": "ext-1", "active": true}}))
+            .user_attributes()
+            .unwrap();
+        assert!(attributes.contains(&(String::from("externalid"), json!("ext-1"))));
+        assert!(attributes.contains(&(String::from("active"), json!(true))));
+    }
+
+    #[test]
+    fn test_user_patch_rejects_invalid_operations() {
+        assert!(operation(json!({"op": "remove", "path": "externalId"})).user_attributes().is_err());
+        assert!(operation(json!({"op": "move", "path": "active", "value": true})).user_attributes().is_err());
+        assert!(operation(json!({"op": "replace", "path": "active"})).user_attributes().is_err());
+        assert!(operation(json!({"op": "replace", "value": true})).user_attributes().is_err());
+    }
+
+    #[test]
+    fn test_group_patch() {
+        let changes = operation(json!({"op": "add", "path": "members", "value": [{"value": "m1"}, {"value": "m2"}]}))
+            .group_changes()
+            .unwrap();
+        assert_eq!(changes, vec![GroupChange::AddMembers(vec![String::from("m1").into(), String::from("m2").into()])]);
+
+        let changes = operation(json!({"op": "Remove", "path": r#"members[value eq "m1"]"#})).group_changes().unwrap();
+        assert_eq!(changes, vec![GroupChange::RemoveMembers(vec![String::from("m1").into()])]);
+
+        let changes = operation(json!({"op": "replace", "value": {"displayName": "Group", "externalId": "ext-1"}}))
+            .group_changes()
+            .unwrap();
+        assert!(changes.contains(&GroupChange::DisplayName(String::from("Group"))));
+        assert!(changes.contains(&GroupChange::ExternalId(Some(String::from("ext-1")))));
+
+        assert!(operation(json!({"op": "add", "path": "displayName", "value": "Group"})).group_changes().is_err());
+        assert!(operation(json!({"op": "copy", "path": "members", "value": []})).group_changes().is_err());
+    }
+}
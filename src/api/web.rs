@@ -14,6 +14,7 @@ use crate::{
     auth::decode_file_download,
     db::models::{AttachmentId, CipherId},
     error::Error,
+    storage::{storage, PathType, StorageFile},
     util::Cached,
     CONFIG,
 };
@@ -160,7 +161,7 @@ async fn web_files(p: PathBuf) -> Cached<Option<NamedFile>> {
 }
 
 #[get("/attachments/<cipher_id>/<file_id>?<token>")]
-async fn attachments(cipher_id: CipherId, file_id: AttachmentId, token: String) -> Option<NamedFile> {
+async fn attachments(cipher_id: CipherId, file_id: AttachmentId, token: String) -> Option<StorageFile> {
     let Ok(claims) = decode_file_download(&token) else {
         return None;
     };
@@ -168,7 +169,7 @@ async fn attachments(cipher_id: CipherId, file_id: AttachmentId, token: String)
         return None;
     }
 
-    NamedFile::open(Path::new(&CONFIG.attachments_folder()).join(cipher_id.as_ref()).join(file_id.as_ref())).await.ok()
+    storage().download(PathType::Attachments, &format!("{cipher_id}/{file_id}")).await
 }
 
 // We use DbConn here to let the alive healthcheck also verify the database connection.
@@ -223,12 +224,17 @@ pub fn static_files(filename: &str) -> Result<(ContentType, &'static [u8]), Erro
         "admin.js" => Ok((ContentType::JavaScript, include_bytes!("../static/scripts/admin.js"))),
         "admin_settings.js" => Ok((ContentType::JavaScript, include_bytes!("../static/scripts/admin_settings.js"))),
         "admin_users.js" => Ok((ContentType::JavaScript, include_bytes!("../static/scripts/admin_users.js"))),
+        "admin_user_details.js" => {
+            Ok((ContentType::JavaScript, include_bytes!("../static/scripts/admin_user_details.js")))
+        }
         "admin_organizations.js" => {
             Ok((ContentType::JavaScript, include_bytes!("../static/scripts/admin_organizations.js")))
         }
         "admin_diagnostics.js" => {
             Ok((ContentType::JavaScript, include_bytes!("../static/scripts/admin_diagnostics.js")))
         }
+        "admin_accounts.js" => Ok((ContentType::JavaScript, include_bytes!("../static/scripts/admin_accounts.js"))),
+        "admin_api_tokens.js" => Ok((ContentType::JavaScript, include_bytes!("../static/scripts/admin_api_tokens.js"))),
         "bootstrap.css" => Ok((ContentType::CSS, include_bytes!("../static/scripts/bootstrap.css"))),
         "bootstrap.bundle.js" => Ok((ContentType::JavaScript, include_bytes!("../static/scripts/bootstrap.bundle.js"))),
         "jdenticon-3.3.0.js" => Ok((ContentType::JavaScript, include_bytes!("../static/scripts/jdenticon-3.3.0.js"))),
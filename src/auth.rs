@@ -36,6 +36,9 @@ static JWT_SEND_ISSUER: Lazy<String> = Lazy::new(|| format!("{}|send", CONFIG.do
 static JWT_ORG_API_KEY_ISSUER: Lazy<String> = Lazy::new(|| format!("{}|api.organization", CONFIG.domain_origin()));
 static JWT_FILE_DOWNLOAD_ISSUER: Lazy<String> = Lazy::new(|| format!("{}|file_download", CONFIG.domain_origin()));
 static JWT_REGISTER_VERIFY_ISSUER: Lazy<String> = Lazy::new(|| format!("{}|register_verify", CONFIG.domain_origin()));
+static JWT_WEBAUTHN_REGISTER_ISSUER: Lazy<String> =
+    Lazy::new(|| format!("{}|webauthn_register", CONFIG.domain_origin()));
+static JWT_WEBAUTHN_LOGIN_ISSUER: Lazy<String> = Lazy::new(|| format!("{}|webauthn_login", CONFIG.domain_origin()));
 
 static PRIVATE_RSA_KEY: OnceCell<EncodingKey> = OnceCell::new();
 static PUBLIC_RSA_KEY: OnceCell<DecodingKey> = OnceCell::new();
@@ -150,6 +153,14 @@ pub fn decode_register_verify(token: &str) -> Result<RegisterVerifyClaims, Error
     decode_jwt(token, JWT_REGISTER_VERIFY_ISSUER.to_string())
 }
 
+pub fn decode_webauthn_register(token: &str) -> Result<WebauthnStateClaims, Error> {
+    decode_jwt(token, JWT_WEBAUTHN_REGISTER_ISSUER.to_string())
+}
+
+pub fn decode_webauthn_login(token: &str) -> Result<WebauthnStateClaims, Error> {
+    decode_jwt(token, JWT_WEBAUTHN_LOGIN_ISSUER.to_string())
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginJwtClaims {
     // Not before
@@ -347,6 +358,44 @@ pub fn generate_register_verify_claims(email: String, name: Option<String>, veri
     }
 }
 
+// Holds the state of a passkey ceremony between the options request and the response of the authenticator.
+// The state is signed, not encrypted, it only contains the challenge and the public credentials.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebauthnStateClaims {
+    // Not before
+    pub nbf: i64,
+    // Expiration time
+    pub exp: i64,
+    // Issuer
+    pub iss: String,
+    // Subject, the user registering or using a passkey, empty for a login
+    pub sub: String,
+
+    pub state: String,
+}
+
+pub fn generate_webauthn_register_claims(user_id: &UserId, state: String) -> WebauthnStateClaims {
+    let time_now = Utc::now();
+    WebauthnStateClaims {
+        nbf: time_now.timestamp(),
+        exp: (time_now + TimeDelta::try_minutes(10).unwrap()).timestamp(),
+        iss: JWT_WEBAUTHN_REGISTER_ISSUER.to_string(),
+        sub: user_id.to_string(),
+        state,
+    }
+}
+
+pub fn generate_webauthn_login_claims(sub: String, state: String) -> WebauthnStateClaims {
+    let time_now = Utc::now();
+    WebauthnStateClaims {
+        nbf: time_now.timestamp(),
+        exp: (time_now + TimeDelta::try_minutes(5).unwrap()).timestamp(),
+        iss: JWT_WEBAUTHN_LOGIN_ISSUER.to_string(),
+        sub,
+        state,
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BasicJwtClaims {
     // Not before
@@ -381,13 +430,13 @@ pub fn generate_verify_email_claims(user_id: UserId) -> BasicJwtClaims {
     }
 }
 
-pub fn generate_admin_claims() -> BasicJwtClaims {
+pub fn generate_admin_claims(sub: String) -> BasicJwtClaims {
     let time_now = Utc::now();
     BasicJwtClaims {
         nbf: time_now.timestamp(),
         exp: (time_now + TimeDelta::try_minutes(CONFIG.admin_session_lifetime()).unwrap()).timestamp(),
         iss: JWT_ADMIN_ISSUER.to_string(),
-        sub: "admin_panel".to_string(),
+        sub,
     }
 }
 
@@ -560,6 +609,8 @@ impl<'r> FromRequest<'r> for Headers {
             }
         }
 
+        request.local_cache(|| crate::util::RequestUser(user.uuid.to_string()));
+
         Outcome::Success(Headers {
             host,
             device,
@@ -0,0 +1,944 @@
+//
+// Full server backups, usable with every database backend
+//
+// A backup is an uncompressed tar archive containing:
+// - `manifest.json`, with the version of the archive layout and the number of rows of every table
+// - `db/<table>.jsonl`, a backend neutral dump of every table, with one JSON object per row
+// - `attachments/...` and `sends/...`, the files stored by the storage backend
+// - `rsa_key.pem` and `config.json`
+//
+// When a public key is configured the archive is encrypted with AES-256-GCM, using a random key
+// which is stored encrypted with the RSA public key in front of the encrypted archive.
+//
+// The archive is written and read as a stream, neither the database nor any of the files is loaded into memory.
+//
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use once_cell::sync::Lazy;
+use openssl::{
+    pkey::{Private, Public},
+    rsa::{Padding, Rsa},
+    symm::{Cipher, Crypter, Mode},
+};
+use serde_json::Value;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter},
+    sync::Mutex,
+};
+
+use crate::{
+    api::EmptyResult,
+    config::CONFIG_FILE,
+    crypto,
+    db::{dump, DbConn, DbConnType, DbPool},
+    error::Error,
+    storage::{storage, PathType},
+    util::get_display_size,
+    CONFIG, VERSION,
+};
+
+/// Version of the layout of the archive, needs to be increased on incompatible changes
+const BACKUP_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const RSA_KEY_ENTRY: &str = "rsa_key.pem";
+const CONFIG_ENTRY: &str = "config.json";
+
+const ENCRYPTED_MAGIC: &[u8; 8] = b"VWBKENC1";
+const KEY_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+
+const FILE_PREFIX: &str = "backup_";
+const FILE_DATE_FORMAT: &str = "%Y%m%d_%H%M%S";
+const PARTIAL_SUFFIX: &str = ".partial";
+const FAILED_SUFFIX: &str = ".failed";
+const STAGING_PREFIX: &str = ".restore_";
+const EXPORT_PREFIX: &str = ".backup_";
+const RESTORE_SUFFIX: &str = ".restore";
+
+// Size of the chunks in which the entries of an archive are extracted
+const CHUNK_SIZE: usize = 64 * 1024;
+// The manifest is read into memory, so it is limited to a sane size
+const MAX_MANIFEST_SIZE: u64 = 1024 * 1024;
+
+// Prevents a scheduled backup from starting while the previous one is still running
+static BACKUP_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    format_version: u32,
+    vaultwarden_version: Option<String>,
+    database: String,
+    created_at: NaiveDateTime,
+    row_counts: HashMap<String, usize>,
+}
+
+/// Returns a new file name for a backup, based on the current time
+pub fn backup_file_name() -> String {
+    let extension = if CONFIG.backup_public_key().is_some() {
+        "tar.enc"
+    } else {
+        "tar"
+    };
+    format!("{FILE_PREFIX}{}.{extension}", Utc::now().format(FILE_DATE_FORMAT))
+}
+
+/// Creates a backup at `path`, which must not exist yet.
+/// The backup is encrypted when `BACKUP_PUBLIC_KEY` is configured.
+pub async fn create_backup(path: &Path, conn: &mut DbConn) -> EmptyResult {
+    let res = write_backup(path, conn).await;
+    if res.is_err() {
+        // Don't leave an incomplete backup behind
+        tokio::fs::remove_file(path).await.ok();
+    }
+    res
+}
+
+async fn write_backup(path: &Path, conn: &mut DbConn) -> EmptyResult {
+    let public_key = match CONFIG.backup_public_key() {
+        Some(key_file) => Some(Rsa::public_key_from_pem(&tokio::fs::read(key_file).await?)?),
+        None => None,
+    };
+
+    // The tables are exported into a staging folder first, the manifest needs the number of rows of every table
+    let export = dump::dump_folder(EXPORT_PREFIX);
+    dump::create_dump_folder(&export).await?;
+    let res = write_archive(path, public_key.as_ref(), &export, conn).await;
+    dump::remove_dump_folder(&export).await;
+    res
+}
+
+async fn write_archive(path: &Path, public_key: Option<&Rsa<Public>>, export: &Path, conn: &mut DbConn) -> EmptyResult {
+    let row_counts = dump::export_tables(export, conn).await?;
+
+    let mut archive = ArchiveWriter::create(path, public_key).await?;
+    let manifest = Manifest {
+        format_version: BACKUP_FORMAT_VERSION,
+        vaultwarden_version: VERSION.map(String::from),
+        database: database_type()?.to_string(),
+        created_at: Utc::now().naive_utc(),
+        row_counts,
+    };
+    archive.add(MANIFEST_ENTRY, &serde_json::to_vec_pretty(&manifest)?).await?;
+
+    for table in dump::TABLES {
+        archive
+            .add_file(&format!("db/{table}.jsonl"), File::open(export.join(format!("{table}.jsonl"))).await?)
+            .await?;
+    }
+
+    for table in ["attachments", "sends"] {
+        let mut rows = BufReader::new(File::open(export.join(format!("{table}.jsonl"))).await?).lines();
+        while let Some(row) = rows.next_line().await? {
+            let Some((path_type, file_path)) = stored_file(table, &serde_json::from_str(&row)?) else {
+                continue;
+            };
+            let name = format!("{}/{file_path}", path_type.prefix());
+            match storage().size(path_type, &file_path).await {
+                Ok(size) => {
+                    archive.start_entry(&name, size).await?;
+                    storage().copy_to(path_type, &file_path, &mut archive).await?;
+                    archive.end_entry(size).await?;
+                }
+                Err(e) => warn!("Unable to add {name} to the backup: {e:?}"),
+            }
+        }
+    }
+
+    match File::open(CONFIG.private_rsa_key()).await {
+        Ok(file) => archive.add_file(RSA_KEY_ENTRY, file).await?,
+        Err(e) => warn!("Unable to add the RSA key to the backup: {e:?}"),
+    }
+
+    if let Ok(file) = File::open(&*CONFIG_FILE).await {
+        archive.add_file(CONFIG_ENTRY, file).await?;
+    }
+
+    archive.finish().await
+}
+
+/// Restores a backup into the configured database, which needs to be empty.
+/// The database backend can be different from the one the backup was created with.
+/// The PEM encoded RSA `private_key` is only needed for encrypted backups.
+///
+/// The archive is extracted into a staging folder and authenticated first, then the files are stored.
+/// The database is imported last, when that fails the stored files are removed again.
+pub async fn restore_backup(path: &Path, private_key: Option<&Path>, conn: &mut DbConn) -> EmptyResult {
+    if !dump::is_empty(conn).await? {
+        err!("The database is not empty, a backup can only be restored into a new database")
+    }
+
+    let private_key = match private_key {
+        Some(key_file) => Some(Rsa::private_key_from_pem(&tokio::fs::read(key_file).await?)?),
+        None => None,
+    };
+
+    let staging = dump::dump_folder(STAGING_PREFIX);
+    let res = restore_staged_backup(path, private_key.as_ref(), &staging, conn).await;
+    dump::remove_dump_folder(&staging).await;
+    res
+}
+
+async fn restore_staged_backup(
+    path: &Path,
+    private_key: Option<&Rsa<Private>>,
+    staging: &Path,
+    conn: &mut DbConn,
+) -> EmptyResult {
+    let staged = extract_archive(path, private_key, staging).await?;
+    info!("Restoring a backup of a {} database created at {}", staged.manifest.database, staged.manifest.created_at);
+
+    // Everything is put into place before the database is imported, so a failed import can be undone.
+    // Only the RSA key and the config are moved over the current ones after the import was committed.
+    let mut stored = Vec::new();
+    let mut renames = Vec::new();
+    let res: EmptyResult = async {
+        for (path_type, file_path) in &staged.files {
+            stored.push((*path_type, file_path.as_str()));
+            storage().write_file(*path_type, file_path, &staging.join(path_type.prefix()).join(file_path)).await?;
+        }
+
+        let mut entries = Vec::new();
+        if staged.rsa_key {
+            entries.push((RSA_KEY_ENTRY, PathBuf::from(CONFIG.private_rsa_key())));
+        }
+        if staged.config {
+            entries.push((CONFIG_ENTRY, PathBuf::from(&*CONFIG_FILE)));
+        }
+        for (entry, target) in entries {
+            let temp_path = PathBuf::from(format!("{}{RESTORE_SUFFIX}", target.display()));
+            renames.push((temp_path.clone(), target));
+            tokio::fs::copy(staging.join(entry), temp_path).await?;
+        }
+
+        dump::import_tables(&staging.join("db"), &staged.manifest.row_counts, conn).await
+    }
+    .await;
+
+    if let Err(e) = res {
+        for (path_type, file_path) in stored {
+            if let Err(e) = storage().delete(path_type, file_path).await {
+                warn!("Unable to remove {}/{file_path} after the failed restore: {e:?}", path_type.prefix());
+            }
+        }
+        for (temp_path, _) in renames {
+            tokio::fs::remove_file(temp_path).await.ok();
+        }
+        return Err(e);
+    }
+
+    for (temp_path, target) in renames {
+        tokio::fs::rename(temp_path, target).await?;
+    }
+    Ok(())
+}
+
+/// The entries of a backup which was extracted into a staging folder
+struct StagedBackup {
+    manifest: Manifest,
+    files: Vec<(PathType, String)>,
+    rsa_key: bool,
+    config: bool,
+}
+
+/// Extracts the archive into `staging`, and verifies the authentication tag of an encrypted archive.
+/// Nothing outside of `staging` is written, so a corrupted or modified archive can't affect the server.
+async fn extract_archive(
+    path: &Path,
+    private_key: Option<&Rsa<Private>>,
+    staging: &Path,
+) -> Result<StagedBackup, Error> {
+    let mut archive = ArchiveReader::open(path, private_key).await?;
+
+    let manifest: Manifest = match archive.next_entry().await? {
+        Some((name, size)) if name == MANIFEST_ENTRY && size <= MAX_MANIFEST_SIZE => {
+            serde_json::from_slice(&archive.read_entry(size).await?)?
+        }
+        _ => err!("The file is not a Vaultwarden backup"),
+    };
+    if manifest.format_version != BACKUP_FORMAT_VERSION {
+        err!(format!("Unsupported backup format version {}", manifest.format_version))
+    }
+    if manifest.vaultwarden_version.as_deref() != VERSION {
+        warn!(
+            "The backup was created by Vaultwarden {}, restoring it with a different version might fail",
+            manifest.vaultwarden_version.as_deref().unwrap_or("(unknown version)")
+        );
+    }
+
+    // The staging folder will contain the RSA key and other secrets
+    dump::create_dump_folder(staging).await?;
+
+    let mut staged = StagedBackup {
+        manifest,
+        files: Vec::new(),
+        rsa_key: false,
+        config: false,
+    };
+    while let Some((name, size)) = archive.next_entry().await? {
+        if name.split('/').any(|c| c.is_empty() || c == "." || c == "..") {
+            err!(format!("Invalid backup entry {name}"))
+        }
+
+        if let Some(table) = name.strip_prefix("db/").and_then(|n| n.strip_suffix(".jsonl")) {
+            if !dump::TABLES.contains(&table) {
+                warn!("Ignoring the unknown table {table}");
+            }
+        } else if let Some(file_path) = name.strip_prefix("attachments/") {
+            staged.files.push((PathType::Attachments, file_path.to_string()));
+        } else if let Some(file_path) = name.strip_prefix("sends/") {
+            staged.files.push((PathType::Sends, file_path.to_string()));
+        } else if name == RSA_KEY_ENTRY {
+            staged.rsa_key = true;
+        } else if name == CONFIG_ENTRY {
+            staged.config = true;
+        } else {
+            warn!("Ignoring unknown backup entry {name}");
+            archive.copy_entry(size, &mut tokio::io::sink()).await?;
+            continue;
+        }
+
+        let entry_path = staging.join(&name);
+        if let Some(parent) = entry_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = BufWriter::new(File::create(entry_path).await?);
+        archive.copy_entry(size, &mut file).await?;
+        file.flush().await?;
+    }
+
+    // Verify the integrity of an encrypted backup before anything is restored
+    archive.finish().await?;
+    Ok(staged)
+}
+
+/// Creates a backup in the backup folder and removes the backups which are no longer retained.
+/// A failed backup leaves a small `.failed` file with the error behind, to list it on the diagnostics page.
+pub async fn backup_job(pool: DbPool) {
+    debug!("Creating scheduled backup");
+    let Ok(_lock) = BACKUP_LOCK.try_lock() else {
+        warn!("Skipping the scheduled backup, the previous one is still running");
+        return;
+    };
+
+    let folder = PathBuf::from(CONFIG.backup_folder());
+    let path = folder.join(backup_file_name());
+
+    let res: EmptyResult = async {
+        let mut conn = pool.get().await?;
+        write_folder_backup(&folder, &path, &mut conn).await
+    }
+    .await;
+
+    match res {
+        Ok(()) => info!("Scheduled backup to '{}' was successful", path.display()),
+        Err(e) => {
+            error!("Scheduled backup failed. {e:?}");
+            let failed_path = format!("{}{FAILED_SUFFIX}", path.display());
+            if let Err(e) = tokio::fs::write(failed_path, e.to_string()).await {
+                error!("Unable to record the failed backup. {e:?}");
+            }
+        }
+    }
+
+    if let Err(e) = remove_old_backups(&folder).await {
+        error!("Error removing old backups. {e:?}");
+    }
+}
+
+/// Creates a backup in the backup folder right away, like the scheduled backups do.
+/// Returns the file name of the backup.
+pub async fn create_folder_backup(conn: &mut DbConn) -> Result<String, Error> {
+    let Ok(_lock) = BACKUP_LOCK.try_lock() else {
+        err!("Another backup is still running")
+    };
+
+    let folder = PathBuf::from(CONFIG.backup_folder());
+    let file_name = backup_file_name();
+    write_folder_backup(&folder, &folder.join(&file_name), conn).await?;
+    Ok(file_name)
+}
+
+/// Writes the backup to a partial file first, so an incomplete backup is never listed as complete
+async fn write_folder_backup(folder: &Path, path: &Path, conn: &mut DbConn) -> EmptyResult {
+    let partial_path = PathBuf::from(format!("{}{PARTIAL_SUFFIX}", path.display()));
+    tokio::fs::create_dir_all(folder).await?;
+    create_backup(&partial_path, conn).await?;
+    tokio::fs::rename(&partial_path, path).await?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum BackupStatus {
+    Complete,
+    Running,
+    Failed,
+}
+
+#[derive(Serialize)]
+pub struct BackupEntry {
+    name: String,
+    created_at: NaiveDateTime,
+    size: String,
+    encrypted: bool,
+    status: BackupStatus,
+    error: Option<String>,
+}
+
+/// Lists the backups in the backup folder, newest first.
+/// Files which don't follow the naming of the scheduled backups are ignored.
+pub async fn list_backups() -> Result<Vec<BackupEntry>, Error> {
+    let mut backups = Vec::new();
+
+    let mut entries = match tokio::fs::read_dir(CONFIG.backup_folder()).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(backups),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(created_at) = name
+            .strip_prefix(FILE_PREFIX)
+            .and_then(|n| n.get(..15))
+            .and_then(|d| NaiveDateTime::parse_from_str(d, FILE_DATE_FORMAT).ok())
+        else {
+            continue;
+        };
+
+        let (status, error) = if name.ends_with(PARTIAL_SUFFIX) {
+            (BackupStatus::Running, None)
+        } else if name.ends_with(FAILED_SUFFIX) {
+            (BackupStatus::Failed, tokio::fs::read_to_string(entry.path()).await.ok())
+        } else if name.ends_with(".tar") || name.ends_with(".tar.enc") {
+            (BackupStatus::Complete, None)
+        } else {
+            continue;
+        };
+
+        backups.push(BackupEntry {
+            encrypted: name.contains(".tar.enc"),
+            size: get_display_size(entry.metadata().await?.len() as i64),
+            name,
+            created_at,
+            status,
+            error,
+        });
+    }
+
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(backups)
+}
+
+/// Removes the backups which exceed `BACKUP_RETAIN_COUNT` or are older than `BACKUP_RETAIN_DAYS`.
+/// Failed backups are kept as long as they are newer than the oldest retained backup.
+async fn remove_old_backups(folder: &Path) -> EmptyResult {
+    let retain_count = CONFIG.backup_retain_count() as usize;
+    let min_date = CONFIG
+        .backup_retain_days()
+        .and_then(TimeDelta::try_days)
+        .and_then(|days| Utc::now().naive_utc().checked_sub_signed(days));
+
+    let mut complete = 0;
+    for backup in list_backups().await? {
+        let expired = min_date.is_some_and(|min_date| backup.created_at < min_date);
+        let retained = match backup.status {
+            BackupStatus::Complete => {
+                complete += 1;
+                !expired && (retain_count == 0 || complete <= retain_count)
+            }
+            BackupStatus::Failed => !expired && (retain_count == 0 || complete < retain_count),
+            BackupStatus::Running => true,
+        };
+
+        if !retained {
+            info!("Removing old backup '{}'", backup.name);
+            tokio::fs::remove_file(folder.join(&backup.name)).await?;
+        }
+    }
+    Ok(())
+}
+
+fn database_type() -> Result<&'static str, Error> {
+    Ok(match DbConnType::from_url(&CONFIG.database_url())? {
+        DbConnType::sqlite => "sqlite",
+        DbConnType::mysql => "mysql",
+        DbConnType::postgresql => "postgresql",
+    })
+}
+
+/// Returns the location of the file belonging to a row of a table, if any
+fn stored_file(table: &str, row: &Value) -> Option<(PathType, String)> {
+    match table {
+        "attachments" => {
+            Some((PathType::Attachments, format!("{}/{}", row["cipher_uuid"].as_str()?, row["id"].as_str()?)))
+        }
+        "sends" if row["atype"].as_i64() == Some(crate::db::models::SendType::File as i64) => {
+            let data: Value = serde_json::from_str(row["data"].as_str()?).ok()?;
+            Some((PathType::Sends, format!("{}/{}", row["uuid"].as_str()?, data["id"].as_str()?)))
+        }
+        _ => None,
+    }
+}
+
+//
+// Minimal reader and writer of ustar archives, only regular files are supported
+//
+const BLOCK_SIZE: usize = 512;
+
+fn write_octal(field: &mut [u8], value: u64) -> EmptyResult {
+    // The last byte of every field is a NUL terminator
+    let digits = format!("{value:0width$o}", width = field.len() - 1);
+    if digits.len() != field.len() - 1 {
+        err!(format!("Value {value} is too large for the archive header"))
+    }
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+    Ok(())
+}
+
+fn read_octal(field: &[u8]) -> Result<u64, Error> {
+    let digits = String::from_utf8_lossy(field);
+    match u64::from_str_radix(digits.trim_matches(|c: char| c == '\0' || c == ' '), 8) {
+        Ok(value) => Ok(value),
+        Err(_) => err!("Invalid number in the archive header"),
+    }
+}
+
+fn padding(size: u64) -> usize {
+    (BLOCK_SIZE - (size % BLOCK_SIZE as u64) as usize) % BLOCK_SIZE
+}
+
+/// Writes an archive, which is encrypted on the fly when a public key is given.
+/// The content of an entry is written through `AsyncWrite`, between `start_entry` and `end_entry`.
+struct ArchiveWriter {
+    file: BufWriter<File>,
+    crypter: Option<Crypter>,
+    // Encrypted data which was not written to the file yet
+    pending: Vec<u8>,
+    // Number of bytes written since the last header
+    written: u64,
+}
+
+impl ArchiveWriter {
+    async fn create(path: &Path, public_key: Option<&Rsa<Public>>) -> Result<Self, Error> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        // The backup contains the RSA key and other secrets
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file = BufWriter::new(options.open(path).await?);
+
+        let crypter = match public_key {
+            Some(public_key) => {
+                let key = crypto::get_random_bytes::<KEY_SIZE>();
+                let nonce = crypto::get_random_bytes::<NONCE_SIZE>();
+                let mut encrypted_key = vec![0u8; public_key.size() as usize];
+                let len = public_key.public_encrypt(&key, &mut encrypted_key, Padding::PKCS1_OAEP)?;
+
+                file.write_all(ENCRYPTED_MAGIC).await?;
+                file.write_all(&(len as u16).to_be_bytes()).await?;
+                file.write_all(&encrypted_key[..len]).await?;
+                file.write_all(&nonce).await?;
+                Some(Crypter::new(Cipher::aes_256_gcm(), Mode::Encrypt, &key, Some(&nonce))?)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            file,
+            crypter,
+            pending: Vec::new(),
+            written: 0,
+        })
+    }
+
+    /// Writes the header of an entry, the `size` bytes of its content need to be written next
+    async fn start_entry(&mut self, name: &str, size: u64) -> EmptyResult {
+        let mut header = [0u8; BLOCK_SIZE];
+        if name.len() >= 100 {
+            err!(format!("The name of the archive entry {name} is too long"))
+        }
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        write_octal(&mut header[100..108], 0o600)?; // mode
+        write_octal(&mut header[108..116], 0)?; // uid
+        write_octal(&mut header[116..124], 0)?; // gid
+        write_octal(&mut header[124..136], size)?;
+        write_octal(&mut header[136..148], Utc::now().timestamp() as u64)?;
+        header[156] = b'0'; // Regular file
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+
+        // The checksum is calculated with the checksum field filled with spaces
+        header[148..156].fill(b' ');
+        let checksum: u64 = header.iter().map(|b| u64::from(*b)).sum();
+        write_octal(&mut header[148..155], checksum)?;
+
+        self.write_all(&header).await?;
+        self.written = 0;
+        Ok(())
+    }
+
+    /// Completes an entry, after checking that the size given in its header was written
+    async fn end_entry(&mut self, size: u64) -> EmptyResult {
+        if self.written != size {
+            err!(format!(
+                "The size of an archive entry changed from {size} to {} bytes while it was written",
+                self.written
+            ))
+        }
+        self.write_all(&[0u8; BLOCK_SIZE][..padding(size)]).await?;
+        Ok(())
+    }
+
+    async fn add(&mut self, name: &str, data: &[u8]) -> EmptyResult {
+        self.start_entry(name, data.len() as u64).await?;
+        self.write_all(data).await?;
+        self.end_entry(data.len() as u64).await
+    }
+
+    async fn add_file(&mut self, name: &str, file: File) -> EmptyResult {
+        let size = file.metadata().await?.len();
+        self.start_entry(name, size).await?;
+        tokio::io::copy(&mut file.take(size), self).await?;
+        self.end_entry(size).await
+    }
+
+    async fn finish(mut self) -> EmptyResult {
+        // The end of the archive is marked by two empty blocks
+        self.write_all(&[0u8; BLOCK_SIZE * 2]).await?;
+        self.flush().await?;
+        if let Some(mut crypter) = self.crypter.take() {
+            let mut rest = [0u8; TAG_SIZE];
+            let len = crypter.finalize(&mut rest)?;
+            self.file.write_all(&rest[..len]).await?;
+            let mut tag = [0u8; TAG_SIZE];
+            crypter.get_tag(&mut tag)?;
+            self.file.write_all(&tag).await?;
+        }
+        self.file.flush().await?;
+        self.file.get_ref().sync_all().await?;
+        Ok(())
+    }
+
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let len = ready!(Pin::new(&mut self.file).poll_write(cx, &self.pending))?;
+            if len == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending.drain(..len);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for ArchiveWriter {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        let len = match &mut this.crypter {
+            // The data is encrypted right away, and written from `pending` by the next calls
+            Some(crypter) => {
+                this.pending.resize(buf.len() + TAG_SIZE, 0);
+                let len = crypter.update(buf, &mut this.pending).map_err(io::Error::other)?;
+                this.pending.truncate(len);
+                buf.len()
+            }
+            None => ready!(Pin::new(&mut this.file).poll_write(cx, buf))?,
+        };
+        this.written += len as u64;
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        Pin::new(&mut this.file).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        Pin::new(&mut this.file).poll_shutdown(cx)
+    }
+}
+
+struct Decryption {
+    crypter: Crypter,
+    // Number of encrypted bytes left before the authentication tag
+    remaining: u64,
+}
+
+struct ArchiveReader {
+    file: BufReader<File>,
+    decryption: Option<Decryption>,
+}
+
+impl ArchiveReader {
+    async fn open(path: &Path, private_key: Option<&Rsa<Private>>) -> Result<Self, Error> {
+        let file = File::open(path).await?;
+        let file_size = file.metadata().await?.len();
+        let mut file = BufReader::new(file);
+
+        let mut magic = [0u8; ENCRYPTED_MAGIC.len()];
+        file.read_exact(&mut magic).await?;
+        if &magic != ENCRYPTED_MAGIC {
+            file.rewind().await?;
+            return Ok(Self {
+                file,
+                decryption: None,
+            });
+        }
+
+        let Some(private_key) = private_key else {
+            err!("The backup is encrypted, the private key is needed to restore it")
+        };
+
+        let mut len = [0u8; 2];
+        file.read_exact(&mut len).await?;
+        let mut encrypted_key = vec![0u8; u16::from_be_bytes(len) as usize];
+        file.read_exact(&mut encrypted_key).await?;
+        let mut nonce = [0u8; NONCE_SIZE];
+        file.read_exact(&mut nonce).await?;
+
+        let mut key = vec![0u8; private_key.size() as usize];
+        let Ok(key_len) = private_key.private_decrypt(&encrypted_key, &mut key, Padding::PKCS1_OAEP) else {
+            err!("Unable to decrypt the backup, it was encrypted with a different key")
+        };
+        if key_len != KEY_SIZE {
+            err!("Unable to decrypt the backup, invalid key")
+        }
+
+        let header_size = (ENCRYPTED_MAGIC.len() + len.len() + encrypted_key.len() + NONCE_SIZE) as u64;
+        let Some(remaining) = file_size.checked_sub(header_size + TAG_SIZE as u64) else {
+            err!("The backup is truncated")
+        };
+
+        Ok(Self {
+            file,
+            decryption: Some(Decryption {
+                crypter: Crypter::new(Cipher::aes_256_gcm(), Mode::Decrypt, &key[..KEY_SIZE], Some(&nonce))?,
+                remaining,
+            }),
+        })
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8]) -> EmptyResult {
+        match &mut self.decryption {
+            Some(decryption) => {
+                if buf.len() as u64 > decryption.remaining {
+                    err!("The backup is truncated")
+                }
+                let mut encrypted = vec![0u8; buf.len()];
+                self.file.read_exact(&mut encrypted).await?;
+                decryption.remaining -= buf.len() as u64;
+
+                // GCM is a stream cipher mode, the output has the same length as the input
+                let mut decrypted = vec![0u8; buf.len() + TAG_SIZE];
+                let len = decryption.crypter.update(&encrypted, &mut decrypted)?;
+                if len != buf.len() {
+                    err!("Unable to decrypt the backup")
+                }
+                buf.copy_from_slice(&decrypted[..len]);
+            }
+            None => {
+                self.file.read_exact(buf).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Verifies the authentication tag of an encrypted archive, needs to be called after the last entry was read
+    async fn finish(mut self) -> EmptyResult {
+        if let Some(remaining) = self.decryption.as_ref().map(|d| d.remaining) {
+            let mut rest = vec![0u8; remaining as usize];
+            self.read_exact(&mut rest).await?;
+
+            let mut tag = [0u8; TAG_SIZE];
+            self.file.read_exact(&mut tag).await?;
+            if let Some(mut decryption) = self.decryption.take() {
+                decryption.crypter.set_tag(&tag)?;
+                let mut last = [0u8; TAG_SIZE];
+                if decryption.crypter.finalize(&mut last).is_err() {
+                    err!("The backup is corrupted or was modified, the authentication failed")
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the name and the size of the next regular file, or `None` at the end of the archive.
+    /// The content of the entry needs to be read with `copy_entry` or `read_entry` before the next call.
+    async fn next_entry(&mut self) -> Result<Option<(String, u64)>, Error> {
+        loop {
+            let mut header = [0u8; BLOCK_SIZE];
+            self.read_exact(&mut header).await?;
+            if header.iter().all(|b| *b == 0) {
+                return Ok(None);
+            }
+            if &header[257..262] != b"ustar" {
+                err!("The file is not a valid archive")
+            }
+
+            let expected_checksum = read_octal(&header[148..156])?;
+            header[148..156].fill(b' ');
+            if header.iter().map(|b| u64::from(*b)).sum::<u64>() != expected_checksum {
+                err!("The archive is corrupted, invalid header checksum")
+            }
+
+            let name = String::from_utf8_lossy(&header[..100]).trim_end_matches('\0').to_string();
+            let prefix = String::from_utf8_lossy(&header[345..500]).trim_end_matches('\0').to_string();
+            let name = if prefix.is_empty() {
+                name
+            } else {
+                format!("{prefix}/{name}")
+            };
+            let size = read_octal(&header[124..136])?;
+
+            // Skip directories and other special entries which might have been added when the archive was repacked
+            if matches!(header[156], b'0' | 0) {
+                return Ok(Some((name, size)));
+            }
+            self.copy_entry(size, &mut tokio::io::sink()).await?;
+        }
+    }
+
+    /// Copies the content of the current entry into `writer`, in chunks of `CHUNK_SIZE`
+    async fn copy_entry(&mut self, size: u64, writer: &mut (impl AsyncWrite + Unpin)) -> EmptyResult {
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        let mut remaining = size;
+        while remaining > 0 {
+            let len = remaining.min(CHUNK_SIZE as u64) as usize;
+            self.read_exact(&mut buffer[..len]).await?;
+            writer.write_all(&buffer[..len]).await?;
+            remaining -= len as u64;
+        }
+        let mut pad = [0u8; BLOCK_SIZE];
+        self.read_exact(&mut pad[..padding(size)]).await
+    }
+
+    /// Reads the content of the current entry into memory, only used for small entries
+    async fn read_entry(&mut self, size: u64) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::with_capacity(size as usize);
+        self.copy_entry(size, &mut data).await?;
+        Ok(data)
+    }
+}
+
+#[cfg(all(test, sqlite))]
+mod tests {
+    use super::*;
+    use crate::db::models::User;
+
+    fn test_folder() -> PathBuf {
+        std::env::temp_dir().join(format!("vw_backup_test_{}", crate::util::get_uuid()))
+    }
+
+    async fn test_pool(folder: &Path, name: &str) -> DbPool {
+        DbPool::from_url(folder.join(name).to_str().unwrap()).unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn test_backup_round_trip() {
+        let folder = test_folder();
+        tokio::fs::create_dir_all(&folder).await.unwrap();
+
+        let source = test_pool(&folder, "source.sqlite3").await;
+        let mut conn = source.get().await.unwrap();
+        let mut user = User::new(String::from("backup@example.com"));
+        user.save(&mut conn).await.unwrap();
+
+        let path = folder.join("backup.tar");
+        create_backup(&path, &mut conn).await.unwrap();
+
+        let target = test_pool(&folder, "target.sqlite3").await;
+        let mut conn = target.get().await.unwrap();
+        restore_staged_backup(&path, None, &folder.join("staging"), &mut conn).await.unwrap();
+
+        let restored = User::find_by_mail("backup@example.com", &mut conn).await.unwrap();
+        assert_eq!(restored.uuid, user.uuid);
+
+        tokio::fs::remove_dir_all(&folder).await.ok();
+    }
+
+    #[rocket::async_test]
+    async fn test_restore_rejects_tampered_archive() {
+        let folder = test_folder();
+        tokio::fs::create_dir_all(&folder).await.unwrap();
+
+        let private_key = Rsa::generate(2048).unwrap();
+        let public_key = Rsa::public_key_from_pem(&private_key.public_key_to_pem().unwrap()).unwrap();
+
+        let manifest = Manifest {
+            format_version: BACKUP_FORMAT_VERSION,
+            vaultwarden_version: VERSION.map(String::from),
+            database: String::from("sqlite"),
+            created_at: Utc::now().naive_utc(),
+            row_counts: HashMap::from([(String::from("invitations"), 1)]),
+        };
+        let path = folder.join("backup.tar.enc");
+        let mut archive = ArchiveWriter::create(&path, Some(&public_key)).await.unwrap();
+        archive.add(MANIFEST_ENTRY, &serde_json::to_vec(&manifest).unwrap()).await.unwrap();
+        archive.add("db/invitations.jsonl", b"{\"email\":\"invited@example.com\"}\n").await.unwrap();
+        archive.finish().await.unwrap();
+
+        // The intact archive can be extracted
+        extract_archive(&path, Some(&private_key), &folder.join("intact")).await.unwrap();
+        assert!(folder.join("intact/db/invitations.jsonl").exists());
+
+        // Every entry can still be read when only the authentication tag was modified,
+        // so this is only detected by the verification at the end
+        let mut data = tokio::fs::read(&path).await.unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        tokio::fs::write(&path, data).await.unwrap();
+
+        let target = test_pool(&folder, "target.sqlite3").await;
+        let mut conn = target.get().await.unwrap();
+        assert!(restore_staged_backup(&path, Some(&private_key), &folder.join("staging"), &mut conn).await.is_err());
+        assert!(dump::is_empty(&mut conn).await.unwrap());
+
+        tokio::fs::remove_dir_all(&folder).await.ok();
+    }
+
+    #[rocket::async_test]
+    async fn test_failed_import_removes_restored_files() {
+        let folder = test_folder();
+        tokio::fs::create_dir_all(&folder).await.unwrap();
+
+        // The manifest expects more rows than the archive contains, so the import is rolled back
+        let manifest = Manifest {
+            format_version: BACKUP_FORMAT_VERSION,
+            vaultwarden_version: VERSION.map(String::from),
+            database: String::from("sqlite"),
+            created_at: Utc::now().naive_utc(),
+            row_counts: HashMap::from([(String::from("invitations"), 2)]),
+        };
+        let file_path = format!("{}/file", crate::util::get_uuid());
+        let path = folder.join("backup.tar");
+        let mut archive = ArchiveWriter::create(&path, None).await.unwrap();
+        archive.add(MANIFEST_ENTRY, &serde_json::to_vec(&manifest).unwrap()).await.unwrap();
+        archive.add("db/invitations.jsonl", b"{\"email\":\"invited@example.com\"}\n").await.unwrap();
+        archive.add(&format!("attachments/{file_path}"), &vec![1u8; CHUNK_SIZE * 2 + 1]).await.unwrap();
+        archive.finish().await.unwrap();
+
+        let target = test_pool(&folder, "target.sqlite3").await;
+        let mut conn = target.get().await.unwrap();
+        assert!(restore_staged_backup(&path, None, &folder.join("staging"), &mut conn).await.is_err());
+        assert!(dump::is_empty(&mut conn).await.unwrap());
+        assert!(!storage().exists(PathType::Attachments, &file_path).await);
+
+        tokio::fs::remove_dir_all(&folder).await.ok();
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     env::consts::EXE_SUFFIX,
     process::exit,
     sync::{
@@ -14,10 +15,13 @@ use reqwest::Url;
 use crate::{
     db::DbConnType,
     error::Error,
-    util::{get_env, get_env_bool, get_web_vault_version, is_valid_email, parse_experimental_client_feature_flags},
+    util::{
+        get_env, get_env_bool, get_web_vault_version, is_valid_email, parse_bool,
+        parse_experimental_client_feature_flags,
+    },
 };
 
-static CONFIG_FILE: Lazy<String> = Lazy::new(|| {
+pub static CONFIG_FILE: Lazy<String> = Lazy::new(|| {
     let data_folder = get_env("DATA_FOLDER").unwrap_or_else(|| String::from("data"));
     get_env("CONFIG_FILE").unwrap_or_else(|| format!("{data_folder}/config.json"))
 });
@@ -116,6 +120,68 @@ macro_rules! make_config {
                 serde_json::from_str(&config_str).map_err(Into::into)
             }
 
+            /// Lists the options which differ between both builders, as `{"name", "old", "new"}` objects.
+            /// Like in the support JSON, the values of passwords are replaced by `***`.
+            pub fn masked_diff(&self, other: &Self) -> Vec<serde_json::Value> {
+                let mut changes = Vec::new();
+                $($(
+                    if self.$name != other.$name {
+                        // We do not use the json!() macro here since that causes a lot of macro recursion.
+                        let mut change = serde_json::Map::new();
+                        change.insert("name".into(), (stringify!($name)).into());
+                        change.insert("old".into(), make_config!{ @maskstr self.$name, $ty });
+                        change.insert("new".into(), make_config!{ @maskstr other.$name, $ty });
+                        changes.push(serde_json::Value::Object(change));
+                    }
+                )+)+
+                changes
+            }
+
+            /// Reads the options from the variables of a `.env` file, which use the same names as the environment variables.
+            /// Also returns the names of the variables which are not config options, those are ignored.
+            pub fn from_env_vars(vars: &HashMap<String, String>) -> Result<(Self, Vec<String>), Error> {
+                let mut builder = ConfigBuilder::default();
+                let mut known = Vec::new();
+                $($(
+                    let key = pastey::paste!(stringify!([<$name:upper>]));
+                    known.push(key);
+                    if let Some(value) = vars.get(key) {
+                        builder.$name = Some(make_config!{ @parsestr key, value, $ty });
+                    }
+                )+)+
+
+                let mut unknown: Vec<String> = vars.keys().filter(|k| !known.contains(&k.as_str())).cloned().collect();
+                unknown.sort();
+                Ok((builder, unknown))
+            }
+
+            /// Writes the options which are set as the lines of a `.env` file
+            pub fn to_env_file(&self) -> String {
+                let mut lines = Vec::new();
+                $($(
+                    if let Some(value) = &self.$name {
+                        lines.push(format!("{}={}", pastey::paste!(stringify!([<$name:upper>])), quote_env_value(&value.to_string())));
+                    }
+                )+)+
+                lines.join("\n")
+            }
+
+            /// Removes the passwords and secret keys
+            pub fn clear_passwords(&mut self) {
+                $($(
+                    make_config!{ @clearpass self.$name, $ty };
+                )+)+
+                // The database URL can contain the password of the database user
+                self.database_url = None;
+            }
+
+            /// Takes the passwords and secret keys from `other`, to restore a config which was saved without them
+            pub fn copy_passwords(&mut self, other: &Self) {
+                $($(
+                    make_config!{ @copypass self.$name, other.$name, $ty };
+                )+)+
+            }
+
             fn clear_non_editable(&mut self) {
                 $($(
                     if !$editable {
@@ -255,10 +321,16 @@ macro_rules! make_config {
                     "domain",
                     "helo_name",
                     "org_creation_users",
+                    "s3_access_key_id",
+                    "s3_bucket",
+                    "s3_endpoint",
                     "signups_domains_whitelist",
                     "smtp_from",
                     "smtp_host",
                     "smtp_username",
+                    "sso_authority",
+                    "sso_callback_path",
+                    "sso_client_id",
                     "_smtp_img_src",
                 ];
 
@@ -298,6 +370,26 @@ macro_rules! make_config {
                 })
             }
 
+            /// Returns the value of every config option, to compare them before and after a change.
+            /// The passwords are replaced by a keyed hash, so a change can be seen without storing them.
+            pub fn get_audit_json(&self) -> serde_json::Map<String, serde_json::Value> {
+                static MASK_KEY: Lazy<String> = Lazy::new(|| crate::crypto::encode_random_bytes::<32>(data_encoding::HEXLOWER));
+                fn _mask_pass(value: &str) -> String {
+                    format!("*** ({})", &crate::crypto::hmac_sign(&MASK_KEY, value)[..8])
+                }
+
+                let cfg = {
+                    let inner = &self.inner.read().unwrap();
+                    inner.config.clone()
+                };
+
+                let mut json = serde_json::Map::new();
+                $($(
+                    json.insert(stringify!($name).into(), make_config!{ @auditstr cfg.$name, $ty, $none_action });
+                )+)+;
+                json
+            }
+
             pub fn get_overrides(&self) -> Vec<String> {
                 let overrides = {
                     let inner = &self.inner.read().unwrap();
@@ -328,6 +420,15 @@ macro_rules! make_config {
     ( @supportstr $name:ident, $value:expr, $ty:ty, option ) => { serde_json::to_value($value).unwrap() }; // Optional other value, we return as is or convert to string to apply the privacy config
     ( @supportstr $name:ident, $value:expr, $ty:ty, $none_action:ident ) => { ($value).into() }; // Required other value, we return as is or convert to string to apply the privacy config
 
+    // Masked value of an optional builder field
+    ( @maskstr $value:expr, Pass ) => { serde_json::to_value($value.as_ref().map(|_| String::from("***"))).unwrap() };
+    ( @maskstr $value:expr, $ty:ty ) => { serde_json::to_value(&$value).unwrap() };
+
+    // Audit value, only the passwords are masked
+    ( @auditstr $value:expr, Pass, option ) => { serde_json::to_value($value.as_ref().map(|v| _mask_pass(v))).unwrap() };
+    ( @auditstr $value:expr, Pass, $none_action:ident ) => { _mask_pass(&$value).into() };
+    ( @auditstr $value:expr, $ty:ty, $none_action:ident ) => { serde_json::to_value($value).unwrap() };
+
     // Group or empty string
     ( @show ) => { "" };
     ( @show $lit:literal ) => { $lit };
@@ -356,6 +457,27 @@ macro_rules! make_config {
     ( @getenv $name:expr, bool ) => { get_env_bool($name) };
     ( @getenv $name:expr, $ty:ident ) => { get_env($name) };
 
+    // Parse a value of a `.env` file, booleans accept the same values as the environment variables
+    ( @parsestr $key:expr, $value:expr, bool ) => {
+        match parse_bool($value) {
+            Some(v) => v,
+            None => err!(format!("`{}` is not a valid boolean: `{}`", $key, $value)),
+        }
+    };
+    ( @parsestr $key:expr, $value:expr, $ty:ident ) => {
+        match $value.parse::<$ty>() {
+            Ok(v) => v,
+            Err(_) => err!(format!("`{}` has an invalid value: `{}`", $key, $value)),
+        }
+    };
+
+    // Only passwords are cleared
+    ( @clearpass $field:expr, Pass ) => { $field = None };
+    ( @clearpass $field:expr, $ty:ident ) => {};
+
+    ( @copypass $field:expr, $other:expr, Pass ) => { $field = $other.clone() };
+    ( @copypass $field:expr, $other:expr, $ty:ident ) => {};
+
 }
 
 //STRUCTURE:
@@ -391,6 +513,62 @@ make_config! {
         /// Web vault folder
         web_vault_folder:       String, false,  def,    "web-vault/".to_string();
     },
+    storage {
+        /// Storage backend |> Where attachments and Send files are stored. Either "local" (the folders above) or "s3" (an S3 compatible object store)
+        storage_backend:        String, false,  def,    "local".to_string();
+        /// S3 endpoint |> Base URL of the object store, for example http://minio:9000. Defaults to AWS S3 in the configured region
+        s3_endpoint:            String, false,  option;
+        /// S3 region
+        s3_region:              String, false,  def,    "us-east-1".to_string();
+        /// S3 bucket
+        s3_bucket:              String, false,  option;
+        /// S3 access key id
+        s3_access_key_id:       String, false,  option;
+        /// S3 secret access key
+        s3_secret_access_key:   Pass,   false,  option;
+        /// S3 path style |> Use path style (http://host/bucket/key) instead of virtual host style (http://bucket.host/key) URLs. Most self-hosted object stores need this
+        s3_path_style:          bool,   false,  def,    true;
+        /// S3 presigned URL expiration |> Number of seconds a presigned download URL stays valid
+        s3_presign_expiration:  u64,    false,  def,    300;
+    },
+    backup {
+        /// Backup schedule |> Cron schedule of the job that creates a full backup in the backup folder. Set blank to disable this job
+        backup_schedule:        String, false,  def,    String::new();
+        /// Backup folder
+        backup_folder:          String, false,  auto,   |c| format!("{}/{}", c.data_folder, "backups");
+        /// Backup retention count |> Number of successful backups to keep, older ones are removed. Set to 0 to keep all of them
+        backup_retain_count:    u32,    false,  def,    7;
+        /// Backup retention days |> Backups older than this number of days are removed
+        backup_retain_days:     i64,    false,  option;
+        /// Backup public key |> Path to a PEM encoded RSA public key. When set, the backups are encrypted and can only be restored with the matching private key
+        backup_public_key:      String, false,  option;
+    },
+    metrics {
+        /// Enable metrics |> Expose Prometheus metrics on /metrics, scrapers need to send the metrics token as bearer token
+        metrics_enabled:        bool,   false,  def,    false;
+        /// Metrics token |> Needs to be set when the metrics are enabled
+        metrics_token:          Pass,   false,  option;
+    },
+    inactive_users {
+        /// Inactive user warning days |> Number of days without a login, sync or token refresh after which a user is warned by email, requires mail to be enabled. Leave empty to not warn
+        inactive_user_warn_days:    i64,    true,   option;
+        /// Inactive user action days |> Number of days of inactivity after which the inactive user action is applied, at least this many days minus the warning days after the warning. Leave empty to never act
+        inactive_user_action_days:  i64,    true,   option;
+        /// Inactive user action |> Either "disable" to disable the account, or "revoke" to revoke its organization memberships
+        inactive_user_action:       String, true,   def,    "disable".to_string();
+        /// Inactive user allowlist |> Comma separated emails of the accounts which are never warned or acted on, for example service accounts
+        inactive_user_allowlist:    String, true,   def,    String::new();
+    },
+    event_sink {
+        /// Event sink |> Forwards every logged event to a SIEM, either "syslog" or "file". Leave blank to disable
+        event_sink:             String, false,  def,    String::new();
+        /// Event sink format |> Format of the syslog messages, one of "rfc5424", "cef" or "leef". The file sink always uses JSON Lines
+        event_sink_format:      String, false,  def,    "rfc5424".to_string();
+        /// Event sink address |> Address of the syslog server, either "udp://host:port", "tcp://host:port" or "unix:///path/to/socket"
+        event_sink_address:     String, false,  def,    "udp://127.0.0.1:514".to_string();
+        /// Event sink file |> Path of the JSON Lines file the events are appended to
+        event_sink_file:        String, false,  option;
+    },
     ws {
         /// Enable websocket notifications
         enable_websocket:       bool,   false,  def,    true;
@@ -411,7 +589,7 @@ make_config! {
         /// Job scheduler poll interval |> How often the job scheduler thread checks for jobs to run.
         /// Set to 0 to globally disable scheduled jobs.
         job_poll_interval_ms:   u64,    false,  def,    30_000;
-        /// Send purge schedule |> Cron schedule of the job that checks for Sends past their deletion date.
+        /// Send purge schedule |> Cron schedule of the job that checks for Sends past their deletion date, it also frees the expired rate limits.
         /// Defaults to hourly. Set blank to disable this job.
         send_purge_schedule:    String, false,  def,    "0 5 * * * *".to_string();
         /// Trash purge schedule |> Cron schedule of the job that checks for trashed items to delete permanently.
@@ -435,6 +613,15 @@ make_config! {
         /// Duo Auth context cleanup schedule |> Cron schedule of the job that cleans expired Duo contexts from the database. Does nothing if Duo MFA is disabled or set to use the legacy iframe prompt.
         /// Defaults to once every minute. Set blank to disable this job.
         duo_context_purge_schedule:   String, false,  def,    "30 * * * * *".to_string();
+        /// SSO auth cleanup schedule |> Cron schedule of the job that cleans unfinished SSO logins from the database. Does nothing if SSO is disabled.
+        /// Defaults to every 10 minutes. Set blank to disable this job.
+        sso_auth_purge_schedule:   String, false,  def,    "0 */10 * * * *".to_string();
+        /// Webhook delivery schedule |> Cron schedule of the job that delivers the queued organization webhook events and retries the failed ones.
+        /// Defaults to once every minute. Set blank to disable this job.
+        webhook_delivery_schedule:   String, false,  def,    "15 * * * * *".to_string();
+        /// Inactive user schedule |> Cron schedule of the job that warns inactive users and applies the inactive user action. Does nothing unless the inactive user days are set.
+        /// Defaults to daily. Set blank to disable this job.
+        inactive_user_schedule:   String, false,  def,    "0 20 2 * * *".to_string();
     },
 
     /// General settings
@@ -458,11 +645,11 @@ make_config! {
         /// HIBP Api Key |> HaveIBeenPwned API Key, request it here: https://haveibeenpwned.com/API/Key
         hibp_api_key:           Pass,   true,   option;
 
-        /// Per-user attachment storage limit (KB) |> Max kilobytes of attachment storage allowed per user. When this limit is reached, the user will not be allowed to upload further attachments.
+        /// Per-user attachment storage limit (KB) |> Max kilobytes of attachment storage allowed per user. When this limit is reached, the user will not be allowed to upload further attachments. Can be overridden per user in the users overview.
         user_attachment_limit:  i64,    true,   option;
-        /// Per-organization attachment storage limit (KB) |> Max kilobytes of attachment storage allowed per org. When this limit is reached, org members will not be allowed to upload further attachments for ciphers owned by that org.
+        /// Per-organization attachment storage limit (KB) |> Max kilobytes of attachment storage allowed per org. When this limit is reached, org members will not be allowed to upload further attachments for ciphers owned by that org. Can be overridden per organization in the organizations overview.
         org_attachment_limit:   i64,    true,   option;
-        /// Per-user send storage limit (KB) |> Max kilobytes of sends storage allowed per user. When this limit is reached, the user will not be allowed to upload further sends.
+        /// Per-user send storage limit (KB) |> Max kilobytes of sends storage allowed per user. When this limit is reached, the user will not be allowed to upload further sends. Can be overridden per user in the users overview.
         user_send_limit:   i64,    true,   option;
 
         /// Trash auto-delete days |> Number of days to wait before auto-deleting a trashed item.
@@ -525,6 +712,9 @@ make_config! {
 
         /// Events days retain |> Number of days to retain events stored in the database. If unset, events are kept indefinitely.
         events_days_retain:     i64,    false,   option;
+
+        /// Webhook max attempts |> Number of times the delivery of an event to an organization webhook is attempted before it is marked as failed
+        webhook_max_attempts:   u32,    true,   def,    8;
     },
 
     /// Advanced settings
@@ -596,6 +786,8 @@ make_config! {
         use_syslog:             bool,   false,  def,    false;
         /// Log file path
         log_file:               String, false,  option;
+        /// Log format |> Either "text" or "json". With "json" every log line is a JSON object, request logs include the correlation id, route, status, latency, client IP and user id
+        log_format:             String, false,  def,    "text".to_string();
         /// Log level |> Valid values are "trace", "debug", "info", "warn", "error" and "off"
         /// For a specific module append it as a comma separated value "info,path::to::module=debug"
         log_level:              String, false,  def,    "info".to_string();
@@ -619,6 +811,10 @@ make_config! {
         /// Bypass admin page security (Know the risks!) |> Disables the Admin Token for the admin page so you may use your own auth in-front
         disable_admin_token:    bool,   false,  def,    false;
 
+        /// Enable admin accounts |> Allows named admin accounts, each with their own password, optional TOTP and role, to log in to the admin panel.
+        /// This also enables the admin panel without an `ADMIN_TOKEN`, the first account can be created with the `admin-account create` command
+        enable_admin_accounts:  bool,   false,  def,    false;
+
         /// Allowed iframe ancestors (Know the risks!) |> Allows other domains to embed the web vault into an iframe, useful for embedding into secure intranets
         allowed_iframe_ancestors: String, true, def,    String::new();
 
@@ -629,8 +825,30 @@ make_config! {
         login_ratelimit_seconds:       u64, false, def, 60;
         /// Max burst size for login requests |> Allow a burst of requests of up to this size, while maintaining the average indicated by `login_ratelimit_seconds`. Note that this applies to both the login and the 2FA, so it's recommended to allow a burst size of at least 2
         login_ratelimit_max_burst:     u32, false, def, 10;
-
-        /// Seconds between admin login requests |> Number of seconds, on average, between admin requests from the same IP address before rate limiting kicks in
+        /// Seconds between login requests per subnet |> Number of seconds, on average, between login requests from the same subnet before rate limiting kicks in.
+        /// This slows down attacks spread over many addresses of one network, without the per IP limit being too low for users behind a shared NAT
+        login_ratelimit_subnet_seconds:   u64, false, def, 6;
+        /// Max burst size for login requests per subnet |> Allow a burst of requests of up to this size from the same subnet. 0 disables the subnet limit
+        login_ratelimit_subnet_max_burst: u32, false, def, 50;
+        /// IPv4 subnet prefix length |> The IPv4 addresses sharing this many leading bits are counted as one subnet
+        login_ratelimit_ipv4_prefix:      u8,  false, def, 24;
+        /// IPv6 subnet prefix length |> The IPv6 addresses sharing this many leading bits are counted as one subnet
+        login_ratelimit_ipv6_prefix:      u8,  false, def, 64;
+        /// Seconds between login requests per account |> Number of seconds, on average, between login requests for the same account, from any IP address, before rate limiting kicks in
+        login_ratelimit_account_seconds:   u64, false, def, 60;
+        /// Max burst size for login requests per account |> Allow a burst of requests of up to this size for the same account. 0 disables the account limit
+        login_ratelimit_account_max_burst: u32, false, def, 10;
+
+        /// Failed login delay |> Seconds an account has to wait after a failed master password login from the same IP address, doubled for every following failure. 0 disables the delay
+        login_failure_delay_seconds:     u64, true,  def, 0;
+        /// Max failed login delay |> The delay between failed logins doesn't grow beyond this number of seconds
+        login_failure_max_delay_seconds: u64, true,  def, 60;
+        /// Failed logins before lockout |> Lock the account temporarily after this many failed master password logins in a row, the user is notified by email. 0 disables the lockout
+        login_lockout_attempts:          u32, true,  def, 0;
+        /// Lockout duration |> Number of minutes an account stays locked, an admin can unlock it sooner in the admin panel
+        login_lockout_minutes:           i64, true,  def, 30;
+
+        /// Seconds between admin login requests |> Number of seconds, on average, between admin requests from the same IP address before rate limiting kicks in. The admin API requests with a missing or invalid token count towards the same limit
         admin_ratelimit_seconds:       u64, false, def, 300;
         /// Max burst size for admin login requests |> Allow a burst of requests of up to this size, while maintaining the average indicated by `admin_ratelimit_seconds`
         admin_ratelimit_max_burst:     u32, false, def, 3;
@@ -638,6 +856,14 @@ make_config! {
         /// Admin session lifetime |> Set the lifetime of admin sessions to this value (in minutes).
         admin_session_lifetime:        i64, true,  def, 20;
 
+        /// Rotate refresh tokens |> Issue a new refresh token on every token refresh. When a refresh token which was already replaced is used again,
+        /// the session of that device is revoked and a failed login is logged, since the token was most likely stolen
+        refresh_token_rotation:        bool, true,  def, true;
+        /// Session idle lifetime |> Number of days after which a device needs to log in again when it did not refresh its session. 0 means no limit
+        session_idle_days:             i64, true,  def, 0;
+        /// Session absolute lifetime |> Number of days after the login after which a device needs to log in again, regardless of its activity. 0 means no limit
+        session_absolute_days:         i64, true,  def, 0;
+
         /// Enable groups (BETA!) (Know the risks!) |> Enables groups support for organizations (Currently contains known issues!).
         org_groups_enabled:            bool, false, def, false;
 
@@ -653,6 +879,30 @@ make_config! {
         enforce_single_org_with_reset_pw_policy: bool, false, def, false;
     },
 
+    /// SSO settings
+    sso: sso_enabled {
+        /// Enabled |> Allow users to log in using an OpenID Connect identity provider
+        sso_enabled:            bool,   true,   def,    false;
+        /// Only SSO login |> Disable the email and master password login. Users still need their master password to unlock the vault
+        sso_only:               bool,   true,   def,    false;
+        /// Allow email association |> Link an existing account to the SSO identity when the verified email addresses match
+        sso_signups_match_email: bool,  true,   def,    true;
+        /// Authority |> Base URL of the identity provider, the discovery document is fetched from `<authority>/.well-known/openid-configuration`
+        sso_authority:          String, true,   def,    String::new();
+        /// Scopes |> Additional scopes to request, `openid` is always requested
+        sso_scopes:             String, true,   def,    "email profile".to_string();
+        /// Client ID
+        sso_client_id:          String, true,   def,    String::new();
+        /// Client Secret
+        sso_client_secret:      Pass,   true,   def,    String::new();
+        /// Use PKCE |> Use PKCE when requesting the authorization code from the identity provider
+        sso_pkce:               bool,   true,   def,    true;
+        /// Allow HMAC ID tokens |> Accept ID tokens signed with the client secret (HS256, HS384 or HS512). Only enable this when the identity provider can't sign them with a key pair
+        sso_allow_hmac:         bool,   true,   def,    false;
+        /// Callback URL |> Redirect URL to register at the identity provider (generated automatically)
+        sso_callback_path:      String, false,  generated, |c| generate_sso_callback_path(&c.domain);
+    },
+
     /// Yubikey settings
     yubico: _enable_yubico {
         /// Enabled
@@ -758,11 +1008,29 @@ fn validate_config(cfg: &ConfigItems) -> Result<(), Error> {
         err!("PASSWORD_ITERATIONS should be at least 100000 or higher. The default is 600000!");
     }
 
+    if cfg.login_ratelimit_ipv4_prefix > 32 || cfg.login_ratelimit_ipv6_prefix > 128 {
+        err!("`LOGIN_RATELIMIT_IPV4_PREFIX` must be at most 32 and `LOGIN_RATELIMIT_IPV6_PREFIX` at most 128");
+    }
+
+    if (cfg.login_ratelimit_subnet_max_burst > 0 && cfg.login_ratelimit_subnet_seconds == 0)
+        || (cfg.login_ratelimit_account_max_burst > 0 && cfg.login_ratelimit_account_seconds == 0)
+    {
+        err!("`LOGIN_RATELIMIT_SUBNET_SECONDS` and `LOGIN_RATELIMIT_ACCOUNT_SECONDS` must be at least 1 when their limit is enabled");
+    }
+
+    if cfg.login_lockout_attempts > 0 && cfg.login_lockout_minutes < 1 {
+        err!("`LOGIN_LOCKOUT_MINUTES` must be at least 1 when the lockout is enabled");
+    }
+
     let limit = 256;
     if cfg.database_max_conns < 1 || cfg.database_max_conns > limit {
         err!(format!("`DATABASE_MAX_CONNS` contains an invalid value. Ensure it is between 1 and {limit}.",));
     }
 
+    if !["text", "json"].contains(&cfg.log_format.as_str()) {
+        err!("`LOG_FORMAT` is invalid. It needs to be one of the following options: text or json")
+    }
+
     if let Some(log_file) = &cfg.log_file {
         if std::fs::OpenOptions::new().append(true).create(true).open(log_file).is_err() {
             err!("Unable to write to log file", log_file);
@@ -875,6 +1143,105 @@ fn validate_config(cfg: &ConfigItems) -> Result<(), Error> {
         }
     }
 
+    match cfg.storage_backend.as_str() {
+        "local" => (),
+        "s3" => {
+            if cfg.s3_bucket.is_none() {
+                err!("`S3_BUCKET` must be set when using the s3 storage backend")
+            }
+            if cfg.s3_access_key_id.is_none() || cfg.s3_secret_access_key.is_none() {
+                err!("Both `S3_ACCESS_KEY_ID` and `S3_SECRET_ACCESS_KEY` must be set when using the s3 storage backend")
+            }
+            if let Some(endpoint) = &cfg.s3_endpoint {
+                let endpoint = endpoint.to_lowercase();
+                if !(endpoint.starts_with("http://") || endpoint.starts_with("https://"))
+                    || Url::parse(&endpoint).is_err()
+                {
+                    err!("`S3_ENDPOINT` must be a valid URL and start with 'http://' or 'https://'")
+                }
+            }
+            if cfg.s3_presign_expiration < 1 || cfg.s3_presign_expiration > 604_800 {
+                err!("`S3_PRESIGN_EXPIRATION` must be between 1 and 604800 seconds")
+            }
+        }
+        _ => err!("`STORAGE_BACKEND` is invalid. It needs to be one of the following options: local or s3"),
+    }
+
+    if cfg.metrics_enabled && cfg.metrics_token.as_ref().is_none_or(|t| t.trim().is_empty()) {
+        err!("`METRICS_TOKEN` must be set when the metrics are enabled")
+    }
+
+    match cfg.event_sink.as_str() {
+        "" => {}
+        "syslog" => {
+            if !["rfc5424", "cef", "leef"].contains(&cfg.event_sink_format.as_str()) {
+                err!("`EVENT_SINK_FORMAT` must be one of the following options: rfc5424, cef or leef")
+            }
+            if !crate::event_sink::is_valid_address(&cfg.event_sink_address) {
+                err!("`EVENT_SINK_ADDRESS` must be a udp://host:port, tcp://host:port or unix:///path address")
+            }
+        }
+        "file" => match &cfg.event_sink_file {
+            Some(file) if std::fs::OpenOptions::new().append(true).create(true).open(file).is_ok() => {}
+            Some(file) => err!("Unable to write to the event sink file", file),
+            None => err!("`EVENT_SINK_FILE` must be set when the file event sink is used"),
+        },
+        _ => err!("`EVENT_SINK` is invalid. It needs to be one of the following options: syslog or file"),
+    }
+
+    if !cfg.inactive_user_schedule.is_empty() && cfg.inactive_user_schedule.parse::<Schedule>().is_err() {
+        err!("`INACTIVE_USER_SCHEDULE` is not a valid cron expression")
+    }
+
+    if cfg.inactive_user_warn_days.is_some_and(|d| d < 1) || cfg.inactive_user_action_days.is_some_and(|d| d < 1) {
+        err!("`INACTIVE_USER_WARN_DAYS` and `INACTIVE_USER_ACTION_DAYS` have a minimum of 1 day")
+    }
+
+    if let (Some(warn_days), Some(action_days)) = (cfg.inactive_user_warn_days, cfg.inactive_user_action_days) {
+        if action_days <= warn_days {
+            err!("`INACTIVE_USER_ACTION_DAYS` needs to be larger than `INACTIVE_USER_WARN_DAYS`")
+        }
+    }
+
+    if cfg.inactive_user_warn_days.is_some() && !(cfg._enable_smtp && (cfg.smtp_host.is_some() || cfg.use_sendmail)) {
+        err!("`INACTIVE_USER_WARN_DAYS` requires mail to be enabled, the warnings are sent by email")
+    }
+
+    if !["disable", "revoke"].contains(&cfg.inactive_user_action.as_str()) {
+        err!("`INACTIVE_USER_ACTION` is invalid. It needs to be one of the following options: disable or revoke")
+    }
+
+    if cfg.session_idle_days < 0 || cfg.session_absolute_days < 0 {
+        err!("`SESSION_IDLE_DAYS` and `SESSION_ABSOLUTE_DAYS` can't be negative")
+    }
+
+    if !cfg.backup_schedule.is_empty() && cfg.backup_schedule.parse::<Schedule>().is_err() {
+        err!("`BACKUP_SCHEDULE` is not a valid cron expression")
+    }
+
+    if let Some(days) = cfg.backup_retain_days {
+        if days < 1 {
+            err!("`BACKUP_RETAIN_DAYS` has a minimum of 1 day")
+        }
+    }
+
+    if let Some(key_file) = &cfg.backup_public_key {
+        let key_ok = std::fs::read(key_file).ok().and_then(|pem| openssl::rsa::Rsa::public_key_from_pem(&pem).ok());
+        if key_ok.is_none() {
+            err!("`BACKUP_PUBLIC_KEY` needs to point to a PEM encoded RSA public key")
+        }
+    }
+
+    if cfg.sso_enabled {
+        if cfg.sso_client_id.is_empty() || cfg.sso_client_secret.is_empty() {
+            err!("`SSO_CLIENT_ID` and `SSO_CLIENT_SECRET` must be set when SSO is enabled")
+        }
+        let authority = cfg.sso_authority.to_lowercase();
+        if !(authority.starts_with("http://") || authority.starts_with("https://")) || Url::parse(&authority).is_err() {
+            err!("`SSO_AUTHORITY` must be a valid URL and start with 'http://' or 'https://'")
+        }
+    }
+
     if cfg._enable_duo
         && (cfg.duo_host.is_some() || cfg.duo_ikey.is_some() || cfg.duo_skey.is_some())
         && !(cfg.duo_host.is_some() && cfg.duo_ikey.is_some() && cfg.duo_skey.is_some())
@@ -1034,6 +1401,18 @@ fn validate_config(cfg: &ConfigItems) -> Result<(), Error> {
         err!("`AUTH_REQUEST_PURGE_SCHEDULE` is not a valid cron expression")
     }
 
+    if !cfg.webhook_delivery_schedule.is_empty() && cfg.webhook_delivery_schedule.parse::<Schedule>().is_err() {
+        err!("`WEBHOOK_DELIVERY_SCHEDULE` is not a valid cron expression")
+    }
+
+    if cfg.webhook_max_attempts == 0 {
+        err!("`WEBHOOK_MAX_ATTEMPTS` must be at least 1")
+    }
+
+    if !cfg.sso_auth_purge_schedule.is_empty() && cfg.sso_auth_purge_schedule.parse::<Schedule>().is_err() {
+        err!("`SSO_AUTH_PURGE_SCHEDULE` is not a valid cron expression")
+    }
+
     if !cfg.disable_admin_token {
         match cfg.admin_token.as_ref() {
             Some(t) if t.starts_with("$argon2") => {
@@ -1082,6 +1461,10 @@ fn extract_url_path(url: &str) -> String {
     }
 }
 
+fn generate_sso_callback_path(domain: &str) -> String {
+    format!("{domain}/identity/connect/oidc-signin")
+}
+
 fn generate_smtp_img_src(embed_images: bool, domain: &str) -> String {
     if embed_images {
         "cid:".to_string()
@@ -1132,6 +1515,16 @@ fn smtp_convert_deprecated_ssl_options(smtp_ssl: Option<bool>, smtp_explicit_tls
     "starttls".to_string()
 }
 
+/// Quotes a value for a `.env` file. Single quotes keep the value as is,
+/// double quotes are only used when needed since those also expand variables and escapes.
+fn quote_env_value(value: &str) -> String {
+    if !value.contains(['\'', '\n']) {
+        return format!("'{value}'");
+    }
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"").replace('$', "\\$").replace('\n', "\\n");
+    format!("\"{escaped}\"")
+}
+
 impl Config {
     pub fn load() -> Result<Self, Error> {
         // Loading from env and file
@@ -1199,6 +1592,64 @@ impl Config {
         Ok(())
     }
 
+    /// Validates `other` the same way as `update_config` with `ignore_non_editable`, without saving or applying it.
+    /// Returns a standalone config with the proposed values, which can be used to test them.
+    pub fn dry_run_update(&self, mut other: ConfigBuilder) -> Result<Config, Error> {
+        other.clear_non_editable();
+        self.dry_run(other)
+    }
+
+    /// Validates `other` the same way as `patch_config`, without saving or applying it
+    pub fn dry_run_patch(&self, mut other: ConfigBuilder) -> Result<Config, Error> {
+        other.clear_non_editable();
+        let builder = {
+            let usr = &self.inner.read().unwrap()._usr;
+            let mut _overrides = Vec::new();
+            usr.merge(&other, false, &mut _overrides)
+        };
+        self.dry_run(builder)
+    }
+
+    fn dry_run(&self, builder: ConfigBuilder) -> Result<Config, Error> {
+        let mut overrides = Vec::new();
+        let env = self.inner.read().unwrap()._env.clone();
+        let config = env.merge(&builder, false, &mut overrides).build();
+        validate_config(&config)?;
+
+        Ok(Config {
+            inner: RwLock::new(Inner {
+                rocket_shutdown_handle: None,
+                templates: Handlebars::new(),
+                config,
+                _env: env,
+                _usr: builder,
+                _overrides: overrides,
+            }),
+        })
+    }
+
+    /// Creates a standalone config with only the options of `builder` and the defaults, ignoring the environment.
+    /// It is not validated, the tests use it to not depend on the configuration of the machine running them.
+    #[cfg(test)]
+    pub fn from_builder(builder: ConfigBuilder) -> Self {
+        Config {
+            inner: RwLock::new(Inner {
+                rocket_shutdown_handle: None,
+                templates: Handlebars::new(),
+                config: builder.build(),
+                _env: ConfigBuilder::default(),
+                _usr: builder,
+                _overrides: Vec::new(),
+            }),
+        }
+    }
+
+    /// Only changes the options which are set in `other`, the options which are not editable are ignored
+    pub fn patch_config(&self, mut other: ConfigBuilder) -> Result<(), Error> {
+        other.clear_non_editable();
+        self.update_config_partial(other)
+    }
+
     fn update_config_partial(&self, other: ConfigBuilder) -> Result<(), Error> {
         let builder = {
             let usr = &self.inner.read().unwrap()._usr;
@@ -1223,6 +1674,11 @@ impl Config {
         whitelist.is_empty() || whitelist.split(',').any(|d| d.trim() == email_domain)
     }
 
+    /// Tests whether an account is excluded from the inactive user warnings and actions
+    pub fn is_inactive_user_allowlisted(&self, email: &str) -> bool {
+        self.inactive_user_allowlist().split(',').any(|e| e.trim().eq_ignore_ascii_case(email))
+    }
+
     /// Tests whether signup is allowed for an email address, taking into
     /// account the signups_allowed and signups_domains_whitelist settings.
     pub fn is_signup_allowed(&self, email: &str) -> bool {
@@ -1247,6 +1703,25 @@ impl Config {
         }
     }
 
+    /// The options saved in the config file, which are the ones changed in the admin panel
+    pub fn get_user_config(&self) -> ConfigBuilder {
+        self.inner.read().unwrap()._usr.clone()
+    }
+
+    /// The effective configuration, being the environment variables overridden by the config file, without the defaults.
+    /// The passwords and secret keys are only included when `include_secrets` is set.
+    pub fn export_config(&self, include_secrets: bool) -> ConfigBuilder {
+        let mut builder = {
+            let inner = &self.inner.read().unwrap();
+            let mut _overrides = Vec::new();
+            inner._env.merge(&inner._usr, false, &mut _overrides)
+        };
+        if !include_secrets {
+            builder.clear_passwords();
+        }
+        builder
+    }
+
     pub fn delete_user_config(&self) -> Result<(), Error> {
         std::fs::remove_file(&*CONFIG_FILE)?;
 
@@ -1381,6 +1856,8 @@ where
     reg!("email/emergency_access_recovery_reminder", ".html");
     reg!("email/emergency_access_recovery_timed_out", ".html");
     reg!("email/incomplete_2fa_login", ".html");
+    reg!("email/login_lockout", ".html");
+    reg!("email/inactive_account_warning", ".html");
     reg!("email/invite_accepted", ".html");
     reg!("email/invite_confirmed", ".html");
     reg!("email/new_device_logged_in", ".html");
@@ -1402,8 +1879,13 @@ where
     reg!("admin/login");
     reg!("admin/settings");
     reg!("admin/users");
+    reg!("admin/user_details");
     reg!("admin/organizations");
     reg!("admin/diagnostics");
+    reg!("admin/accounts");
+    reg!("admin/account");
+    reg!("admin/audit");
+    reg!("admin/api_tokens");
 
     reg!("404");
 
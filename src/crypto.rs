@@ -33,6 +33,13 @@ pub fn hmac_sign(key: &str, data: &str) -> String {
     HEXLOWER.encode(signature.as_ref())
 }
 
+//
+// SHA-256
+//
+pub fn sha256_hex(data: &str) -> String {
+    HEXLOWER.encode(digest::digest(&digest::SHA256, data.as_bytes()).as_ref())
+}
+
 //
 // Random values
 //
@@ -106,6 +113,35 @@ pub fn generate_api_key() -> String {
     get_random_string_alphanum(30)
 }
 
+//
+// Argon2id PHC strings, used by the admin token and the admin accounts
+//
+
+/// Returns the name and the parameters of the "bitwarden" (default) or "owasp" preset
+pub fn argon2id_preset(preset: Option<&str>) -> (&'static str, argon2::Params) {
+    let (name, m_cost, t_cost, p_cost) = match preset {
+        Some("owasp") => ("owasp", 19456, 2, 1),
+        _ => ("bitwarden", 65540, 3, 4),
+    };
+    (name, argon2::Params::new(m_cost, t_cost, p_cost, None).expect("Invalid Argon2 parameters"))
+}
+
+pub fn hash_argon2id(password: &str, params: argon2::Params) -> Result<String, argon2::password_hash::Error> {
+    use argon2::{password_hash::SaltString, Algorithm::Argon2id, Argon2, PasswordHasher, Version::V0x13};
+
+    let argon2 = Argon2::new(Argon2id, V0x13, params);
+    let salt = SaltString::encode_b64(&get_random_bytes::<32>())?;
+    Ok(argon2.hash_password(password.as_bytes(), &salt)?.to_string())
+}
+
+/// Verifies a password against an Argon2 PHC string, the parameters stored in the PHC string are used
+pub fn verify_argon2(password: &str, phc: &str) -> Result<bool, argon2::password_hash::Error> {
+    use argon2::password_hash::{PasswordHash, PasswordVerifier};
+
+    let hash = PasswordHash::new(phc)?;
+    Ok(argon2::Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
 //
 // Constant time compare
 //
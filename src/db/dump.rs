@@ -0,0 +1,229 @@
+//
+// Backend neutral dump and restore of all the tables of the database
+//
+// Every table is dumped into its own `<table>.jsonl` file, with one JSON object per row,
+// so neither the export nor the import needs to load a whole table into memory.
+//
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    api::EmptyResult,
+    db::DbConn,
+    error::{Error, MapResult},
+    util::get_uuid,
+    CONFIG,
+};
+
+#[cfg(mysql)]
+use super::models::__mysql_model;
+#[cfg(postgresql)]
+use super::models::__postgresql_model;
+#[cfg(sqlite)]
+use super::models::__sqlite_model;
+
+// Number of rows inserted per statement, this keeps the number of bound parameters well below the limits of all backends
+const IMPORT_CHUNK_SIZE: usize = 100;
+
+fn table_file(folder: &Path, table: &str) -> PathBuf {
+    folder.join(format!("{table}.jsonl"))
+}
+
+// Writes all the rows of a table into its file in `$folder`, and returns the number of rows.
+// The rows are loaded one by one where the backend supports it, to not load the whole table into memory.
+macro_rules! export_table {
+    ( $conn:ident, $folder:ident, $table:ident, $model:ty, $loading_mode:ty ) => {{
+        let mut file = BufWriter::new(File::create(table_file($folder, stringify!($table)))?);
+        let mut count = 0;
+        for row in $table::table
+            .load_iter::<$model, $loading_mode>($conn)
+            .map_res(concat!("Error loading ", stringify!($table)))?
+        {
+            serde_json::to_writer(&mut file, &row.map_res(concat!("Error loading ", stringify!($table)))?)?;
+            file.write_all(b"\n")?;
+            count += 1;
+        }
+        file.flush()?;
+        (stringify!($table).to_string(), count)
+    }};
+}
+
+// Inserts the rows of the file of a table in `$folder`, and returns the number of rows.
+// A missing file is imported as an empty table.
+macro_rules! import_table {
+    ( $conn:ident, $folder:ident, $table:ident, $model:ty ) => {{
+        let path = table_file($folder, stringify!($table));
+        let mut count = 0;
+        if path.exists() {
+            let mut lines = BufReader::new(File::open(path)?).lines().peekable();
+            while lines.peek().is_some() {
+                let rows = lines
+                    .by_ref()
+                    .take(IMPORT_CHUNK_SIZE)
+                    .map(|line| Ok(serde_json::from_str::<$model>(&line?)?))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                let _: () = diesel::insert_into($table::table)
+                    .values(&rows)
+                    .execute($conn)
+                    .map_res(concat!("Error importing ", stringify!($table)))?;
+                count += rows.len();
+            }
+        }
+        count
+    }};
+}
+
+macro_rules! dump_tables {
+    ( $( $table:ident: $model:ident ),+ $(,)? ) => { pastey::paste! {
+        /// All the tables of the database, ordered so that every table comes after the tables it references
+        pub const TABLES: &[&str] = &[ $( stringify!($table) ),+ ];
+
+        /// Writes all the rows of all the tables into `folder`, and returns the number of rows per table.
+        /// All the tables are read within a single transaction, to get a consistent snapshot of a running server.
+        pub async fn export_tables(folder: &Path, conn: &mut DbConn) -> Result<HashMap<String, usize>, Error> {
+            db_run! { conn:
+                sqlite, mysql {
+                    conn.transaction::<_, Error, _>(|conn| {
+                        Ok(HashMap::from([ $(
+                            export_table!(conn, folder, $table, [<$model Db>], diesel::connection::DefaultLoadingMode),
+                        )+ ]))
+                    })
+                }
+                postgresql {
+                    // The default isolation level of PostgreSQL uses a new snapshot for every statement
+                    conn.build_transaction().repeatable_read().read_only().run::<_, Error, _>(|conn| {
+                        Ok(HashMap::from([ $(
+                            export_table!(conn, folder, $table, [<$model Db>], diesel::pg::PgRowByRowLoadingMode),
+                        )+ ]))
+                    })
+                }
+            }
+        }
+
+        pub async fn count_table(table: &str, conn: &mut DbConn) -> Result<i64, Error> {
+            match table {
+                $( stringify!($table) => db_run! { conn: {
+                    $table::table.count().get_result::<i64>(conn).map_res(concat!("Error counting ", stringify!($table)))
+                }}, )+
+                _ => err!(format!("Unknown table {table}")),
+            }
+        }
+
+        /// Inserts the rows previously exported into `folder`, all the tables are imported within a single transaction.
+        /// The transaction is rolled back when the number of rows of any table differs from `row_counts`.
+        pub async fn import_tables(
+            folder: &Path,
+            row_counts: &HashMap<String, usize>,
+            conn: &mut DbConn,
+        ) -> EmptyResult {
+            db_run! { conn: {
+                conn.transaction::<_, Error, _>(|conn| {
+                    // The tables are imported in an order which doesn't violate any foreign key constraint
+                    $(
+                        let table = stringify!($table);
+                        let found = import_table!(conn, folder, $table, [<$model Db>]);
+                        let expected = row_counts.get(table).copied().unwrap_or_default();
+                        if found != expected {
+                            err!(format!("The table {table} contains {found} rows, but {expected} rows were expected"))
+                        }
+                    )+
+                    Ok(())
+                })
+            }}
+        }
+    }};
+}
+
+dump_tables! {
+    users: User,
+    organizations: Organization,
+    invitations: Invitation,
+    sso_users: SsoUser,
+    sso_auth: SsoAuth,
+    twofactor: TwoFactor,
+    twofactor_incomplete: TwoFactorIncomplete,
+    twofactor_duo_ctx: TwoFactorDuoContext,
+    devices: Device,
+    webauthn_credentials: WebAuthnCredential,
+    auth_requests: AuthRequest,
+    emergency_access: EmergencyAccess,
+    folders: Folder,
+    collections: Collection,
+    ciphers: Cipher,
+    attachments: Attachment,
+    folders_ciphers: FolderCipher,
+    ciphers_collections: CollectionCipher,
+    favorites: Favorite,
+    users_organizations: Membership,
+    users_collections: CollectionUser,
+    groups: Group,
+    groups_users: GroupUser,
+    collections_groups: CollectionGroup,
+    org_policies: OrgPolicy,
+    organization_api_key: OrganizationApiKey,
+    org_webhooks: Webhook,
+    webhook_deliveries: WebhookDelivery,
+    sends: Send,
+    event: Event,
+    admin_accounts: AdminAccount,
+    admin_audit_log: AdminAuditLog,
+    admin_api_tokens: AdminApiToken,
+}
+
+/// Checks that none of the tables contain any rows
+pub async fn is_empty(conn: &mut DbConn) -> Result<bool, Error> {
+    for table in TABLES {
+        if count_table(table, conn).await? > 0 {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Returns a new folder in the data folder, used to stage the files of a dump
+pub fn dump_folder(prefix: &str) -> PathBuf {
+    PathBuf::from(CONFIG.data_folder()).join(format!("{prefix}{}", get_uuid()))
+}
+
+/// Creates a folder for the files of a dump, which is only accessible by the current user as a dump contains secrets
+pub async fn create_dump_folder(folder: &Path) -> EmptyResult {
+    let mut builder = tokio::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder.create(folder).await?;
+    Ok(())
+}
+
+/// Removes a folder created by `create_dump_folder`, failures are only logged
+pub async fn remove_dump_folder(folder: &Path) {
+    if let Err(e) = tokio::fs::remove_dir_all(folder).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!("Unable to remove the folder '{}': {e:?}", folder.display());
+        }
+    }
+}
+
+/// Copies all the tables of the `source` database into the empty `target` database.
+/// The tables are staged in a folder in the data folder, which is removed afterwards.
+/// Returns the number of rows copied per table.
+pub async fn copy_database(source: &mut DbConn, target: &mut DbConn) -> Result<HashMap<String, usize>, Error> {
+    if !is_empty(target).await? {
+        err!("The target database is not empty")
+    }
+
+    let folder = dump_folder(".migrate_");
+    create_dump_folder(&folder).await?;
+    let res = async {
+        let row_counts = export_tables(&folder, source).await?;
+        import_tables(&folder, &row_counts, target).await?;
+        Ok(row_counts)
+    }
+    .await;
+    remove_dump_folder(&folder).await;
+    res
+}
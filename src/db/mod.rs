@@ -121,17 +121,35 @@ macro_rules! generate_connections {
         }
 
         impl DbPool {
-            // For the given database URL, guess its type, run migrations, create pool, and return it
+            // For the configured database URL, guess its type, run migrations, create pool, and return it
             pub fn from_config() -> Result<Self, Error> {
-                let url = CONFIG.database_url();
-                let conn_type = DbConnType::from_url(&url)?;
+                Self::from_url(&CONFIG.database_url())
+            }
+
+            // For the given database URL, guess its type, run migrations, create pool, and return it
+            pub fn from_url(url: &str) -> Result<Self, Error> {
+                Self::open(url, true)
+            }
+
+            // Same as `from_url`, but the database is not modified. Fails when the database has pending migrations,
+            // since the queries would not match its schema.
+            pub fn from_url_without_migrations(url: &str) -> Result<Self, Error> {
+                Self::open(url, false)
+            }
+
+            fn open(url: &str, migrate: bool) -> Result<Self, Error> {
+                let conn_type = DbConnType::from_url(url)?;
 
                 match conn_type { $(
                     DbConnType::$name => {
                         #[cfg($name)]
                         {
-                            pastey::paste!{ [< $name _migrations >]::run_migrations()?; }
-                            let manager = ConnectionManager::new(&url);
+                            if migrate {
+                                pastey::paste!{ [< $name _migrations >]::run_migrations(url)?; }
+                            } else if pastey::paste!{ [< $name _migrations >]::has_pending_migrations(url)? } {
+                                err!("The database has pending migrations, start Vaultwarden with it first to update it")
+                            }
+                            let manager = ConnectionManager::new(url);
                             let pool = Pool::builder()
                                 .max_size(CONFIG.database_max_conns())
                                 .connection_timeout(Duration::from_secs(CONFIG.database_timeout()))
@@ -150,6 +168,17 @@ macro_rules! generate_connections {
                     },
                 )+ }
             }
+            // Number of open connections of the pool and how many of them are in use
+            pub fn state(&self) -> (u32, u32) {
+                match self.pool.as_ref().expect("DbPool.pool should always be Some()") {  $(
+                    #[cfg($name)]
+                    DbPoolInner::$name(p) => {
+                        let state = p.state();
+                        (state.connections, state.connections - state.idle_connections)
+                    },
+                )+ }
+            }
+
             // Get a connection from the pool
             pub async fn get(&self) -> Result<DbConn, Error> {
                 let duration = Duration::from_secs(CONFIG.database_timeout());
@@ -364,6 +393,9 @@ macro_rules! db_object {
 // Reexport the models, needs to be after the macros are defined so it can access them
 pub mod models;
 
+// Backend neutral dump and restore of all the tables, used by the backup, restore and migrate-db commands
+pub mod dump;
+
 /// Creates a back-up of the sqlite database
 /// MySQL/MariaDB and PostgreSQL are not supported.
 pub async fn backup_database(conn: &mut DbConn) -> Result<String, Error> {
@@ -429,13 +461,12 @@ mod sqlite_migrations {
     use diesel_migrations::{EmbeddedMigrations, MigrationHarness};
     pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/sqlite");
 
-    pub fn run_migrations() -> Result<(), super::Error> {
+    pub fn run_migrations(url: &str) -> Result<(), super::Error> {
         use diesel::{Connection, RunQueryDsl};
-        let url = crate::CONFIG.database_url();
 
         // Establish a connection to the sqlite database (this will create a new one, if it does
         // not exist, and exit if there is an error).
-        let mut connection = diesel::sqlite::SqliteConnection::establish(&url)?;
+        let mut connection = diesel::sqlite::SqliteConnection::establish(url)?;
 
         // Run the migrations after successfully establishing a connection
         // Disable Foreign Key Checks during migration
@@ -452,6 +483,20 @@ mod sqlite_migrations {
         connection.run_pending_migrations(MIGRATIONS).expect("Error running migrations");
         Ok(())
     }
+
+    pub fn has_pending_migrations(url: &str) -> Result<bool, super::Error> {
+        use diesel::Connection;
+
+        // Establishing the connection would create a new database
+        if !std::path::Path::new(url).exists() {
+            err!(format!("The SQLite database '{url}' does not exist"))
+        }
+        let mut connection = diesel::sqlite::SqliteConnection::establish(url)?;
+        match connection.has_pending_migration(MIGRATIONS) {
+            Ok(pending) => Ok(pending),
+            Err(e) => err!(format!("Unable to check the migrations of the database: {e}")),
+        }
+    }
 }
 
 #[cfg(mysql)]
@@ -459,10 +504,10 @@ mod mysql_migrations {
     use diesel_migrations::{EmbeddedMigrations, MigrationHarness};
     pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/mysql");
 
-    pub fn run_migrations() -> Result<(), super::Error> {
+    pub fn run_migrations(url: &str) -> Result<(), super::Error> {
         use diesel::{Connection, RunQueryDsl};
         // Make sure the database is up to date (create if it doesn't exist, or run the migrations)
-        let mut connection = diesel::mysql::MysqlConnection::establish(&crate::CONFIG.database_url())?;
+        let mut connection = diesel::mysql::MysqlConnection::establish(url)?;
         // Disable Foreign Key Checks during migration
 
         // Scoped to a connection/session.
@@ -473,6 +518,15 @@ mod mysql_migrations {
         connection.run_pending_migrations(MIGRATIONS).expect("Error running migrations");
         Ok(())
     }
+
+    pub fn has_pending_migrations(url: &str) -> Result<bool, super::Error> {
+        use diesel::Connection;
+        let mut connection = diesel::mysql::MysqlConnection::establish(url)?;
+        match connection.has_pending_migration(MIGRATIONS) {
+            Ok(pending) => Ok(pending),
+            Err(e) => err!(format!("Unable to check the migrations of the database: {e}")),
+        }
+    }
 }
 
 #[cfg(postgresql)]
@@ -480,11 +534,20 @@ mod postgresql_migrations {
     use diesel_migrations::{EmbeddedMigrations, MigrationHarness};
     pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/postgresql");
 
-    pub fn run_migrations() -> Result<(), super::Error> {
+    pub fn run_migrations(url: &str) -> Result<(), super::Error> {
         use diesel::Connection;
         // Make sure the database is up to date (create if it doesn't exist, or run the migrations)
-        let mut connection = diesel::pg::PgConnection::establish(&crate::CONFIG.database_url())?;
+        let mut connection = diesel::pg::PgConnection::establish(url)?;
         connection.run_pending_migrations(MIGRATIONS).expect("Error running migrations");
         Ok(())
     }
+
+    pub fn has_pending_migrations(url: &str) -> Result<bool, super::Error> {
+        use diesel::Connection;
+        let mut connection = diesel::pg::PgConnection::establish(url)?;
+        match connection.has_pending_migration(MIGRATIONS) {
+            Ok(pending) => Ok(pending),
+            Err(e) => err!(format!("Unable to check the migrations of the database: {e}")),
+        }
+    }
 }
@@ -0,0 +1,229 @@
+use chrono::{NaiveDateTime, Utc};
+use data_encoding::BASE32;
+use derive_more::{AsRef, Deref, Display, From};
+use serde_json::Value;
+
+use crate::{
+    api::{
+        core::two_factor::authenticator::{check_totp_code, TotpCheck},
+        EmptyResult,
+    },
+    crypto,
+    db::DbConn,
+    error::MapResult,
+    util::format_date,
+};
+use macros::UuidFromParam;
+
+db_object! {
+    // A named account for the admin panel, used next to or instead of the shared `ADMIN_TOKEN`
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
+    #[diesel(table_name = admin_accounts)]
+    #[diesel(treat_none_as_null = true)]
+    #[diesel(primary_key(uuid))]
+    pub struct AdminAccount {
+        pub uuid: AdminAccountId,
+        pub username: String,
+        pub password_hash: String, // Argon2id PHC string
+        pub role: i32,
+        pub totp_secret: Option<String>,
+        pub totp_last_used: i64,
+        pub enabled: bool,
+        pub created_at: NaiveDateTime,
+        pub last_login_at: Option<NaiveDateTime>,
+    }
+}
+
+// Every role includes the permissions of the roles before it
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, num_derive::FromPrimitive)]
+pub enum AdminRole {
+    // View the users, organizations and diagnostics
+    ReadOnly = 0,
+    // Enable, disable and deauthorize users and resend invitations
+    UserSupport = 1,
+    // Everything, including the settings and the admin accounts
+    Admin = 2,
+}
+
+impl AdminRole {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "0" | "read-only" => Some(Self::ReadOnly),
+            "1" | "user-support" => Some(Self::UserSupport),
+            "2" | "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ReadOnly => "read-only",
+            Self::UserSupport => "user-support",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Local methods
+impl AdminAccount {
+    pub fn new(username: String, role: AdminRole) -> Self {
+        Self {
+            uuid: AdminAccountId(crate::util::get_uuid()),
+            username: username.to_lowercase(),
+            password_hash: String::new(),
+            role: role as i32,
+            totp_secret: None,
+            totp_last_used: 0,
+            enabled: true,
+            created_at: Utc::now().naive_utc(),
+            last_login_at: None,
+        }
+    }
+
+    pub fn role(&self) -> AdminRole {
+        num_traits::FromPrimitive::from_i32(self.role).unwrap_or(AdminRole::ReadOnly)
+    }
+
+    /// Hashes the password with the same Argon2id parameters as the `hash` command
+    pub fn set_password(&mut self, password: &str) -> EmptyResult {
+        if password.len() < MIN_PASSWORD_LENGTH {
+            err!(format!("The password must contain at least {MIN_PASSWORD_LENGTH} characters"))
+        }
+
+        let (_, params) = crypto::argon2id_preset(None);
+        match crypto::hash_argon2id(password, params) {
+            Ok(hash) => {
+                self.password_hash = hash;
+                Ok(())
+            }
+            Err(e) => err!(format!("Unable to hash the password: {e}")),
+        }
+    }
+
+    pub fn check_password(&self, password: &str) -> bool {
+        crypto::verify_argon2(password, &self.password_hash).unwrap_or(false)
+    }
+
+    /// Checks a TOTP code of the account, the account needs to be saved afterwards to prevent the reuse of the code
+    pub fn check_totp(&mut self, totp_code: &str) -> bool {
+        let Some(decoded_secret) = self.totp_secret.as_ref().and_then(|s| BASE32.decode(s.as_bytes()).ok()) else {
+            return false;
+        };
+
+        match check_totp_code(&decoded_secret, totp_code, self.totp_last_used) {
+            TotpCheck::Valid(time_step) => {
+                self.totp_last_used = time_step;
+                true
+            }
+            TotpCheck::AlreadyUsed | TotpCheck::Invalid => false,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.uuid,
+            "username": self.username,
+            "role": self.role().name(),
+            "totp_enabled": self.totp_secret.is_some(),
+            "enabled": self.enabled,
+            "created_at": format_date(&self.created_at),
+            "last_login_at": self.last_login_at.as_ref().map(format_date),
+        })
+    }
+}
+
+/// Database methods
+impl AdminAccount {
+    pub async fn save(&self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn:
+            sqlite, mysql {
+                diesel::replace_into(admin_accounts::table)
+                    .values(AdminAccountDb::to_db(self))
+                    .execute(conn)
+                    .map_res("Error saving admin account")
+            }
+            postgresql {
+                let value = AdminAccountDb::to_db(self);
+                diesel::insert_into(admin_accounts::table)
+                    .values(&value)
+                    .on_conflict(admin_accounts::uuid)
+                    .do_update()
+                    .set(&value)
+                    .execute(conn)
+                    .map_res("Error saving admin account")
+            }
+        }
+    }
+
+    pub async fn delete(self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::delete(admin_accounts::table.filter(admin_accounts::uuid.eq(self.uuid)))
+                .execute(conn)
+                .map_res("Error deleting admin account")
+        }}
+    }
+
+    pub async fn find_by_uuid(uuid: &AdminAccountId, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            admin_accounts::table
+                .filter(admin_accounts::uuid.eq(uuid))
+                .first::<AdminAccountDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    pub async fn find_by_username(username: &str, conn: &mut DbConn) -> Option<Self> {
+        let lower_username = username.to_lowercase();
+        db_run! { conn: {
+            admin_accounts::table
+                .filter(admin_accounts::username.eq(lower_username))
+                .first::<AdminAccountDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    pub async fn get_all(conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            admin_accounts::table
+                .order(admin_accounts::username.asc())
+                .load::<AdminAccountDb>(conn)
+                .expect("Error loading admin accounts")
+                .from_db()
+        }}
+    }
+
+    pub async fn count_enabled_by_role(role: AdminRole, conn: &mut DbConn) -> i64 {
+        db_run! { conn: {
+            admin_accounts::table
+                .filter(admin_accounts::role.eq(role as i32))
+                .filter(admin_accounts::enabled.eq(true))
+                .count()
+                .first::<i64>(conn)
+                .unwrap_or(0)
+        }}
+    }
+}
+
+#[derive(
+    Clone,
+    Debug,
+    AsRef,
+    Deref,
+    DieselNewType,
+    Display,
+    From,
+    FromForm,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    UuidFromParam,
+)]
+#[deref(forward)]
+#[from(forward)]
+pub struct AdminAccountId(String);
@@ -0,0 +1,215 @@
+use chrono::{NaiveDateTime, Utc};
+use derive_more::{AsRef, Deref, Display, From};
+use serde_json::Value;
+
+use crate::{api::EmptyResult, crypto, db::DbConn, error::MapResult, util::format_date};
+use macros::UuidFromParam;
+
+db_object! {
+    // A bearer token for the JSON admin API, only the hash of the token is stored
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
+    #[diesel(table_name = admin_api_tokens)]
+    #[diesel(treat_none_as_null = true)]
+    #[diesel(primary_key(uuid))]
+    pub struct AdminApiToken {
+        pub uuid: AdminApiTokenId,
+        pub name: String,
+        pub token_hash: String,
+        pub scopes: String, // JSON array of the scope names
+        pub created_by: String,
+        pub created_at: NaiveDateTime,
+        pub expires_at: Option<NaiveDateTime>,
+        pub last_used_at: Option<NaiveDateTime>,
+        pub revoked_at: Option<NaiveDateTime>,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AdminApiScope {
+    UsersRead,
+    UsersWrite,
+    OrganizationsRead,
+    OrganizationsWrite,
+    InvitationsWrite,
+    ConfigRead,
+    ConfigWrite,
+    BackupsRead,
+    BackupsWrite,
+}
+
+impl AdminApiScope {
+    pub const ALL: [Self; 9] = [
+        Self::UsersRead,
+        Self::UsersWrite,
+        Self::OrganizationsRead,
+        Self::OrganizationsWrite,
+        Self::InvitationsWrite,
+        Self::ConfigRead,
+        Self::ConfigWrite,
+        Self::BackupsRead,
+        Self::BackupsWrite,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::UsersRead => "users:read",
+            Self::UsersWrite => "users:write",
+            Self::OrganizationsRead => "organizations:read",
+            Self::OrganizationsWrite => "organizations:write",
+            Self::InvitationsWrite => "invitations:write",
+            Self::ConfigRead => "config:read",
+            Self::ConfigWrite => "config:write",
+            Self::BackupsRead => "backups:read",
+            Self::BackupsWrite => "backups:write",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.name() == name)
+    }
+}
+
+// Makes the tokens recognizable, for example by secret scanners
+const TOKEN_PREFIX: &str = "vwadm_";
+
+/// Local methods
+impl AdminApiToken {
+    /// Returns the new token together with its secret, which is only available at this moment
+    pub fn new(
+        name: String,
+        scopes: &[AdminApiScope],
+        created_by: String,
+        expires_at: Option<NaiveDateTime>,
+    ) -> (Self, String) {
+        let secret = format!("{TOKEN_PREFIX}{}", crypto::get_random_string_alphanum(40));
+        let token = Self {
+            uuid: AdminApiTokenId(crate::util::get_uuid()),
+            name,
+            token_hash: crypto::sha256_hex(&secret),
+            scopes: serde_json::to_string(&scopes.iter().map(|s| s.name()).collect::<Vec<_>>()).unwrap_or_default(),
+            created_by,
+            created_at: Utc::now().naive_utc(),
+            expires_at,
+            last_used_at: None,
+            revoked_at: None,
+        };
+        (token, secret)
+    }
+
+    pub fn scope_list(&self) -> Vec<AdminApiScope> {
+        serde_json::from_str::<Vec<String>>(&self.scopes)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|s| AdminApiScope::from_name(s))
+            .collect()
+    }
+
+    pub fn has_scope(&self, scope: AdminApiScope) -> bool {
+        self.scope_list().contains(&scope)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|e| e > Utc::now().naive_utc())
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.uuid,
+            "name": self.name,
+            "scopes": self.scope_list().iter().map(|s| s.name()).collect::<Vec<_>>(),
+            "created_by": self.created_by,
+            "created_at": format_date(&self.created_at),
+            "expires_at": self.expires_at.as_ref().map(format_date),
+            "last_used_at": self.last_used_at.as_ref().map(format_date),
+            "revoked_at": self.revoked_at.as_ref().map(format_date),
+            "active": self.is_active(),
+        })
+    }
+}
+
+/// Database methods
+impl AdminApiToken {
+    pub async fn save(&self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn:
+            sqlite, mysql {
+                diesel::replace_into(admin_api_tokens::table)
+                    .values(AdminApiTokenDb::to_db(self))
+                    .execute(conn)
+                    .map_res("Error saving admin API token")
+            }
+            postgresql {
+                let value = AdminApiTokenDb::to_db(self);
+                diesel::insert_into(admin_api_tokens::table)
+                    .values(&value)
+                    .on_conflict(admin_api_tokens::uuid)
+                    .do_update()
+                    .set(&value)
+                    .execute(conn)
+                    .map_res("Error saving admin API token")
+            }
+        }
+    }
+
+    pub async fn find_by_uuid(uuid: &AdminApiTokenId, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            admin_api_tokens::table
+                .filter(admin_api_tokens::uuid.eq(uuid))
+                .first::<AdminApiTokenDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    /// Looks up a token by its secret, the token still needs to be checked with `is_active`
+    pub async fn find_by_secret(secret: &str, conn: &mut DbConn) -> Option<Self> {
+        let token_hash = crypto::sha256_hex(secret);
+        db_run! { conn: {
+            admin_api_tokens::table
+                .filter(admin_api_tokens::token_hash.eq(token_hash))
+                .first::<AdminApiTokenDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    pub async fn get_all(conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            admin_api_tokens::table
+                .order(admin_api_tokens::created_at.desc())
+                .load::<AdminApiTokenDb>(conn)
+                .expect("Error loading admin API tokens")
+                .from_db()
+        }}
+    }
+
+    pub async fn update_last_used(&mut self, conn: &mut DbConn) -> EmptyResult {
+        let now = Utc::now().naive_utc();
+        self.last_used_at = Some(now);
+        db_run! { conn: {
+            diesel::update(admin_api_tokens::table.filter(admin_api_tokens::uuid.eq(&self.uuid)))
+                .set(admin_api_tokens::last_used_at.eq(now))
+                .execute(conn)
+                .map_res("Error updating the last use of the admin API token")
+        }}
+    }
+}
+
+#[derive(
+    Clone,
+    Debug,
+    AsRef,
+    Deref,
+    DieselNewType,
+    Display,
+    From,
+    FromForm,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    UuidFromParam,
+)]
+#[deref(forward)]
+#[from(forward)]
+pub struct AdminApiTokenId(String);
@@ -0,0 +1,160 @@
+use chrono::{NaiveDateTime, Utc};
+use derive_more::{AsRef, Deref, Display, From};
+use serde_json::Value;
+
+use crate::{api::EmptyResult, db::DbConn, error::MapResult, util::format_date};
+use macros::UuidFromParam;
+
+db_object! {
+    // An action done in the admin panel, kept to know who changed what
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
+    #[diesel(table_name = admin_audit_log)]
+    #[diesel(treat_none_as_null = true)]
+    #[diesel(primary_key(uuid))]
+    pub struct AdminAuditLog {
+        pub uuid: AdminAuditLogId,
+        pub created_at: NaiveDateTime,
+        pub actor: String, // Username of the admin account, or the way the admin panel was accessed without one
+        pub ip_address: String,
+        pub action: String,
+        pub target_type: Option<String>,
+        pub target_id: Option<String>,
+        pub details: Option<String>, // JSON, for example the changed settings
+    }
+}
+
+/// The filters of the audit log page, all of them are optional
+#[derive(Default, FromForm)]
+pub struct AdminAuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub target: Option<String>,
+    pub start: Option<String>, // YYYY-MM-DD
+    pub end: Option<String>,   // YYYY-MM-DD, inclusive
+}
+
+impl AdminAuditFilter {
+    fn date(value: Option<&str>) -> Option<chrono::NaiveDate> {
+        value.filter(|v| !v.is_empty()).and_then(|v| chrono::NaiveDate::parse_from_str(v, "%Y-%m-%d").ok())
+    }
+
+    fn start_date(&self) -> Option<NaiveDateTime> {
+        Self::date(self.start.as_deref()).and_then(|d| d.and_hms_opt(0, 0, 0))
+    }
+
+    fn end_date(&self) -> Option<NaiveDateTime> {
+        Self::date(self.end.as_deref()).and_then(|d| d.succ_opt()).and_then(|d| d.and_hms_opt(0, 0, 0))
+    }
+
+    fn non_empty(value: &Option<String>) -> Option<String> {
+        value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(String::from)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "actor": self.actor,
+            "action": self.action,
+            "target": self.target,
+            "start": self.start,
+            "end": self.end,
+        })
+    }
+}
+
+/// Local methods
+impl AdminAuditLog {
+    pub fn new(actor: String, ip_address: String, action: &str) -> Self {
+        Self {
+            uuid: AdminAuditLogId(crate::util::get_uuid()),
+            created_at: Utc::now().naive_utc(),
+            actor,
+            ip_address,
+            action: action.to_string(),
+            target_type: None,
+            target_id: None,
+            details: None,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.uuid,
+            "created_at": format_date(&self.created_at),
+            "actor": self.actor,
+            "ip_address": self.ip_address,
+            "action": self.action,
+            "target_type": self.target_type,
+            "target_id": self.target_id,
+            "details": self.details,
+        })
+    }
+}
+
+/// Database methods
+impl AdminAuditLog {
+    pub async fn save(&self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::insert_into(admin_audit_log::table)
+                .values(AdminAuditLogDb::to_db(self))
+                .execute(conn)
+                .map_res("Error saving admin audit log entry")
+        }}
+    }
+
+    /// Returns the newest entries first, `limit` is ignored when it is `None`
+    pub async fn find(filter: &AdminAuditFilter, limit: Option<i64>, conn: &mut DbConn) -> Vec<Self> {
+        let actor = AdminAuditFilter::non_empty(&filter.actor);
+        let action = AdminAuditFilter::non_empty(&filter.action);
+        let target = AdminAuditFilter::non_empty(&filter.target);
+        let start = filter.start_date();
+        let end = filter.end_date();
+
+        db_run! { conn: {
+            let mut query = admin_audit_log::table.into_boxed();
+            if let Some(actor) = actor {
+                query = query.filter(admin_audit_log::actor.eq(actor));
+            }
+            if let Some(action) = action {
+                query = query.filter(admin_audit_log::action.eq(action));
+            }
+            if let Some(target) = target {
+                query = query.filter(admin_audit_log::target_id.eq(target));
+            }
+            if let Some(start) = start {
+                query = query.filter(admin_audit_log::created_at.ge(start));
+            }
+            if let Some(end) = end {
+                query = query.filter(admin_audit_log::created_at.lt(end));
+            }
+            if let Some(limit) = limit {
+                query = query.limit(limit);
+            }
+
+            query
+                .order(admin_audit_log::created_at.desc())
+                .load::<AdminAuditLogDb>(conn)
+                .expect("Error loading admin audit log")
+                .from_db()
+        }}
+    }
+}
+
+#[derive(
+    Clone,
+    Debug,
+    AsRef,
+    Deref,
+    DieselNewType,
+    Display,
+    From,
+    FromForm,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    UuidFromParam,
+)]
+#[deref(forward)]
+#[from(forward)]
+pub struct AdminAuditLogId(String);
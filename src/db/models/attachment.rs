@@ -1,15 +1,13 @@
-use std::io::ErrorKind;
-
 use bigdecimal::{BigDecimal, ToPrimitive};
 use derive_more::{AsRef, Deref, Display};
 use serde_json::Value;
 
 use super::{CipherId, OrganizationId, UserId};
-use crate::CONFIG;
+use crate::storage::{storage, PathType};
 use macros::IdFromParam;
 
 db_object! {
-    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
     #[diesel(table_name = attachments)]
     #[diesel(treat_none_as_null = true)]
     #[diesel(primary_key(id))]
@@ -40,11 +38,16 @@ impl Attachment {
         }
     }
 
-    pub fn get_file_path(&self) -> String {
-        format!("{}/{}/{}", CONFIG.attachments_folder(), self.cipher_uuid, self.id)
+    /// Path of the file relative to the attachments storage root
+    pub fn get_storage_path(&self) -> String {
+        format!("{}/{}", self.cipher_uuid, self.id)
     }
 
     pub fn get_url(&self, host: &str) -> String {
+        if let Some(url) = storage().presigned_url(PathType::Attachments, &self.get_storage_path()) {
+            return url;
+        }
+
         let token = encode_jwt(&generate_file_download_claims(self.cipher_uuid.clone(), self.id.clone()));
         format!("{}/attachments/{}/{}?token={}", host, self.cipher_uuid, self.id, token)
     }
@@ -103,32 +106,30 @@ impl Attachment {
     }
 
     pub async fn delete(&self, conn: &mut DbConn) -> EmptyResult {
-        db_run! { conn: {
-            let _: () = crate::util::retry(
+        let _: () = db_run! { conn: {
+            crate::util::retry(
                 || diesel::delete(attachments::table.filter(attachments::id.eq(&self.id))).execute(conn),
                 10,
             )
-            .map_res("Error deleting attachment")?;
-
-            let file_path = &self.get_file_path();
-
-            match std::fs::remove_file(file_path) {
-                // Ignore "file not found" errors. This can happen when the
-                // upstream caller has already cleaned up the file as part of
-                // its own error handling.
-                Err(e) if e.kind() == ErrorKind::NotFound => {
-                    debug!("File '{}' already deleted.", file_path);
-                    Ok(())
-                }
-                Err(e) => Err(e.into()),
-                _ => Ok(()),
-            }
-        }}
+            .map_res("Error deleting attachment")
+        }}?;
+
+        storage().delete(PathType::Attachments, &self.get_storage_path()).await
     }
 
     pub async fn delete_all_by_cipher(cipher_uuid: &CipherId, conn: &mut DbConn) -> EmptyResult {
-        for attachment in Attachment::find_by_cipher(cipher_uuid, conn).await {
-            attachment.delete(conn).await?;
+        let attachments = Attachment::find_by_cipher(cipher_uuid, conn).await;
+        let _: () = db_run! { conn: {
+            diesel::delete(attachments::table.filter(attachments::cipher_uuid.eq(cipher_uuid)))
+                .execute(conn)
+                .map_res("Error deleting attachments")
+        }}?;
+
+        // The rows are gone already, an unreachable storage backend only leaves orphaned files behind
+        for attachment in attachments {
+            if let Err(e) = storage().delete(PathType::Attachments, &attachment.get_storage_path()).await {
+                error!("Unable to delete the file of attachment {}: {e:?}", attachment.id);
+            }
         }
         Ok(())
     }
@@ -36,6 +36,8 @@ db_object! {
 }
 
 impl AuthRequest {
+    pub const ADMIN_REQUEST_EXPIRATION_DAYS: i64 = 7;
+
     pub fn new(
         user_uuid: UserId,
         request_device_identifier: DeviceId,
@@ -66,6 +68,18 @@ impl AuthRequest {
         }
     }
 
+    /// A request approved by an organization admin instead of another device of the user, used with trusted devices
+    pub fn is_admin_request(&self) -> bool {
+        self.organization_uuid.is_some()
+    }
+
+    /// Admin requests are answered for a week, like the official server, after that the user has to ask again
+    pub fn is_expired_admin_request(&self) -> bool {
+        let admin_expiry_time =
+            Utc::now().naive_utc() - chrono::TimeDelta::try_days(Self::ADMIN_REQUEST_EXPIRATION_DAYS).unwrap();
+        self.is_admin_request() && self.creation_date <= admin_expiry_time
+    }
+
     pub fn to_json_for_pending_device(&self) -> Value {
         json!({
             "id": self.uuid,
@@ -156,6 +170,32 @@ impl AuthRequest {
         }}
     }
 
+    /// The admin requests of an organization which were not answered yet
+    pub async fn find_pending_by_org(org_uuid: &OrganizationId, conn: &mut DbConn) -> Vec<Self> {
+        db_run! {conn: {
+            auth_requests::table
+                .filter(auth_requests::organization_uuid.eq(org_uuid))
+                .filter(auth_requests::approved.is_null())
+                .order_by(auth_requests::creation_date.asc())
+                .load::<AuthRequestDb>(conn).expect("Error loading auth_requests").from_db()
+        }}
+    }
+
+    pub async fn find_by_uuid_and_org(
+        uuid: &AuthRequestId,
+        org_uuid: &OrganizationId,
+        conn: &mut DbConn,
+    ) -> Option<Self> {
+        db_run! {conn: {
+            auth_requests::table
+                .filter(auth_requests::uuid.eq(uuid))
+                .filter(auth_requests::organization_uuid.eq(org_uuid))
+                .first::<AuthRequestDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
     pub async fn find_created_before(dt: &NaiveDateTime, conn: &mut DbConn) -> Vec<Self> {
         db_run! {conn: {
             auth_requests::table
@@ -177,8 +217,14 @@ impl AuthRequest {
     }
 
     pub async fn purge_expired_auth_requests(conn: &mut DbConn) {
-        let expiry_time = Utc::now().naive_utc() - chrono::TimeDelta::try_minutes(5).unwrap(); //after 5 minutes, clients reject the request
+        let now = Utc::now().naive_utc();
+        let expiry_time = now - chrono::TimeDelta::try_minutes(5).unwrap(); //after 5 minutes, clients reject the request
+
         for auth_request in Self::find_created_before(&expiry_time, conn).await {
+            // Admin requests wait for an admin to answer them
+            if auth_request.is_admin_request() && !auth_request.is_expired_admin_request() {
+                continue;
+            }
             auth_request.delete(conn).await.ok();
         }
     }
@@ -14,7 +14,7 @@ use macros::UuidFromParam;
 use std::borrow::Cow;
 
 db_object! {
-    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
     #[diesel(table_name = ciphers)]
     #[diesel(treat_none_as_null = true)]
     #[diesel(primary_key(uuid))]
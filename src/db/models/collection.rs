@@ -9,7 +9,7 @@ use crate::CONFIG;
 use macros::UuidFromParam;
 
 db_object! {
-    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
     #[diesel(table_name = collections)]
     #[diesel(treat_none_as_null = true)]
     #[diesel(primary_key(uuid))]
@@ -20,7 +20,7 @@ db_object! {
         pub external_id: Option<String>,
     }
 
-    #[derive(Identifiable, Queryable, Insertable)]
+    #[derive(Identifiable, Queryable, Insertable, Serialize, Deserialize)]
     #[diesel(table_name = users_collections)]
     #[diesel(primary_key(user_uuid, collection_uuid))]
     pub struct CollectionUser {
@@ -31,7 +31,7 @@ db_object! {
         pub manage: bool,
     }
 
-    #[derive(Identifiable, Queryable, Insertable)]
+    #[derive(Identifiable, Queryable, Insertable, Serialize, Deserialize)]
     #[diesel(table_name = ciphers_collections)]
     #[diesel(primary_key(cipher_uuid, collection_uuid))]
     pub struct CollectionCipher {
@@ -0,0 +1,139 @@
+use chrono::{NaiveDateTime, Utc};
+use derive_more::{AsRef, Deref, Display, From};
+
+use crate::{api::EmptyResult, db::DbConn, error::MapResult};
+use macros::UuidFromParam;
+
+db_object! {
+    // A version of the config file, saved on every change in the admin panel to be able to review and roll back changes
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
+    #[diesel(table_name = config_history)]
+    #[diesel(treat_none_as_null = true)]
+    #[diesel(primary_key(uuid))]
+    pub struct ConfigHistory {
+        pub uuid: ConfigHistoryId,
+        pub version: i32,
+        pub created_at: NaiveDateTime,
+        pub actor: String,
+        pub action: String, // "initial", "update", "delete" or "rollback"
+        pub config: String, // The content of the config file after the change, JSON without the passwords
+        pub restored_from: Option<i32>, // The version restored by a rollback
+    }
+}
+
+/// Local methods
+impl ConfigHistory {
+    /// The number of versions kept, older versions are removed when a new one is saved
+    pub const MAX_VERSIONS: usize = 100;
+
+    // Number of times saving is tried when the version was taken by a concurrent change
+    const SAVE_ATTEMPTS: usize = 3;
+
+    /// Creates an entry, the version is assigned by `save_new_version`
+    pub fn new(actor: String, action: &str, config: String) -> Self {
+        Self {
+            uuid: ConfigHistoryId(crate::util::get_uuid()),
+            version: 0,
+            created_at: Utc::now().naive_utc(),
+            actor,
+            action: action.to_string(),
+            config,
+            restored_from: None,
+        }
+    }
+}
+
+/// Database methods
+impl ConfigHistory {
+    /// Saves the entry as the version after the latest one.
+    /// The versions are unique, when a concurrent change saved the same version the next one is tried.
+    pub async fn save_new_version(&mut self, conn: &mut DbConn) -> EmptyResult {
+        for _ in 0..Self::SAVE_ATTEMPTS {
+            self.version = Self::latest_version(conn).await.unwrap_or_default() + 1;
+            let res = db_run! { conn: {
+                diesel::insert_into(config_history::table)
+                    .values(ConfigHistoryDb::to_db(self))
+                    .execute(conn)
+            }};
+            match res {
+                Ok(_) => return Ok(()),
+                Err(diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _)) => {
+                    continue
+                }
+                Err(e) => return Err::<(), _>(e).map_res("Error saving config history"),
+            }
+        }
+        err!("Error saving config history, the version was taken by concurrent changes")
+    }
+
+    pub async fn find_by_version(version: i32, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            config_history::table
+                .filter(config_history::version.eq(version))
+                .first::<ConfigHistoryDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    /// Returns the newest versions first
+    pub async fn get_all(conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            config_history::table
+                .order(config_history::version.desc())
+                .load::<ConfigHistoryDb>(conn)
+                .expect("Error loading config history")
+                .from_db()
+        }}
+    }
+
+    pub async fn latest_version(conn: &mut DbConn) -> Option<i32> {
+        db_run! { conn: {
+            config_history::table
+                .select(diesel::dsl::max(config_history::version))
+                .first::<Option<i32>>(conn)
+                .ok()
+                .flatten()
+        }}
+    }
+
+    /// Removes the versions beyond the newest `MAX_VERSIONS`
+    pub async fn prune(conn: &mut DbConn) -> EmptyResult {
+        let versions: Vec<i32> = db_run! { conn: {
+            config_history::table
+                .select(config_history::version)
+                .order(config_history::version.desc())
+                .load::<i32>(conn)
+                .expect("Error loading config history")
+        }};
+        let Some(&oldest_kept) = versions.get(Self::MAX_VERSIONS - 1) else {
+            return Ok(());
+        };
+
+        db_run! { conn: {
+            diesel::delete(config_history::table.filter(config_history::version.lt(oldest_kept)))
+                .execute(conn)
+                .map_res("Error pruning config history")
+        }}
+    }
+}
+
+#[derive(
+    Clone,
+    Debug,
+    AsRef,
+    Deref,
+    DieselNewType,
+    Display,
+    From,
+    FromForm,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    UuidFromParam,
+)]
+#[deref(forward)]
+#[from(forward)]
+pub struct ConfigHistoryId(String);
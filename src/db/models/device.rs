@@ -1,4 +1,4 @@
-use chrono::{NaiveDateTime, Utc};
+use chrono::{NaiveDateTime, TimeDelta, Utc};
 use derive_more::{Display, From};
 use serde_json::Value;
 
@@ -7,7 +7,7 @@ use crate::{crypto, util::format_date, CONFIG};
 use macros::IdFromParam;
 
 db_object! {
-    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
     #[diesel(table_name = devices)]
     #[diesel(treat_none_as_null = true)]
     #[diesel(primary_key(uuid, user_uuid))]
@@ -25,11 +25,26 @@ db_object! {
 
         pub refresh_token: String,
         pub twofactor_remember: Option<String>,
+
+        pub previous_refresh_token: Option<String>, // The refresh token replaced by the last rotation, to detect reuse
+        pub session_started_at: Option<NaiveDateTime>,
+        pub last_refreshed_at: Option<NaiveDateTime>,
+
+        pub last_seen_at: Option<NaiveDateTime>, // Last login or token refresh
+        pub last_ip: Option<String>,
+
+        // Keys of a trusted device, which can decrypt the user key without the master password
+        pub encrypted_user_key: Option<String>,
+        pub encrypted_public_key: Option<String>,
+        pub encrypted_private_key: Option<String>,
     }
 }
 
 /// Local methods
 impl Device {
+    /// Seconds during which the refresh token replaced by a rotation is still accepted
+    const ROTATION_GRACE_SECONDS: i64 = 30;
+
     pub fn new(uuid: DeviceId, user_uuid: UserId, name: String, atype: i32) -> Self {
         let now = Utc::now().naive_utc();
 
@@ -46,6 +61,17 @@ impl Device {
             push_token: None,
             refresh_token: String::new(),
             twofactor_remember: None,
+
+            previous_refresh_token: None,
+            session_started_at: None,
+            last_refreshed_at: None,
+
+            last_seen_at: None,
+            last_ip: None,
+
+            encrypted_user_key: None,
+            encrypted_public_key: None,
+            encrypted_private_key: None,
         }
     }
 
@@ -56,11 +82,38 @@ impl Device {
             "type": self.atype,
             "identifier": self.push_uuid,
             "creationDate": format_date(&self.created_at),
-            "isTrusted": false,
+            "revisionDate": format_date(&self.updated_at),
+            "lastActivityDate": self.last_seen_at.as_ref().map(format_date),
+            "lastIpAddress": self.last_ip,
+            "isTrusted": self.is_trusted(),
+            "encryptedUserKey": self.encrypted_user_key,
+            "encryptedPublicKey": self.encrypted_public_key,
             "object":"device"
         })
     }
 
+    /// Records a login or token refresh of this device
+    pub fn touch(&mut self, ip: &std::net::IpAddr) {
+        self.last_seen_at = Some(Utc::now().naive_utc());
+        self.last_ip = Some(ip.to_string());
+    }
+
+    pub fn is_trusted(&self) -> bool {
+        self.encrypted_user_key.is_some() && self.encrypted_public_key.is_some()
+    }
+
+    pub fn set_trust_keys(&mut self, user_key: String, public_key: String, private_key: String) {
+        self.encrypted_user_key = Some(user_key);
+        self.encrypted_public_key = Some(public_key);
+        self.encrypted_private_key = Some(private_key);
+    }
+
+    pub fn clear_trust_keys(&mut self) {
+        self.encrypted_user_key = None;
+        self.encrypted_public_key = None;
+        self.encrypted_private_key = None;
+    }
+
     pub fn refresh_twofactor_remember(&mut self) -> String {
         use data_encoding::BASE64;
         let twofactor_remember = crypto::encode_random_bytes::<180>(BASE64);
@@ -73,11 +126,63 @@ impl Device {
         self.twofactor_remember = None;
     }
 
+    /// Starts a new session on login, replacing the refresh token of any previous session of this device
+    pub fn start_session(&mut self) {
+        let now = Utc::now().naive_utc();
+        self.refresh_token = Self::generate_refresh_token();
+        self.previous_refresh_token = None;
+        self.session_started_at = Some(now);
+        self.last_refreshed_at = Some(now);
+    }
+
+    /// Replaces the refresh token on a token refresh, keeping the old one to be able to detect its reuse
+    pub fn rotate_refresh_token(&mut self) {
+        self.previous_refresh_token = Some(std::mem::replace(&mut self.refresh_token, Self::generate_refresh_token()));
+        self.last_refreshed_at = Some(Utc::now().naive_utc());
+    }
+
+    /// Ends the session, the refresh tokens can't be used anymore and the device needs to log in again
+    pub fn end_session(&mut self) {
+        self.refresh_token = String::new();
+        self.previous_refresh_token = None;
+        self.session_started_at = None;
+        self.last_refreshed_at = None;
+    }
+
+    /// Whether the session exceeded the configured idle or absolute lifetime.
+    /// Sessions from before these were tracked use the device dates instead.
+    pub fn is_session_expired(&self) -> bool {
+        self.is_session_expired_after(CONFIG.session_idle_days(), CONFIG.session_absolute_days())
+    }
+
+    fn is_session_expired_after(&self, idle_days: i64, absolute_days: i64) -> bool {
+        let now = Utc::now().naive_utc();
+
+        let last_refreshed_at = self.last_refreshed_at.unwrap_or(self.updated_at);
+        if idle_days > 0 && now - last_refreshed_at > TimeDelta::days(idle_days) {
+            return true;
+        }
+
+        let session_started_at = self.session_started_at.unwrap_or(self.created_at);
+        absolute_days > 0 && now - session_started_at > TimeDelta::days(absolute_days)
+    }
+
+    /// The replaced refresh token is still accepted shortly after the rotation,
+    /// for clients which retry a refresh or send concurrent refreshes
+    pub fn is_in_rotation_grace_period(&self) -> bool {
+        self.last_refreshed_at
+            .is_some_and(|dt| Utc::now().naive_utc() - dt <= TimeDelta::seconds(Self::ROTATION_GRACE_SECONDS))
+    }
+
+    fn generate_refresh_token() -> String {
+        use data_encoding::BASE64URL;
+        crypto::encode_random_bytes::<64>(BASE64URL)
+    }
+
     pub fn refresh_tokens(&mut self, user: &super::User, scope: Vec<String>) -> (String, i64) {
         // If there is no refresh token, we create one
         if self.refresh_token.is_empty() {
-            use data_encoding::BASE64URL;
-            self.refresh_token = crypto::encode_random_bytes::<64>(BASE64URL);
+            self.start_session();
         }
 
         // Update the expiration of the device and the last update date
@@ -152,16 +257,9 @@ impl DeviceWithAuthRequest {
             Some(auth_request) => auth_request.to_json_for_pending_device(),
             None => Value::Null,
         };
-        json!({
-            "id": self.device.uuid,
-            "name": self.device.name,
-            "type": self.device.atype,
-            "identifier": self.device.push_uuid,
-            "creationDate": format_date(&self.device.created_at),
-            "devicePendingAuthRequest": auth_request,
-            "isTrusted": false,
-            "object": "device",
-        })
+        let mut json = self.device.to_json();
+        json["devicePendingAuthRequest"] = auth_request;
+        json
     }
 
     pub fn from(c: Device, a: Option<AuthRequest>) -> Self {
@@ -198,6 +296,14 @@ impl Device {
         }
     }
 
+    pub async fn delete(self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::delete(devices::table.filter(devices::uuid.eq(self.uuid)).filter(devices::user_uuid.eq(self.user_uuid)))
+                .execute(conn)
+                .map_res("Error removing device")
+        }}
+    }
+
     pub async fn delete_all_by_user(user_uuid: &UserId, conn: &mut DbConn) -> EmptyResult {
         db_run! { conn: {
             diesel::delete(devices::table.filter(devices::user_uuid.eq(user_uuid)))
@@ -256,6 +362,21 @@ impl Device {
                 .map_res("Error removing push token")
         }}
     }
+
+    pub async fn clear_trust_keys_by_user(user_uuid: &UserId, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::update(devices::table)
+                .filter(devices::user_uuid.eq(user_uuid))
+                .set((
+                    devices::encrypted_user_key.eq::<Option<String>>(None),
+                    devices::encrypted_public_key.eq::<Option<String>>(None),
+                    devices::encrypted_private_key.eq::<Option<String>>(None),
+                ))
+                .execute(conn)
+                .map_res("Error removing device keys")
+        }}
+    }
+
     pub async fn find_by_refresh_token(refresh_token: &str, conn: &mut DbConn) -> Option<Self> {
         db_run! { conn: {
             devices::table
@@ -266,6 +387,16 @@ impl Device {
         }}
     }
 
+    pub async fn find_by_previous_refresh_token(refresh_token: &str, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            devices::table
+                .filter(devices::previous_refresh_token.eq(refresh_token))
+                .first::<DeviceDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
     pub async fn find_latest_active_by_user(user_uuid: &UserId, conn: &mut DbConn) -> Option<Self> {
         db_run! { conn: {
             devices::table
@@ -395,3 +526,78 @@ impl DeviceType {
     Clone, Debug, DieselNewType, Display, From, FromForm, Hash, PartialEq, Eq, Serialize, Deserialize, IdFromParam,
 )]
 pub struct DeviceId(String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_device() -> Device {
+        let mut device = Device::new(
+            DeviceId::from(String::from("device")),
+            UserId::from(String::from("user")),
+            String::from("test"),
+            DeviceType::LinuxDesktop as i32,
+        );
+        device.start_session();
+        device
+    }
+
+    #[test]
+    fn test_rotate_refresh_token() {
+        let mut device = test_device();
+        let first = device.refresh_token.clone();
+
+        device.rotate_refresh_token();
+        assert_ne!(device.refresh_token, first);
+        assert_eq!(device.previous_refresh_token.as_deref(), Some(first.as_str()));
+
+        // Only the token replaced by the last rotation is kept
+        let second = device.refresh_token.clone();
+        device.rotate_refresh_token();
+        assert_eq!(device.previous_refresh_token, Some(second));
+
+        device.end_session();
+        assert!(device.refresh_token.is_empty());
+        assert!(device.previous_refresh_token.is_none());
+    }
+
+    #[test]
+    fn test_rotation_grace_period() {
+        let mut device = test_device();
+        device.rotate_refresh_token();
+        assert!(device.is_in_rotation_grace_period());
+
+        let rotated_at = Utc::now().naive_utc() - TimeDelta::seconds(Device::ROTATION_GRACE_SECONDS + 1);
+        device.last_refreshed_at = Some(rotated_at);
+        assert!(!device.is_in_rotation_grace_period());
+
+        device.last_refreshed_at = None;
+        assert!(!device.is_in_rotation_grace_period());
+    }
+
+    #[test]
+    fn test_session_expiry() {
+        let mut device = test_device();
+        assert!(!device.is_session_expired_after(1, 1));
+        // 0 disables the limits
+        assert!(!device.is_session_expired_after(0, 0));
+
+        // Idle for two days, the session started three days ago
+        let now = Utc::now().naive_utc();
+        device.last_refreshed_at = Some(now - TimeDelta::days(2));
+        device.session_started_at = Some(now - TimeDelta::days(3));
+        assert!(device.is_session_expired_after(1, 0));
+        assert!(!device.is_session_expired_after(3, 0));
+        assert!(device.is_session_expired_after(0, 2));
+        assert!(!device.is_session_expired_after(0, 4));
+
+        // Sessions from before the dates were tracked use the dates of the device
+        device.last_refreshed_at = None;
+        device.session_started_at = None;
+        device.updated_at = now - TimeDelta::days(5);
+        device.created_at = now - TimeDelta::days(10);
+        assert!(device.is_session_expired_after(4, 0));
+        assert!(device.is_session_expired_after(0, 9));
+        assert!(!device.is_session_expired_after(6, 11));
+    }
+}
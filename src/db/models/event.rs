@@ -11,7 +11,7 @@ db_object! {
     // Upstream: https://github.com/bitwarden/server/blob/8a22c0479e987e756ce7412c48a732f9002f0a2d/src/Core/Services/Implementations/EventService.cs
     // Upstream: https://github.com/bitwarden/server/blob/8a22c0479e987e756ce7412c48a732f9002f0a2d/src/Api/Models/Public/Response/EventResponseModel.cs
     // Upstream SQL: https://github.com/bitwarden/server/blob/8a22c0479e987e756ce7412c48a732f9002f0a2d/src/Sql/dbo/Tables/Event.sql
-    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
     #[diesel(table_name = event)]
     #[diesel(treat_none_as_null = true)]
     #[diesel(primary_key(uuid))]
@@ -37,7 +37,7 @@ db_object! {
 }
 
 // Upstream enum: https://github.com/bitwarden/server/blob/8a22c0479e987e756ce7412c48a732f9002f0a2d/src/Core/Enums/EventType.cs
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, num_derive::FromPrimitive)]
 pub enum EventType {
     // User
     UserLoggedIn = 1000,
@@ -214,7 +214,7 @@ impl Event {
         }
     }
 
-    pub async fn save_user_event(events: Vec<Event>, conn: &mut DbConn) -> EmptyResult {
+    pub async fn save_user_event(events: &[Event], conn: &mut DbConn) -> EmptyResult {
         // Special save function which is able to handle multiple events.
         // SQLite doesn't support the DEFAULT argument, and does not support inserting multiple values at the same time.
         // MySQL and PostgreSQL do.
@@ -224,10 +224,10 @@ impl Event {
             // We loop through the events here and insert them one at a time.
             sqlite {
                 for event in events {
-                    diesel::insert_or_ignore_into(event::table)
-                    .values(EventDb::to_db(&event))
+                    let _: () = diesel::insert_or_ignore_into(event::table)
+                    .values(EventDb::to_db(event))
                     .execute(conn)
-                    .unwrap_or_default();
+                    .map_res("Error saving event")?;
                 }
                 Ok(())
             }
@@ -236,8 +236,7 @@ impl Event {
                 diesel::insert_or_ignore_into(event::table)
                 .values(&events)
                 .execute(conn)
-                .unwrap_or_default();
-                Ok(())
+                .map_res("Error saving events")
             }
             postgresql {
                 let events: Vec<EventDb> = events.iter().map(EventDb::to_db).collect();
@@ -245,8 +244,7 @@ impl Event {
                 .values(&events)
                 .on_conflict_do_nothing()
                 .execute(conn)
-                .unwrap_or_default();
-                Ok(())
+                .map_res("Error saving events")
             }
         }
     }
@@ -312,6 +310,23 @@ impl Event {
         }}
     }
 
+    /// The most recent successful and failed logins of a user, only the copy of the event without an org is used
+    pub async fn find_login_events_by_user(user_uuid: &UserId, limit: i64, conn: &mut DbConn) -> Vec<Self> {
+        let login_events =
+            [EventType::UserLoggedIn as i32, EventType::UserFailedLogIn as i32, EventType::UserFailedLogIn2fa as i32];
+        db_run! { conn: {
+            event::table
+                .filter(event::user_uuid.eq(user_uuid))
+                .filter(event::org_uuid.is_null())
+                .filter(event::event_type.eq_any(login_events))
+                .order_by(event::event_date.desc())
+                .limit(limit)
+                .load::<EventDb>(conn)
+                .expect("Error filtering events")
+                .from_db()
+        }}
+    }
+
     pub async fn find_by_cipher_uuid(
         cipher_uuid: &CipherId,
         start: &NaiveDateTime,
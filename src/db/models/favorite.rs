@@ -1,7 +1,7 @@
 use super::{CipherId, User, UserId};
 
 db_object! {
-    #[derive(Identifiable, Queryable, Insertable)]
+    #[derive(Identifiable, Queryable, Insertable, Serialize, Deserialize)]
     #[diesel(table_name = favorites)]
     #[diesel(primary_key(user_uuid, cipher_uuid))]
     pub struct Favorite {
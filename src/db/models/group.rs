@@ -8,7 +8,7 @@ use macros::UuidFromParam;
 use serde_json::Value;
 
 db_object! {
-    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
     #[diesel(table_name = groups)]
     #[diesel(treat_none_as_null = true)]
     #[diesel(primary_key(uuid))]
@@ -22,7 +22,7 @@ db_object! {
         pub revision_date: NaiveDateTime,
     }
 
-    #[derive(Identifiable, Queryable, Insertable)]
+    #[derive(Identifiable, Queryable, Insertable, Serialize, Deserialize)]
     #[diesel(table_name = collections_groups)]
     #[diesel(primary_key(collections_uuid, groups_uuid))]
     pub struct CollectionGroup {
@@ -33,7 +33,7 @@ db_object! {
         pub manage: bool,
     }
 
-    #[derive(Identifiable, Queryable, Insertable)]
+    #[derive(Identifiable, Queryable, Insertable, Serialize, Deserialize)]
     #[diesel(table_name = groups_users)]
     #[diesel(primary_key(groups_uuid, users_organizations_uuid))]
     pub struct GroupUser {
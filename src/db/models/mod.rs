@@ -1,7 +1,11 @@
+mod admin_account;
+mod admin_api_token;
+mod admin_audit_log;
 mod attachment;
 mod auth_request;
 mod cipher;
 mod collection;
+mod config_history;
 mod device;
 mod emergency_access;
 mod event;
@@ -11,15 +15,22 @@ mod group;
 mod org_policy;
 mod organization;
 mod send;
+mod sso;
 mod two_factor;
 mod two_factor_duo_context;
 mod two_factor_incomplete;
 mod user;
+mod webauthn_credential;
+mod webhook;
 
+pub use self::admin_account::{AdminAccount, AdminAccountId, AdminRole};
+pub use self::admin_api_token::{AdminApiScope, AdminApiToken, AdminApiTokenId};
+pub use self::admin_audit_log::{AdminAuditFilter, AdminAuditLog, AdminAuditLogId};
 pub use self::attachment::{Attachment, AttachmentId};
 pub use self::auth_request::{AuthRequest, AuthRequestId};
 pub use self::cipher::{Cipher, CipherId, RepromptType};
 pub use self::collection::{Collection, CollectionCipher, CollectionId, CollectionUser};
+pub use self::config_history::{ConfigHistory, ConfigHistoryId};
 pub use self::device::{Device, DeviceId, DeviceType};
 pub use self::emergency_access::{EmergencyAccess, EmergencyAccessId, EmergencyAccessStatus, EmergencyAccessType};
 pub use self::event::{Event, EventType};
@@ -35,7 +46,32 @@ pub use self::send::{
     id::{SendFileId, SendId},
     Send, SendType,
 };
+pub use self::sso::{SsoAuth, SsoUser};
 pub use self::two_factor::{TwoFactor, TwoFactorType};
 pub use self::two_factor_duo_context::TwoFactorDuoContext;
 pub use self::two_factor_incomplete::TwoFactorIncomplete;
 pub use self::user::{Invitation, User, UserId, UserKdfType, UserStampException};
+pub use self::webauthn_credential::{WebAuthnCredential, WebAuthnCredentialId, WebAuthnPrfStatus};
+pub use self::webhook::{Webhook, WebhookDelivery, WebhookDeliveryId, WebhookDeliveryStatus, WebhookId};
+
+// Combines the Diesel models of all the tables per database backend, used to dump and restore the whole database
+macro_rules! combine_db_models {
+    ( $( $db:ident ),+ ) => { pastey::paste! { $(
+        #[cfg($db)]
+        pub mod [<__ $db _model>] {
+            pub use super::{
+                admin_account::[<__ $db _model>]::*, admin_api_token::[<__ $db _model>]::*, admin_audit_log::[<__ $db _model>]::*,
+                attachment::[<__ $db _model>]::*, auth_request::[<__ $db _model>]::*, cipher::[<__ $db _model>]::*,
+                collection::[<__ $db _model>]::*, config_history::[<__ $db _model>]::*, device::[<__ $db _model>]::*,
+                emergency_access::[<__ $db _model>]::*,
+                event::[<__ $db _model>]::*, favorite::[<__ $db _model>]::*, folder::[<__ $db _model>]::*,
+                group::[<__ $db _model>]::*, org_policy::[<__ $db _model>]::*, organization::[<__ $db _model>]::*,
+                send::[<__ $db _model>]::*, sso::[<__ $db _model>]::*, two_factor::[<__ $db _model>]::*,
+                two_factor_duo_context::[<__ $db _model>]::*, two_factor_incomplete::[<__ $db _model>]::*,
+                user::[<__ $db _model>]::*, webauthn_credential::[<__ $db _model>]::*, webhook::[<__ $db _model>]::*,
+            };
+        }
+    )+ }};
+}
+
+combine_db_models!(sqlite, mysql, postgresql);
@@ -15,7 +15,7 @@ use crate::CONFIG;
 use macros::UuidFromParam;
 
 db_object! {
-    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
     #[diesel(table_name = organizations)]
     #[diesel(treat_none_as_null = true)]
     #[diesel(primary_key(uuid))]
@@ -25,9 +25,11 @@ db_object! {
         pub billing_email: String,
         pub private_key: Option<String>,
         pub public_key: Option<String>,
+        // Attachment storage quota in KB set by an admin, when empty ORG_ATTACHMENT_LIMIT is used
+        pub attachment_quota_kb: Option<i64>,
     }
 
-    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
     #[diesel(table_name = users_organizations)]
     #[diesel(treat_none_as_null = true)]
     #[diesel(primary_key(uuid))]
@@ -44,7 +46,7 @@ db_object! {
         pub external_id: Option<String>,
     }
 
-    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
     #[diesel(table_name = organization_api_key)]
     #[diesel(primary_key(uuid, org_uuid))]
     pub struct OrganizationApiKey {
@@ -175,8 +177,14 @@ impl Organization {
             billing_email,
             private_key,
             public_key,
+            attachment_quota_kb: None,
         }
     }
+
+    /// The attachment storage limit of this organization in KB, `None` means unlimited
+    pub fn attachment_limit_kb(&self) -> Option<i64> {
+        self.attachment_quota_kb.or_else(|| CONFIG.org_attachment_limit())
+    }
     // https://github.com/bitwarden/server/blob/13d1e74d6960cf0d042620b72d85bf583a4236f7/src/Api/Models/Response/Organizations/OrganizationResponseModel.cs
     pub fn to_json(&self) -> Value {
         json!({
@@ -193,7 +201,7 @@ impl Organization {
             "useTotp": true,
             "usePolicies": true,
             "useScim": false, // Not supported (Not AGPLv3 Licensed)
-            "useSso": false, // Not supported
+            "useSso": CONFIG.sso_enabled(),
             "useKeyConnector": false, // Not supported
             "usePasswordManager": true,
             "useSecretsManager": false, // Not supported (Not AGPLv3 Licensed)
@@ -319,7 +327,7 @@ impl OrganizationApiKey {
 use crate::db::DbConn;
 
 use crate::api::EmptyResult;
-use crate::error::MapResult;
+use crate::error::{Error, MapResult};
 
 /// Database methods
 impl Organization {
@@ -365,7 +373,7 @@ impl Organization {
     }
 
     pub async fn delete(self, conn: &mut DbConn) -> EmptyResult {
-        use super::{Cipher, Collection};
+        use super::{Cipher, Collection, Webhook};
 
         Cipher::delete_all_by_organization(&self.uuid, conn).await?;
         Collection::delete_all_by_organization(&self.uuid, conn).await?;
@@ -373,6 +381,7 @@ impl Organization {
         OrgPolicy::delete_all_by_organization(&self.uuid, conn).await?;
         Group::delete_all_by_organization(&self.uuid, conn).await?;
         OrganizationApiKey::delete_all_by_organization(&self.uuid, conn).await?;
+        Webhook::delete_all_by_organization(&self.uuid, conn).await?;
 
         db_run! { conn: {
             diesel::delete(organizations::table.filter(organizations::uuid.eq(self.uuid)))
@@ -445,7 +454,7 @@ impl Membership {
             "resetPasswordEnrolled": self.reset_password_key.is_some(),
             "useResetPassword": CONFIG.mail_enabled(),
             "ssoBound": false, // Not supported
-            "useSso": false, // Not supported
+            "useSso": CONFIG.sso_enabled(),
             "useKeyConnector": false,
             "useSecretsManager": false, // Not supported (Not AGPLv3 Licensed)
             "usePasswordManager": true,
@@ -741,6 +750,28 @@ impl Membership {
         }}
     }
 
+    /// Makes `new_owner` an owner and the `demoted` owners admins, in a single transaction
+    pub async fn transfer_ownership(new_owner: &Membership, demoted: &[Membership], conn: &mut DbConn) -> EmptyResult {
+        let demoted_ids: Vec<&MembershipId> = demoted.iter().map(|m| &m.uuid).collect();
+        let _: () = db_run! { conn: {
+            conn.transaction::<_, Error, _>(|conn| {
+                diesel::update(users_organizations::table.filter(users_organizations::uuid.eq(&new_owner.uuid)))
+                    .set(users_organizations::atype.eq(MembershipType::Owner as i32))
+                    .execute(conn)
+                    .map_res("Error updating the new owner")?;
+                diesel::update(users_organizations::table.filter(users_organizations::uuid.eq_any(&demoted_ids)))
+                    .set(users_organizations::atype.eq(MembershipType::Admin as i32))
+                    .execute(conn)
+                    .map_res("Error demoting the previous owners")
+            })
+        }}?;
+
+        for member in std::iter::once(new_owner).chain(demoted) {
+            User::update_uuid_revision(&member.user_uuid, conn).await;
+        }
+        Ok(())
+    }
+
     pub async fn delete_all_by_organization(org_uuid: &OrganizationId, conn: &mut DbConn) -> EmptyResult {
         for member in Self::find_by_org(org_uuid, conn).await {
             member.delete(conn).await?;
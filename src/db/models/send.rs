@@ -7,7 +7,7 @@ use super::{OrganizationId, User, UserId};
 use id::SendId;
 
 db_object! {
-    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
     #[diesel(table_name = sends)]
     #[diesel(treat_none_as_null = true)]
     #[diesel(primary_key(uuid))]
@@ -226,7 +226,7 @@ impl Send {
         self.update_users_revision(conn).await;
 
         if self.atype == SendType::File as i32 {
-            std::fs::remove_dir_all(std::path::Path::new(&crate::CONFIG.sends_folder()).join(&self.uuid)).ok();
+            crate::storage::storage().delete_dir(crate::storage::PathType::Sends, &self.uuid).await.ok();
         }
 
         db_run! { conn: {
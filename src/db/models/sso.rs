@@ -0,0 +1,171 @@
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+
+use super::UserId;
+use crate::{api::EmptyResult, db::DbConn, error::MapResult};
+
+db_object! {
+    // State of an SSO login which is in progress.
+    // Created when the client is redirected to the identity provider, and removed once the client exchanged the code.
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
+    #[diesel(table_name = sso_auth)]
+    #[diesel(treat_none_as_null = true)]
+    #[diesel(primary_key(state))]
+    pub struct SsoAuth {
+        pub state: String,          // State sent to the identity provider
+        pub client_state: String,   // State sent by the Bitwarden client, returned to it after the redirect
+        pub code_challenge: String, // PKCE challenge sent by the Bitwarden client
+        pub redirect_uri: String,   // Location of the Bitwarden client to return to
+        pub nonce: String,
+        pub verifier: Option<String>, // PKCE verifier used with the identity provider
+        pub code: Option<String>,     // Code handed out to the Bitwarden client
+        pub idp_code: Option<String>, // Code received from the identity provider
+        pub created_at: NaiveDateTime,
+    }
+
+    // Links a user to the subject of the identity provider
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
+    #[diesel(table_name = sso_users)]
+    #[diesel(primary_key(user_uuid))]
+    pub struct SsoUser {
+        pub user_uuid: UserId,
+        pub identifier: String,
+    }
+}
+
+/// Number of minutes a user has to complete the login at the identity provider
+const SSO_AUTH_EXPIRATION_MINUTES: i64 = 10;
+
+/// Local methods
+impl SsoAuth {
+    pub fn new(
+        state: String,
+        client_state: String,
+        code_challenge: String,
+        redirect_uri: String,
+        nonce: String,
+        verifier: Option<String>,
+    ) -> Self {
+        Self {
+            state,
+            client_state,
+            code_challenge,
+            redirect_uri,
+            nonce,
+            verifier,
+            code: None,
+            idp_code: None,
+            created_at: Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.created_at + TimeDelta::try_minutes(SSO_AUTH_EXPIRATION_MINUTES).unwrap() < Utc::now().naive_utc()
+    }
+}
+
+/// Database methods
+impl SsoAuth {
+    pub async fn save(&self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn:
+            sqlite, mysql {
+                diesel::replace_into(sso_auth::table)
+                    .values(SsoAuthDb::to_db(self))
+                    .execute(conn)
+                    .map_res("Error saving SSO auth")
+            }
+            postgresql {
+                let value = SsoAuthDb::to_db(self);
+                diesel::insert_into(sso_auth::table)
+                    .values(&value)
+                    .on_conflict(sso_auth::state)
+                    .do_update()
+                    .set(&value)
+                    .execute(conn)
+                    .map_res("Error saving SSO auth")
+            }
+        }
+    }
+
+    pub async fn find_by_state(state: &str, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            sso_auth::table
+                .filter(sso_auth::state.eq(state))
+                .first::<SsoAuthDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    pub async fn find_by_code(code: &str, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            sso_auth::table
+                .filter(sso_auth::code.eq(code))
+                .first::<SsoAuthDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    pub async fn delete(&self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::delete(sso_auth::table.filter(sso_auth::state.eq(&self.state)))
+                .execute(conn)
+                .map_res("Error deleting SSO auth")
+        }}
+    }
+
+    pub async fn purge_expired(conn: &mut DbConn) -> EmptyResult {
+        let expiry_time = Utc::now().naive_utc() - TimeDelta::try_minutes(SSO_AUTH_EXPIRATION_MINUTES).unwrap();
+        db_run! { conn: {
+            diesel::delete(sso_auth::table.filter(sso_auth::created_at.lt(expiry_time)))
+                .execute(conn)
+                .map_res("Error purging expired SSO auth")
+        }}
+    }
+}
+
+impl SsoUser {
+    pub fn new(user_uuid: UserId, identifier: String) -> Self {
+        Self {
+            user_uuid,
+            identifier,
+        }
+    }
+
+    pub async fn save(&self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::insert_into(sso_users::table)
+                .values(SsoUserDb::to_db(self))
+                .execute(conn)
+                .map_res("Error saving SSO user")
+        }}
+    }
+
+    pub async fn find_by_identifier(identifier: &str, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            sso_users::table
+                .filter(sso_users::identifier.eq(identifier))
+                .first::<SsoUserDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    pub async fn find_by_user(user_uuid: &UserId, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            sso_users::table
+                .filter(sso_users::user_uuid.eq(user_uuid))
+                .first::<SsoUserDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    pub async fn delete_all_by_user(user_uuid: &UserId, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::delete(sso_users::table.filter(sso_users::user_uuid.eq(user_uuid)))
+                .execute(conn)
+                .map_res("Error deleting SSO user")
+        }}
+    }
+}
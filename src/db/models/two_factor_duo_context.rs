@@ -3,7 +3,7 @@ use chrono::Utc;
 use crate::{api::EmptyResult, db::DbConn, error::MapResult};
 
 db_object! {
-    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
     #[diesel(table_name = twofactor_duo_ctx)]
     #[diesel(primary_key(state))]
     pub struct TwoFactorDuoContext {
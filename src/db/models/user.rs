@@ -3,7 +3,8 @@ use derive_more::{AsRef, Deref, Display, From};
 use serde_json::Value;
 
 use super::{
-    Cipher, Device, EmergencyAccess, Favorite, Folder, Membership, MembershipType, TwoFactor, TwoFactorIncomplete,
+    Cipher, Device, EmergencyAccess, Favorite, Folder, Membership, MembershipType, SsoUser, TwoFactor,
+    TwoFactorIncomplete, WebAuthnCredential,
 };
 use crate::{
     api::EmptyResult,
@@ -16,7 +17,7 @@ use crate::{
 use macros::UuidFromParam;
 
 db_object! {
-    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
     #[diesel(table_name = users)]
     #[diesel(treat_none_as_null = true)]
     #[diesel(primary_key(uuid))]
@@ -63,9 +64,21 @@ db_object! {
         pub avatar_color: Option<String>,
 
         pub external_id: Option<String>, // Todo: Needs to be removed in the future, this is not used anymore.
+
+        pub last_login_at: Option<NaiveDateTime>,
+        pub last_sync_at: Option<NaiveDateTime>,
+        pub inactivity_warned_at: Option<NaiveDateTime>,
+
+        // Storage quotas in KB set by an admin, when empty the global limits are used
+        pub attachment_quota_kb: Option<i64>,
+        pub send_quota_kb: Option<i64>,
+
+        // Failed master password logins since the last successful one, used for the lockout
+        pub failed_login_count: i32,
+        pub locked_until: Option<NaiveDateTime>,
     }
 
-    #[derive(Identifiable, Queryable, Insertable)]
+    #[derive(Identifiable, Queryable, Insertable, Serialize, Deserialize)]
     #[diesel(table_name = invitations)]
     #[diesel(primary_key(email))]
     pub struct Invitation {
@@ -141,6 +154,16 @@ impl User {
             avatar_color: None,
 
             external_id: None, // Todo: Needs to be removed in the future, this is not used anymore.
+
+            last_login_at: None,
+            last_sync_at: None,
+            inactivity_warned_at: None,
+
+            attachment_quota_kb: None,
+            send_quota_kb: None,
+
+            failed_login_count: 0,
+            locked_until: None,
         }
     }
 
@@ -165,6 +188,16 @@ impl User {
         matches!(self.api_key, Some(ref api_key) if crypto::ct_eq(api_key, key))
     }
 
+    /// The attachment storage limit of this user in KB, `None` means unlimited
+    pub fn attachment_limit_kb(&self) -> Option<i64> {
+        self.attachment_quota_kb.or_else(|| CONFIG.user_attachment_limit())
+    }
+
+    /// The send storage limit of this user in KB, `None` means unlimited
+    pub fn send_limit_kb(&self) -> Option<i64> {
+        self.send_quota_kb.or_else(|| CONFIG.user_send_limit())
+    }
+
     /// Set the password hash generated
     /// And resets the security_stamp. Based upon the allow_next_route the security_stamp will be different.
     ///
@@ -222,6 +255,33 @@ impl User {
     pub fn reset_stamp_exception(&mut self) {
         self.stamp_exception = None;
     }
+
+    /// Returns the end of the lockout when the account is locked after too many failed logins
+    pub fn login_locked_until(&self) -> Option<NaiveDateTime> {
+        self.locked_until.filter(|locked_until| *locked_until > Utc::now().naive_utc())
+    }
+
+    /// Counts a failed login, returns true when this failure locked the account.
+    /// The retry delay after a failed login is kept per IP address by `ratelimit::register_login_failure`.
+    pub fn register_failed_login(&mut self) -> bool {
+        self.count_failed_login(CONFIG.login_lockout_attempts() as i32, CONFIG.login_lockout_minutes())
+    }
+
+    fn count_failed_login(&mut self, lockout_attempts: i32, lockout_minutes: i64) -> bool {
+        self.failed_login_count += 1;
+        if lockout_attempts > 0 && self.failed_login_count >= lockout_attempts {
+            self.locked_until = Some(Utc::now().naive_utc() + TimeDelta::minutes(lockout_minutes));
+            // The next failures after the lockout start counting from zero again
+            self.failed_login_count = 0;
+            return true;
+        }
+        false
+    }
+
+    pub fn reset_failed_logins(&mut self) {
+        self.failed_login_count = 0;
+        self.locked_until = None;
+    }
 }
 
 /// Database methods
@@ -323,6 +383,8 @@ impl User {
         Device::delete_all_by_user(&self.uuid, conn).await?;
         TwoFactor::delete_all_by_user(&self.uuid, conn).await?;
         TwoFactorIncomplete::delete_all_by_user(&self.uuid, conn).await?;
+        SsoUser::delete_all_by_user(&self.uuid, conn).await?;
+        WebAuthnCredential::delete_all_by_user(&self.uuid, conn).await?;
         Invitation::take(&self.email, conn).await; // Delete invitation if any
 
         db_run! {conn: {
@@ -332,6 +394,16 @@ impl User {
         }}
     }
 
+    /// Only writes the failed login count and the lockout, a failed login doesn't overwrite other changes to the user
+    pub async fn save_failed_logins(&self, conn: &mut DbConn) -> EmptyResult {
+        db_run! {conn: {
+            diesel::update(users::table.filter(users::uuid.eq(&self.uuid)))
+                .set((users::failed_login_count.eq(self.failed_login_count), users::locked_until.eq(self.locked_until)))
+                .execute(conn)
+                .map_res("Error saving the failed logins")
+        }}
+    }
+
     pub async fn update_uuid_revision(uuid: &UserId, conn: &mut DbConn) {
         if let Err(e) = Self::_update_revision(uuid, &Utc::now().naive_utc(), conn).await {
             warn!("Failed to update revision for {}: {:#?}", uuid, e);
@@ -397,6 +469,45 @@ impl User {
             None => None,
         }
     }
+
+    /// The latest of the logins, syncs and token refreshes, or the creation date when the user was never active
+    pub async fn last_activity(&self, conn: &mut DbConn) -> NaiveDateTime {
+        [self.last_login_at, self.last_sync_at, self.last_active(conn).await]
+            .into_iter()
+            .flatten()
+            .fold(self.created_at, NaiveDateTime::max)
+    }
+
+    pub async fn update_last_login(uuid: &UserId, conn: &mut DbConn) -> EmptyResult {
+        let now = Utc::now().naive_utc();
+        db_run! {conn: {
+            diesel::update(users::table.filter(users::uuid.eq(uuid)))
+                .set(users::last_login_at.eq(now))
+                .execute(conn)
+                .map_res("Error updating the last login of the user")
+        }}
+    }
+
+    pub async fn update_last_sync(uuid: &UserId, conn: &mut DbConn) -> EmptyResult {
+        let now = Utc::now().naive_utc();
+        db_run! {conn: {
+            diesel::update(users::table.filter(users::uuid.eq(uuid)))
+                .set(users::last_sync_at.eq(now))
+                .execute(conn)
+                .map_res("Error updating the last sync of the user")
+        }}
+    }
+
+    pub async fn set_inactivity_warned(&mut self, conn: &mut DbConn) -> EmptyResult {
+        let now = Utc::now().naive_utc();
+        self.inactivity_warned_at = Some(now);
+        db_run! {conn: {
+            diesel::update(users::table.filter(users::uuid.eq(&self.uuid)))
+                .set(users::inactivity_warned_at.eq(now))
+                .execute(conn)
+                .map_res("Error updating the inactivity warning of the user")
+        }}
+    }
 }
 
 impl Invitation {
@@ -478,3 +589,32 @@ impl Invitation {
 #[deref(forward)]
 #[from(forward)]
 pub struct UserId(String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_failed_login() {
+        let mut user = User::new(String::from("user@example.com"));
+
+        // The lockout is disabled by default, the failures are only counted
+        assert!(!user.register_failed_login());
+        assert!(!user.register_failed_login());
+        assert_eq!(user.failed_login_count, 2);
+        assert!(user.login_locked_until().is_none());
+
+        assert!(user.count_failed_login(3, 30));
+        assert_eq!(user.failed_login_count, 0);
+        let locked_until = user.login_locked_until().unwrap();
+        assert!(locked_until > Utc::now().naive_utc() + TimeDelta::minutes(29));
+
+        assert!(!user.count_failed_login(3, 30));
+        assert_eq!(user.failed_login_count, 1);
+        assert_eq!(user.login_locked_until(), Some(locked_until));
+
+        user.reset_failed_logins();
+        assert_eq!(user.failed_login_count, 0);
+        assert!(user.login_locked_until().is_none());
+    }
+}
@@ -0,0 +1,184 @@
+use chrono::{NaiveDateTime, Utc};
+use derive_more::{AsRef, Deref, Display, From};
+use serde_json::Value;
+
+use super::UserId;
+use crate::{api::EmptyResult, db::DbConn, error::MapResult};
+use macros::UuidFromParam;
+
+db_object! {
+    // A passkey which can be used to log in without the master password.
+    // When the authenticator supports the PRF extension, the user key wrapped with the PRF output is stored as well,
+    // so the vault can be unlocked with the passkey too.
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
+    #[diesel(table_name = webauthn_credentials)]
+    #[diesel(treat_none_as_null = true)]
+    #[diesel(primary_key(uuid))]
+    pub struct WebAuthnCredential {
+        pub uuid: WebAuthnCredentialId,
+        pub user_uuid: UserId,
+        pub name: String,
+        pub credential_id: String, // Base64url of the raw credential id, used to find the credential on login
+        pub credential: String, // The `webauthn_rs` credential as JSON
+        pub supports_prf: bool,
+        pub encrypted_user_key: Option<String>,
+        pub encrypted_public_key: Option<String>,
+        pub encrypted_private_key: Option<String>,
+        pub created_at: NaiveDateTime,
+        pub updated_at: NaiveDateTime,
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum WebAuthnPrfStatus {
+    Enabled = 0,
+    Supported = 1,
+    Unsupported = 2,
+}
+
+/// Local methods
+impl WebAuthnCredential {
+    pub fn new(user_uuid: UserId, name: String, credential_id: String, credential: String, supports_prf: bool) -> Self {
+        let now = Utc::now().naive_utc();
+        Self {
+            uuid: WebAuthnCredentialId(crate::util::get_uuid()),
+            user_uuid,
+            name,
+            credential_id,
+            credential,
+            supports_prf,
+            encrypted_user_key: None,
+            encrypted_public_key: None,
+            encrypted_private_key: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Whether the vault can be unlocked with this passkey
+    pub fn has_prf_keys(&self) -> bool {
+        self.supports_prf
+            && self.encrypted_user_key.is_some()
+            && self.encrypted_public_key.is_some()
+            && self.encrypted_private_key.is_some()
+    }
+
+    pub fn prf_status(&self) -> WebAuthnPrfStatus {
+        if self.has_prf_keys() {
+            WebAuthnPrfStatus::Enabled
+        } else if self.supports_prf {
+            WebAuthnPrfStatus::Supported
+        } else {
+            WebAuthnPrfStatus::Unsupported
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.uuid,
+            "name": self.name,
+            "prfStatus": self.prf_status() as i32,
+            "encryptedUserKey": self.encrypted_user_key,
+            "encryptedPublicKey": self.encrypted_public_key,
+            "object": "webauthnCredential",
+        })
+    }
+}
+
+/// Database methods
+impl WebAuthnCredential {
+    pub async fn save(&mut self, conn: &mut DbConn) -> EmptyResult {
+        self.updated_at = Utc::now().naive_utc();
+
+        db_run! { conn:
+            sqlite, mysql {
+                diesel::replace_into(webauthn_credentials::table)
+                    .values(WebAuthnCredentialDb::to_db(self))
+                    .execute(conn)
+                    .map_res("Error saving passkey")
+            }
+            postgresql {
+                let value = WebAuthnCredentialDb::to_db(self);
+                diesel::insert_into(webauthn_credentials::table)
+                    .values(&value)
+                    .on_conflict(webauthn_credentials::uuid)
+                    .do_update()
+                    .set(&value)
+                    .execute(conn)
+                    .map_res("Error saving passkey")
+            }
+        }
+    }
+
+    pub async fn delete(self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::delete(webauthn_credentials::table.filter(webauthn_credentials::uuid.eq(self.uuid)))
+                .execute(conn)
+                .map_res("Error deleting passkey")
+        }}
+    }
+
+    pub async fn find_by_uuid_and_user(
+        uuid: &WebAuthnCredentialId,
+        user_uuid: &UserId,
+        conn: &mut DbConn,
+    ) -> Option<Self> {
+        db_run! { conn: {
+            webauthn_credentials::table
+                .filter(webauthn_credentials::uuid.eq(uuid))
+                .filter(webauthn_credentials::user_uuid.eq(user_uuid))
+                .first::<WebAuthnCredentialDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    pub async fn find_by_credential_id(credential_id: &str, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            webauthn_credentials::table
+                .filter(webauthn_credentials::credential_id.eq(credential_id))
+                .first::<WebAuthnCredentialDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    pub async fn find_by_user(user_uuid: &UserId, conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            webauthn_credentials::table
+                .filter(webauthn_credentials::user_uuid.eq(user_uuid))
+                .order(webauthn_credentials::created_at.asc())
+                .load::<WebAuthnCredentialDb>(conn)
+                .expect("Error loading passkeys")
+                .from_db()
+        }}
+    }
+
+    pub async fn delete_all_by_user(user_uuid: &UserId, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::delete(webauthn_credentials::table.filter(webauthn_credentials::user_uuid.eq(user_uuid)))
+                .execute(conn)
+                .map_res("Error deleting passkeys")
+        }}
+    }
+}
+
+#[derive(
+    Clone,
+    Debug,
+    AsRef,
+    Deref,
+    DieselNewType,
+    Display,
+    From,
+    FromForm,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    UuidFromParam,
+)]
+#[deref(forward)]
+#[from(forward)]
+pub struct WebAuthnCredentialId(String);
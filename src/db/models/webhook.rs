@@ -0,0 +1,310 @@
+use chrono::{NaiveDateTime, Utc};
+use derive_more::{AsRef, Deref, Display, From};
+use serde_json::Value;
+
+use super::OrganizationId;
+use crate::{api::EmptyResult, db::DbConn, error::MapResult, util::format_date};
+use macros::UuidFromParam;
+
+db_object! {
+    // An endpoint of an organization which receives the events of the organization
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
+    #[diesel(table_name = org_webhooks)]
+    #[diesel(primary_key(uuid))]
+    pub struct Webhook {
+        pub uuid: WebhookId,
+        pub org_uuid: OrganizationId,
+        pub url: String,
+        pub secret: String,      // Key used to sign the deliveries
+        pub event_types: String, // JSON array of the event types to deliver, all events are delivered when empty
+        pub enabled: bool,
+        pub created_at: NaiveDateTime,
+    }
+
+    // A single event to deliver to a webhook, kept as history once it was delivered or failed
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize)]
+    #[diesel(table_name = webhook_deliveries)]
+    #[diesel(treat_none_as_null = true)]
+    #[diesel(primary_key(uuid))]
+    pub struct WebhookDelivery {
+        pub uuid: WebhookDeliveryId,
+        pub webhook_uuid: WebhookId,
+        pub event_type: i32,
+        pub payload: String,
+        pub status: i32,
+        pub attempts: i32,
+        pub next_attempt_at: NaiveDateTime,
+        pub last_attempt_at: Option<NaiveDateTime>,
+        pub response_code: Option<i32>,
+        pub last_error: Option<String>,
+        pub created_at: NaiveDateTime,
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, num_derive::FromPrimitive)]
+pub enum WebhookDeliveryStatus {
+    Pending = 0,
+    Delivered = 1,
+    Failed = 2,
+}
+
+/// Local methods
+impl Webhook {
+    pub fn new(org_uuid: OrganizationId, url: String, event_types: &[i32], enabled: bool) -> Self {
+        Self {
+            uuid: WebhookId(crate::util::get_uuid()),
+            org_uuid,
+            url,
+            secret: crate::crypto::generate_id::<32>(),
+            event_types: serde_json::to_string(event_types).unwrap_or_default(),
+            enabled,
+            created_at: Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn event_type_list(&self) -> Vec<i32> {
+        serde_json::from_str(&self.event_types).unwrap_or_default()
+    }
+
+    pub fn set_event_types(&mut self, event_types: &[i32]) {
+        self.event_types = serde_json::to_string(event_types).unwrap_or_default();
+    }
+
+    pub fn accepts(&self, event_type: i32) -> bool {
+        let event_types = self.event_type_list();
+        self.enabled && (event_types.is_empty() || event_types.contains(&event_type))
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.uuid,
+            "organizationId": self.org_uuid,
+            "url": self.url,
+            "secret": self.secret,
+            "eventTypes": self.event_type_list(),
+            "enabled": self.enabled,
+            "creationDate": format_date(&self.created_at),
+            "object": "webhook",
+        })
+    }
+}
+
+impl WebhookDelivery {
+    pub fn new(webhook_uuid: WebhookId, event_type: i32, payload: String) -> Self {
+        let now = Utc::now().naive_utc();
+        Self {
+            uuid: WebhookDeliveryId(crate::util::get_uuid()),
+            webhook_uuid,
+            event_type,
+            payload,
+            status: WebhookDeliveryStatus::Pending as i32,
+            attempts: 0,
+            next_attempt_at: now,
+            last_attempt_at: None,
+            response_code: None,
+            last_error: None,
+            created_at: now,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let status = match num_traits::FromPrimitive::from_i32(self.status) {
+            Some(WebhookDeliveryStatus::Pending) => "pending",
+            Some(WebhookDeliveryStatus::Delivered) => "delivered",
+            Some(WebhookDeliveryStatus::Failed) | None => "failed",
+        };
+        json!({
+            "id": self.uuid,
+            "webhookId": self.webhook_uuid,
+            "eventType": self.event_type,
+            "status": status,
+            "attempts": self.attempts,
+            "nextAttemptDate": (self.status == WebhookDeliveryStatus::Pending as i32).then(|| format_date(&self.next_attempt_at)),
+            "lastAttemptDate": self.last_attempt_at.as_ref().map(format_date),
+            "responseCode": self.response_code,
+            "lastError": self.last_error,
+            "creationDate": format_date(&self.created_at),
+            "object": "webhookDelivery",
+        })
+    }
+}
+
+/// Database methods
+impl Webhook {
+    pub async fn save(&self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn:
+            sqlite, mysql {
+                match diesel::replace_into(org_webhooks::table)
+                    .values(WebhookDb::to_db(self))
+                    .execute(conn)
+                {
+                    Ok(_) => Ok(()),
+                    // Record already exists and causes a Foreign Key Violation because replace_into() wants to delete the record first.
+                    Err(diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::ForeignKeyViolation, _)) => {
+                        diesel::update(org_webhooks::table)
+                            .filter(org_webhooks::uuid.eq(&self.uuid))
+                            .set(WebhookDb::to_db(self))
+                            .execute(conn)
+                            .map_res("Error saving webhook")
+                    }
+                    Err(e) => Err(e.into()),
+                }.map_res("Error saving webhook")
+            }
+            postgresql {
+                let value = WebhookDb::to_db(self);
+                diesel::insert_into(org_webhooks::table)
+                    .values(&value)
+                    .on_conflict(org_webhooks::uuid)
+                    .do_update()
+                    .set(&value)
+                    .execute(conn)
+                    .map_res("Error saving webhook")
+            }
+        }
+    }
+
+    pub async fn delete(self, conn: &mut DbConn) -> EmptyResult {
+        WebhookDelivery::delete_all_by_webhook(&self.uuid, conn).await?;
+
+        db_run! { conn: {
+            diesel::delete(org_webhooks::table.filter(org_webhooks::uuid.eq(self.uuid)))
+                .execute(conn)
+                .map_res("Error deleting webhook")
+        }}
+    }
+
+    pub async fn delete_all_by_organization(org_uuid: &OrganizationId, conn: &mut DbConn) -> EmptyResult {
+        for webhook in Self::find_by_org(org_uuid, conn).await {
+            webhook.delete(conn).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn find_by_uuid(uuid: &WebhookId, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            org_webhooks::table
+                .filter(org_webhooks::uuid.eq(uuid))
+                .first::<WebhookDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    pub async fn find_by_uuid_and_org(uuid: &WebhookId, org_uuid: &OrganizationId, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            org_webhooks::table
+                .filter(org_webhooks::uuid.eq(uuid))
+                .filter(org_webhooks::org_uuid.eq(org_uuid))
+                .first::<WebhookDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    pub async fn find_by_org(org_uuid: &OrganizationId, conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            org_webhooks::table
+                .filter(org_webhooks::org_uuid.eq(org_uuid))
+                .order(org_webhooks::created_at.asc())
+                .load::<WebhookDb>(conn)
+                .expect("Error loading webhooks")
+                .from_db()
+        }}
+    }
+}
+
+impl WebhookDelivery {
+    pub async fn save(&self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn:
+            sqlite, mysql {
+                diesel::replace_into(webhook_deliveries::table)
+                    .values(WebhookDeliveryDb::to_db(self))
+                    .execute(conn)
+                    .map_res("Error saving webhook delivery")
+            }
+            postgresql {
+                let value = WebhookDeliveryDb::to_db(self);
+                diesel::insert_into(webhook_deliveries::table)
+                    .values(&value)
+                    .on_conflict(webhook_deliveries::uuid)
+                    .do_update()
+                    .set(&value)
+                    .execute(conn)
+                    .map_res("Error saving webhook delivery")
+            }
+        }
+    }
+
+    pub async fn delete_all_by_webhook(webhook_uuid: &WebhookId, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::delete(webhook_deliveries::table.filter(webhook_deliveries::webhook_uuid.eq(webhook_uuid)))
+                .execute(conn)
+                .map_res("Error deleting webhook deliveries")
+        }}
+    }
+
+    /// Returns the most recent deliveries of a webhook, newest first
+    pub async fn find_by_webhook(webhook_uuid: &WebhookId, limit: i64, conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            webhook_deliveries::table
+                .filter(webhook_deliveries::webhook_uuid.eq(webhook_uuid))
+                .order(webhook_deliveries::created_at.desc())
+                .limit(limit)
+                .load::<WebhookDeliveryDb>(conn)
+                .expect("Error loading webhook deliveries")
+                .from_db()
+        }}
+    }
+
+    /// Returns the pending deliveries which are due for a (new) attempt, oldest first
+    pub async fn find_due(now: &NaiveDateTime, limit: i64, conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            webhook_deliveries::table
+                .filter(webhook_deliveries::status.eq(WebhookDeliveryStatus::Pending as i32))
+                .filter(webhook_deliveries::next_attempt_at.le(now))
+                .order(webhook_deliveries::next_attempt_at.asc())
+                .limit(limit)
+                .load::<WebhookDeliveryDb>(conn)
+                .expect("Error loading webhook deliveries")
+                .from_db()
+        }}
+    }
+
+    /// Removes the history of the deliveries which were finished before `dt`
+    pub async fn purge_finished_before(dt: &NaiveDateTime, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::delete(
+                webhook_deliveries::table
+                    .filter(webhook_deliveries::status.ne(WebhookDeliveryStatus::Pending as i32))
+                    .filter(webhook_deliveries::created_at.lt(dt)),
+            )
+            .execute(conn)
+            .map_res("Error purging webhook deliveries")
+        }}
+    }
+}
+
+#[derive(
+    Clone,
+    Debug,
+    AsRef,
+    Deref,
+    DieselNewType,
+    Display,
+    From,
+    FromForm,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    UuidFromParam,
+)]
+#[deref(forward)]
+#[from(forward)]
+pub struct WebhookId(String);
+
+#[derive(Clone, Debug, AsRef, Deref, DieselNewType, Display, From, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[deref(forward)]
+#[from(forward)]
+pub struct WebhookDeliveryId(String);
@@ -55,6 +55,14 @@ table! {
         push_token -> Nullable<Text>,
         refresh_token -> Text,
         twofactor_remember -> Nullable<Text>,
+        previous_refresh_token -> Nullable<Text>,
+        session_started_at -> Nullable<Timestamp>,
+        last_refreshed_at -> Nullable<Timestamp>,
+        last_seen_at -> Nullable<Timestamp>,
+        last_ip -> Nullable<Text>,
+        encrypted_user_key -> Nullable<Text>,
+        encrypted_public_key -> Nullable<Text>,
+        encrypted_private_key -> Nullable<Text>,
     }
 }
 
@@ -126,6 +134,7 @@ table! {
         billing_email -> Text,
         private_key -> Nullable<Text>,
         public_key -> Nullable<Text>,
+        attachment_quota_kb -> Nullable<BigInt>,
     }
 }
 
@@ -175,6 +184,27 @@ table! {
     }
 }
 
+table! {
+    sso_auth (state) {
+        state -> Text,
+        client_state -> Text,
+        code_challenge -> Text,
+        redirect_uri -> Text,
+        nonce -> Text,
+        verifier -> Nullable<Text>,
+        code -> Nullable<Text>,
+        idp_code -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    sso_users (user_uuid) {
+        user_uuid -> Text,
+        identifier -> Text,
+    }
+}
+
 table! {
     twofactor_duo_ctx (state) {
         state -> Text,
@@ -217,6 +247,13 @@ table! {
         api_key -> Nullable<Text>,
         avatar_color -> Nullable<Text>,
         external_id -> Nullable<Text>,
+        last_login_at -> Nullable<Timestamp>,
+        last_sync_at -> Nullable<Timestamp>,
+        inactivity_warned_at -> Nullable<Timestamp>,
+        attachment_quota_kb -> Nullable<BigInt>,
+        send_quota_kb -> Nullable<BigInt>,
+        failed_login_count -> Integer,
+        locked_until -> Nullable<Timestamp>,
     }
 }
 
@@ -320,6 +357,103 @@ table! {
     }
 }
 
+table! {
+    org_webhooks (uuid) {
+        uuid -> Text,
+        org_uuid -> Text,
+        url -> Text,
+        secret -> Text,
+        event_types -> Text,
+        enabled -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    webhook_deliveries (uuid) {
+        uuid -> Text,
+        webhook_uuid -> Text,
+        event_type -> Integer,
+        payload -> Text,
+        status -> Integer,
+        attempts -> Integer,
+        next_attempt_at -> Timestamp,
+        last_attempt_at -> Nullable<Timestamp>,
+        response_code -> Nullable<Integer>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    admin_accounts (uuid) {
+        uuid -> Text,
+        username -> Text,
+        password_hash -> Text,
+        role -> Integer,
+        totp_secret -> Nullable<Text>,
+        totp_last_used -> BigInt,
+        enabled -> Bool,
+        created_at -> Timestamp,
+        last_login_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    admin_audit_log (uuid) {
+        uuid -> Text,
+        created_at -> Timestamp,
+        actor -> Text,
+        ip_address -> Text,
+        action -> Text,
+        target_type -> Nullable<Text>,
+        target_id -> Nullable<Text>,
+        details -> Nullable<Text>,
+    }
+}
+
+table! {
+    admin_api_tokens (uuid) {
+        uuid -> Text,
+        name -> Text,
+        token_hash -> Text,
+        scopes -> Text,
+        created_by -> Text,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    config_history (uuid) {
+        uuid -> Text,
+        version -> Integer,
+        created_at -> Timestamp,
+        actor -> Text,
+        action -> Text,
+        config -> Text,
+        restored_from -> Nullable<Integer>,
+    }
+}
+
+table! {
+    webauthn_credentials (uuid) {
+        uuid -> Text,
+        user_uuid -> Text,
+        name -> Text,
+        credential_id -> Text,
+        credential -> Text,
+        supports_prf -> Bool,
+        encrypted_user_key -> Nullable<Text>,
+        encrypted_public_key -> Nullable<Text>,
+        encrypted_private_key -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 joinable!(attachments -> ciphers (cipher_uuid));
 joinable!(ciphers -> organizations (organization_uuid));
 joinable!(ciphers -> users (user_uuid));
@@ -348,12 +482,20 @@ joinable!(collections_groups -> collections (collections_uuid));
 joinable!(collections_groups -> groups (groups_uuid));
 joinable!(event -> users_organizations (uuid));
 joinable!(auth_requests -> users (user_uuid));
+joinable!(sso_users -> users (user_uuid));
+joinable!(org_webhooks -> organizations (org_uuid));
+joinable!(webhook_deliveries -> org_webhooks (webhook_uuid));
+joinable!(webauthn_credentials -> users (user_uuid));
 
 allow_tables_to_appear_in_same_query!(
+    admin_accounts,
+    admin_api_tokens,
+    admin_audit_log,
     attachments,
     ciphers,
     ciphers_collections,
     collections,
+    config_history,
     devices,
     folders,
     folders_ciphers,
@@ -372,4 +514,9 @@ allow_tables_to_appear_in_same_query!(
     collections_groups,
     event,
     auth_requests,
+    sso_auth,
+    sso_users,
+    org_webhooks,
+    webhook_deliveries,
+    webauthn_credentials,
 );
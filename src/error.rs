@@ -210,11 +210,14 @@ use rocket::request::Request;
 use rocket::response::{self, Responder, Response};
 
 impl Responder<'_, 'static> for Error {
-    fn respond_to(self, _: &Request<'_>) -> response::Result<'static> {
+    fn respond_to(self, req: &Request<'_>) -> response::Result<'static> {
         match self.error {
             ErrorKind::Empty(_) => {}  // Don't print the error in this situation
             ErrorKind::Simple(_) => {} // Don't print the error in this situation
-            _ => error!(target: "error", "{:#?}", self),
+            _ => {
+                let request_id = crate::util::RequestContext::from_request(req).id.as_str();
+                error!(target: "error", request_id = request_id; "{:#?}", self)
+            }
         };
 
         let code = Status::from_code(self.error_code).unwrap_or(Status::BadRequest);
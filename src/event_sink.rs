@@ -0,0 +1,414 @@
+//
+// Streams the logged events to a SIEM, either via syslog (RFC 5424, CEF or LEEF) or as JSON Lines written to a local file.
+// The events are forwarded when they are logged, so this doesn't depend on `EVENTS_DAYS_RETAIN`.
+// The names of the users and organizations are looked up by the task writing to the sink, not while handling a request.
+//
+use std::{io, path::PathBuf};
+
+use chrono::SecondsFormat;
+use once_cell::sync::OnceCell;
+use serde_json::Value;
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpStream, UdpSocket},
+    sync::mpsc,
+};
+
+use crate::{
+    db::{
+        models::{Event, EventType, Membership, Organization, User},
+        DbConn, DbPool,
+    },
+    CONFIG,
+};
+
+// Number of events which can wait to be written, new events are dropped while the queue is full
+const QUEUE_SIZE: usize = 10_000;
+// The structured data id of the RFC 5424 messages, 32473 is the enterprise number reserved for documentation (RFC 5612)
+const SD_ID: &str = "vaultwarden@32473";
+// Facility `log audit` (13) with severity `informational` (6)
+const SYSLOG_PRI: u8 = 13 * 8 + 6;
+
+static SENDER: OnceCell<mpsc::Sender<Event>> = OnceCell::new();
+
+#[derive(Debug, PartialEq, Eq)]
+enum Target {
+    Udp(String),
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+    File(PathBuf),
+}
+
+impl Target {
+    fn from_address(address: &str) -> Option<Self> {
+        let (scheme, location) = address.split_once("://")?;
+        if location.is_empty() {
+            return None;
+        }
+        match scheme {
+            "udp" => Some(Self::Udp(location.to_string())),
+            "tcp" => Some(Self::Tcp(location.to_string())),
+            #[cfg(unix)]
+            "unix" => Some(Self::Unix(PathBuf::from(location))),
+            _ => None,
+        }
+    }
+}
+
+/// Checks the syslog address of `EVENT_SINK_ADDRESS`
+pub fn is_valid_address(address: &str) -> bool {
+    Target::from_address(address).is_some()
+}
+
+enum Writer {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixDatagram, PathBuf),
+    File(tokio::fs::File),
+}
+
+impl Writer {
+    async fn connect(target: &Target) -> io::Result<Self> {
+        match target {
+            Target::Udp(address) => {
+                let Some(addr) = tokio::net::lookup_host(address).await?.next() else {
+                    return Err(io::Error::new(io::ErrorKind::NotFound, format!("Unable to resolve {address}")));
+                };
+                let bind_addr = if addr.is_ipv4() {
+                    "0.0.0.0:0"
+                } else {
+                    "[::]:0"
+                };
+                let socket = UdpSocket::bind(bind_addr).await?;
+                socket.connect(addr).await?;
+                Ok(Self::Udp(socket))
+            }
+            Target::Tcp(address) => Ok(Self::Tcp(TcpStream::connect(address).await?)),
+            #[cfg(unix)]
+            Target::Unix(path) => Ok(Self::Unix(tokio::net::UnixDatagram::unbound()?, path.clone())),
+            Target::File(path) => {
+                let file = tokio::fs::OpenOptions::new().append(true).create(true).open(path).await?;
+                Ok(Self::File(file))
+            }
+        }
+    }
+
+    async fn write(&mut self, message: &str) -> io::Result<()> {
+        match self {
+            Self::Udp(socket) => socket.send(message.as_bytes()).await.map(|_| ()),
+            // Uses the non-transparent framing of RFC 6587, every message ends with a newline
+            Self::Tcp(stream) => stream.write_all(format!("{message}\n").as_bytes()).await,
+            #[cfg(unix)]
+            Self::Unix(socket, path) => socket.send_to(message.as_bytes(), path).await.map(|_| ()),
+            Self::File(file) => {
+                file.write_all(format!("{message}\n").as_bytes()).await?;
+                file.flush().await
+            }
+        }
+    }
+}
+
+/// Starts the task which writes the events to the configured sink, does nothing when `EVENT_SINK` is not set
+pub fn start(pool: DbPool) {
+    let target = match CONFIG.event_sink().as_str() {
+        "syslog" => Target::from_address(&CONFIG.event_sink_address()),
+        "file" => CONFIG.event_sink_file().map(|f| Target::File(PathBuf::from(f))),
+        _ => None,
+    };
+    let Some(target) = target else {
+        return;
+    };
+
+    let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+    if SENDER.set(sender).is_ok() {
+        info!("Forwarding the events to {target:?}");
+        tokio::spawn(write_events(target, receiver, pool));
+    }
+}
+
+async fn write_events(target: Target, mut receiver: mpsc::Receiver<Event>, pool: DbPool) {
+    let mut writer: Option<Writer> = None;
+    while let Some(event) = receiver.recv().await {
+        let message = match pool.get().await {
+            Ok(mut conn) => format_event(&SinkEvent::resolve(&event, &mut conn).await),
+            Err(e) => {
+                error!("Unable to get a database connection to forward an event: {e:?}");
+                continue;
+            }
+        };
+
+        // Connect on first use, and reconnect once when writing over an existing connection fails
+        for retry in [false, true] {
+            if writer.is_none() {
+                match Writer::connect(&target).await {
+                    Ok(w) => writer = Some(w),
+                    Err(e) => {
+                        error!("Unable to connect to the event sink {target:?}: {e}");
+                        break;
+                    }
+                }
+            }
+
+            let Some(w) = writer.as_mut() else {
+                break;
+            };
+            match w.write(&message).await {
+                Ok(()) => break,
+                Err(e) => {
+                    writer = None;
+                    if retry {
+                        error!("Unable to write an event to the event sink {target:?}: {e}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// An event together with the names which are resolved for the SIEM
+struct SinkEvent<'a> {
+    event: &'a Event,
+    name: String,
+    acting_user_email: Option<String>,
+    user_email: Option<String>,
+    org_name: Option<String>,
+}
+
+impl<'a> SinkEvent<'a> {
+    async fn resolve(event: &'a Event, conn: &mut DbConn) -> SinkEvent<'a> {
+        let acting_user_email = match &event.act_user_uuid {
+            Some(user_uuid) => User::find_by_uuid(user_uuid, conn).await.map(|u| u.email),
+            None => None,
+        };
+
+        // The user the event is about, for the member events this is the user of the membership
+        let mut user_uuid = event.user_uuid.clone();
+        if user_uuid.is_none() {
+            if let Some(member_uuid) = &event.org_user_uuid {
+                user_uuid = Membership::find_by_uuid(member_uuid, conn).await.map(|m| m.user_uuid);
+            }
+        }
+        let user_email = match &user_uuid {
+            Some(user_uuid) => User::find_by_uuid(user_uuid, conn).await.map(|u| u.email),
+            None => None,
+        };
+
+        let org_name = match &event.org_uuid {
+            Some(org_uuid) => Organization::find_by_uuid(org_uuid, conn).await.map(|o| o.name),
+            None => None,
+        };
+
+        SinkEvent {
+            event,
+            name: event_name(event.event_type),
+            acting_user_email,
+            user_email,
+            org_name,
+        }
+    }
+
+    fn to_json_line(&self) -> String {
+        let mut json = self.event.to_json();
+        json["name"] = Value::from(self.name.clone());
+        json["actingUserEmail"] = Value::from(self.acting_user_email.clone());
+        json["userEmail"] = Value::from(self.user_email.clone());
+        json["organizationName"] = Value::from(self.org_name.clone());
+        json.to_string()
+    }
+
+    /// The fields of the event, in the order they are listed in the RFC 5424 structured data
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let event = self.event;
+        [
+            ("type", Some(event.event_type.to_string())),
+            ("organizationId", event.org_uuid.as_ref().map(ToString::to_string)),
+            ("organizationName", self.org_name.clone()),
+            ("actingUserId", event.act_user_uuid.as_ref().map(ToString::to_string)),
+            ("actingUserEmail", self.acting_user_email.clone()),
+            ("userId", event.user_uuid.as_ref().map(ToString::to_string)),
+            ("userEmail", self.user_email.clone()),
+            ("organizationUserId", event.org_user_uuid.as_ref().map(ToString::to_string)),
+            ("cipherId", event.cipher_uuid.as_ref().map(ToString::to_string)),
+            ("collectionId", event.collection_uuid.as_ref().map(ToString::to_string)),
+            ("groupId", event.group_uuid.as_ref().map(ToString::to_string)),
+            ("policyId", event.policy_uuid.as_ref().map(ToString::to_string)),
+            ("deviceType", event.device_type.map(|d| d.to_string())),
+            ("ipAddress", event.ip_address.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|v| (key, v)))
+        .collect()
+    }
+
+    fn syslog_header(&self, hostname: &str) -> String {
+        let timestamp = self.event.event_date.and_utc().to_rfc3339_opts(SecondsFormat::Millis, true);
+        format!("<{SYSLOG_PRI}>1 {timestamp} {hostname} vaultwarden {} {}", std::process::id(), self.event.event_type)
+    }
+
+    fn to_rfc5424(&self, hostname: &str) -> String {
+        let params: String =
+            self.fields().into_iter().map(|(key, value)| format!(" {key}=\"{}\"", escape_sd_value(&value))).collect();
+        format!("{} [{SD_ID}{params}] {}", self.syslog_header(hostname), self.name)
+    }
+
+    fn to_cef(&self, hostname: &str) -> String {
+        let event = self.event;
+        let mut extension = vec![("rt", event.event_date.and_utc().timestamp_millis().to_string())];
+        let mut add = |key: &'static str, value: Option<String>| {
+            if let Some(value) = value {
+                extension.push((key, value));
+            }
+        };
+        add("suser", self.acting_user_email.clone());
+        add("suid", event.act_user_uuid.as_ref().map(ToString::to_string));
+        add("duser", self.user_email.clone());
+        add("duid", event.user_uuid.as_ref().map(ToString::to_string));
+        add("src", event.ip_address.clone());
+        add("cs1Label", event.org_uuid.as_ref().map(|_| String::from("organizationId")));
+        add("cs1", event.org_uuid.as_ref().map(ToString::to_string));
+        add("cs2Label", self.org_name.as_ref().map(|_| String::from("organizationName")));
+        add("cs2", self.org_name.clone());
+        add("cs3Label", event.cipher_uuid.as_ref().map(|_| String::from("cipherId")));
+        add("cs3", event.cipher_uuid.as_ref().map(ToString::to_string));
+
+        let extension: Vec<String> =
+            extension.into_iter().map(|(key, value)| format!("{key}={}", escape_cef_extension(&value))).collect();
+        format!(
+            "{} - CEF:0|Vaultwarden|Vaultwarden|{}|{}|{}|3|{}",
+            self.syslog_header(hostname),
+            escape_cef_header(crate::VERSION.unwrap_or("unknown")),
+            event.event_type,
+            escape_cef_header(&self.name),
+            extension.join(" ")
+        )
+    }
+
+    fn to_leef(&self, hostname: &str) -> String {
+        let event = self.event;
+        let mut attributes = vec![("devTime", event.event_date.and_utc().to_rfc3339_opts(SecondsFormat::Millis, true))];
+        attributes.push(("devTimeFormat", String::from("yyyy-MM-dd'T'HH:mm:ss.SSSX")));
+        attributes.push(("cat", self.name.clone()));
+        if let Some(email) = &self.acting_user_email {
+            attributes.push(("usrName", email.clone()));
+        }
+        if let Some(ip) = &event.ip_address {
+            attributes.push(("src", ip.clone()));
+        }
+        for (key, value) in self.fields() {
+            attributes.push((key, value));
+        }
+
+        let attributes: Vec<String> =
+            attributes.into_iter().map(|(key, value)| format!("{key}={}", value.replace(['\t', '\n'], " "))).collect();
+        format!(
+            "{} - LEEF:1.0|Vaultwarden|Vaultwarden|{}|{}|{}",
+            self.syslog_header(hostname),
+            crate::VERSION.unwrap_or("unknown").replace('|', " "),
+            event.event_type,
+            attributes.join("\t")
+        )
+    }
+}
+
+fn event_name(event_type: i32) -> String {
+    match <EventType as num_traits::FromPrimitive>::from_i32(event_type) {
+        Some(event_type) => format!("{event_type:?}"),
+        None => format!("Event{event_type}"),
+    }
+}
+
+fn escape_sd_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace(']', "\\]")
+}
+
+fn escape_cef_header(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+fn escape_cef_extension(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('=', "\\=").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+/// Forwards a logged event to the event sink, the names of the users and the organization are resolved first
+fn format_event(sink_event: &SinkEvent<'_>) -> String {
+    if CONFIG.event_sink() == "file" {
+        sink_event.to_json_line()
+    } else {
+        let domain = url::Url::parse(&CONFIG.domain()).ok();
+        let hostname = domain.as_ref().and_then(|d| d.host_str()).unwrap_or("-");
+        match CONFIG.event_sink_format().as_str() {
+            "cef" => sink_event.to_cef(hostname),
+            "leef" => sink_event.to_leef(hostname),
+            _ => sink_event.to_rfc5424(hostname),
+        }
+    }
+}
+
+/// Queues a saved event to be written to the sink, does nothing when `EVENT_SINK` is not set
+pub fn forward_event(event: Event) {
+    let Some(sender) = SENDER.get() else {
+        return;
+    };
+
+    let event_type = event.event_type;
+    if sender.try_send(event).is_err() {
+        warn!("The event sink queue is full, dropping an event of type {event_type}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+
+    fn test_event() -> Event {
+        let date = NaiveDateTime::parse_from_str("2025-04-01 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let mut event = Event::new(EventType::CipherCreated as i32, Some(date));
+        event.cipher_uuid = Some(String::from("cipher").into());
+        event.ip_address = Some(String::from("192.0.2.1"));
+        event
+    }
+
+    fn sink_event(event: &Event) -> SinkEvent<'_> {
+        SinkEvent {
+            event,
+            name: event_name(event.event_type),
+            acting_user_email: Some(String::from("user@example.com")),
+            user_email: None,
+            org_name: Some(String::from("Org \"A\" [1]")),
+        }
+    }
+
+    #[test]
+    fn test_rfc5424_format() {
+        let event = test_event();
+        let message = sink_event(&event).to_rfc5424("vault.example.com");
+        let expected_start = format!(
+            "<110>1 2025-04-01T12:00:00.000Z vault.example.com vaultwarden {} 1100 [vaultwarden@32473 type=\"1100\"",
+            std::process::id()
+        );
+        assert!(message.starts_with(&expected_start));
+        assert!(message.contains(" organizationName=\"Org \\\"A\\\" [1\\]\""));
+        assert!(message.contains(" cipherId=\"cipher\""));
+        assert!(message.ends_with("] CipherCreated"));
+    }
+
+    #[test]
+    fn test_cef_format() {
+        let event = test_event();
+        let message = sink_event(&event).to_cef("vault.example.com");
+        assert!(message.contains(" - CEF:0|Vaultwarden|Vaultwarden|"));
+        assert!(message.contains("|1100|CipherCreated|3|rt=1743508800000 suser=user@example.com src=192.0.2.1"));
+        assert!(message.contains(" cs3Label=cipherId cs3=cipher"));
+    }
+
+    #[test]
+    fn test_target_from_address() {
+        assert_eq!(Target::from_address("udp://127.0.0.1:514"), Some(Target::Udp(String::from("127.0.0.1:514"))));
+        assert_eq!(Target::from_address("tcp://siem:6514"), Some(Target::Tcp(String::from("siem:6514"))));
+        assert_eq!(Target::from_address("http://siem"), None);
+        assert_eq!(Target::from_address("udp://"), None);
+    }
+}
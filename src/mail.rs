@@ -16,6 +16,7 @@ use crate::{
         encode_jwt, generate_delete_claims, generate_emergency_access_invite_claims, generate_invite_claims,
         generate_verify_email_claims,
     },
+    config::Config,
     db::models::{Device, DeviceType, EmergencyAccessId, MembershipId, OrganizationId, User, UserId},
     error::Error,
     CONFIG,
@@ -30,25 +31,29 @@ fn sendmail_transport() -> AsyncSendmailTransport<Tokio1Executor> {
 }
 
 fn smtp_transport() -> AsyncSmtpTransport<Tokio1Executor> {
+    smtp_transport_for(&CONFIG)
+}
+
+fn smtp_transport_for(config: &Config) -> AsyncSmtpTransport<Tokio1Executor> {
     use std::time::Duration;
-    let host = CONFIG.smtp_host().unwrap();
+    let host = config.smtp_host().unwrap();
 
     let smtp_client = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host.as_str())
-        .port(CONFIG.smtp_port())
-        .timeout(Some(Duration::from_secs(CONFIG.smtp_timeout())));
+        .port(config.smtp_port())
+        .timeout(Some(Duration::from_secs(config.smtp_timeout())));
 
     // Determine security
-    let smtp_client = if CONFIG.smtp_security() != *"off" {
+    let smtp_client = if config.smtp_security() != *"off" {
         let mut tls_parameters = TlsParameters::builder(host);
-        if CONFIG.smtp_accept_invalid_hostnames() {
+        if config.smtp_accept_invalid_hostnames() {
             tls_parameters = tls_parameters.dangerous_accept_invalid_hostnames(true);
         }
-        if CONFIG.smtp_accept_invalid_certs() {
+        if config.smtp_accept_invalid_certs() {
             tls_parameters = tls_parameters.dangerous_accept_invalid_certs(true);
         }
         let tls_parameters = tls_parameters.build().unwrap();
 
-        if CONFIG.smtp_security() == *"force_tls" {
+        if config.smtp_security() == *"force_tls" {
             smtp_client.tls(Tls::Wrapper(tls_parameters))
         } else {
             smtp_client.tls(Tls::Required(tls_parameters))
@@ -57,17 +62,17 @@ fn smtp_transport() -> AsyncSmtpTransport<Tokio1Executor> {
         smtp_client
     };
 
-    let smtp_client = match (CONFIG.smtp_username(), CONFIG.smtp_password()) {
+    let smtp_client = match (config.smtp_username(), config.smtp_password()) {
         (Some(user), Some(pass)) => smtp_client.credentials(Credentials::new(user, pass)),
         _ => smtp_client,
     };
 
-    let smtp_client = match CONFIG.helo_name() {
+    let smtp_client = match config.helo_name() {
         Some(helo_name) => smtp_client.hello_name(ClientId::Domain(helo_name)),
         None => smtp_client,
     };
 
-    let smtp_client = match CONFIG.smtp_auth_mechanism() {
+    let smtp_client = match config.smtp_auth_mechanism() {
         Some(mechanism) => {
             let allowed_mechanisms = [SmtpAuthMechanism::Plain, SmtpAuthMechanism::Login, SmtpAuthMechanism::Xoauth2];
             let mut selected_mechanisms = vec![];
@@ -284,6 +289,30 @@ pub async fn send_invite(
     org_name: &str,
     invited_by_email: Option<String>,
 ) -> EmptyResult {
+    let url = org_invite_url(user, org_id, member_id, org_name, invited_by_email, false)?;
+    send_org_invite_email(user, &url, org_name).await
+}
+
+/// Invites the owner of an organization created in the admin panel, the organization keys are created when accepting it
+pub async fn send_org_owner_invite(
+    user: &User,
+    org_id: OrganizationId,
+    member_id: MembershipId,
+    org_name: &str,
+) -> EmptyResult {
+    let url = org_invite_url(user, org_id, member_id, org_name, None, true)?;
+    send_org_invite_email(user, &url, org_name).await
+}
+
+/// Returns the link to accept an organization invitation in the web vault
+pub fn org_invite_url(
+    user: &User,
+    org_id: OrganizationId,
+    member_id: MembershipId,
+    org_name: &str,
+    invited_by_email: Option<String>,
+    init_organization: bool,
+) -> Result<String, Error> {
     let claims = generate_invite_claims(
         user.uuid.clone(),
         user.email.clone(),
@@ -304,17 +333,24 @@ pub async fn send_invite(
         if user.private_key.is_some() {
             query_params.append_pair("orgUserHasExistingUser", "true");
         }
+        if init_organization {
+            query_params.append_pair("initOrganization", "true");
+        }
     }
 
     let Some(query_string) = query.query() else {
         err!("Failed to build invite URL query parameters")
     };
 
+    // `url.Url` would place the anchor `#` after the query parameters
+    Ok(format!("{}/#/accept-organization/?{}", CONFIG.domain(), query_string))
+}
+
+async fn send_org_invite_email(user: &User, url: &str, org_name: &str) -> EmptyResult {
     let (subject, body_html, body_text) = get_text(
         "email/send_org_invite",
         json!({
-            // `url.Url` would place the anchor `#` after the query parameters
-            "url": format!("{}/#/accept-organization/?{}", CONFIG.domain(), query_string),
+            "url": url,
             "img_src": CONFIG._smtp_img_src(),
             "org_name": org_name,
         }),
@@ -518,6 +554,25 @@ pub async fn send_new_device_logged_in(address: &str, ip: &str, dt: &NaiveDateTi
     send_email(address, &subject, body_html, body_text).await
 }
 
+pub async fn send_inactive_account_warning(
+    address: &str,
+    last_activity: &NaiveDateTime,
+    days_left: Option<i64>,
+) -> EmptyResult {
+    let (subject, body_html, body_text) = get_text(
+        "email/inactive_account_warning",
+        json!({
+            "url": CONFIG.domain(),
+            "img_src": CONFIG._smtp_img_src(),
+            "last_activity": crate::util::format_naive_datetime_local(last_activity, "%A, %B %_d, %Y"),
+            "days_left": days_left,
+            "revoke": CONFIG.inactive_user_action() == "revoke",
+        }),
+    )?;
+
+    send_email(address, &subject, body_html, body_text).await
+}
+
 pub async fn send_incomplete_2fa_login(
     address: &str,
     ip: &str,
@@ -544,6 +599,28 @@ pub async fn send_incomplete_2fa_login(
     send_email(address, &subject, body_html, body_text).await
 }
 
+pub async fn send_login_lockout(
+    address: &str,
+    ip: &str,
+    dt: &NaiveDateTime,
+    locked_until: &NaiveDateTime,
+) -> EmptyResult {
+    let fmt = "%A, %B %_d, %Y at %r %Z";
+    let (subject, body_html, body_text) = get_text(
+        "email/login_lockout",
+        json!({
+            "url": CONFIG.domain(),
+            "img_src": CONFIG._smtp_img_src(),
+            "ip": ip,
+            "attempts": CONFIG.login_lockout_attempts(),
+            "datetime": crate::util::format_naive_datetime_local(dt, fmt),
+            "locked_until": crate::util::format_naive_datetime_local(locked_until, fmt),
+        }),
+    )?;
+
+    send_email(address, &subject, body_html, body_text).await
+}
+
 pub async fn send_token(address: &str, token: &str) -> EmptyResult {
     let (subject, body_html, body_text) = get_text(
         "email/twofactor_email",
@@ -608,6 +685,19 @@ pub async fn send_protected_action_token(address: &str, token: &str) -> EmptyRes
     send_email(address, &subject, body_html, body_text).await
 }
 
+/// Connects to the SMTP server of `config` and logs in, without sending anything.
+/// Used to check a proposed config before it is applied.
+pub async fn test_smtp_connection(config: &Config) -> EmptyResult {
+    match smtp_transport_for(config).test_connection().await {
+        Ok(true) => Ok(()),
+        Ok(false) => err!("SMTP server did not accept the connection"),
+        Err(e) => {
+            debug!("SMTP test connection error: {:#?}", e);
+            err!(format!("SMTP error: {e}"));
+        }
+    }
+}
+
 async fn send_with_selected_transport(email: Message) -> EmptyResult {
     if CONFIG.use_sendmail() {
         match sendmail_transport().send(email).await {
@@ -689,5 +779,7 @@ async fn send_email(address: &str, subject: &str, body_html: String, body_text:
         .subject(subject)
         .multipart(body)?;
 
-    send_with_selected_transport(email).await
+    let res = send_with_selected_transport(email).await;
+    crate::metrics::count_mail(res.is_ok());
+    res
 }
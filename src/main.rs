@@ -49,13 +49,18 @@ use tokio::signal::unix::SignalKind;
 mod error;
 mod api;
 mod auth;
+mod backup;
 mod config;
 mod crypto;
 #[macro_use]
 mod db;
+mod event_sink;
 mod http_client;
 mod mail;
+mod metrics;
 mod ratelimit;
+mod sso;
+mod storage;
 mod util;
 
 use crate::api::core::two_factor::duo_oidc::purge_duo_contexts;
@@ -88,6 +93,7 @@ async fn main() -> Result<(), Error> {
 
     let pool = create_db_pool().await;
     schedule_jobs(pool.clone());
+    event_sink::start(pool.clone());
     db::models::TwoFactor::migrate_u2f_to_webauthn(&mut pool.get().await.unwrap()).await.unwrap();
 
     let extra_debug = matches!(level, log::LevelFilter::Trace | log::LevelFilter::Debug);
@@ -106,8 +112,18 @@ FLAGS:
 
 COMMAND:
     hash [--preset {bitwarden|owasp}]  Generate an Argon2id PHC ADMIN_TOKEN
-    backup                             Create a backup of the SQLite database
-                                       You can also send the USR1 signal to trigger a backup
+    backup [--output <file>]           Create a backup of the database, attachments, Sends, RSA key and config.json
+                                       The backup is encrypted when BACKUP_PUBLIC_KEY is configured
+                                       You can also send the USR1 signal to trigger a backup of the SQLite database
+    restore <file> [--private-key <key>]
+                                       Restore a backup into a new, empty, database of any supported type
+                                       Encrypted backups need the PEM encoded RSA private key
+    migrate-db --from <url> --to <url> Copy all the data from one database into a new, empty, database
+                                       The database types can differ, for example from SQLite to PostgreSQL
+                                       The source is not modified, it needs to be up to date with this version
+    admin-account create <username> [--role {read-only|user-support|admin}]
+                                       Create a named admin panel account, the default role is admin
+                                       Needs ENABLE_ADMIN_ACCOUNTS=true to be able to log in with it
 
 PRESETS:                  m=         t=          p=
     bitwarden (default) 64MiB, 3 Iterations, 4 Threads
@@ -135,48 +151,15 @@ async fn parse_args() {
 
     if let Some(command) = pargs.subcommand().unwrap_or_default() {
         if command == "hash" {
-            use argon2::{
-                password_hash::SaltString, Algorithm::Argon2id, Argon2, ParamsBuilder, PasswordHasher, Version::V0x13,
-            };
-
-            let mut argon2_params = ParamsBuilder::new();
             let preset: Option<String> = pargs.opt_value_from_str(["-p", "--preset"]).unwrap_or_default();
-            let selected_preset;
-            match preset.as_deref() {
-                Some("owasp") => {
-                    selected_preset = "owasp";
-                    argon2_params.m_cost(19456);
-                    argon2_params.t_cost(2);
-                    argon2_params.p_cost(1);
-                }
-                _ => {
-                    // Bitwarden preset is the default
-                    selected_preset = "bitwarden";
-                    argon2_params.m_cost(65540);
-                    argon2_params.t_cost(3);
-                    argon2_params.p_cost(4);
-                }
-            }
+            let (selected_preset, argon2_params) = crypto::argon2id_preset(preset.as_deref());
 
             println!("Generate an Argon2id PHC string using the '{selected_preset}' preset:\n");
 
-            let password = rpassword::prompt_password("Password: ").unwrap();
-            if password.len() < 8 {
-                println!("\nPassword must contain at least 8 characters");
-                exit(1);
-            }
-
-            let password_verify = rpassword::prompt_password("Confirm Password: ").unwrap();
-            if password != password_verify {
-                println!("\nPasswords do not match");
-                exit(1);
-            }
-
-            let argon2 = Argon2::new(Argon2id, V0x13, argon2_params.build().unwrap());
-            let salt = SaltString::encode_b64(&crypto::get_random_bytes::<32>()).unwrap();
+            let password = prompt_new_password();
 
             let argon2_timer = tokio::time::Instant::now();
-            if let Ok(password_hash) = argon2.hash_password(password.as_bytes(), &salt) {
+            if let Ok(password_hash) = crypto::hash_argon2id(&password, argon2_params) {
                 println!(
                     "\n\
                     ADMIN_TOKEN='{password_hash}'\n\n\
@@ -188,9 +171,17 @@ async fn parse_args() {
                 exit(1);
             }
         } else if command == "backup" {
-            match backup_sqlite().await {
-                Ok(f) => {
-                    println!("Backup to '{f}' was successful");
+            let output: Option<String> = pargs.opt_value_from_str(["-o", "--output"]).unwrap_or_default();
+            let output = output.unwrap_or_else(|| format!("{}/{}", CONFIG.data_folder(), backup::backup_file_name()));
+
+            let res: Result<(), Error> = async {
+                let mut conn = db::DbPool::from_config()?.get().await?;
+                backup::create_backup(Path::new(&output), &mut conn).await
+            }
+            .await;
+            match res {
+                Ok(()) => {
+                    println!("Backup to '{output}' was successful");
                     exit(0);
                 }
                 Err(e) => {
@@ -198,11 +189,118 @@ async fn parse_args() {
                     exit(1);
                 }
             }
+        } else if command == "migrate-db" {
+            let (Ok(from), Ok(to)) =
+                (pargs.value_from_str::<_, String>("--from"), pargs.value_from_str::<_, String>("--to"))
+            else {
+                println!("Both the --from and --to database URLs are required");
+                exit(1);
+            };
+            if from == to {
+                println!("The source and target databases need to be different");
+                exit(1);
+            }
+
+            let res: Result<HashMap<String, usize>, Error> = async {
+                // The source is only read, it must already be migrated to the schema of this version
+                let mut source = db::DbPool::from_url_without_migrations(&from)?.get().await?;
+                let mut target = db::DbPool::from_url(&to)?.get().await?;
+                db::dump::copy_database(&mut source, &mut target).await
+            }
+            .await;
+            match res {
+                Ok(row_counts) => {
+                    for table in db::dump::TABLES {
+                        println!("{table:<24} {:>10} rows", row_counts.get(*table).copied().unwrap_or_default());
+                    }
+                    println!("Migration of the database was successful");
+                    exit(0);
+                }
+                Err(e) => {
+                    println!("Migration failed. {e:?}");
+                    exit(1);
+                }
+            }
+        } else if command == "admin-account" {
+            let role: Option<String> = pargs.opt_value_from_str(["-r", "--role"]).unwrap_or_default();
+            let (Ok(action), Ok(username)) = (pargs.free_from_str::<String>(), pargs.free_from_str::<String>()) else {
+                println!("Usage: vaultwarden admin-account create <username> [--role <role>]");
+                exit(1);
+            };
+            if action != "create" {
+                println!("Unknown admin-account action '{action}', only 'create' is supported");
+                exit(1);
+            }
+            let Some(role) = db::models::AdminRole::from_str(role.as_deref().unwrap_or("admin")) else {
+                println!("Invalid role, use one of: read-only, user-support, admin");
+                exit(1);
+            };
+
+            let password = prompt_new_password();
+
+            let res: Result<(), Error> = async {
+                let mut conn = db::DbPool::from_config()?.get().await?;
+                if db::models::AdminAccount::find_by_username(&username, &mut conn).await.is_some() {
+                    err!("An admin account with this username already exists")
+                }
+                let mut account = db::models::AdminAccount::new(username.clone(), role);
+                account.set_password(&password)?;
+                account.save(&mut conn).await
+            }
+            .await;
+            match res {
+                Ok(()) => {
+                    println!("\nAdmin account '{username}' with the '{}' role was created", role.name());
+                    exit(0);
+                }
+                Err(e) => {
+                    println!("\nCreating the admin account failed. {e:?}");
+                    exit(1);
+                }
+            }
+        } else if command == "restore" {
+            let private_key: Option<String> = pargs.opt_value_from_str("--private-key").unwrap_or_default();
+            let Ok(input) = pargs.free_from_str::<String>() else {
+                println!("The backup file to restore is missing");
+                exit(1);
+            };
+
+            let res: Result<(), Error> = async {
+                let mut conn = db::DbPool::from_config()?.get().await?;
+                backup::restore_backup(Path::new(&input), private_key.as_deref().map(Path::new), &mut conn).await
+            }
+            .await;
+            match res {
+                Ok(()) => {
+                    println!("Restore of '{input}' was successful");
+                    exit(0);
+                }
+                Err(e) => {
+                    println!("Restore failed. {e:?}");
+                    exit(1);
+                }
+            }
         }
         exit(0);
     }
 }
 
+/// Prompts twice for a new password of at least 8 characters, exits when it is too short or doesn't match
+fn prompt_new_password() -> String {
+    let password = rpassword::prompt_password("Password: ").unwrap();
+    if password.len() < 8 {
+        println!("\nPassword must contain at least 8 characters");
+        exit(1);
+    }
+
+    let password_verify = rpassword::prompt_password("Confirm Password: ").unwrap();
+    if password != password_verify {
+        println!("\nPasswords do not match");
+        exit(1);
+    }
+    password
+}
+
 async fn backup_sqlite() -> Result<String, Error> {
     use crate::db::{backup_database, DbConnType};
     if DbConnType::from_url(&CONFIG.database_url()).map(|t| t == DbConnType::sqlite).unwrap_or(false) {
@@ -364,7 +462,21 @@ fn init_logging() -> Result<log::LevelFilter, Error> {
         logger = logger.level_for(path.to_string(), level);
     }
 
-    if CONFIG.extended_logging() {
+    if CONFIG.log_format() == "json" {
+        logger = logger.format(|out, message, record| {
+            let mut entry = serde_json::Map::new();
+            entry.insert(
+                "timestamp".into(),
+                chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true).into(),
+            );
+            entry.insert("level".into(), record.level().as_str().into());
+            entry.insert("target".into(), record.target().into());
+            entry.insert("message".into(), message.to_string().into());
+            // Add the structured fields of the log record, like the correlation id of requests
+            record.key_values().visit(&mut util::JsonLogFields(&mut entry)).ok();
+            out.finish(format_args!("{}", serde_json::Value::Object(entry)))
+        });
+    } else if CONFIG.extended_logging() {
         logger = logger.format(|out, message, record| {
             out.finish(format_args!(
                 "[{}][{}][{}] {}",
@@ -579,6 +691,8 @@ async fn launch_rocket(pool: db::DbPool, extra_debug: bool) -> Result<(), Error>
         .mount([basepath, "/identity"].concat(), api::identity_routes())
         .mount([basepath, "/icons"].concat(), api::icons_routes())
         .mount([basepath, "/notifications"].concat(), api::notifications_routes())
+        .mount([basepath, "/scim/v2"].concat(), api::scim_routes())
+        .mount([basepath, "/metrics"].concat(), api::metrics_routes())
         .register([basepath, "/"].concat(), api::web_catchers())
         .register([basepath, "/api"].concat(), api::core_catchers())
         .register([basepath, "/admin"].concat(), api::admin_catchers())
@@ -588,6 +702,7 @@ async fn launch_rocket(pool: db::DbPool, extra_debug: bool) -> Result<(), Error>
         .attach(util::AppHeaders())
         .attach(util::Cors())
         .attach(util::BetterLogging(extra_debug))
+        .attach(metrics::RequestMetrics)
         .ignite()
         .await?;
 
@@ -637,17 +752,18 @@ fn schedule_jobs(pool: db::DbPool) {
 
             let mut sched = JobScheduler::new();
 
-            // Purge sends that are past their deletion date.
+            // Purge sends that are past their deletion date, and the rate limiter states which expired.
             if !CONFIG.send_purge_schedule().is_empty() {
                 sched.add(Job::new(CONFIG.send_purge_schedule().parse().unwrap(), || {
-                    runtime.spawn(api::purge_sends(pool.clone()));
+                    ratelimit::purge_limiters();
+                    runtime.spawn(metrics::time_job("send_purge", api::purge_sends(pool.clone())));
                 }));
             }
 
             // Purge trashed items that are old enough to be auto-deleted.
             if !CONFIG.trash_purge_schedule().is_empty() {
                 sched.add(Job::new(CONFIG.trash_purge_schedule().parse().unwrap(), || {
-                    runtime.spawn(api::purge_trashed_ciphers(pool.clone()));
+                    runtime.spawn(metrics::time_job("trash_purge", api::purge_trashed_ciphers(pool.clone())));
                 }));
             }
 
@@ -655,7 +771,10 @@ fn schedule_jobs(pool: db::DbPool) {
             // indicates that a user's master password has been compromised.
             if !CONFIG.incomplete_2fa_schedule().is_empty() {
                 sched.add(Job::new(CONFIG.incomplete_2fa_schedule().parse().unwrap(), || {
-                    runtime.spawn(api::send_incomplete_2fa_notifications(pool.clone()));
+                    runtime.spawn(metrics::time_job(
+                        "incomplete_2fa",
+                        api::send_incomplete_2fa_notifications(pool.clone()),
+                    ));
                 }));
             }
 
@@ -664,7 +783,10 @@ fn schedule_jobs(pool: db::DbPool) {
             // sending reminders for requests that are about to be granted anyway.
             if !CONFIG.emergency_request_timeout_schedule().is_empty() {
                 sched.add(Job::new(CONFIG.emergency_request_timeout_schedule().parse().unwrap(), || {
-                    runtime.spawn(api::emergency_request_timeout_job(pool.clone()));
+                    runtime.spawn(metrics::time_job(
+                        "emergency_request_timeout",
+                        api::emergency_request_timeout_job(pool.clone()),
+                    ));
                 }));
             }
 
@@ -672,20 +794,30 @@ fn schedule_jobs(pool: db::DbPool) {
             // emergency access requests.
             if !CONFIG.emergency_notification_reminder_schedule().is_empty() {
                 sched.add(Job::new(CONFIG.emergency_notification_reminder_schedule().parse().unwrap(), || {
-                    runtime.spawn(api::emergency_notification_reminder_job(pool.clone()));
+                    runtime.spawn(metrics::time_job(
+                        "emergency_notification_reminder",
+                        api::emergency_notification_reminder_job(pool.clone()),
+                    ));
                 }));
             }
 
             if !CONFIG.auth_request_purge_schedule().is_empty() {
                 sched.add(Job::new(CONFIG.auth_request_purge_schedule().parse().unwrap(), || {
-                    runtime.spawn(purge_auth_requests(pool.clone()));
+                    runtime.spawn(metrics::time_job("auth_request_purge", purge_auth_requests(pool.clone())));
                 }));
             }
 
             // Clean unused, expired Duo authentication contexts.
             if !CONFIG.duo_context_purge_schedule().is_empty() && CONFIG._enable_duo() && !CONFIG.duo_use_iframe() {
                 sched.add(Job::new(CONFIG.duo_context_purge_schedule().parse().unwrap(), || {
-                    runtime.spawn(purge_duo_contexts(pool.clone()));
+                    runtime.spawn(metrics::time_job("duo_context_purge", purge_duo_contexts(pool.clone())));
+                }));
+            }
+
+            // Clean SSO logins which were never completed.
+            if !CONFIG.sso_auth_purge_schedule().is_empty() && CONFIG.sso_enabled() {
+                sched.add(Job::new(CONFIG.sso_auth_purge_schedule().parse().unwrap(), || {
+                    runtime.spawn(metrics::time_job("sso_auth_purge", api::purge_sso_auth(pool.clone())));
                 }));
             }
 
@@ -695,7 +827,28 @@ fn schedule_jobs(pool: db::DbPool) {
                 && CONFIG.events_days_retain().is_some()
             {
                 sched.add(Job::new(CONFIG.event_cleanup_schedule().parse().unwrap(), || {
-                    runtime.spawn(api::event_cleanup_job(pool.clone()));
+                    runtime.spawn(metrics::time_job("event_cleanup", api::event_cleanup_job(pool.clone())));
+                }));
+            }
+
+            // Deliver the queued events to the organization webhooks, and retry the failed deliveries.
+            if CONFIG.org_events_enabled() && !CONFIG.webhook_delivery_schedule().is_empty() {
+                sched.add(Job::new(CONFIG.webhook_delivery_schedule().parse().unwrap(), || {
+                    runtime.spawn(metrics::time_job("webhook_delivery", api::webhook_delivery_job(pool.clone())));
+                }));
+            }
+
+            // Warn the inactive users, and disable them or revoke their organization memberships.
+            if !CONFIG.inactive_user_schedule().is_empty() {
+                sched.add(Job::new(CONFIG.inactive_user_schedule().parse().unwrap(), || {
+                    runtime.spawn(metrics::time_job("inactive_user", api::inactive_user_job(pool.clone())));
+                }));
+            }
+
+            // Create a full backup and remove the backups which are no longer retained.
+            if !CONFIG.backup_schedule().is_empty() {
+                sched.add(Job::new(CONFIG.backup_schedule().parse().unwrap(), || {
+                    runtime.spawn(metrics::time_job("backup", backup::backup_job(pool.clone())));
                 }));
             }
 
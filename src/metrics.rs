@@ -0,0 +1,231 @@
+//
+// Prometheus metrics, exported in the text exposition format by the `/metrics` endpoint
+// See: https://prometheus.io/docs/instrumenting/exposition_formats/
+//
+// Nothing is recorded unless `METRICS_ENABLED` is set.
+//
+use std::{fmt::Write, future::Future, time::Instant};
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    Data, Request, Response,
+};
+
+use crate::{util::RequestContext, CONFIG};
+
+// Upper bounds of the buckets of the duration histograms, in seconds
+const DURATION_BUCKETS: [f64; 12] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+#[derive(Default)]
+struct Histogram {
+    // Cumulative, every bucket counts the observations less than or equal to its upper bound
+    buckets: [u64; DURATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        for (bucket, bound) in self.buckets.iter().zip(DURATION_BUCKETS) {
+            writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {bucket}").ok();
+        }
+        writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", self.count).ok();
+        writeln!(out, "{name}_sum{{{labels}}} {}", self.sum).ok();
+        writeln!(out, "{name}_count{{{labels}}} {}", self.count).ok();
+    }
+}
+
+// Keyed by method, route and status code
+static HTTP_REQUESTS: Lazy<DashMap<(String, String, u16), u64>> = Lazy::new(DashMap::new);
+// Keyed by method and route
+static HTTP_REQUEST_DURATIONS: Lazy<DashMap<(String, String), Histogram>> = Lazy::new(DashMap::new);
+// Keyed by the name of the rate limiter
+static RATELIMIT_REJECTIONS: Lazy<DashMap<&'static str, u64>> = Lazy::new(DashMap::new);
+// Keyed by the result, `success` or `failure`
+static MAILS_SENT: Lazy<DashMap<&'static str, u64>> = Lazy::new(DashMap::new);
+// Keyed by the result, `hit`, `negative_hit` or `miss`
+static ICON_CACHE_REQUESTS: Lazy<DashMap<&'static str, u64>> = Lazy::new(DashMap::new);
+// Keyed by the name of the job
+static JOB_DURATIONS: Lazy<DashMap<&'static str, Histogram>> = Lazy::new(DashMap::new);
+
+fn increment(counter: &DashMap<&'static str, u64>, label: &'static str) {
+    if CONFIG.metrics_enabled() {
+        *counter.entry(label).or_default() += 1;
+    }
+}
+
+pub fn count_ratelimit_rejection(limiter: &'static str) {
+    increment(&RATELIMIT_REJECTIONS, limiter);
+}
+
+pub fn count_mail(success: bool) {
+    let result = match success {
+        true => "success",
+        false => "failure",
+    };
+    increment(&MAILS_SENT, result);
+}
+
+pub fn count_icon_cache(result: &'static str) {
+    increment(&ICON_CACHE_REQUESTS, result);
+}
+
+/// Runs a scheduled job and records how long it took
+pub async fn time_job(job: &'static str, fut: impl Future<Output = ()>) {
+    let start = Instant::now();
+    fut.await;
+    if CONFIG.metrics_enabled() {
+        JOB_DURATIONS.entry(job).or_default().observe(start.elapsed().as_secs_f64());
+    }
+}
+
+/// Records the number and the duration of the requests per route
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request Metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _data: &mut Data<'_>) {
+        if CONFIG.metrics_enabled() {
+            RequestContext::from_request(request);
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if !CONFIG.metrics_enabled() {
+            return;
+        }
+
+        let elapsed = RequestContext::from_request(request).start.elapsed();
+        let method = request.method().as_str().to_string();
+        // Use the route template instead of the actual path, to keep the number of label values bounded
+        let route = match request.route() {
+            Some(route) => route.uri.as_str().to_string(),
+            None => String::from("unmatched"),
+        };
+
+        *HTTP_REQUESTS.entry((method.clone(), route.clone(), response.status().code)).or_default() += 1;
+        HTTP_REQUEST_DURATIONS.entry((method, route)).or_default().observe(elapsed.as_secs_f64());
+    }
+}
+
+/// Values which are read at the time of the scrape
+pub struct Gauges {
+    pub db_connections: u32,
+    pub db_connections_in_use: u32,
+    pub db_max_connections: u32,
+    pub websocket_connections: usize,
+    pub websocket_anonymous_subscriptions: usize,
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {name} {help}").ok();
+    writeln!(out, "# TYPE {name} {kind}").ok();
+}
+
+fn render_counter(out: &mut String, name: &str, help: &str, label: &str, counter: &DashMap<&'static str, u64>) {
+    write_header(out, name, "counter", help);
+    for entry in counter.iter() {
+        writeln!(out, "{name}{{{label}=\"{}\"}} {}", entry.key(), entry.value()).ok();
+    }
+}
+
+/// Renders all the metrics in the Prometheus text format
+pub fn render(gauges: &Gauges) -> String {
+    let mut out = String::new();
+
+    let name = "vaultwarden_http_requests_total";
+    write_header(&mut out, name, "counter", "Number of HTTP requests per route and status code");
+    for entry in HTTP_REQUESTS.iter() {
+        let (method, route, status) = entry.key();
+        writeln!(
+            out,
+            "{name}{{method=\"{method}\",route=\"{}\",status=\"{status}\"}} {}",
+            escape_label(route),
+            entry.value()
+        )
+        .ok();
+    }
+
+    let name = "vaultwarden_http_request_duration_seconds";
+    write_header(&mut out, name, "histogram", "Duration of the HTTP requests per route");
+    for entry in HTTP_REQUEST_DURATIONS.iter() {
+        let (method, route) = entry.key();
+        entry.value().render(&mut out, name, &format!("method=\"{method}\",route=\"{}\"", escape_label(route)));
+    }
+
+    for (name, help, value) in [
+        ("vaultwarden_db_pool_connections", "Number of open database connections", gauges.db_connections),
+        (
+            "vaultwarden_db_pool_connections_in_use",
+            "Number of database connections in use",
+            gauges.db_connections_in_use,
+        ),
+        ("vaultwarden_db_pool_max_connections", "Maximum number of database connections", gauges.db_max_connections),
+    ] {
+        write_header(&mut out, name, "gauge", help);
+        writeln!(out, "{name} {value}").ok();
+    }
+
+    for (name, help, value) in [
+        (
+            "vaultwarden_websocket_connections",
+            "Number of WebSocket connections of logged in users",
+            gauges.websocket_connections,
+        ),
+        (
+            "vaultwarden_websocket_anonymous_subscriptions",
+            "Number of anonymous WebSocket subscriptions, used for login with device",
+            gauges.websocket_anonymous_subscriptions,
+        ),
+    ] {
+        write_header(&mut out, name, "gauge", help);
+        writeln!(out, "{name} {value}").ok();
+    }
+
+    render_counter(
+        &mut out,
+        "vaultwarden_ratelimit_rejections_total",
+        "Number of requests rejected by a rate limiter",
+        "limiter",
+        &RATELIMIT_REJECTIONS,
+    );
+    render_counter(&mut out, "vaultwarden_mails_sent_total", "Number of mails sent, per result", "result", &MAILS_SENT);
+    render_counter(
+        &mut out,
+        "vaultwarden_icon_cache_requests_total",
+        "Number of icon requests, per cache result",
+        "result",
+        &ICON_CACHE_REQUESTS,
+    );
+
+    let name = "vaultwarden_job_duration_seconds";
+    write_header(&mut out, name, "histogram", "Duration of the scheduled jobs");
+    for entry in JOB_DURATIONS.iter() {
+        entry.value().render(&mut out, name, &format!("job=\"{}\"", entry.key()));
+    }
+
+    out
+}
@@ -1,9 +1,15 @@
+use chrono::Utc;
+use dashmap::DashMap;
 use once_cell::sync::Lazy;
-use std::{net::IpAddr, num::NonZeroU32, time::Duration};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    num::NonZeroU32,
+    time::Duration,
+};
 
 use governor::{clock::DefaultClock, state::keyed::DashMapStateStore, Quota, RateLimiter};
 
-use crate::{Error, CONFIG};
+use crate::{db::models::UserId, Error, CONFIG};
 
 type Limiter<T = IpAddr> = RateLimiter<T, DashMapStateStore<T>, DefaultClock>;
 
@@ -13,26 +19,231 @@ static LIMITER_LOGIN: Lazy<Limiter> = Lazy::new(|| {
     RateLimiter::keyed(Quota::with_period(seconds).expect("Non-zero login ratelimit seconds").allow_burst(burst))
 });
 
+// The subnet and account limits can be disabled by setting their burst to 0
+static LIMITER_LOGIN_SUBNET: Lazy<Option<Limiter>> = Lazy::new(|| {
+    let seconds = Duration::from_secs(CONFIG.login_ratelimit_subnet_seconds());
+    let burst = NonZeroU32::new(CONFIG.login_ratelimit_subnet_max_burst())?;
+    Some(RateLimiter::keyed(
+        Quota::with_period(seconds).expect("Non-zero login subnet ratelimit seconds").allow_burst(burst),
+    ))
+});
+
+static LIMITER_LOGIN_ACCOUNT: Lazy<Option<Limiter<UserId>>> = Lazy::new(|| {
+    let seconds = Duration::from_secs(CONFIG.login_ratelimit_account_seconds());
+    let burst = NonZeroU32::new(CONFIG.login_ratelimit_account_max_burst())?;
+    Some(RateLimiter::keyed(
+        Quota::with_period(seconds).expect("Non-zero login account ratelimit seconds").allow_burst(burst),
+    ))
+});
+
+/// The failed master password logins for one account from one IP address
+struct LoginFailures {
+    count: u32,
+    last_failed: i64,
+}
+
+impl LoginFailures {
+    /// The failures are forgotten once the longest delay has passed since the last one
+    fn is_expired(&self, now: i64, max_delay: i64) -> bool {
+        now - self.last_failed >= max_delay
+    }
+
+    /// The seconds left to wait, the delay doubles with every failure up to `max_delay`
+    fn remaining_delay(&self, now: i64, base_delay: i64, max_delay: i64) -> i64 {
+        if base_delay == 0 || self.count == 0 {
+            return 0;
+        }
+        let delay = base_delay.saturating_mul(1 << (self.count - 1).min(30)).min(max_delay);
+        delay - (now - self.last_failed)
+    }
+
+    fn register(&mut self, now: i64, max_delay: i64) {
+        if self.is_expired(now, max_delay) {
+            self.count = 0;
+        }
+        self.count = self.count.saturating_add(1);
+        self.last_failed = now;
+    }
+}
+
+// Keyed by account and IP address, so failed logins from elsewhere can't keep the owner of an account waiting
+static LOGIN_FAILURES: Lazy<DashMap<(UserId, IpAddr), LoginFailures>> = Lazy::new(DashMap::new);
+
 static LIMITER_ADMIN: Lazy<Limiter> = Lazy::new(|| {
     let seconds = Duration::from_secs(CONFIG.admin_ratelimit_seconds());
     let burst = NonZeroU32::new(CONFIG.admin_ratelimit_max_burst()).expect("Non-zero admin ratelimit burst");
     RateLimiter::keyed(Quota::with_period(seconds).expect("Non-zero admin ratelimit seconds").allow_burst(burst))
 });
 
-pub fn check_limit_login(ip: &IpAddr) -> Result<(), Error> {
-    match LIMITER_LOGIN.check_key(ip) {
-        Ok(_) => Ok(()),
-        Err(_e) => {
-            err_code!("Too many login requests", 429);
+/// The first address of the subnet of `ip`, using the configured prefix lengths
+fn subnet_of(ip: &IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => {
+            let prefix = u32::from(CONFIG.login_ratelimit_ipv4_prefix().min(32));
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(*ip) & mask))
+        }
+        IpAddr::V6(ip) => {
+            let prefix = u32::from(CONFIG.login_ratelimit_ipv6_prefix().min(128));
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(*ip) & mask))
         }
     }
 }
 
+pub fn check_limit_login(ip: &IpAddr) -> Result<(), Error> {
+    let subnet_allowed = match *LIMITER_LOGIN_SUBNET {
+        Some(ref limiter) => limiter.check_key(&subnet_of(ip)).is_ok(),
+        None => true,
+    };
+
+    if !subnet_allowed || LIMITER_LOGIN.check_key(ip).is_err() {
+        crate::metrics::count_ratelimit_rejection("login");
+        err_code!("Too many login requests", 429);
+    }
+    Ok(())
+}
+
+/// Limits the login requests for one existing account, wherever they come from.
+/// Unknown emails are only limited per IP and subnet, so they can't fill the limiter.
+pub fn check_limit_login_account(user_id: &UserId) -> Result<(), Error> {
+    let Some(ref limiter) = *LIMITER_LOGIN_ACCOUNT else {
+        return Ok(());
+    };
+
+    if limiter.check_key(user_id).is_err() {
+        crate::metrics::count_ratelimit_rejection("login_account");
+        err_code!("Too many login requests for this account", 429);
+    }
+    Ok(())
+}
+
+/// Returns the number of seconds to wait before the next login attempt for this account from this IP address
+pub fn login_retry_delay(user_id: &UserId, ip: &IpAddr) -> Option<i64> {
+    let failures = LOGIN_FAILURES.get(&(user_id.clone(), *ip))?;
+    let remaining = failures.remaining_delay(
+        Utc::now().timestamp(),
+        CONFIG.login_failure_delay_seconds() as i64,
+        CONFIG.login_failure_max_delay_seconds() as i64,
+    );
+    (remaining > 0).then_some(remaining)
+}
+
+/// Counts a failed master password login for the retry delay, nothing is kept when the delay is disabled
+pub fn register_login_failure(user_id: &UserId, ip: &IpAddr) {
+    if CONFIG.login_failure_delay_seconds() == 0 {
+        return;
+    }
+    let now = Utc::now().timestamp();
+    LOGIN_FAILURES
+        .entry((user_id.clone(), *ip))
+        .or_insert(LoginFailures {
+            count: 0,
+            last_failed: now,
+        })
+        .register(now, CONFIG.login_failure_max_delay_seconds() as i64);
+}
+
+pub fn reset_login_failures(user_id: &UserId, ip: &IpAddr) {
+    LOGIN_FAILURES.remove(&(user_id.clone(), *ip));
+}
+
 pub fn check_limit_admin(ip: &IpAddr) -> Result<(), Error> {
     match LIMITER_ADMIN.check_key(ip) {
         Ok(_) => Ok(()),
         Err(_e) => {
+            crate::metrics::count_ratelimit_rejection("admin");
             err_code!("Too many admin requests", 429);
         }
     }
 }
+
+/// Forgets the keys whose limit was fully replenished, and frees the memory they used
+pub fn purge_limiters() {
+    LIMITER_LOGIN.retain_recent();
+    LIMITER_LOGIN.shrink_to_fit();
+    LIMITER_ADMIN.retain_recent();
+    LIMITER_ADMIN.shrink_to_fit();
+    if let Some(ref limiter) = *LIMITER_LOGIN_SUBNET {
+        limiter.retain_recent();
+        limiter.shrink_to_fit();
+    }
+    if let Some(ref limiter) = *LIMITER_LOGIN_ACCOUNT {
+        limiter.retain_recent();
+        limiter.shrink_to_fit();
+    }
+    purge_login_failures(Utc::now().timestamp(), CONFIG.login_failure_max_delay_seconds() as i64);
+}
+
+fn purge_login_failures(now: i64, max_delay: i64) {
+    LOGIN_FAILURES.retain(|_, failures| !failures.is_expired(now, max_delay));
+    LOGIN_FAILURES.shrink_to_fit();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_key() -> (UserId, IpAddr) {
+        (UserId::from(crate::util::get_uuid()), IpAddr::V4(Ipv4Addr::LOCALHOST))
+    }
+
+    #[test]
+    fn test_login_retry_delay() {
+        let now = Utc::now().timestamp();
+        let mut failures = LoginFailures {
+            count: 0,
+            last_failed: now,
+        };
+        assert_eq!(failures.remaining_delay(now, 1, 60), 0);
+
+        failures.register(now, 60);
+        assert_eq!(failures.remaining_delay(now, 1, 60), 1);
+        failures.register(now, 60);
+        failures.register(now, 60);
+        assert_eq!(failures.remaining_delay(now, 1, 60), 4);
+        assert_eq!(failures.remaining_delay(now + 3, 1, 60), 1);
+        assert_eq!(failures.remaining_delay(now, 0, 60), 0);
+
+        // The delay stops growing at the maximum, and the failures are forgotten after it
+        for _ in 0..40 {
+            failures.register(now, 60);
+        }
+        assert_eq!(failures.remaining_delay(now, 1, 60), 60);
+        failures.register(now + 60, 60);
+        assert_eq!(failures.count, 1);
+
+        // The delay is disabled by default
+        let (user_id, ip) = test_key();
+        register_login_failure(&user_id, &ip);
+        assert!(login_retry_delay(&user_id, &ip).is_none());
+    }
+
+    #[test]
+    fn test_purge_login_failures() {
+        let now = Utc::now().timestamp();
+        let (recent, ip) = test_key();
+        let (expired, _) = test_key();
+        LOGIN_FAILURES.insert(
+            (recent.clone(), ip),
+            LoginFailures {
+                count: 1,
+                last_failed: now - 59,
+            },
+        );
+        LOGIN_FAILURES.insert(
+            (expired.clone(), ip),
+            LoginFailures {
+                count: 5,
+                last_failed: now - 60,
+            },
+        );
+
+        purge_login_failures(now, 60);
+        assert!(LOGIN_FAILURES.contains_key(&(recent.clone(), ip)));
+        assert!(!LOGIN_FAILURES.contains_key(&(expired, ip)));
+
+        reset_login_failures(&recent, &ip);
+        assert!(!LOGIN_FAILURES.contains_key(&(recent, ip)));
+    }
+}
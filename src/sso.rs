@@ -0,0 +1,624 @@
+//
+// OpenID Connect client used for the SSO login flow
+//
+use std::{
+    str::FromStr,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use once_cell::sync::Lazy;
+use reqwest::{header, Method, RequestBuilder};
+use ring::digest::{digest, SHA256};
+use serde::de::DeserializeOwned;
+use url::Url;
+
+use crate::{
+    api::ApiResult,
+    crypto,
+    db::models::{EventType, SsoAuth},
+    http_client::make_http_request,
+    CONFIG,
+};
+
+// Number of seconds the discovery document and the signing keys of the identity provider are cached.
+const PROVIDER_CACHE_SECS: u64 = 600;
+
+// Size of the random state and nonce sent to the identity provider.
+// If increasing this above 64, also increase the size of the sso_auth.state and
+// sso_auth.nonce database columns for postgres and mariadb.
+const STATE_LENGTH: usize = 64;
+
+// https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderMetadata
+#[derive(Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+    userinfo_endpoint: Option<String>,
+    id_token_signing_alg_values_supported: Option<Vec<String>>,
+}
+
+#[derive(Clone)]
+struct Provider {
+    authority: String,
+    metadata: ProviderMetadata,
+    jwks: JwkSet,
+    fetched_at: Instant,
+}
+
+static PROVIDER: Lazy<RwLock<Option<Provider>>> = Lazy::new(|| RwLock::new(None));
+
+/// The registration of Vaultwarden at the identity provider
+struct ClientSettings {
+    authority: String,
+    client_id: String,
+    client_secret: String,
+    callback_path: String,
+    allow_hmac: bool,
+}
+
+impl ClientSettings {
+    fn from_config() -> Self {
+        Self {
+            authority: CONFIG.sso_authority().trim_end_matches('/').to_string(),
+            client_id: CONFIG.sso_client_id(),
+            client_secret: CONFIG.sso_client_secret(),
+            callback_path: CONFIG.sso_callback_path(),
+            allow_hmac: CONFIG.sso_allow_hmac(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    id_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    email_verified: Option<bool>,
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UserInfoResponse {
+    sub: String,
+    email: Option<String>,
+    email_verified: Option<bool>,
+    name: Option<String>,
+}
+
+/// The identity of the user as reported by the identity provider
+pub struct UserInformation {
+    /// Unique identifier of the user, made of the issuer and the subject
+    pub identifier: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+}
+
+fn http_request(method: Method, url: &str) -> ApiResult<RequestBuilder> {
+    // The tests run a mock identity provider on localhost, which the filter of `make_http_request` would block
+    if cfg!(test) {
+        return Ok(reqwest::Client::new().request(method, url));
+    }
+    make_http_request(method, url)
+}
+
+async fn get_json<T: DeserializeOwned>(url: &str) -> ApiResult<T> {
+    let res = match http_request(Method::GET, url)?.header(header::ACCEPT, "application/json").send().await {
+        Ok(r) => r,
+        Err(e) => err!(format!("Error requesting {url}: {e}")),
+    };
+
+    match res.error_for_status() {
+        Ok(r) => match r.json::<T>().await {
+            Ok(json) => Ok(json),
+            Err(e) => err!(format!("Error decoding the response of {url}: {e}")),
+        },
+        Err(e) => err!(format!("Error requesting {url}: {e}")),
+    }
+}
+
+async fn fetch_provider(authority: String) -> ApiResult<Provider> {
+    let metadata: ProviderMetadata = get_json(&format!("{authority}/.well-known/openid-configuration")).await?;
+
+    // The issuer needs to be identical to the URL the discovery document was retrieved from
+    // See: https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderConfigurationValidation
+    if metadata.issuer.trim_end_matches('/') != authority {
+        err!(format!("The issuer of the identity provider ({}) does not match SSO_AUTHORITY", metadata.issuer))
+    }
+
+    let jwks: JwkSet = get_json(&metadata.jwks_uri).await?;
+
+    Ok(Provider {
+        authority,
+        metadata,
+        jwks,
+        fetched_at: Instant::now(),
+    })
+}
+
+/// Returns the metadata and keys of the identity provider.
+/// These are cached, unless a `refresh` is requested, for example when an unknown key is used to sign a token.
+async fn provider(authority: &str, refresh: bool) -> ApiResult<Provider> {
+    if !refresh {
+        if let Some(provider) = PROVIDER.read().unwrap().as_ref() {
+            if provider.authority == authority
+                && provider.fetched_at.elapsed() < Duration::from_secs(PROVIDER_CACHE_SECS)
+            {
+                return Ok(provider.clone());
+            }
+        }
+    }
+
+    let provider = fetch_provider(authority.to_string()).await?;
+    *PROVIDER.write().unwrap() = Some(provider.clone());
+    Ok(provider)
+}
+
+fn scopes() -> String {
+    let mut scopes = vec!["openid"];
+    scopes.extend(CONFIG.sso_scopes().split_whitespace().filter(|s| *s != "openid"));
+    scopes.join(" ")
+}
+
+/// Computes the S256 PKCE code challenge of a verifier
+pub fn pkce_challenge(verifier: &str) -> String {
+    BASE64URL_NOPAD.encode(digest(&SHA256, verifier.as_bytes()).as_ref())
+}
+
+/// Creates the state of a new SSO login and the URL of the identity provider the client has to be redirected to
+pub async fn authorize(
+    client_state: String,
+    code_challenge: String,
+    redirect_uri: String,
+) -> ApiResult<(SsoAuth, String)> {
+    let settings = ClientSettings::from_config();
+    let provider = provider(&settings.authority, false).await?;
+
+    let state = crypto::get_random_string_alphanum(STATE_LENGTH);
+    let nonce = crypto::get_random_string_alphanum(STATE_LENGTH);
+    let verifier = CONFIG.sso_pkce().then(|| crypto::encode_random_bytes::<64>(BASE64URL_NOPAD));
+
+    let mut url = match Url::parse(&provider.metadata.authorization_endpoint) {
+        Ok(url) => url,
+        Err(e) => err!(format!("Invalid authorization endpoint of the identity provider: {e}")),
+    };
+
+    {
+        let mut query_params = url.query_pairs_mut();
+        query_params.append_pair("response_type", "code");
+        query_params.append_pair("client_id", &settings.client_id);
+        query_params.append_pair("redirect_uri", &settings.callback_path);
+        query_params.append_pair("scope", &scopes());
+        query_params.append_pair("state", &state);
+        query_params.append_pair("nonce", &nonce);
+        if let Some(ref verifier) = verifier {
+            query_params.append_pair("code_challenge", &pkce_challenge(verifier));
+            query_params.append_pair("code_challenge_method", "S256");
+        }
+    }
+
+    let auth = SsoAuth::new(state, client_state, code_challenge, redirect_uri, nonce, verifier);
+    Ok((auth, url.to_string()))
+}
+
+/// Exchanges the code received from the identity provider and returns the validated identity of the user
+pub async fn exchange_code(auth: &SsoAuth) -> ApiResult<UserInformation> {
+    let settings = ClientSettings::from_config();
+    let provider = provider(&settings.authority, false).await?;
+    _exchange_code(auth, &settings, provider).await
+}
+
+async fn _exchange_code(auth: &SsoAuth, settings: &ClientSettings, provider: Provider) -> ApiResult<UserInformation> {
+    let Some(ref idp_code) = auth.idp_code else {
+        err!("The SSO login was not completed")
+    };
+
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", idp_code.as_str()),
+        ("redirect_uri", settings.callback_path.as_str()),
+    ];
+    if let Some(ref verifier) = auth.verifier {
+        form.push(("code_verifier", verifier.as_str()));
+    }
+
+    let res = match http_request(Method::POST, &provider.metadata.token_endpoint)?
+        .basic_auth(&settings.client_id, Some(&settings.client_secret))
+        .form(&form)
+        .send()
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => err!(format!("Error exchanging the SSO code: {e}")),
+    };
+
+    let status = res.status();
+    if !status.is_success() {
+        err!(
+            "Error exchanging the SSO code",
+            format!("Identity provider response {status}: {}", res.text().await.unwrap_or_default()),
+            ErrorEvent {
+                event: EventType::UserFailedLogIn
+            }
+        )
+    }
+
+    let token: TokenResponse = match res.json().await {
+        Ok(t) => t,
+        Err(e) => err!(format!("Error decoding the token response of the identity provider: {e}")),
+    };
+
+    let Some(id_token) = token.id_token else {
+        err!("The identity provider did not return an ID token")
+    };
+
+    let claims = validate_id_token(&id_token, &auth.nonce, settings, provider.clone()).await?;
+
+    let mut user_info = UserInformation {
+        identifier: format!("{}/{}", provider.authority, claims.sub),
+        email: claims.email,
+        email_verified: claims.email_verified.unwrap_or(false),
+        name: claims.name,
+    };
+
+    // Not all identity providers put the email address in the ID token, in that case ask the userinfo endpoint
+    if user_info.email.is_none() {
+        if let Some(ref userinfo_endpoint) = provider.metadata.userinfo_endpoint {
+            let res = match http_request(Method::GET, userinfo_endpoint)?
+                .bearer_auth(&token.access_token)
+                .header(header::ACCEPT, "application/json")
+                .send()
+                .await
+            {
+                Ok(r) => r,
+                Err(e) => err!(format!("Error requesting the userinfo of the identity provider: {e}")),
+            };
+
+            let userinfo: UserInfoResponse = match res.error_for_status() {
+                Ok(r) => match r.json().await {
+                    Ok(u) => u,
+                    Err(e) => err!(format!("Error decoding the userinfo of the identity provider: {e}")),
+                },
+                Err(e) => err!(format!("Error requesting the userinfo of the identity provider: {e}")),
+            };
+
+            if userinfo.sub != claims.sub {
+                err!("The userinfo subject does not match the ID token subject")
+            }
+
+            user_info.email = userinfo.email;
+            user_info.email_verified = userinfo.email_verified.unwrap_or(false);
+            user_info.name = user_info.name.or(userinfo.name);
+        }
+    }
+
+    user_info.email = user_info.email.map(|e| e.to_lowercase());
+    Ok(user_info)
+}
+
+/// The algorithms an ID token may be signed with: the ones announced by the identity provider, or RS256 when it
+/// announces none. HMAC uses the client secret as key, so it is only accepted when explicitly allowed.
+fn allowed_algorithms(metadata: &ProviderMetadata, allow_hmac: bool) -> Vec<Algorithm> {
+    let algorithms = match metadata.id_token_signing_alg_values_supported {
+        Some(ref values) => values.iter().filter_map(|v| Algorithm::from_str(v).ok()).collect(),
+        None => vec![Algorithm::RS256],
+    };
+    algorithms
+        .into_iter()
+        .filter(|a| allow_hmac || !matches!(a, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512))
+        .collect()
+}
+
+async fn validate_id_token(
+    id_token: &str,
+    nonce: &str,
+    settings: &ClientSettings,
+    mut provider: Provider,
+) -> ApiResult<IdTokenClaims> {
+    let header = match jsonwebtoken::decode_header(id_token) {
+        Ok(h) => h,
+        Err(e) => err!(format!("Invalid ID token: {e}")),
+    };
+
+    if !allowed_algorithms(&provider.metadata, settings.allow_hmac).contains(&header.alg) {
+        err!(
+            "Error validating the ID token",
+            format!("The ID token is signed with a rejected algorithm ({:?})", header.alg),
+            ErrorEvent {
+                event: EventType::UserFailedLogIn
+            }
+        )
+    }
+
+    let key = match header.alg {
+        // Tokens signed with a symmetric algorithm use the client secret as key
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+            DecodingKey::from_secret(settings.client_secret.as_bytes())
+        }
+        _ => {
+            let Some(ref kid) = header.kid else {
+                err!("The ID token does not reference a signing key")
+            };
+
+            // The identity provider might have rotated its keys since they were cached
+            if provider.jwks.find(kid).is_none() {
+                provider = self::provider(&settings.authority, true).await?;
+            }
+
+            let Some(jwk) = provider.jwks.find(kid) else {
+                err!(format!("The ID token is signed with an unknown key ({kid})"))
+            };
+
+            // A key which is meant for one algorithm can't be used with another one
+            if let Some(ref key_algorithm) = jwk.common.key_algorithm {
+                if Algorithm::from_str(&key_algorithm.to_string()).ok() != Some(header.alg) {
+                    err!(format!("The ID token algorithm does not match the algorithm of its key ({kid})"))
+                }
+            }
+
+            match DecodingKey::from_jwk(jwk) {
+                Ok(key) => key,
+                Err(e) => err!(format!("Invalid signing key of the identity provider: {e}")),
+            }
+        }
+    };
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_required_spec_claims(&["exp", "aud", "iss", "sub"]);
+    validation.set_audience(&[&settings.client_id]);
+    validation.set_issuer(&[&provider.metadata.issuer]);
+
+    let claims = match jsonwebtoken::decode::<IdTokenClaims>(id_token, &key, &validation) {
+        Ok(t) => t.claims,
+        Err(e) => err!(
+            "Error validating the ID token",
+            format!("{e}"),
+            ErrorEvent {
+                event: EventType::UserFailedLogIn
+            }
+        ),
+    };
+
+    match claims.nonce {
+        Some(ref n) if crypto::ct_eq(n, nonce) => Ok(claims),
+        _ => err!(
+            "Error validating the ID token, nonce mismatch",
+            ErrorEvent {
+                event: EventType::UserFailedLogIn
+            }
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use jsonwebtoken::{EncodingKey, Header};
+    use openssl::rsa::Rsa;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+    };
+
+    use serde_json::Value;
+
+    use super::*;
+
+    const CLIENT_ID: &str = "vaultwarden";
+    const CLIENT_SECRET: &str = "test-client-secret";
+    const IDP_CODE: &str = "test-code";
+    const NONCE: &str = "test-nonce";
+    const VERIFIER: &str = "test-verifier";
+
+    /// A local OpenID Connect provider, which answers the discovery, the keys and the code exchange
+    struct MockIdp {
+        authority: String,
+        private_key: Vec<u8>,
+        jwk: Value,
+        algorithms: Vec<&'static str>,
+        // Sign the ID token with the client secret instead of the private key
+        sign_with_secret: bool,
+    }
+
+    impl MockIdp {
+        fn id_token(&self) -> String {
+            let claims = json!({
+                "iss": self.authority,
+                "sub": "subject",
+                "aud": CLIENT_ID,
+                "exp": chrono::Utc::now().timestamp() + 300,
+                "nonce": NONCE,
+                "email": "User@Example.com",
+                "email_verified": true,
+                "name": "Test User",
+            });
+            if self.sign_with_secret {
+                let key = EncodingKey::from_secret(CLIENT_SECRET.as_bytes());
+                jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &key).unwrap()
+            } else {
+                let mut header = Header::new(Algorithm::RS256);
+                header.kid = Some(String::from("test-key"));
+                let key = EncodingKey::from_rsa_pem(&self.private_key).unwrap();
+                jsonwebtoken::encode(&header, &claims, &key).unwrap()
+            }
+        }
+
+        fn respond(&self, method: &str, path: &str, body: &str) -> (u16, Value) {
+            match (method, path) {
+                ("GET", "/.well-known/openid-configuration") => (
+                    200,
+                    json!({
+                        "issuer": self.authority,
+                        "authorization_endpoint": format!("{}/authorize", self.authority),
+                        "token_endpoint": format!("{}/token", self.authority),
+                        "jwks_uri": format!("{}/jwks", self.authority),
+                        "id_token_signing_alg_values_supported": self.algorithms,
+                    }),
+                ),
+                ("GET", "/jwks") => (200, json!({ "keys": [self.jwk] })),
+                ("POST", "/token") => {
+                    let form: std::collections::HashMap<String, String> =
+                        url::form_urlencoded::parse(body.as_bytes()).into_owned().collect();
+                    // Like a real provider, only accept the verifier of the challenge sent with the authorization
+                    let code_verifier = form.get("code_verifier").map(String::as_str).unwrap_or_default();
+                    if form.get("code").map(String::as_str) != Some(IDP_CODE)
+                        || pkce_challenge(code_verifier) != pkce_challenge(VERIFIER)
+                    {
+                        return (400, json!({ "error": "invalid_grant" }));
+                    }
+                    (200, json!({ "access_token": "test-access-token", "id_token": self.id_token() }))
+                }
+                _ => (404, json!({ "error": "not_found" })),
+            }
+        }
+    }
+
+    async fn handle_connection(idp: Arc<MockIdp>, stream: TcpStream) -> std::io::Result<()> {
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).await?;
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).await?;
+
+        let (status, json) = idp.respond(method, path, &String::from_utf8_lossy(&body));
+        let json = json.to_string();
+        let response = format!(
+            "HTTP/1.1 {status} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{json}",
+            json.len()
+        );
+        reader.get_mut().write_all(response.as_bytes()).await
+    }
+
+    async fn serve_idp(algorithms: Vec<&'static str>, sign_with_secret: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let authority = format!("http://{}", listener.local_addr().unwrap());
+
+        let rsa = Rsa::generate(2048).unwrap();
+        let jwk = json!({
+            "kty": "RSA",
+            "kid": "test-key",
+            "alg": "RS256",
+            "use": "sig",
+            "n": BASE64URL_NOPAD.encode(&rsa.n().to_vec()),
+            "e": BASE64URL_NOPAD.encode(&rsa.e().to_vec()),
+        });
+        let idp = Arc::new(MockIdp {
+            authority: authority.clone(),
+            private_key: rsa.private_key_to_pem().unwrap(),
+            jwk,
+            algorithms,
+            sign_with_secret,
+        });
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(idp.clone(), stream));
+            }
+        });
+        authority
+    }
+
+    fn settings(authority: &str, allow_hmac: bool) -> ClientSettings {
+        ClientSettings {
+            authority: authority.to_string(),
+            client_id: String::from(CLIENT_ID),
+            client_secret: String::from(CLIENT_SECRET),
+            callback_path: String::from("http://localhost/identity/connect/oidc-signin"),
+            allow_hmac,
+        }
+    }
+
+    fn completed_auth(nonce: &str, verifier: &str) -> SsoAuth {
+        let mut auth = SsoAuth::new(
+            String::from("state"),
+            String::from("client-state"),
+            String::from("client-challenge"),
+            String::from("bitwarden://sso-callback"),
+            nonce.to_string(),
+            Some(verifier.to_string()),
+        );
+        auth.idp_code = Some(String::from(IDP_CODE));
+        auth
+    }
+
+    #[rocket::async_test]
+    async fn test_discovery_and_code_exchange() {
+        let authority = serve_idp(vec!["RS256"], false).await;
+        let provider = fetch_provider(authority.clone()).await.unwrap();
+        assert_eq!(provider.metadata.token_endpoint, format!("{authority}/token"));
+        assert!(provider.jwks.find("test-key").is_some());
+
+        let auth = completed_auth(NONCE, VERIFIER);
+        let user_info = _exchange_code(&auth, &settings(&authority, false), provider).await.unwrap();
+        assert_eq!(user_info.identifier, format!("{authority}/subject"));
+        assert_eq!(user_info.email.as_deref(), Some("user@example.com"));
+        assert!(user_info.email_verified);
+    }
+
+    #[rocket::async_test]
+    async fn test_code_exchange_rejects_nonce_mismatch() {
+        let authority = serve_idp(vec!["RS256"], false).await;
+        let provider = fetch_provider(authority.clone()).await.unwrap();
+
+        let auth = completed_auth("another-nonce", VERIFIER);
+        assert!(_exchange_code(&auth, &settings(&authority, false), provider).await.is_err());
+    }
+
+    #[rocket::async_test]
+    async fn test_code_exchange_rejects_pkce_mismatch() {
+        let authority = serve_idp(vec!["RS256"], false).await;
+        let provider = fetch_provider(authority.clone()).await.unwrap();
+
+        let auth = completed_auth(NONCE, "another-verifier");
+        assert!(_exchange_code(&auth, &settings(&authority, false), provider).await.is_err());
+    }
+
+    #[rocket::async_test]
+    async fn test_hmac_id_token_needs_to_be_allowed() {
+        let authority = serve_idp(vec!["RS256", "HS256"], true).await;
+        let provider = fetch_provider(authority.clone()).await.unwrap();
+
+        let auth = completed_auth(NONCE, VERIFIER);
+        assert!(_exchange_code(&auth, &settings(&authority, false), provider.clone()).await.is_err());
+        assert!(_exchange_code(&auth, &settings(&authority, true), provider).await.is_ok());
+    }
+
+    #[rocket::async_test]
+    async fn test_id_token_algorithm_not_announced() {
+        // The provider only announces ES256, so its RS256 signed tokens are rejected
+        let authority = serve_idp(vec!["ES256"], false).await;
+        let provider = fetch_provider(authority.clone()).await.unwrap();
+
+        let auth = completed_auth(NONCE, VERIFIER);
+        assert!(_exchange_code(&auth, &settings(&authority, false), provider).await.is_err());
+    }
+}
@@ -0,0 +1,164 @@
+"use strict";
+/* eslint-env es2017, browser */
+/* global _post:readable, BASE_URL:readable */
+
+function createAdminAccount(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const data = JSON.stringify({
+        "username": document.getElementById("admin-account-username").value,
+        "password": document.getElementById("admin-account-password").value,
+        "role": document.getElementById("admin-account-role").value
+    });
+    _post(`${BASE_URL}/admin/accounts`,
+        "Admin account created correctly",
+        "Error creating admin account",
+        data
+    );
+}
+
+function updateAdminAccount(account_id, role, enabled, password, successMsg) {
+    const data = JSON.stringify({
+        "role": role,
+        "enabled": enabled,
+        "password": password
+    });
+    _post(`${BASE_URL}/admin/accounts/${account_id}/update`,
+        successMsg,
+        "Error updating admin account",
+        data
+    );
+}
+
+function changeAdminRole(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const select = event.target;
+    updateAdminAccount(
+        select.dataset.vwAccountId,
+        select.value,
+        select.dataset.vwAccountEnabled === "true",
+        null,
+        "Role updated correctly"
+    );
+}
+
+function toggleAdminAccount(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const data = event.target.parentNode.dataset;
+    const enable = data.vwAccountEnabled !== "true";
+    updateAdminAccount(
+        data.vwAccountId,
+        data.vwAccountRole,
+        enable,
+        null,
+        enable ? "Admin account enabled correctly" : "Admin account disabled correctly"
+    );
+}
+
+function resetAdminPassword(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const data = event.target.parentNode.dataset;
+    const password = prompt(`Enter the new password for "${data.vwAccountUsername}" (at least 8 characters)`);
+    if (password) {
+        updateAdminAccount(
+            data.vwAccountId,
+            data.vwAccountRole,
+            data.vwAccountEnabled === "true",
+            password,
+            "Password changed correctly"
+        );
+    }
+}
+
+function resetAdminTotp(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const data = event.target.parentNode.dataset;
+    const confirmed = confirm(`Are you sure you want to reset the TOTP of "${data.vwAccountUsername}"?`);
+    if (confirmed) {
+        _post(`${BASE_URL}/admin/accounts/${data.vwAccountId}/reset-totp`,
+            "TOTP reset correctly",
+            "Error resetting TOTP"
+        );
+    }
+}
+
+function deleteAdminAccount(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const data = event.target.parentNode.dataset;
+    const input_username = prompt(`To delete the admin account "${data.vwAccountUsername}", please type the username below.`);
+    if (input_username != null) {
+        if (input_username == data.vwAccountUsername) {
+            _post(`${BASE_URL}/admin/accounts/${data.vwAccountId}/delete`,
+                "Admin account deleted correctly",
+                "Error deleting admin account"
+            );
+        } else {
+            alert("Wrong username, please try again");
+        }
+    }
+}
+
+function changeOwnPassword(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const data = JSON.stringify({
+        "current_password": document.getElementById("admin-current-password").value,
+        "new_password": document.getElementById("admin-new-password").value
+    });
+    _post(`${BASE_URL}/admin/account/password`,
+        "Password changed correctly",
+        "Error changing password",
+        data
+    );
+}
+
+function enableOwnTotp(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const data = JSON.stringify({
+        "secret": document.getElementById("admin-totp-secret").textContent.trim(),
+        "code": document.getElementById("admin-totp-code").value
+    });
+    _post(`${BASE_URL}/admin/account/totp`,
+        "TOTP enabled correctly",
+        "Error enabling TOTP",
+        data
+    );
+}
+
+// onLoad events
+document.addEventListener("DOMContentLoaded", (/*event*/) => {
+    document.querySelectorAll("select[data-vw-admin-role]").forEach(select => {
+        select.addEventListener("change", changeAdminRole);
+    });
+    document.querySelectorAll("button[vw-toggle-admin-account]").forEach(btn => {
+        btn.addEventListener("click", toggleAdminAccount);
+    });
+    document.querySelectorAll("button[vw-reset-admin-password]").forEach(btn => {
+        btn.addEventListener("click", resetAdminPassword);
+    });
+    document.querySelectorAll("button[vw-reset-admin-totp]").forEach(btn => {
+        btn.addEventListener("click", resetAdminTotp);
+    });
+    document.querySelectorAll("button[vw-delete-admin-account]").forEach(btn => {
+        btn.addEventListener("click", deleteAdminAccount);
+    });
+
+    const createForm = document.getElementById("create-admin-account-form");
+    if (createForm) {
+        createForm.addEventListener("submit", createAdminAccount);
+    }
+    const passwordForm = document.getElementById("change-admin-password-form");
+    if (passwordForm) {
+        passwordForm.addEventListener("submit", changeOwnPassword);
+    }
+    const totpForm = document.getElementById("enable-admin-totp-form");
+    if (totpForm) {
+        totpForm.addEventListener("submit", enableOwnTotp);
+    }
+});
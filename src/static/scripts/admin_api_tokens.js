@@ -0,0 +1,66 @@
+"use strict";
+/* eslint-env es2017, browser */
+/* global _post:readable, BASE_URL:readable, reload:readable */
+
+function createApiToken(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const scopes = Array.from(document.querySelectorAll("input[data-vw-api-token-scope]:checked")).map(input => input.value);
+    const expires = document.getElementById("api-token-expires").value;
+    const data = JSON.stringify({
+        "name": document.getElementById("api-token-name").value,
+        "scopes": scopes,
+        "expires_in_days": expires ? parseInt(expires, 10) : null
+    });
+
+    // Not using `_post`, since the token needs to be shown from the response
+    fetch(`${BASE_URL}/admin/api-tokens`, {
+        method: "POST",
+        body: data,
+        mode: "same-origin",
+        credentials: "same-origin",
+        headers: { "Content-Type": "application/json" }
+    }).then(resp => resp.json().then(respJson => ({ ok: resp.ok, respJson: respJson })))
+    .then(({ ok, respJson }) => {
+        if (ok) {
+            prompt("API token created, copy it now, it won't be shown again:", respJson.token);
+            reload();
+        } else {
+            const message = respJson.errorModel && respJson.errorModel.message ? respJson.errorModel.message : "Unknown error";
+            alert(`Error creating API token\n${message}`);
+        }
+    }).catch(e => {
+        alert(`Error creating API token\n${e}`);
+    });
+}
+
+function revokeApiToken(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const token_id = event.target.dataset.vwApiTokenId;
+    const token_name = event.target.dataset.vwApiTokenName;
+    if (!token_id) {
+        alert("Required parameters not found!");
+        return false;
+    }
+
+    const confirmed = confirm(`Are you sure you want to revoke the API token "${token_name}"?\nThis cannot be undone!`);
+    if (confirmed) {
+        _post(`${BASE_URL}/admin/api-tokens/${token_id}/revoke`,
+            "API token revoked correctly",
+            "Error revoking API token"
+        );
+    }
+}
+
+// onLoad events
+document.addEventListener("DOMContentLoaded", (/*event*/) => {
+    document.querySelectorAll("button[vw-revoke-api-token]").forEach(btn => {
+        btn.addEventListener("click", revokeApiToken);
+    });
+
+    const createForm = document.getElementById("create-api-token-form");
+    if (createForm) {
+        createForm.addEventListener("submit", createApiToken);
+    }
+});
@@ -30,10 +30,123 @@ function deleteOrganization(event) {
     }
 }
 
+function renameOrganization(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const org_uuid = event.target.dataset.vwOrgUuid;
+    const org_name = event.target.dataset.vwOrgName;
+    if (!org_uuid) {
+        alert("Required parameters not found!");
+        return false;
+    }
+
+    const new_name = prompt(`Enter the new name of the organization "${org_name}".`, org_name);
+    if (new_name != null && new_name.trim() != "" && new_name != org_name) {
+        _post(`${BASE_URL}/admin/organizations/${org_uuid}/rename`,
+            "Organization renamed correctly",
+            "Error renaming organization",
+            JSON.stringify({ "name": new_name })
+        );
+    }
+}
+
+function setOrganizationQuota(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const org_uuid = event.target.dataset.vwOrgUuid;
+    const org_name = event.target.dataset.vwOrgName;
+    if (!org_uuid) {
+        alert("Required parameters not found!");
+        return false;
+    }
+
+    const value = prompt(`Attachment storage quota in KB of "${org_name}".\nLeave empty to use the global limit, 0 disables uploads.`, event.target.dataset.vwAttachmentQuota);
+    if (value == null) {
+        return false;
+    }
+    const quota = value.trim() == "" ? null : Number(value.trim());
+    if (quota !== null && (!Number.isInteger(quota) || quota < 0)) {
+        alert("The quota needs to be a positive number of KB");
+        return false;
+    }
+    _post(`${BASE_URL}/admin/organizations/${org_uuid}/quota`,
+        "Storage quota updated correctly",
+        "Error updating storage quota",
+        JSON.stringify({ "attachment_quota_kb": quota })
+    );
+}
+
+function setOrganizationOwner(event, transfer) {
+    event.preventDefault();
+    event.stopPropagation();
+    const org_uuid = event.target.dataset.vwOrgUuid;
+    const org_name = event.target.dataset.vwOrgName;
+    if (!org_uuid) {
+        alert("Required parameters not found!");
+        return false;
+    }
+
+    const question = transfer
+        ? `Enter the email of the confirmed member who becomes the owner of "${org_name}".\nThe current owners become admins.`
+        : `Enter the email of the member who becomes an additional owner of "${org_name}".`;
+    const email = prompt(question);
+    if (email != null && email.trim() != "") {
+        _post(`${BASE_URL}/admin/organizations/${org_uuid}/owner`,
+            transfer ? "Ownership transferred correctly" : "Owner added correctly",
+            transfer ? "Error transferring ownership" : "Error adding owner",
+            JSON.stringify({ "email": email, "transfer": transfer })
+        );
+    }
+}
+
+function createOrganization(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const data = JSON.stringify({
+        "name": document.getElementById("createOrgName").value,
+        "owner_email": document.getElementById("createOrgOwnerEmail").value,
+        "billing_email": document.getElementById("createOrgBillingEmail").value
+    });
+    fetch(`${BASE_URL}/admin/organizations`, {
+        method: "POST",
+        body: data,
+        mode: "same-origin",
+        credentials: "same-origin",
+        headers: { "Content-Type": "application/json" }
+    }).then(resp => resp.json().then(respJson => {
+        if (!resp.ok) {
+            const apiMsg = respJson.errorModel && respJson.errorModel.message ? respJson.errorModel.message : `${resp.status} - ${resp.statusText}`;
+            alert(`Error creating organization\n${apiMsg}`);
+            return;
+        }
+        if (respJson.invite_url) {
+            // Mail is disabled, the invitation link has to be shared with the owner manually
+            prompt("Organization created correctly.\nShare this invitation link with the owner:", respJson.invite_url);
+        } else {
+            alert("Organization created correctly, the owner has been invited");
+        }
+        reload();
+    })).catch(e => {
+        alert(`Error creating organization\n${e}`);
+    });
+}
+
 function initActions() {
     document.querySelectorAll("button[vw-delete-organization]").forEach(btn => {
         btn.addEventListener("click", deleteOrganization);
     });
+    document.querySelectorAll("button[vw-rename-organization]").forEach(btn => {
+        btn.addEventListener("click", renameOrganization);
+    });
+    document.querySelectorAll("button[vw-org-quota]").forEach(btn => {
+        btn.addEventListener("click", setOrganizationQuota);
+    });
+    document.querySelectorAll("button[vw-promote-owner]").forEach(btn => {
+        btn.addEventListener("click", event => setOrganizationOwner(event, false));
+    });
+    document.querySelectorAll("button[vw-transfer-ownership]").forEach(btn => {
+        btn.addEventListener("click", event => setOrganizationOwner(event, true));
+    });
 
     if (jdenticon) {
         jdenticon();
@@ -67,4 +180,9 @@ document.addEventListener("DOMContentLoaded", (/*event*/) => {
     if (btnReload) {
         btnReload.addEventListener("click", reload);
     }
+
+    const createOrganizationForm = document.getElementById("createOrganizationForm");
+    if (createOrganizationForm) {
+        createOrganizationForm.addEventListener("submit", createOrganization);
+    }
 });
\ No newline at end of file
@@ -79,6 +79,206 @@ function backupDatabase(event) {
     );
 }
 
+function formatConfigValue(value) {
+    return value === null ? "(not set)" : JSON.stringify(value);
+}
+
+function loadConfigHistory() {
+    const rows = document.getElementById("configHistoryRows");
+    fetch(`${BASE_URL}/admin/config/history`, {
+        mode: "same-origin",
+        credentials: "same-origin",
+    }).then(resp => resp.json().then(respJson => {
+        if (!resp.ok) {
+            const apiMsg = respJson.errorModel && respJson.errorModel.message ? respJson.errorModel.message : `${resp.status} - ${resp.statusText}`;
+            alert(`Error loading the config history\n${apiMsg}`);
+            return;
+        }
+        rows.replaceChildren();
+        if (respJson.length === 0) {
+            const row = rows.insertRow();
+            const cell = row.insertCell();
+            cell.colSpan = 5;
+            cell.textContent = "No saved versions yet.";
+            return;
+        }
+        respJson.forEach(version => {
+            const row = rows.insertRow();
+            row.insertCell().textContent = version.version;
+            row.insertCell().textContent = version.created_at;
+            row.insertCell().textContent = version.actor;
+
+            const changes = row.insertCell();
+            const action = document.createElement("span");
+            action.className = "badge bg-secondary me-2";
+            action.textContent = version.restored_from ? `rollback to ${version.restored_from}` : version.action;
+            changes.appendChild(action);
+            if (version.changes.length === 0) {
+                changes.appendChild(document.createTextNode("No changes"));
+            }
+            version.changes.forEach(change => {
+                const line = document.createElement("code");
+                line.className = "d-block text-break";
+                line.textContent = `${change.name}: ${formatConfigValue(change.old)} → ${formatConfigValue(change.new)}`;
+                changes.appendChild(line);
+            });
+
+            const actions = row.insertCell();
+            actions.className = "text-end";
+            if (version.current) {
+                actions.textContent = "Current";
+            } else {
+                const btn = document.createElement("button");
+                btn.type = "button";
+                btn.className = "btn btn-sm btn-link p-0 border-0";
+                btn.textContent = "Rollback";
+                btn.dataset.vwConfigVersion = version.version;
+                btn.addEventListener("click", rollbackConfig);
+                actions.appendChild(btn);
+            }
+        });
+    })).catch(e => {
+        alert(`Error loading the config history\n${e}`);
+    });
+}
+
+function rollbackConfig(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const version = event.target.dataset.vwConfigVersion;
+    if (!version) {
+        alert("Required parameters not found!");
+        return false;
+    }
+    const confirmed = confirm(`Are you sure you want to restore the settings of version ${version}?`);
+    if (confirmed) {
+        _post(`${BASE_URL}/admin/config/history/${version}/rollback`,
+            "Config restored correctly",
+            "Error restoring config"
+        );
+    }
+}
+
+// Shows the result of a dry run, being the validation error or the changes and the connectivity checks
+function showConfigCheckResult(title, report) {
+    const result = document.getElementById("configCheckResult");
+    result.replaceChildren();
+    result.classList.remove("d-none");
+
+    const header = document.createElement("h6");
+    header.textContent = `${title}: `;
+    const status = document.createElement("span");
+    status.className = report.valid ? "badge bg-success" : "badge bg-danger";
+    status.textContent = report.valid ? "Valid" : "Invalid";
+    header.appendChild(status);
+    result.appendChild(header);
+
+    if (report.error) {
+        const error = document.createElement("pre");
+        error.className = "text-danger text-wrap mb-2";
+        error.textContent = report.error;
+        result.appendChild(error);
+    }
+
+    if (report.valid) {
+        const changes = document.createElement("div");
+        changes.className = "mb-2";
+        changes.textContent = report.changes.length === 0 ? "No changes compared to the saved settings." : "Changes compared to the saved settings:";
+        report.changes.forEach(change => {
+            const line = document.createElement("code");
+            line.className = "d-block text-break";
+            line.textContent = `${change.name}: ${formatConfigValue(change.old)} → ${formatConfigValue(change.new)}`;
+            changes.appendChild(line);
+        });
+        result.appendChild(changes);
+    }
+
+    report.checks.forEach(check => {
+        const line = document.createElement("div");
+        const badge = document.createElement("span");
+        badge.className = `badge me-2 ${check.status === "ok" ? "bg-success" : check.status === "failed" ? "bg-danger" : "bg-secondary"}`;
+        badge.textContent = check.status;
+        line.appendChild(badge);
+        line.appendChild(document.createTextNode(check.message ? `${check.name}: ${check.message}` : check.name));
+        result.appendChild(line);
+    });
+
+    if (report.ignored && report.ignored.length > 0) {
+        const ignored = document.createElement("div");
+        ignored.className = "mt-2 text-body-secondary";
+        ignored.textContent = `Ignored unknown variables: ${report.ignored.join(", ")}`;
+        result.appendChild(ignored);
+    }
+}
+
+function postForReport(url, data, title) {
+    fetch(url, {
+        method: "POST",
+        body: data,
+        mode: "same-origin",
+        credentials: "same-origin",
+        headers: { "Content-Type": "application/json" }
+    }).then(resp => resp.json().then(respJson => {
+        if (!resp.ok) {
+            const apiMsg = respJson.errorModel && respJson.errorModel.message ? respJson.errorModel.message : `${resp.status} - ${resp.statusText}`;
+            alert(`${title} failed\n${apiMsg}`);
+            return;
+        }
+        showConfigCheckResult(title, respJson);
+    })).catch(e => {
+        alert(`${title} failed\n${e}`);
+    });
+}
+
+function validateConfig(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    postForReport(`${BASE_URL}/admin/config/validate`, JSON.stringify(getFormData()), "Validation");
+}
+
+function exportConfig(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const format = document.getElementById("config-export-format").value;
+    const includeSecrets = document.getElementById("config-export-secrets").checked;
+    window.location.href = `${BASE_URL}/admin/config/export?format=${format}&include_secrets=${includeSecrets}`;
+}
+
+function readImportFile(callback) {
+    const file = document.getElementById("config-import-file").files[0];
+    if (!file) {
+        alert("Please select a file to import");
+        return;
+    }
+    const format = file.name.toLowerCase().endsWith(".json") ? "json" : "env";
+    file.text().then(content => callback(format, content));
+}
+
+function checkImportConfig(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    readImportFile((format, content) => {
+        const data = JSON.stringify({ "format": format, "content": content, "dry_run": true });
+        postForReport(`${BASE_URL}/admin/config/import`, data, "Import check");
+    });
+}
+
+function importConfig(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    readImportFile((format, content) => {
+        if (!confirm("Are you sure you want to import this file? The options in it will overwrite the current settings.")) {
+            return;
+        }
+        const data = JSON.stringify({ "format": format, "content": content, "dry_run": false });
+        _post(`${BASE_URL}/admin/config/import`,
+            "Config imported correctly",
+            "Error importing config",
+            data
+        );
+    });
+}
+
 // Two functions to help check if there were changes to the form fields
 // Useful for example during the smtp test to prevent people from clicking save before testing there new settings
 function initChangeDetection(form) {
@@ -217,6 +417,14 @@ document.addEventListener("DOMContentLoaded", (/*event*/) => {
     if (btnBackupDatabase) {
         btnBackupDatabase.addEventListener("click", backupDatabase);
     }
+    const configHistory = document.getElementById("g_config_history");
+    if (configHistory) {
+        configHistory.addEventListener("show.bs.collapse", loadConfigHistory);
+    }
+    document.getElementById("validateConfig").addEventListener("click", validateConfig);
+    document.getElementById("exportConfig").addEventListener("click", exportConfig);
+    document.getElementById("checkImportConfig").addEventListener("click", checkImportConfig);
+    document.getElementById("importConfig").addEventListener("click", importConfig);
     const btnDeleteConf = document.getElementById("deleteConf");
     if (btnDeleteConf) {
         btnDeleteConf.addEventListener("click", deleteConf);
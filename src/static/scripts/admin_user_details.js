@@ -0,0 +1,35 @@
+"use strict";
+/* eslint-env es2017, browser */
+/* global _post:readable, BASE_URL:readable, jdenticon:readable */
+
+function revokeDevice(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const userBlock = document.getElementById("user-details-block");
+    const user_uuid = userBlock.dataset.vwUserUuid;
+    const email = userBlock.dataset.vwUserEmail;
+    const device_uuid = event.target.dataset.vwDeviceUuid;
+    const device_name = event.target.dataset.vwDeviceName;
+    if (!user_uuid || !device_uuid) {
+        alert("Required parameters not found!");
+        return false;
+    }
+    const confirmed = confirm(`Are you sure you want to revoke the device "${device_name}" of "${email}"?\nThe device needs to log in again.`);
+    if (confirmed) {
+        _post(`${BASE_URL}/admin/users/${user_uuid}/devices/${device_uuid}/revoke`,
+            "Device revoked correctly",
+            "Error revoking device"
+        );
+    }
+}
+
+// onLoad events
+document.addEventListener("DOMContentLoaded", (/*event*/) => {
+    document.querySelectorAll("button[vw-revoke-device]").forEach(btn => {
+        btn.addEventListener("click", revokeDevice);
+    });
+
+    if (jdenticon) {
+        jdenticon();
+    }
+});
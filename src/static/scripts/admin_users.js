@@ -60,6 +60,47 @@ function deauthUser(event) {
     }
 }
 
+// Asks for a storage quota in KB, returns undefined when canceled and null to use the global limit
+function promptQuota(question, current) {
+    const value = prompt(`${question}\nLeave empty to use the global limit, 0 disables uploads.`, current);
+    if (value == null) {
+        return undefined;
+    }
+    if (value.trim() == "") {
+        return null;
+    }
+    const quota = Number(value.trim());
+    if (!Number.isInteger(quota) || quota < 0) {
+        alert("The quota needs to be a positive number of KB");
+        return undefined;
+    }
+    return quota;
+}
+
+function setUserQuota(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const id = event.target.parentNode.dataset.vwUserUuid;
+    const email = event.target.parentNode.dataset.vwUserEmail;
+    if (!id || !email) {
+        alert("Required parameters not found!");
+        return false;
+    }
+    const attachmentQuota = promptQuota(`Attachment storage quota in KB of "${email}".`, event.target.dataset.vwAttachmentQuota);
+    if (attachmentQuota === undefined) {
+        return false;
+    }
+    const sendQuota = promptQuota(`Send storage quota in KB of "${email}".`, event.target.dataset.vwSendQuota);
+    if (sendQuota === undefined) {
+        return false;
+    }
+    _post(`${BASE_URL}/admin/users/${id}/quota`,
+        "Storage quota updated correctly",
+        "Error updating storage quota",
+        JSON.stringify({ "attachment_quota_kb": attachmentQuota, "send_quota_kb": sendQuota })
+    );
+}
+
 function disableUser(event) {
     event.preventDefault();
     event.stopPropagation();
@@ -96,6 +137,24 @@ function enableUser(event) {
     }
 }
 
+function unlockUser(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const id = event.target.parentNode.dataset.vwUserUuid;
+    const email = event.target.parentNode.dataset.vwUserEmail;
+    if (!id || !email) {
+        alert("Required parameters not found!");
+        return false;
+    }
+    const confirmed = confirm(`Are you sure you want to unlock user "${email}"? This also resets their failed login attempts.`);
+    if (confirmed) {
+        _post(`${BASE_URL}/admin/users/${id}/unlock`,
+            "User unlocked successfully",
+            "Error unlocking user"
+        );
+    }
+}
+
 function updateRevisions(event) {
     event.preventDefault();
     event.stopPropagation();
@@ -120,6 +179,123 @@ function inviteUser(event) {
     );
 }
 
+function bulkUserAction(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const select = document.getElementById("bulkUserAction");
+    const action = select.value;
+    const selected = Array.from(document.querySelectorAll("input[vw-select-user]:checked"));
+    if (selected.length == 0) {
+        alert("No users selected");
+        return false;
+    }
+
+    const emails = selected.map(e => e.dataset.vwUserEmail).join("\n");
+    const actionName = select.options[select.selectedIndex].text;
+    if (!confirm(`${actionName} the following ${selected.length} users?\n\n${emails}`)) {
+        return false;
+    }
+    if (action == "delete") {
+        const input_count = prompt(`To delete ${selected.length} users, please type the number of users below.`);
+        if (input_count != selected.length) {
+            alert("Wrong number of users, please try again");
+            return false;
+        }
+    }
+
+    const data = JSON.stringify({
+        "action": action,
+        "user_ids": selected.map(e => e.dataset.vwUserUuid)
+    });
+    fetch(`${BASE_URL}/admin/users/bulk`, {
+        method: "POST",
+        body: data,
+        mode: "same-origin",
+        credentials: "same-origin",
+        headers: { "Content-Type": "application/json" }
+    }).then(resp => resp.json().then(respJson => {
+        if (!resp.ok) {
+            const apiMsg = respJson.errorModel && respJson.errorModel.message ? respJson.errorModel.message : `${resp.status} - ${resp.statusText}`;
+            alert(`Error applying the action\n${apiMsg}`);
+            return;
+        }
+        const errors = respJson.filter(r => r.error !== "").map(r => {
+            const email = selected.find(e => e.dataset.vwUserUuid == r.id).dataset.vwUserEmail;
+            return `${email}: ${r.error}`;
+        });
+        if (errors.length > 0) {
+            alert(`The action failed for ${errors.length} of ${respJson.length} users\n\n${errors.join("\n")}`);
+        } else {
+            alert(`The action was applied to ${respJson.length} users`);
+        }
+        reload();
+    })).catch(e => {
+        alert(`Error applying the action\n${e}`);
+    });
+}
+
+function importUsers(dryRun) {
+    const file = document.getElementById("importUsersFile").files[0];
+    if (!file) {
+        alert("No CSV file selected");
+        return;
+    }
+    if (!dryRun && !confirm("Import the users of the preview?")) {
+        return;
+    }
+
+    file.text().then(csv => fetch(`${BASE_URL}/admin/users/import`, {
+        method: "POST",
+        body: JSON.stringify({ "csv": csv, "dry_run": dryRun }),
+        mode: "same-origin",
+        credentials: "same-origin",
+        headers: { "Content-Type": "application/json" }
+    })).then(resp => resp.json().then(respJson => {
+        if (!resp.ok) {
+            const apiMsg = respJson.errorModel && respJson.errorModel.message ? respJson.errorModel.message : `${resp.status} - ${resp.statusText}`;
+            alert(`Error importing users\n${apiMsg}`);
+            return;
+        }
+        showImportResult(respJson);
+        // Only the previewed file can be imported
+        document.getElementById("importUsersApply").disabled = !dryRun;
+        if (!dryRun) {
+            alert(`Imported ${respJson.rows.length - respJson.failed} users, ${respJson.failed} rows failed`);
+        }
+    })).catch(e => {
+        alert(`Error importing users\n${e}`);
+    });
+}
+
+function showImportResult(result) {
+    const tbody = document.getElementById("importUsersRows");
+    tbody.replaceChildren();
+    for (const row of result.rows) {
+        const tr = document.createElement("tr");
+        const tdRow = document.createElement("td");
+        tdRow.textContent = row.row;
+        const tdEmail = document.createElement("td");
+        tdEmail.textContent = row.email;
+        const tdChanges = document.createElement("td");
+        if (row.error) {
+            const span = document.createElement("span");
+            span.className = "text-danger";
+            span.textContent = row.error;
+            tdChanges.appendChild(span);
+        } else {
+            for (const action of row.actions) {
+                const span = document.createElement("span");
+                span.className = "d-block";
+                span.textContent = action;
+                tdChanges.appendChild(span);
+            }
+        }
+        tr.append(tdRow, tdEmail, tdChanges);
+        tbody.appendChild(tr);
+    }
+    document.getElementById("importUsersResult").classList.remove("d-none");
+}
+
 function resendUserInvite (event) {
     event.preventDefault();
     event.stopPropagation();
@@ -243,6 +419,9 @@ function initUserTable() {
     document.querySelectorAll("button[vw-deauth-user]").forEach(btn => {
         btn.addEventListener("click", deauthUser);
     });
+    document.querySelectorAll("button[vw-user-quota]").forEach(btn => {
+        btn.addEventListener("click", setUserQuota);
+    });
     document.querySelectorAll("button[vw-delete-user]").forEach(btn => {
         btn.addEventListener("click", deleteUser);
     });
@@ -252,6 +431,9 @@ function initUserTable() {
     document.querySelectorAll("button[vw-enable-user]").forEach(btn => {
         btn.addEventListener("click", enableUser);
     });
+    document.querySelectorAll("button[vw-unlock-user]").forEach(btn => {
+        btn.addEventListener("click", unlockUser);
+    });
     document.querySelectorAll("button[vw-resend-user-invite]").forEach(btn => {
         btn.addEventListener("click", resendUserInvite);
     });
@@ -303,4 +485,19 @@ document.addEventListener("DOMContentLoaded", (/*event*/) => {
     if (btnInviteUserForm) {
         btnInviteUserForm.addEventListener("submit", inviteUser);
     }
+    const btnBulkUserAction = document.getElementById("bulkUserActionApply");
+    if (btnBulkUserAction) {
+        btnBulkUserAction.addEventListener("click", bulkUserAction);
+    }
+    const importUsersForm = document.getElementById("importUsersForm");
+    if (importUsersForm) {
+        importUsersForm.addEventListener("submit", event => {
+            event.preventDefault();
+            importUsers(true);
+        });
+        document.getElementById("importUsersFile").addEventListener("change", () => {
+            document.getElementById("importUsersApply").disabled = true;
+        });
+        document.getElementById("importUsersApply").addEventListener("click", () => importUsers(false));
+    }
 });
\ No newline at end of file
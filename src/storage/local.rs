@@ -0,0 +1,96 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use rocket::fs::{NamedFile, TempFile};
+use tokio::io::AsyncWrite;
+
+use super::{PathType, Storage, StorageFile};
+use crate::{api::EmptyResult, error::Error};
+
+/// Stores the files on the local filesystem, in `ATTACHMENTS_FOLDER` and `SENDS_FOLDER`
+pub struct LocalStorage;
+
+impl LocalStorage {
+    fn file_path(path_type: PathType, path: &str) -> PathBuf {
+        PathBuf::from(path_type.local_folder()).join(path)
+    }
+}
+
+#[rocket::async_trait]
+impl Storage for LocalStorage {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn save_temp_file(&self, path_type: PathType, path: &str, file: &mut TempFile<'_>) -> EmptyResult {
+        let file_path = tokio::fs::canonicalize(path_type.local_folder()).await?.join(path);
+        if let Some(folder_path) = file_path.parent() {
+            tokio::fs::create_dir_all(folder_path).await?;
+        }
+
+        if let Err(_err) = file.persist_to(&file_path).await {
+            file.move_copy_to(file_path).await?
+        }
+        Ok(())
+    }
+
+    async fn exists(&self, path_type: PathType, path: &str) -> bool {
+        tokio::fs::metadata(Self::file_path(path_type, path)).await.is_ok()
+    }
+
+    async fn delete(&self, path_type: PathType, path: &str) -> EmptyResult {
+        let file_path = Self::file_path(path_type, path);
+        match tokio::fs::remove_file(&file_path).await {
+            // Ignore "file not found" errors. This can happen when the
+            // upstream caller has already cleaned up the file as part of
+            // its own error handling.
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                debug!("File '{}' already deleted.", file_path.display());
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn delete_dir(&self, path_type: PathType, path: &str) -> EmptyResult {
+        match tokio::fs::remove_dir_all(Self::file_path(path_type, path)).await {
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn presigned_url(&self, _path_type: PathType, _path: &str) -> Option<String> {
+        None
+    }
+
+    async fn download(&self, path_type: PathType, path: &str) -> Option<StorageFile> {
+        NamedFile::open(Self::file_path(path_type, path)).await.ok().map(StorageFile::Local)
+    }
+
+    async fn size(&self, path_type: PathType, path: &str) -> Result<u64, Error> {
+        Ok(tokio::fs::metadata(Self::file_path(path_type, path)).await?.len())
+    }
+
+    async fn copy_to(
+        &self,
+        path_type: PathType,
+        path: &str,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+    ) -> Result<u64, Error> {
+        let mut file = tokio::fs::File::open(Self::file_path(path_type, path)).await?;
+        Ok(tokio::io::copy(&mut file, writer).await?)
+    }
+
+    async fn write_file(&self, path_type: PathType, path: &str, source: &Path) -> EmptyResult {
+        let file_path = Self::file_path(path_type, path);
+        if let Some(folder_path) = file_path.parent() {
+            tokio::fs::create_dir_all(folder_path).await?;
+        }
+        tokio::fs::copy(source, file_path).await?;
+        Ok(())
+    }
+}
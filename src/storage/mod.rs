@@ -0,0 +1,101 @@
+//
+// Storage backends for attachments and Send files
+//
+use std::path::Path;
+
+use once_cell::sync::Lazy;
+use rocket::{fs::NamedFile, fs::TempFile, response::Redirect};
+use tokio::io::AsyncWrite;
+
+use crate::{api::EmptyResult, error::Error, CONFIG};
+
+mod local;
+mod s3;
+
+pub use self::local::LocalStorage;
+pub use self::s3::{S3Signer, S3Storage};
+
+/// The different kinds of files that are handled by the storage backend.
+/// Each one is stored in its own folder (local) or key prefix (S3).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PathType {
+    Attachments,
+    Sends,
+}
+
+impl PathType {
+    /// Local folder used when the `local` backend is active
+    pub fn local_folder(self) -> String {
+        match self {
+            PathType::Attachments => CONFIG.attachments_folder(),
+            PathType::Sends => CONFIG.sends_folder(),
+        }
+    }
+
+    /// Key prefix used when an object storage backend is active
+    pub const fn prefix(self) -> &'static str {
+        match self {
+            PathType::Attachments => "attachments",
+            PathType::Sends => "sends",
+        }
+    }
+}
+
+/// The response sent to the client when downloading a file.
+/// Local files are served directly, object storage files are handed out as a redirect to a presigned URL.
+#[derive(Responder)]
+pub enum StorageFile {
+    Local(NamedFile),
+    Redirect(Redirect),
+}
+
+#[rocket::async_trait]
+pub trait Storage: Send + Sync {
+    /// Name of the backend, used in the logs and on the admin diagnostics page
+    fn name(&self) -> &'static str;
+
+    /// Stores an uploaded file at `path`, overwriting any existing file.
+    /// The file is streamed to the backend, it is never fully loaded into memory when it was spooled to disk.
+    async fn save_temp_file(&self, path_type: PathType, path: &str, file: &mut TempFile<'_>) -> EmptyResult;
+
+    /// Checks if a file exists
+    async fn exists(&self, path_type: PathType, path: &str) -> bool;
+
+    /// Deletes a single file. Deleting a file which does not exist is not an error.
+    async fn delete(&self, path_type: PathType, path: &str) -> EmptyResult;
+
+    /// Deletes all files stored below `path`
+    async fn delete_dir(&self, path_type: PathType, path: &str) -> EmptyResult;
+
+    /// Returns a presigned URL which allows the client to download the file directly from the backend.
+    /// Backends which can't generate these return `None`, and the file will be served by Vaultwarden itself.
+    fn presigned_url(&self, path_type: PathType, path: &str) -> Option<String>;
+
+    /// Returns the response used by the download routes to serve the file
+    async fn download(&self, path_type: PathType, path: &str) -> Option<StorageFile>;
+
+    /// Returns the size of a file in bytes, used when creating a backup
+    async fn size(&self, path_type: PathType, path: &str) -> Result<u64, Error>;
+
+    /// Streams the content of a file into `writer`, and returns the number of bytes copied.
+    /// Used when creating a backup.
+    async fn copy_to(
+        &self,
+        path_type: PathType,
+        path: &str,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+    ) -> Result<u64, Error>;
+
+    /// Stores the local file `source` at `path`, overwriting any existing file. Used when restoring a backup.
+    async fn write_file(&self, path_type: PathType, path: &str, source: &Path) -> EmptyResult;
+}
+
+static STORAGE: Lazy<Box<dyn Storage>> = Lazy::new(|| match CONFIG.storage_backend().as_str() {
+    "s3" => Box::new(S3Storage::from_config()),
+    _ => Box::new(LocalStorage),
+});
+
+/// Returns the configured storage backend
+pub fn storage() -> &'static dyn Storage {
+    STORAGE.as_ref()
+}
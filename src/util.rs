@@ -7,16 +7,17 @@ use num_traits::ToPrimitive;
 use rocket::{
     fairing::{Fairing, Info, Kind},
     http::{ContentType, Header, HeaderMap, Method, Status},
+    request::{FromRequest, Outcome},
     response::{self, Responder},
     Data, Orbit, Request, Response, Rocket,
 };
 
 use tokio::{
     runtime::Handle,
-    time::{sleep, Duration},
+    time::{sleep, Duration, Instant},
 };
 
-use crate::CONFIG;
+use crate::{auth::ClientIp, CONFIG};
 
 pub struct AppHeaders();
 
@@ -50,6 +51,8 @@ impl Fairing for AppHeaders {
             }
         }
 
+        res.set_raw_header(REQUEST_ID_HEADER, RequestContext::from_request(req).id.clone());
+
         // NOTE: When modifying or adding security headers be sure to also update the diagnostic checks in `src/static/scripts/admin_diagnostics.js` in `checkSecurityHeaders`
         res.set_raw_header("Permissions-Policy", "accelerometer=(), ambient-light-sensor=(), autoplay=(), battery=(), camera=(), display-capture=(), document-domain=(), encrypted-media=(), execution-while-not-rendered=(), execution-while-out-of-viewport=(), fullscreen=(), geolocation=(), gyroscope=(), keyboard-map=(), magnetometer=(), microphone=(), midi=(), payment=(), picture-in-picture=(), screen-wake-lock=(), sync-xhr=(), usb=(), web-share=(), xr-spatial-tracking=()");
         res.set_raw_header("Referrer-Policy", "same-origin");
@@ -233,9 +236,64 @@ impl<'r, R: 'r + Responder<'r, 'static> + Send> Responder<'r, 'static> for Cache
     }
 }
 
+// The correlation id of a request. A valid id sent by a reverse proxy is reused, else a new one is generated.
+// It is returned in the same header, and added to the request, response and error logs.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+pub struct RequestContext {
+    pub id: String,
+    pub start: Instant,
+}
+
+impl RequestContext {
+    pub fn from_request<'r>(request: &'r Request<'_>) -> &'r Self {
+        request.local_cache(|| {
+            let id = request
+                .headers()
+                .get_one(REQUEST_ID_HEADER)
+                .filter(|id| {
+                    !id.is_empty()
+                        && id.len() <= 64
+                        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+                })
+                .map(String::from)
+                .unwrap_or_else(get_uuid);
+            Self {
+                id,
+                start: Instant::now(),
+            }
+        })
+    }
+}
+
+// The user authenticated by a request, set by the `auth::Headers` request guard
+pub struct RequestUser(pub String);
+
+/// Adds the key-values of a log record to a JSON log line
+pub struct JsonLogFields<'a>(pub &'a mut serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for JsonLogFields<'_> {
+    fn visit_pair(&mut self, key: log::kv::Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), log::kv::Error> {
+        let value = if let Some(v) = value.to_u64() {
+            serde_json::Value::from(v)
+        } else if let Some(v) = value.to_i64() {
+            serde_json::Value::from(v)
+        } else if let Some(v) = value.to_f64() {
+            serde_json::Value::from(v)
+        } else if let Some(v) = value.to_bool() {
+            serde_json::Value::from(v)
+        } else {
+            serde_json::Value::from(value.to_string())
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
 // Log all the routes from the main paths list, and the attachments endpoint
 // Effectively ignores, any static file route, and the alive endpoint
-const LOGGED_ROUTES: [&str; 7] = ["/api", "/admin", "/identity", "/icons", "/attachments", "/events", "/notifications"];
+const LOGGED_ROUTES: [&str; 8] =
+    ["/api", "/admin", "/identity", "/icons", "/attachments", "/events", "/notifications", "/scim"];
 
 // Boolean is extra debug, when true, we ignore the whitelist above and also print the mounts
 pub struct BetterLogging(pub bool);
@@ -273,6 +331,7 @@ impl Fairing for BetterLogging {
     }
 
     async fn on_request(&self, request: &mut Request<'_>, _data: &mut Data<'_>) {
+        let request_id = RequestContext::from_request(request).id.as_str();
         let method = request.method();
         if !self.0 && method == Method::Options {
             return;
@@ -282,10 +341,12 @@ impl Fairing for BetterLogging {
         let uri_path_str = uri_path.url_decode_lossy();
         let uri_subpath = uri_path_str.strip_prefix(&CONFIG.domain_path()).unwrap_or(&uri_path_str);
         if self.0 || LOGGED_ROUTES.iter().any(|r| uri_subpath.starts_with(r)) {
-            match uri.query() {
-                Some(q) => info!(target: "request", "{} {}?{}", method, uri_path_str, &q[..q.len().min(30)]),
-                None => info!(target: "request", "{} {}", method, uri_path_str),
+            let path = match uri.query() {
+                Some(q) => format!("{}?{}", uri_path_str, &q[..q.len().min(30)]),
+                None => uri_path_str.to_string(),
             };
+            let path = path.as_str();
+            info!(target: "request", request_id = request_id, method = method.as_str(), path = path; "{} {}", method, path);
         }
     }
 
@@ -297,11 +358,39 @@ impl Fairing for BetterLogging {
         let uri_path_str = uri_path.url_decode_lossy();
         let uri_subpath = uri_path_str.strip_prefix(&CONFIG.domain_path()).unwrap_or(&uri_path_str);
         if self.0 || LOGGED_ROUTES.iter().any(|r| uri_subpath.starts_with(r)) {
+            let context = RequestContext::from_request(request);
+            let request_id = context.id.as_str();
+            let latency_ms = context.start.elapsed().as_secs_f64() * 1000.0;
+            let client_ip = match ClientIp::from_request(request).await {
+                Outcome::Success(ip) => ip.ip.to_string(),
+                _ => String::new(),
+            };
+            let client_ip = client_ip.as_str();
+            let user_id = request.local_cache(|| RequestUser(String::new())).0.as_str();
             let status = response.status();
+            let status_code = status.code;
             if let Some(ref route) = request.route() {
-                info!(target: "response", "{} => {}", route, status)
+                let route_uri = route.uri.as_str();
+                info!(
+                    target: "response",
+                    request_id = request_id,
+                    route = route_uri,
+                    status = status_code,
+                    latency_ms = latency_ms,
+                    client_ip = client_ip,
+                    user_id = user_id;
+                    "{} => {}", route, status
+                )
             } else {
-                info!(target: "response", "{}", status)
+                info!(
+                    target: "response",
+                    request_id = request_id,
+                    status = status_code,
+                    latency_ms = latency_ms,
+                    client_ip = client_ip,
+                    user_id = user_id;
+                    "{}", status
+                )
             }
         }
     }
@@ -329,6 +418,22 @@ pub fn get_display_size(size: i64) -> String {
     format!("{:.2} {}", size, UNITS[unit_counter])
 }
 
+/// Formats a single CSV field, quoting it when needed.
+/// Values a spreadsheet would run as a formula are prefixed with a `'` to prevent CSV injection.
+pub fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    };
+
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
 pub fn get_uuid() -> String {
     uuid::Uuid::new_v4().to_string()
 }
@@ -399,13 +504,21 @@ where
 }
 
 pub fn get_env_bool(key: &str) -> Option<bool> {
+    get_env_str_value(key).as_deref().and_then(parse_bool)
+}
+
+/// Parses a boolean the same way as the environment variables, accepting values like `true`, `yes` or `1`
+pub fn parse_bool(val: &str) -> Option<bool> {
     const TRUE_VALUES: &[&str] = &["true", "t", "yes", "y", "1"];
     const FALSE_VALUES: &[&str] = &["false", "f", "no", "n", "0"];
 
-    match get_env_str_value(key) {
-        Some(val) if TRUE_VALUES.contains(&val.to_lowercase().as_ref()) => Some(true),
-        Some(val) if FALSE_VALUES.contains(&val.to_lowercase().as_ref()) => Some(false),
-        _ => None,
+    let val = val.to_lowercase();
+    if TRUE_VALUES.contains(&val.as_str()) {
+        Some(true)
+    } else if FALSE_VALUES.contains(&val.as_str()) {
+        Some(false)
+    } else {
+        None
     }
 }
 